@@ -1,5 +1,6 @@
+use std::env;
 use std::panic::{self, PanicInfo};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
 
 use anyhow::anyhow;
@@ -9,7 +10,8 @@ use console::Term;
 use dialoguer::{theme::ColorfulTheme, Input};
 use regex::Regex;
 
-use crate::config::{load_config, Config};
+use crate::commands::Command;
+use crate::config::{discover_config, load_config, Config};
 use crate::error::Result;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -32,12 +34,23 @@ P2P-Develop
 Wakuchin will generate shuffled \"わくちん\" characters and check whether they match a given regex.
 If they do, it will print the result to stdout.",
 long_version = LONG_VERSION,
-after_long_help = "For more information, see GitHub repository: https://github.com/P2P-Develop/wakuchin-rs")]
+after_long_help = "For more information, see GitHub repository: https://github.com/P2P-Develop/wakuchin-rs",
+args_conflicts_with_subcommands = true)]
 struct Args {
+  #[command(subcommand)]
+  command: Option<Command>,
+
   /// Config file path, can be json, yaml, or toml, detected by extension
+  ///
+  /// If omitted, "wakuchin.toml" or ".wakuchin/config.{toml,yaml,yml,json}"
+  /// is searched for in the current directory and its parents.
   #[arg(value_name = "FILE")]
   config_path: Option<PathBuf>,
 
+  /// Do not search for a config file when FILE is omitted
+  #[arg(long)]
+  no_auto_config: bool,
+
   /// Rest of arguments
   #[command(flatten)]
   config: <Config as ClapSerde>::Opt,
@@ -61,6 +74,17 @@ impl App {
     }
   }
 
+  #[inline]
+  pub fn config_path(&self) -> Option<&Path> {
+    self.args.config_path.as_deref()
+  }
+
+  /// Take the subcommand to run instead of the research, if any.
+  #[inline]
+  pub fn take_command(&mut self) -> Option<Command> {
+    self.args.command.take()
+  }
+
   fn check_interactive(&self) {
     if !self.interactive {
       if cfg!(target_arch = "wasm32") {
@@ -134,12 +158,25 @@ impl App {
   }
 
   pub fn setup_config(&mut self) -> Result<()> {
+    if self.args.config_path.is_none() && !self.args.no_auto_config {
+      self.args.config_path = env::current_dir()
+        .ok()
+        .and_then(|dir| discover_config(&dir));
+    }
+
+    // -H only selects the kind, keep the rest of the [handler] table
+    let handler_kind = self.args.config.handler.take().map(|h| h.kind);
+
     let mut config = if let Some(config_path) = &self.args.config_path {
       load_config(config_path.as_path())?.merge(&mut self.args.config)
     } else {
       Config::from(&mut self.args.config)
     };
 
+    if let Some(kind) = handler_kind {
+      config.handler.kind = kind;
+    }
+
     let term = Term::buffered_stderr();
 
     if config.tries == 0 {
@@ -154,6 +191,11 @@ impl App {
       config.regex = self.prompt_regex(&term)?;
     }
 
+    // always record a seed so that every run can be reproduced
+    if config.seed == 0 {
+      config.seed = fastrand::u64(1..);
+    }
+
     self.config = config;
 
     Ok(())
@@ -0,0 +1,185 @@
+//! Pausing or throttling the research on battery power
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// What to do while running on battery below the threshold.
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryPolicy {
+  #[default]
+  Pause,
+  Throttle,
+}
+
+#[cfg(feature = "battery")]
+pub use self::monitor::BatteryMonitor;
+
+#[cfg(feature = "battery")]
+mod monitor {
+  use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+  use std::thread::{self, JoinHandle};
+  use std::time::{Duration, Instant};
+
+  use anyhow::Result;
+  use starship_battery::{Manager, State};
+
+  use crate::pausing::PauseReasons;
+
+  use super::BatteryPolicy;
+
+  const REASON: &str = "battery";
+
+  /// Interval between two battery samples.
+  const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+  /// Length of the running and paused phases when throttled, halving the
+  /// consumed CPU time.
+  const THROTTLE_PERIOD: Duration = Duration::from_secs(1);
+
+  /// Percents above the threshold needed to leave the low state, so the
+  /// research does not flap around the threshold.
+  const HYSTERESIS: f32 = 5.0;
+
+  /// Lowest charge in percent of the discharging batteries, `None` if no
+  /// battery is discharging, i.e. on AC power.
+  fn discharging_charge(
+    manager: &Manager,
+  ) -> starship_battery::Result<Option<f32>> {
+    let mut charge: Option<f32> = None;
+
+    for battery in manager.batteries()? {
+      let battery = battery?;
+
+      match battery.state() {
+        State::Charging => return Ok(None),
+        State::Discharging | State::Empty => {
+          let percent = battery.state_of_charge().value * 100.0;
+
+          charge = Some(charge.map_or(percent, |charge| charge.min(percent)));
+        }
+        _ => {}
+      }
+    }
+
+    Ok(charge)
+  }
+
+  /// Whether the battery is low, with hysteresis.
+  #[derive(Debug, Default)]
+  struct LowBattery {
+    low: bool,
+  }
+
+  impl LowBattery {
+    fn update(&mut self, charge: Option<f32>, threshold: f32) -> bool {
+      self.low = match charge {
+        None => false,
+        Some(charge) if self.low => charge < threshold + HYSTERESIS,
+        Some(charge) => charge < threshold,
+      };
+
+      self.low
+    }
+  }
+
+  /// Background thread pausing or throttling the research while on battery
+  /// below `threshold` percent.
+  pub struct BatteryMonitor {
+    stop_tx: Sender<()>,
+    thread: JoinHandle<()>,
+  }
+
+  impl BatteryMonitor {
+    pub fn spawn(
+      reasons: PauseReasons,
+      threshold: u8,
+      policy: BatteryPolicy,
+    ) -> Result<Self> {
+      let manager = Manager::new()?;
+      let threshold = f32::from(threshold);
+
+      let (stop_tx, stop_rx) = channel();
+
+      let thread = thread::spawn(move || {
+        let mut battery = LowBattery::default();
+        let mut low = false;
+        let mut next_sample = Instant::now();
+        let mut paused = false;
+
+        loop {
+          if Instant::now() >= next_sample {
+            let charge = discharging_charge(&manager).unwrap_or(None);
+            let was_low = low;
+
+            low = battery.update(charge, threshold);
+            next_sample = Instant::now() + SAMPLE_INTERVAL;
+
+            if low != was_low {
+              eprintln!(
+                "{}",
+                match (low, policy) {
+                  (false, _) => "Battery is fine again, resuming",
+                  (true, BatteryPolicy::Pause) => "Battery is low, pausing",
+                  (true, BatteryPolicy::Throttle) =>
+                    "Battery is low, throttling",
+                }
+              );
+            }
+          }
+
+          paused = match policy {
+            BatteryPolicy::Pause => low,
+            BatteryPolicy::Throttle => low && !paused,
+          };
+
+          reasons.set(REASON, paused);
+
+          let timeout = if low && policy == BatteryPolicy::Throttle {
+            THROTTLE_PERIOD
+          } else {
+            SAMPLE_INTERVAL
+          };
+
+          if !matches!(
+            stop_rx.recv_timeout(timeout),
+            Err(RecvTimeoutError::Timeout)
+          ) {
+            break;
+          }
+        }
+
+        reasons.set(REASON, false);
+      });
+
+      Ok(Self { stop_tx, thread })
+    }
+
+    pub fn stop(self) {
+      let _ = self.stop_tx.send(());
+      let _ = self.thread.join();
+    }
+  }
+
+  #[cfg(test)]
+  mod test {
+    use super::LowBattery;
+
+    #[test]
+    fn test_low_battery() {
+      let mut battery = LowBattery::default();
+
+      assert!(!battery.update(Some(30.0), 20.0));
+      assert!(battery.update(Some(19.0), 20.0));
+      // not enough above the threshold to leave the low state
+      assert!(battery.update(Some(22.0), 20.0));
+      assert!(!battery.update(Some(25.0), 20.0));
+
+      assert!(battery.update(Some(10.0), 20.0));
+      // plugged in
+      assert!(!battery.update(None, 20.0));
+    }
+  }
+}
@@ -0,0 +1,292 @@
+//! Reference consumer of the msgpack progress protocol
+//!
+//! Reads the progress of `wakuchin --handler msgpack` from stdin or a socket,
+//! checks every frame against the protocol and exits with 1 if the stream
+//! does not conform, listing its violations. The result printed after the
+//! final frame is skipped, as GUIs read it apart from the progress.
+//!
+//! GUIs can check their own settings with it as well, e.g. as the consumer of
+//! `wakuchin doctor`.
+
+use std::io::{self, stdin, BufRead, BufReader, ErrorKind, Read};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::process::ExitCode;
+
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose, Engine as _};
+use clap::{Parser, ValueEnum};
+use wakuchin::handlers::msgpack::Compression;
+use wakuchin::progress::{Progress, ProgressKind};
+use wakuchin::protocol::{decode, Frame, ProtocolVersion};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Framing {
+  None,
+  LengthPrefixed,
+  Newline,
+}
+
+#[derive(Parser)]
+#[command(about = "Check the progress frames of wakuchin against the protocol")]
+struct Args {
+  /// Read from the first connection to a TCP address like
+  /// "127.0.0.1:9000" or "unix:PATH" instead of stdin
+  #[arg(long, value_name = "ADDR")]
+  listen: Option<String>,
+
+  /// How frames are delimited, as "frame" of the handler
+  #[arg(long, value_enum, default_value = "none")]
+  frame: Framing,
+
+  /// Frames are encoded with base64
+  #[arg(long)]
+  base64: bool,
+
+  #[arg(long, default_value_t = ProtocolVersion::LATEST.number())]
+  protocol_version: u8,
+}
+
+/// Accept the first connection to `addr`, a TCP address or "unix:PATH".
+fn accept(addr: &str) -> Result<Box<dyn Read>> {
+  if let Some(path) = addr.strip_prefix("unix:") {
+    #[cfg(unix)]
+    return UnixListener::bind(path)
+      .and_then(|listener| listener.accept())
+      .map(|(stream, _)| Box::new(stream) as Box<dyn Read>)
+      .map_err(|e| anyhow!("cannot listen on '{path}': {e}"));
+
+    #[cfg(not(unix))]
+    bail!("cannot listen on '{path}': unix sockets are not supported");
+  }
+
+  TcpListener::bind(addr)
+    .and_then(|listener| listener.accept())
+    .map(|(stream, _)| Box::new(stream) as Box<dyn Read>)
+    .map_err(|e| anyhow!("cannot listen on {addr}: {e}"))
+}
+
+/// Read the next delimited frame, `None` at the end of the stream.
+fn read_frame(
+  reader: &mut impl BufRead,
+  framing: Framing,
+) -> io::Result<Option<Vec<u8>>> {
+  match framing {
+    Framing::None => unreachable!("frames without framing are not delimited"),
+    Framing::LengthPrefixed => {
+      let mut len = [0; 4];
+
+      match reader.read_exact(&mut len) {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+      }
+
+      let mut frame = vec![0; u32::from_be_bytes(len) as usize];
+
+      reader.read_exact(&mut frame)?;
+
+      Ok(Some(frame))
+    }
+    Framing::Newline => {
+      let mut frame = Vec::new();
+
+      if reader.read_until(b'\n', &mut frame)? == 0 {
+        return Ok(None);
+      }
+
+      if frame.pop() != Some(b'\n') {
+        return Err(io::Error::new(
+          ErrorKind::UnexpectedEof,
+          "last frame is not followed by a newline",
+        ));
+      }
+
+      Ok(Some(frame))
+    }
+  }
+}
+
+/// What a consumer can rely on across the frames of a stream.
+struct Conformance {
+  frames: u64,
+  tries: Option<usize>,
+  all_done: bool,
+  skipped: u64,
+  elapsed: f64,
+  violations: Vec<String>,
+}
+
+impl Conformance {
+  fn new() -> Self {
+    Self {
+      frames: 0,
+      tries: None,
+      all_done: false,
+      skipped: 0,
+      elapsed: 0.0,
+      violations: Vec::new(),
+    }
+  }
+
+  fn violation(&mut self, message: impl Into<String>) {
+    self
+      .violations
+      .push(format!("frame {}: {}", self.frames, message.into()));
+  }
+
+  fn check(&mut self, frame: &Frame) {
+    if let Some(seq) = frame.seq {
+      if seq != self.frames {
+        self.violation(format!("seq is {seq}, not {}", self.frames));
+      }
+    }
+
+    if *self.tries.get_or_insert(frame.tries) != frame.tries {
+      self.violation(format!("tries changed to {}", frame.tries));
+    }
+
+    if let Some(skipped) = frame.skipped {
+      if skipped < self.skipped {
+        self.violation(format!("skipped went back to {skipped}"));
+      }
+
+      self.skipped = skipped;
+    }
+
+    if let Some(elapsed) = frame.elapsed {
+      if elapsed < self.elapsed {
+        self.violation(format!("elapsed went back to {elapsed}"));
+      }
+
+      self.elapsed = elapsed;
+    }
+
+    if !frame.current_rate.is_finite() || !frame.remaining_time.is_finite() {
+      self.violation(format!(
+        "rate {} or remaining time {} is not finite",
+        frame.current_rate, frame.remaining_time
+      ));
+    }
+
+    for Progress(kind) in &frame.progresses {
+      if let ProgressKind::Processing(detail) = kind {
+        if detail.current > detail.total {
+          self.violation(format!(
+            "worker {} is at {} of {}",
+            detail.id, detail.current, detail.total
+          ));
+        }
+      }
+    }
+
+    self.all_done |= frame.all_done;
+    self.frames += 1;
+  }
+
+  /// Check the end of the stream, after which the violations are final.
+  fn finish(&mut self) {
+    if !self.all_done {
+      self.violation("stream ended before the final frame");
+    }
+  }
+}
+
+fn run(args: Args) -> Result<Conformance> {
+  let version = ProtocolVersion::try_from(args.protocol_version)?;
+
+  if args.frame == Framing::None && args.base64 {
+    bail!("base64 frames without framing cannot be told apart");
+  }
+
+  if args.frame == Framing::Newline && !args.base64 {
+    bail!(
+      "newline framing requires base64, msgpack frames may contain newlines"
+    );
+  }
+
+  let reader: Box<dyn Read> = match &args.listen {
+    Some(addr) => accept(addr)?,
+    None => Box::new(stdin()),
+  };
+  let mut reader = BufReader::new(reader);
+  let mut conformance = Conformance::new();
+
+  while !conformance.all_done {
+    let decoded = if args.frame == Framing::None {
+      if reader.fill_buf()?.is_empty() {
+        break;
+      }
+
+      decode(version, &mut reader).map_err(|e| format!("cannot decode: {e}"))
+    } else {
+      match read_frame(&mut reader, args.frame) {
+        Ok(Some(frame)) => decode_frame(version, frame, args.base64),
+        Ok(None) => break,
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+          Err(format!("truncated frame: {e}"))
+        }
+        Err(e) => return Err(e.into()),
+      }
+    };
+
+    match decoded {
+      Ok(frame) => conformance.check(&frame),
+      Err(e) => {
+        // the rest of the stream cannot be trusted to be aligned
+        conformance.violation(e);
+
+        return Ok(conformance);
+      }
+    }
+  }
+
+  conformance.finish();
+
+  // the result follows
+  io::copy(&mut reader, &mut io::sink())?;
+
+  Ok(conformance)
+}
+
+/// Decode a delimited frame, which has to be read entirely.
+fn decode_frame(
+  version: ProtocolVersion,
+  frame: Vec<u8>,
+  base64: bool,
+) -> std::result::Result<Frame, String> {
+  let frame = if base64 {
+    general_purpose::STANDARD
+      .decode(frame)
+      .map_err(|e| format!("invalid base64: {e}"))?
+  } else {
+    frame
+  };
+  let frame = Compression::decompress(frame)
+    .map_err(|e| format!("cannot decompress: {e}"))?;
+  let mut rest = &frame[..];
+  let decoded =
+    decode(version, &mut rest).map_err(|e| format!("cannot decode: {e}"))?;
+
+  if !rest.is_empty() {
+    return Err(format!("{} bytes after the frame", rest.len()));
+  }
+
+  Ok(decoded)
+}
+
+fn main() -> Result<ExitCode> {
+  let conformance = run(Args::parse())?;
+
+  if conformance.violations.is_empty() {
+    println!("{} frames conform", conformance.frames);
+
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  for violation in &conformance.violations {
+    eprintln!("{violation}");
+  }
+
+  Ok(ExitCode::FAILURE)
+}
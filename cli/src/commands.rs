@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use clap::{value_parser, Args, Subcommand};
+use wakuchin::protocol::ProtocolVersion;
+
+use crate::error::Result;
+use crate::handlers::{
+  HandlerConfig, HandlerKind, InternalCompression, InternalFraming,
+};
+use crate::registry::Registry;
+use crate::workspace::default_root;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bugreport;
+pub mod demo;
+pub mod doctor;
+pub mod export;
+pub mod key;
+pub mod record;
+pub mod replay;
+pub mod runs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod submit;
+pub mod verify;
+
+#[derive(Args)]
+pub struct RegistryArgs {
+  /// Root directory of run workspaces
+  #[arg(long, value_name = "DIR")]
+  workspace: Option<PathBuf>,
+}
+
+impl RegistryArgs {
+  fn registry(&self) -> Registry {
+    Registry::new(self.workspace.clone().unwrap_or_else(default_root))
+  }
+}
+
+#[derive(Args)]
+pub struct HandlerArgs {
+  /// Progress handler to use, see "wakuchin --help"
+  #[arg(long, value_name = "HANDLER", value_enum)]
+  handler: Option<HandlerKind>,
+
+  /// How consecutive msgpack frames are delimited
+  #[arg(long, value_name = "FRAMING", value_enum, default_value = "none")]
+  frame: InternalFraming,
+
+  /// Compression of each msgpack frame
+  #[arg(long, value_name = "COMPRESSION", value_enum, default_value = "none")]
+  compression: InternalCompression,
+
+  /// Layout of the msgpack progress frames
+  #[arg(
+    long,
+    value_name = "VERSION",
+    default_value_t = ProtocolVersion::LATEST.number(),
+    value_parser = value_parser!(u8).range(1..=ProtocolVersion::LATEST.number() as i64)
+  )]
+  protocol_version: u8,
+}
+
+impl HandlerArgs {
+  /// Options of the handler, `default` if --handler is omitted.
+  fn handler(&self, default: HandlerKind) -> HandlerConfig {
+    HandlerConfig {
+      kind: self.handler.clone().unwrap_or(default),
+      frame: self.frame,
+      compression: self.compression,
+      ..Default::default()
+    }
+  }
+
+  fn protocol_version(&self) -> anyhow::Result<ProtocolVersion> {
+    ProtocolVersion::try_from(self.protocol_version).map_err(Into::into)
+  }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// Inspect and clean up run workspaces
+  #[command(subcommand)]
+  Runs(runs::RunsCommand),
+
+  /// Export the metadata and result of a run as a single JSON file
+  Export(export::ExportCommand),
+
+  /// Manage the key used to sign exported results
+  #[command(subcommand)]
+  Key(key::KeyCommand),
+
+  /// Check an exported result and its signature
+  Verify(verify::VerifyCommand),
+
+  /// Submit a signed result to a leaderboard
+  #[cfg(not(target_arch = "wasm32"))]
+  Submit(submit::SubmitCommand),
+
+  /// Check the environment for common problems and print how to fix them
+  Doctor(doctor::DoctorCommand),
+
+  /// Collect what maintainers need to look into a bug into a single
+  /// redacted file to attach to an issue
+  #[cfg(not(target_arch = "wasm32"))]
+  #[command(name = "bugreport")]
+  BugReport(bugreport::BugReportCommand),
+
+  /// Drive a progress handler with a synthetic research, for developing
+  /// frontends without waiting for real runs
+  Demo(demo::DemoCommand),
+
+  /// Copy a msgpack progress stream to stdout, recording it with timestamps
+  Record(record::RecordCommand),
+
+  /// Emit a stream recorded by "wakuchin record" again
+  Replay(replay::ReplayCommand),
+}
+
+impl Command {
+  pub fn run(self) -> Result<()> {
+    match self {
+      Self::Runs(command) => command.run(),
+      Self::Export(command) => command.run(),
+      Self::Key(command) => command.run(),
+      Self::Verify(command) => command.run(),
+      #[cfg(not(target_arch = "wasm32"))]
+      Self::Submit(command) => command.run(),
+      Self::Doctor(command) => command.run(),
+      #[cfg(not(target_arch = "wasm32"))]
+      Self::BugReport(command) => command.run(),
+      Self::Demo(command) => command.run(),
+      Self::Record(command) => command.run(),
+      Self::Replay(command) => command.run(),
+    }
+  }
+}
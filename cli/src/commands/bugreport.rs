@@ -0,0 +1,422 @@
+use std::collections::BTreeMap;
+use std::env::{self, consts};
+use std::fs::{self, read_dir};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use clap::Args;
+use serde::Serialize;
+use serde_json::Value;
+use ulid::Ulid;
+use wakuchin::fs::atomic_write;
+
+use crate::app::build;
+use crate::config::{discover_config, load_config, Config};
+use crate::error::{AppError, Result};
+use crate::heartbeat::{Heartbeat, HEARTBEAT_FILE};
+use crate::registry::Registry;
+use crate::workspace::{hostname, Artifact, RunMetadata};
+
+use super::doctor::{check_environment, Check};
+use super::RegistryArgs;
+
+/// Lines kept from the end of each log.
+const TAIL_LINES: usize = 200;
+
+/// Environment variables which change how wakuchin behaves, the only ones
+/// included in reports.
+const VARIABLES: &[&str] = &[
+  "TERM",
+  "COLORTERM",
+  "NO_COLOR",
+  "COLUMNS",
+  "LINES",
+  "LANG",
+  "LC_ALL",
+  "LC_NUMERIC",
+];
+
+#[derive(Args)]
+pub struct BugReportCommand {
+  /// Id (or prefix of it) of the run to report, defaults to the latest run
+  #[arg(value_name = "RUN")]
+  run: Option<String>,
+
+  /// Config file of the research, searched for like for a research if
+  /// omitted
+  #[arg(long, value_name = "FILE")]
+  config: Option<PathBuf>,
+
+  /// Write to FILE instead of "wakuchin-bugreport-<ID>.json"
+  #[arg(short, long, value_name = "FILE")]
+  output: Option<PathBuf>,
+
+  #[command(flatten)]
+  registry: RegistryArgs,
+}
+
+/// Everything maintainers ask for in an issue, written by
+/// `wakuchin bugreport`.
+#[derive(Serialize)]
+struct BugReport {
+  created_at: String,
+  build: BuildInfo,
+  environment: Environment,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  config: Option<ConfigReport>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  run: Option<RunReport>,
+  /// Logs of the researches in the background
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  logs: BTreeMap<String, Vec<String>>,
+  /// What could not be collected and why
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct BuildInfo {
+  version: &'static str,
+  commit: &'static str,
+  branch: &'static str,
+  build_time: &'static str,
+  target: &'static str,
+  rust_version: &'static str,
+  features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+  fn new() -> Self {
+    let features = [
+      ("battery", cfg!(feature = "battery")),
+      ("dfa", cfg!(feature = "dfa")),
+      ("i18n", cfg!(feature = "i18n")),
+      ("notify", cfg!(feature = "notify")),
+      ("rng-chacha", cfg!(feature = "rng-chacha")),
+      ("sequential", cfg!(feature = "sequential")),
+      ("sqlite", cfg!(feature = "sqlite")),
+      ("sse", cfg!(feature = "sse")),
+      ("thermal", cfg!(feature = "thermal")),
+      ("webhook", cfg!(feature = "webhook")),
+      ("zstd", cfg!(feature = "zstd")),
+    ];
+
+    Self {
+      version: build::PKG_VERSION,
+      commit: build::COMMIT_HASH,
+      branch: build::BRANCH,
+      build_time: build::BUILD_TIME,
+      target: build::BUILD_TARGET,
+      rust_version: build::RUST_VERSION,
+      features: features
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect(),
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct Environment {
+  os: &'static str,
+  arch: &'static str,
+  variables: BTreeMap<&'static str, String>,
+  /// Checks of `wakuchin doctor`
+  diagnostics: Vec<Check>,
+}
+
+#[derive(Serialize)]
+struct ConfigReport {
+  path: PathBuf,
+  contents: String,
+  /// Config as the research sees it, with the defaults filled in, `None`
+  /// if it cannot be loaded
+  #[serde(skip_serializing_if = "Option::is_none")]
+  effective: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RunReport {
+  metadata: RunMetadata,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  heartbeat: Option<Heartbeat>,
+  /// Latest progress snapshot written on SIGUSR1
+  #[serde(skip_serializing_if = "Option::is_none")]
+  snapshot: Option<Value>,
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  journal: BTreeMap<String, Vec<String>>,
+}
+
+/// Replaces what identifies the user or their machine in the text of a
+/// report.
+struct Redactor {
+  /// Text to replace and its replacement, longest first
+  replacements: Vec<(String, &'static str)>,
+}
+
+impl Redactor {
+  fn new(replacements: Vec<(String, &'static str)>) -> Self {
+    let mut replacements = replacements
+      .into_iter()
+      // too short to be told apart from other words
+      .filter(|(text, _)| text.len() >= 3)
+      .collect::<Vec<_>>();
+
+    replacements.sort_by_key(|(text, _)| std::cmp::Reverse(text.len()));
+
+    Self { replacements }
+  }
+
+  /// Redact the home directory, the user name and the host name.
+  fn from_env() -> Self {
+    let mut replacements = Vec::new();
+
+    if let Some(home) = dirs::home_dir() {
+      replacements.push((home.to_string_lossy().into_owned(), "~"));
+    }
+
+    if let Ok(user) = env::var("USER").or_else(|_| env::var("USERNAME")) {
+      replacements.push((user, "<user>"));
+    }
+
+    if let Some(host) = hostname() {
+      replacements.push((host, "<host>"));
+    }
+
+    Self::new(replacements)
+  }
+
+  fn redact(&self, text: &str) -> String {
+    self
+      .replacements
+      .iter()
+      .fold(text.to_owned(), |text, (from, to)| text.replace(from, to))
+  }
+
+  fn redact_value(&self, value: &mut Value) {
+    match value {
+      Value::String(text) => *text = self.redact(text),
+      Value::Array(values) => {
+        values.iter_mut().for_each(|value| self.redact_value(value));
+      }
+      Value::Object(values) => {
+        values
+          .values_mut()
+          .for_each(|value| self.redact_value(value));
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Return the last [`TAIL_LINES`] lines of the file at `path`.
+fn tail(path: &Path) -> std::io::Result<Vec<String>> {
+  let contents = fs::read(path)?;
+  let contents = String::from_utf8_lossy(&contents);
+  let lines = contents.lines().collect::<Vec<_>>();
+
+  Ok(
+    lines[lines.len().saturating_sub(TAIL_LINES)..]
+      .iter()
+      .map(|line| (*line).to_owned())
+      .collect(),
+  )
+}
+
+impl BugReportCommand {
+  /// Load the config of the research, the default one if there is none.
+  fn config(&self, errors: &mut Vec<String>) -> (Config, Option<ConfigReport>) {
+    let discovered = || {
+      env::current_dir()
+        .ok()
+        .and_then(|dir| discover_config(&dir))
+    };
+    let Some(path) = self.config.clone().or_else(discovered) else {
+      return (Config::default(), None);
+    };
+
+    let contents = match fs::read_to_string(&path) {
+      Ok(contents) => contents,
+      Err(e) => {
+        errors.push(format!("cannot read {}: {e}", path.display()));
+
+        return (Config::default(), None);
+      }
+    };
+
+    let (config, effective) = match load_config(&path) {
+      Ok(config) => {
+        let effective = format!("{config:#?}");
+
+        (config, Some(effective))
+      }
+      Err(e) => {
+        errors.push(format!("cannot load {}: {e}", path.display()));
+
+        (Config::default(), None)
+      }
+    };
+
+    (
+      config,
+      Some(ConfigReport {
+        path,
+        contents,
+        effective,
+      }),
+    )
+  }
+
+  fn run_report(
+    &self,
+    config: &Config,
+    errors: &mut Vec<String>,
+  ) -> Option<RunReport> {
+    let registry = Registry::new(
+      self
+        .registry
+        .workspace
+        .clone()
+        .unwrap_or_else(|| config.workspace_root()),
+    );
+    let entry = match registry.find(self.run.as_deref()) {
+      Ok(entry) => entry,
+      Err(e) => {
+        errors.push(format!("cannot find the run: {e}"));
+
+        return None;
+      }
+    };
+
+    let heartbeat = fs::read_to_string(entry.dir.join(HEARTBEAT_FILE))
+      .ok()
+      .and_then(|contents| serde_json::from_str(&contents).ok());
+    let snapshot = (!config.snapshot_file.as_os_str().is_empty())
+      .then(|| fs::read_to_string(&config.snapshot_file).ok())
+      .flatten()
+      .and_then(|contents| serde_json::from_str(&contents).ok());
+
+    let mut journal = BTreeMap::new();
+
+    if let Ok(files) = read_dir(entry.dir.join(Artifact::Journal.dir_name())) {
+      for file in files.flatten() {
+        match tail(&file.path()) {
+          Ok(lines) => {
+            journal
+              .insert(file.file_name().to_string_lossy().into_owned(), lines);
+          }
+          Err(e) => {
+            errors.push(format!("cannot read {}: {e}", file.path().display()))
+          }
+        }
+      }
+    }
+
+    let mut metadata = entry.metadata;
+
+    metadata.redact();
+
+    Some(RunReport {
+      metadata,
+      heartbeat,
+      snapshot,
+      journal,
+    })
+  }
+
+  pub fn run(self) -> Result<()> {
+    let mut errors = Vec::new();
+    let (config, config_report) = self.config(&mut errors);
+    let run = self.run_report(&config, &mut errors);
+    let mut logs = BTreeMap::new();
+    let (_, log_file) = config.daemon_files();
+
+    if log_file.is_file() {
+      match tail(&log_file) {
+        Ok(lines) => {
+          logs.insert(log_file.to_string_lossy().into_owned(), lines);
+        }
+        Err(e) => {
+          errors.push(format!("cannot read {}: {e}", log_file.display()))
+        }
+      }
+    }
+
+    let report = BugReport {
+      created_at: humantime::format_rfc3339_seconds(SystemTime::now())
+        .to_string(),
+      build: BuildInfo::new(),
+      environment: Environment {
+        os: consts::OS,
+        arch: consts::ARCH,
+        variables: VARIABLES
+          .iter()
+          .filter_map(|name| env::var(name).ok().map(|value| (*name, value)))
+          .collect(),
+        diagnostics: check_environment(&config.workspace_root()),
+      },
+      config: config_report,
+      run,
+      logs,
+      errors,
+    };
+
+    let mut report =
+      serde_json::to_value(report).map_err(|e| AppError::Other(e.into()))?;
+
+    Redactor::from_env().redact_value(&mut report);
+
+    let contents = serde_json::to_string_pretty(&report)
+      .map_err(|e| AppError::Other(e.into()))?;
+    let path = self.output.unwrap_or_else(|| {
+      PathBuf::from(format!("wakuchin-bugreport-{}.json", Ulid::new()))
+    });
+
+    atomic_write(&path, contents).map_err(|source| {
+      AppError::WorkspaceIoError {
+        path: path.as_path().into(),
+        source,
+      }
+    })?;
+
+    println!(
+      "Wrote {}, check it before attaching it to an issue",
+      path.display()
+    );
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use serde_json::json;
+
+  use super::Redactor;
+
+  #[test]
+  fn test_redact() {
+    let redactor = Redactor::new(vec![
+      ("/home/alice".into(), "~"),
+      ("alice".into(), "<user>"),
+      ("alice-desktop".into(), "<host>"),
+      ("al".into(), "<short>"),
+    ]);
+    let mut report = json!({
+      "config": { "path": "/home/alice/wakuchin.toml" },
+      "logs": ["started on alice-desktop by alice", "all done"],
+      "tries": 10,
+    });
+
+    redactor.redact_value(&mut report);
+
+    assert_eq!(
+      report,
+      json!({
+        "config": { "path": "~/wakuchin.toml" },
+        "logs": ["started on <host> by <user>", "all done"],
+        "tries": 10,
+      })
+    );
+  }
+}
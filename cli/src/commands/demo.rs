@@ -0,0 +1,302 @@
+use std::thread;
+use std::time::Duration;
+
+use anyhow::bail;
+use clap::Args;
+use wakuchin::handlers::{ControlFlow, RunInfo};
+use wakuchin::math::total_permutations;
+use wakuchin::progress::{
+  DoneDetail, IdleDetail, ProcessingDetail, Progress, ProgressKind,
+};
+use wakuchin::result::HitCount;
+use wakuchin::symbol::SymbolSet;
+
+use crate::config::{parse_count, parse_duration};
+use crate::error::Result;
+use crate::handlers::HandlerKind;
+
+use super::HandlerArgs;
+
+/// Parse a speed factor, e.g. "10x" or "0.5".
+fn parse_speed(speed: &str) -> std::result::Result<f64, String> {
+  let factor = speed
+    .strip_suffix('x')
+    .unwrap_or(speed)
+    .parse::<f64>()
+    .map_err(|e| e.to_string())?;
+
+  if !factor.is_finite() || factor <= 0.0 {
+    return Err("speed must be a positive factor, e.g. \"10x\"".into());
+  }
+
+  Ok(factor)
+}
+
+#[derive(Args)]
+pub struct DemoCommand {
+  /// How much faster than real time to play the research, e.g. "10x"
+  #[arg(long, value_name = "FACTOR", default_value = "1x", value_parser = parse_speed)]
+  speed: f64,
+
+  /// Duration of the synthetic research in real time, before --speed
+  #[arg(long, value_name = "DURATION", default_value = "1m", value_parser = parse_duration)]
+  duration: Duration,
+
+  /// Number of tries
+  #[arg(short = 'i', long, value_name = "N", default_value = "100M", value_parser = parse_count)]
+  tries: usize,
+
+  /// Wakuchin times n
+  #[arg(short, long, value_name = "N", default_value_t = 2)]
+  times: usize,
+
+  /// Number of workers
+  #[arg(short, long, value_name = "N", default_value_t = 4)]
+  workers: usize,
+
+  /// Progress refresh interval, before --speed
+  #[arg(short = 'd', long, value_name = "DURATION", default_value = "300ms", value_parser = parse_duration)]
+  interval: Duration,
+
+  /// Seed of the script, the same seed plays the same research
+  #[arg(long, value_name = "N", default_value_t = 0)]
+  seed: u64,
+
+  #[command(flatten)]
+  handler: HandlerArgs,
+}
+
+/// Arguments of a single [`ProgressHandler::handle`] call.
+///
+/// [`ProgressHandler::handle`]: wakuchin::handlers::ProgressHandler::handle
+#[derive(Debug)]
+struct Frame {
+  progresses: Vec<Progress>,
+  hit_counts: Vec<HitCount>,
+  current_diff: usize,
+  all_done: bool,
+}
+
+struct Worker {
+  current: usize,
+  total: usize,
+  /// Tries per frame
+  speed: usize,
+}
+
+/// Deterministic script of a research, yielding a frame per interval from
+/// all workers idle to all workers done.
+struct Script {
+  rng: fastrand::Rng,
+  symbols: Vec<u8>,
+  workers: Vec<Worker>,
+  hit_counts: Vec<HitCount>,
+  /// Probability of a try to hit each of `hit_counts`
+  probability: f64,
+  started: bool,
+  finished: bool,
+}
+
+impl Script {
+  fn new(
+    tries: usize,
+    times: usize,
+    workers: usize,
+    frames: usize,
+    seed: u64,
+  ) -> Self {
+    let rng = fastrand::Rng::with_seed(seed);
+    let workers = (0..workers)
+      .map(|id| {
+        let total = tries / workers + usize::from(id < tries % workers);
+        // some workers are up to 20% slower or faster than the others
+        let speed = total as f64 / frames as f64 * (0.8 + rng.f64() * 0.4);
+
+        Worker {
+          current: 0,
+          total,
+          speed: (speed as usize).max(1),
+        }
+      })
+      .collect();
+
+    Self {
+      rng,
+      symbols: b"WKCN".repeat(times),
+      workers,
+      hit_counts: vec![
+        HitCount::new("WKNC".repeat(times), 0),
+        HitCount::new("WCKN".repeat(times), 0),
+      ],
+      probability: total_permutations(times)
+        .map_or(0.0, |permutations| 1.0 / permutations as f64),
+      started: false,
+      finished: false,
+    }
+  }
+
+  fn candidate(&mut self) -> String {
+    self.rng.shuffle(&mut self.symbols);
+
+    String::from_utf8_lossy(&self.symbols).into_owned()
+  }
+}
+
+impl Iterator for Script {
+  type Item = Frame;
+
+  fn next(&mut self) -> Option<Frame> {
+    if self.finished {
+      return None;
+    }
+
+    if !self.started {
+      self.started = true;
+
+      return Some(Frame {
+        progresses: (1..=self.workers.len())
+          .map(|id| Progress(ProgressKind::Idle(IdleDetail { id })))
+          .collect(),
+        hit_counts: self.hit_counts.clone(),
+        current_diff: 0,
+        all_done: false,
+      });
+    }
+
+    let mut current_diff = 0;
+    let mut progresses = Vec::with_capacity(self.workers.len());
+
+    for id in 0..self.workers.len() {
+      let worker = &mut self.workers[id];
+      let step = worker.speed.min(worker.total - worker.current);
+
+      worker.current += step;
+      current_diff += step;
+
+      let (current, total) = (worker.current, worker.total);
+
+      progresses.push(Progress(if current == total {
+        ProgressKind::Done(DoneDetail { id: id + 1, total })
+      } else {
+        ProgressKind::Processing(ProcessingDetail {
+          id: id + 1,
+          wakuchin: self.candidate().into(),
+          current,
+          total,
+        })
+      }));
+    }
+
+    for hit_count in &mut self.hit_counts {
+      let expected = current_diff as f64 * self.probability;
+
+      hit_count.hits +=
+        expected as usize + usize::from(self.rng.f64() < expected.fract());
+    }
+
+    self.finished = self
+      .workers
+      .iter()
+      .all(|worker| worker.current == worker.total);
+
+    Some(Frame {
+      progresses,
+      hit_counts: self.hit_counts.clone(),
+      current_diff,
+      all_done: self.finished,
+    })
+  }
+}
+
+impl DemoCommand {
+  pub fn run(self) -> Result<()> {
+    self.play().map_err(Into::into)
+  }
+
+  fn play(self) -> anyhow::Result<()> {
+    if self.workers == 0 || self.times == 0 || self.interval.is_zero() {
+      bail!("--workers, --times and --interval must not be zero");
+    }
+
+    let mut handler = self.handler.handler(HandlerKind::Console).build(
+      false,
+      self.tries,
+      self.times,
+      &SymbolSet::default(),
+      self.handler.protocol_version()?,
+      0,
+    )?;
+    let frames = (self.duration.as_secs_f64() / self.interval.as_secs_f64())
+      .max(1.0) as usize;
+    let script =
+      Script::new(self.tries, self.times, self.workers, frames, self.seed);
+    let pause = self.interval.div_f64(self.speed);
+
+    handler.before_start(&RunInfo {
+      tries: self.tries,
+      times: self.times,
+      ..RunInfo::new(self.workers)
+    })?;
+
+    for frame in script {
+      let flow = handler.handle(
+        &frame.progresses,
+        &frame.hit_counts,
+        self.interval,
+        frame.current_diff,
+        frame.all_done,
+      )?;
+
+      if frame.all_done {
+        break;
+      }
+
+      if flow == ControlFlow::Stop {
+        handler.on_accidential_stop()?;
+
+        return Ok(());
+      }
+
+      thread::sleep(pause);
+    }
+
+    handler.after_finish()?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use wakuchin::progress::{Progress, ProgressKind};
+
+  use super::{parse_speed, Script};
+
+  #[test]
+  fn test_parse_speed() {
+    assert_eq!(parse_speed("10x"), Ok(10.0));
+    assert_eq!(parse_speed("0.5"), Ok(0.5));
+    assert!(parse_speed("0x").is_err());
+    assert!(parse_speed("fast").is_err());
+  }
+
+  #[test]
+  fn test_script() {
+    let script = || Script::new(10_000, 2, 3, 20, 42).collect::<Vec<_>>();
+    let frames = script();
+    let last = frames.last().unwrap();
+
+    assert!(frames[0]
+      .progresses
+      .iter()
+      .all(|progress| matches!(progress, Progress(ProgressKind::Idle(_)))));
+    assert!(last.all_done);
+    assert_eq!(
+      frames.iter().map(|frame| frame.current_diff).sum::<usize>(),
+      10_000
+    );
+
+    // the same seed plays the same research
+    assert_eq!(format!("{frames:?}"), format!("{:?}", script()));
+  }
+}
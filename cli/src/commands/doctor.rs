@@ -0,0 +1,475 @@
+use std::fs::{self, read_to_string};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, available_parallelism};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use clap::Args;
+use console::Term;
+use owo_colors::OwoColorize as _;
+use serde::Serialize;
+use ulid::Ulid;
+use wakuchin::handlers::RunInfo;
+use wakuchin::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind,
+};
+use wakuchin::protocol::ProtocolVersion;
+use wakuchin::result::HitCount;
+
+use crate::error::Result;
+use crate::handlers::{
+  HandlerConfig, HandlerKind, InternalCompression, DEFAULT_TERMINAL_WIDTH,
+};
+use crate::signing::default_key_path;
+use crate::workspace::default_root;
+
+use super::HandlerArgs;
+
+/// Time the consumer is given to exit after the end of the handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Args)]
+pub struct DoctorCommand {
+  /// Directory to store run artifacts in, defaults to the platform data
+  /// directory
+  #[arg(long, value_name = "DIR")]
+  workspace: Option<PathBuf>,
+
+  #[command(flatten)]
+  handler: HandlerArgs,
+
+  /// Consumer of msgpack progress to check, started with a few frames on
+  /// its stdin, e.g. "-- python3 consumer.py"
+  #[arg(last = true, value_name = "CONSUMER")]
+  consumer: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum Status {
+  Ok,
+  Warn,
+  Fail,
+}
+
+/// Outcome of a single check, with how to fix it if it did not pass.
+#[derive(Serialize)]
+pub(super) struct Check {
+  status: Status,
+  name: &'static str,
+  detail: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  fix: Option<String>,
+}
+
+impl Check {
+  fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+    Self {
+      status: Status::Ok,
+      name,
+      detail: detail.into(),
+      fix: None,
+    }
+  }
+
+  fn warn(
+    name: &'static str,
+    detail: impl Into<String>,
+    fix: impl Into<String>,
+  ) -> Self {
+    Self {
+      status: Status::Warn,
+      name,
+      detail: detail.into(),
+      fix: Some(fix.into()),
+    }
+  }
+
+  fn fail(
+    name: &'static str,
+    detail: impl Into<String>,
+    fix: impl Into<String>,
+  ) -> Self {
+    Self {
+      status: Status::Fail,
+      name,
+      detail: detail.into(),
+      fix: Some(fix.into()),
+    }
+  }
+
+  fn print(&self) {
+    let status = match self.status {
+      Status::Ok => "  ok".green().to_string(),
+      Status::Warn => "warn".yellow().to_string(),
+      Status::Fail => "fail".red().to_string(),
+    };
+
+    println!("{status} {}: {}", self.name.bold(), self.detail);
+
+    if let Some(fix) = &self.fix {
+      println!("     {} {fix}", "= fix:".blue().bold());
+    }
+  }
+}
+
+/// Check the terminal the console handler draws progress on.
+fn check_terminal(workers: usize) -> Vec<Check> {
+  let term = Term::stderr();
+
+  if !term.is_term() {
+    return vec![Check::warn(
+      "terminal",
+      "stderr is not a terminal, progress is printed without redrawing",
+      "run wakuchin in a terminal, or pass --handler msgpack to pipe progress to a program",
+    )];
+  }
+
+  let mut checks = Vec::new();
+  let features = term.features();
+
+  checks.push(if features.colors_supported() {
+    Check::ok("colors", "supported")
+  } else {
+    Check::warn(
+      "colors",
+      "not supported, progress is printed without colors",
+      "set TERM to a color terminal, e.g. \"xterm-256color\"",
+    )
+  });
+
+  checks.push(if features.wants_emoji() {
+    Check::ok("unicode", "UTF-8 locale")
+  } else {
+    Check::warn(
+      "unicode",
+      "no UTF-8 locale, \"わくちん\" and progress bars may be garbled",
+      "set LANG to a UTF-8 locale, e.g. \"en_US.UTF-8\"",
+    )
+  });
+
+  checks.push(match term.size_checked() {
+    Some((rows, columns)) if columns < DEFAULT_TERMINAL_WIDTH => Check::warn(
+      "size",
+      format!("{columns}x{rows}, progress lines wrap"),
+      format!("widen the terminal to {DEFAULT_TERMINAL_WIDTH} columns at least"),
+    ),
+    Some((rows, columns)) if usize::from(rows) < workers + 3 => Check::warn(
+      "size",
+      format!(
+        "{columns}x{rows}, progress of {workers} workers is folded into one line"
+      ),
+      format!("enlarge the terminal to {} rows, or pass -w", workers + 3),
+    ),
+    Some((rows, columns)) => Check::ok("size", format!("{columns}x{rows}")),
+    None => Check::warn(
+      "size",
+      format!("unknown, progress is drawn for {DEFAULT_TERMINAL_WIDTH} columns"),
+      "set COLUMNS and LINES if the terminal does not report its size",
+    ),
+  });
+
+  checks
+}
+
+/// Parse the CPU quota of `cpu.max` of cgroup v2, `None` for no quota.
+fn parse_cpu_max(contents: &str) -> Option<f64> {
+  let mut fields = contents.split_whitespace();
+  let quota = fields.next()?.parse::<f64>().ok()?;
+  let period = fields.next()?.parse::<f64>().ok()?;
+
+  (period > 0.0).then(|| quota / period)
+}
+
+/// Read the CPU quota of the cgroup of this process, in CPUs.
+fn cgroup_cpu_limit() -> Option<f64> {
+  if let Ok(contents) = read_to_string("/sys/fs/cgroup/cpu.max") {
+    return parse_cpu_max(&contents);
+  }
+
+  // cgroup v1, -1 means no quota
+  let quota = read_to_string("/sys/fs/cgroup/cpu/cpu.cfs_quota_us").ok()?;
+  let period = read_to_string("/sys/fs/cgroup/cpu/cpu.cfs_period_us").ok()?;
+
+  parse_cpu_max(&format!("{} {}", quota.trim(), period.trim()))
+    .filter(|limit| *limit > 0.0)
+}
+
+/// Check the number of workers started by default.
+fn check_parallelism() -> (Vec<Check>, usize) {
+  let workers = match available_parallelism() {
+    Ok(workers) => workers.get(),
+    Err(e) => {
+      return (
+        vec![Check::fail(
+          "parallelism",
+          format!("cannot count the CPUs: {e}"),
+          "pass the number of workers with -w",
+        )],
+        1,
+      )
+    }
+  };
+
+  let mut checks = vec![if workers == 1 {
+    Check::warn(
+      "parallelism",
+      "1 CPU available, researches run on a single worker",
+      "allow more CPUs to the process, e.g. with taskset or the container runtime",
+    )
+  } else {
+    Check::ok("parallelism", format!("{workers} workers by default"))
+  }];
+
+  if let Some(limit) = cgroup_cpu_limit() {
+    let allowed = limit.ceil() as usize;
+
+    checks.push(if allowed < workers {
+      Check::warn(
+        "cgroup",
+        format!(
+          "CPU quota of {limit:.2} CPUs, {workers} workers would be throttled"
+        ),
+        format!("pass -w {}", allowed.max(1)),
+      )
+    } else {
+      Check::ok("cgroup", format!("CPU quota of {limit:.2} CPUs"))
+    });
+  } else if cfg!(target_os = "linux") {
+    checks.push(Check::ok("cgroup", "no CPU quota"));
+  }
+
+  (checks, workers)
+}
+
+/// Check that files can be created in `dir`, creating it if needed.
+fn check_writable(name: &'static str, dir: &Path, fix: &str) -> Check {
+  let probe = dir.join(format!(".doctor-{}", Ulid::new()));
+  let written = fs::create_dir_all(dir)
+    .and_then(|_| fs::write(&probe, b""))
+    .and_then(|_| fs::remove_file(&probe));
+
+  match written {
+    Ok(()) => Check::ok(name, format!("{} is writable", dir.display())),
+    Err(e) => Check::fail(
+      name,
+      format!("{} is not writable: {e}", dir.display()),
+      format!("fix the permissions of {}, or {fix}", dir.display()),
+    ),
+  }
+}
+
+/// Feed a few progress frames to `consumer` and wait for it to exit
+/// successfully after the end of its stdin.
+fn handshake(
+  consumer: &[String],
+  handler: &HandlerConfig,
+  protocol_version: ProtocolVersion,
+) -> std::result::Result<(), String> {
+  let mut child = Command::new(&consumer[0])
+    .args(&consumer[1..])
+    .stdin(Stdio::piped())
+    .spawn()
+    .map_err(|e| format!("cannot start {}: {e}", consumer[0]))?;
+
+  let stdin = child.stdin.take().expect("stdin is piped");
+  let writer: Arc<Mutex<dyn Write + Send>> = Arc::new(Mutex::new(stdin));
+  let mut progress_handler = handler
+    .build_msgpack(2, writer, protocol_version)
+    .map_err(|e| e.to_string())?;
+  let hit_counts = [HitCount::new("WKNCWKNC", 0)];
+
+  let fed = progress_handler
+    .before_start(&RunInfo {
+      tries: 2,
+      ..RunInfo::new(1)
+    })
+    .and_then(|_| {
+      progress_handler.handle(
+        &[Progress(ProgressKind::Processing(ProcessingDetail {
+          id: 1,
+          wakuchin: "WKCNWKCN".into(),
+          current: 1,
+          total: 2,
+        }))],
+        &hit_counts,
+        Duration::from_millis(100),
+        1,
+        false,
+      )
+    })
+    .and_then(|_| {
+      progress_handler.handle(
+        &[Progress(ProgressKind::Done(DoneDetail { id: 1, total: 2 }))],
+        &hit_counts,
+        Duration::from_millis(200),
+        1,
+        true,
+      )
+    })
+    .and_then(|_| progress_handler.after_finish());
+
+  // close stdin so the consumer sees the end of the stream
+  drop(progress_handler);
+
+  let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+
+  let status = loop {
+    match child.try_wait() {
+      Ok(Some(status)) => break status,
+      Ok(None) if Instant::now() < deadline => {
+        thread::sleep(Duration::from_millis(50));
+      }
+      Ok(None) => {
+        let _ = child.kill();
+        let _ = child.wait();
+
+        return Err(format!(
+          "still running {}s after the end of its stdin",
+          HANDSHAKE_TIMEOUT.as_secs()
+        ));
+      }
+      Err(e) => return Err(format!("cannot wait for the consumer: {e}")),
+    }
+  };
+
+  if let Err(e) = fed {
+    return Err(format!("stopped reading before the end: {e}"));
+  }
+
+  if !status.success() {
+    return Err(format!("exited with {status}"));
+  }
+
+  Ok(())
+}
+
+/// Run the checks which need nothing but the environment and `workspace`,
+/// the root of run workspaces.
+pub(super) fn check_environment(workspace: &Path) -> Vec<Check> {
+  let (mut checks, workers) = check_parallelism();
+
+  checks.extend(check_terminal(workers));
+  checks.push(check_writable("workspace", workspace, "pass --workspace"));
+
+  let key_path = default_key_path();
+
+  if let Some(dir) = key_path.parent() {
+    checks.push(check_writable(
+      "config",
+      dir,
+      "pass --key to wakuchin key gen",
+    ));
+  }
+
+  checks
+}
+
+impl DoctorCommand {
+  fn check_consumer(&self) -> anyhow::Result<Check> {
+    const NAME: &str = "consumer";
+
+    let handler = self.handler.handler(HandlerKind::Msgpack);
+
+    match handler.kind {
+      HandlerKind::Console => {
+        return Err(anyhow!("the console handler has no consumer to check"))
+      }
+      HandlerKind::Csv => {
+        return Err(anyhow!("the csv handler has no consumer to check"))
+      }
+      HandlerKind::Plain => {
+        return Err(anyhow!("the plain handler has no consumer to check"))
+      }
+      HandlerKind::Msgpack | HandlerKind::MsgpackBase64 => {}
+    }
+
+    handler.check_msgpack()?;
+    handler.compression()?;
+
+    let protocol_version = self.handler.protocol_version()?;
+    let uncompressed = HandlerConfig {
+      compression: InternalCompression::None,
+      ..handler.clone()
+    };
+
+    Ok(match handshake(&self.consumer, &handler, protocol_version) {
+      Ok(()) => Check::ok(
+        NAME,
+        format!("read protocol {} frames", protocol_version.number()),
+      ),
+      Err(e)
+        if handler.compression != InternalCompression::None
+          && handshake(&self.consumer, &uncompressed, protocol_version)
+            .is_ok() =>
+      {
+        Check::fail(
+          NAME,
+          format!("rejected compressed frames ({e}), but read uncompressed frames"),
+          "pass --compression none, or update the consumer",
+        )
+      }
+      Err(e) if protocol_version != ProtocolVersion::V1 => {
+        match handshake(&self.consumer, &handler, ProtocolVersion::V1) {
+          Ok(()) => Check::fail(
+            NAME,
+            format!(
+              "rejected protocol {} frames ({e}), but read protocol 1 frames",
+              protocol_version.number()
+            ),
+            "pass --protocol-version 1, or update the consumer",
+          ),
+          Err(_) => Check::fail(
+            NAME,
+            e,
+            "make sure the consumer reads frames of the handler and framing from stdin until its end, and exits with 0",
+          ),
+        }
+      }
+      Err(e) => Check::fail(
+        NAME,
+        e,
+        "make sure the consumer reads frames of the handler and framing from stdin until its end, and exits with 0",
+      ),
+    })
+  }
+
+  pub fn run(self) -> Result<()> {
+    let mut checks =
+      check_environment(&self.workspace.clone().unwrap_or_else(default_root));
+
+    if !self.consumer.is_empty() {
+      checks.push(self.check_consumer()?);
+    }
+
+    for check in &checks {
+      check.print();
+    }
+
+    let failed = checks
+      .iter()
+      .filter(|check| check.status == Status::Fail)
+      .count();
+
+    if failed != 0 {
+      return Err(anyhow!("{failed} of {} checks failed", checks.len()).into());
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::parse_cpu_max;
+
+  #[test]
+  fn test_parse_cpu_max() {
+    assert_eq!(parse_cpu_max("max 100000\n"), None);
+    assert_eq!(parse_cpu_max("150000 100000\n"), Some(1.5));
+    assert_eq!(parse_cpu_max(""), None);
+  }
+}
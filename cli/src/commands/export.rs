@@ -0,0 +1,279 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use wakuchin::fs::atomic_write;
+use wakuchin::result::WakuchinResult;
+use wakuchin::validate_with;
+
+use crate::dictionary::HitDictionary;
+use crate::error::{AppError, Result};
+use crate::signing::{default_key_path, load_key, sign, signature_path};
+use crate::workspace::RunMetadata;
+
+use super::RegistryArgs;
+
+/// A run as written by `wakuchin export`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedRun {
+  pub run: RunMetadata,
+  pub result: WakuchinResult,
+}
+
+impl ExportedRun {
+  /// Strip identifying metadata, the statistical content is kept as is.
+  pub fn redact(&mut self) {
+    self.run.redact();
+  }
+
+  /// Check that the result is consistent with itself and with the run
+  /// parameters, returning a description of every violation.
+  pub fn check_invariants(&self) -> Vec<String> {
+    let mut violations = Vec::new();
+    let result = &self.result;
+    let symbols = self.run.symbols.clone().unwrap_or_default();
+
+    if self.run.tries != result.tries {
+      violations.push(format!(
+        "run has {} tries but result has {}",
+        self.run.tries, result.tries
+      ));
+    }
+
+    let hits_sum = result.hits.iter().map(|count| count.hits).sum::<usize>();
+
+    if hits_sum != result.hits_total {
+      violations.push(format!(
+        "hit counts sum up to {hits_sum} but hits_total is {}",
+        result.hits_total
+      ));
+    }
+
+    if result.hits_detail.len() > result.tries {
+      violations.push(format!(
+        "{} hits out of {} tries",
+        result.hits_detail.len(),
+        result.tries
+      ));
+    }
+
+    // hits count all their matches with MatchMode::CountAll
+    let matches = result
+      .hits_detail
+      .iter()
+      .map(|hit| hit.matches)
+      .sum::<usize>();
+
+    if matches != result.hits_total {
+      violations.push(format!(
+        "{matches} hits in detail but hits_total is {}",
+        result.hits_total
+      ));
+    }
+
+    for hit in &result.hits_detail {
+      if hit.hit_on >= result.tries {
+        violations.push(format!(
+          "hit on {} is out of {} tries",
+          hit.hit_on, result.tries
+        ));
+      }
+
+      if hit.chars.chars().count() != self.run.times * symbols.len()
+        || !validate_with(&hit.chars, &symbols)
+      {
+        violations.push(format!(
+          "'{}' cannot be generated with times {}",
+          hit.chars, self.run.times
+        ));
+      }
+
+      if !result
+        .hits
+        .iter()
+        .any(|count| count.chars == hit.chars.as_str())
+      {
+        violations.push(format!("'{}' is missing in hit counts", hit.chars));
+      }
+    }
+
+    violations
+  }
+}
+
+#[derive(Args)]
+pub struct ExportCommand {
+  /// Id (or prefix of it) of the run to export, defaults to the latest run
+  #[arg(value_name = "RUN")]
+  run: Option<String>,
+
+  /// Strip host names, labels and file paths to produce a shareable file
+  #[arg(long)]
+  redact: bool,
+
+  /// Write to FILE instead of stdout
+  #[arg(short, long, value_name = "FILE")]
+  output: Option<PathBuf>,
+
+  /// Sign the exported file, the signature is written to "<FILE>.sig"
+  #[arg(long, requires = "output")]
+  sign: bool,
+
+  /// Signing key to use, generated by "wakuchin key gen"
+  #[arg(long, value_name = "FILE")]
+  key: Option<PathBuf>,
+
+  #[command(flatten)]
+  registry: RegistryArgs,
+}
+
+impl ExportCommand {
+  pub fn run(self) -> Result<()> {
+    let registry = self.registry.registry();
+    let entry = registry.find(self.run.as_deref())?;
+    let dictionary = HitDictionary::load(registry.root())?;
+
+    let mut exported = ExportedRun {
+      result: entry.load_result(&dictionary)?,
+      run: entry.metadata,
+    };
+
+    if self.redact {
+      exported.redact();
+    }
+
+    let contents = serde_json::to_string_pretty(&exported)
+      .map_err(|e| AppError::Other(e.into()))?;
+
+    let Some(path) = self.output else {
+      println!("{contents}");
+
+      return Ok(());
+    };
+
+    let write = |path: &PathBuf, contents: &[u8]| {
+      atomic_write(path, contents).map_err(|source| {
+        AppError::WorkspaceIoError {
+          path: path.as_path().into(),
+          source,
+        }
+      })
+    };
+
+    write(&path, contents.as_bytes())?;
+
+    if self.sign {
+      let key = load_key(&self.key.unwrap_or_else(default_key_path))?;
+      let signature =
+        serde_json::to_string_pretty(&sign(&key, contents.as_bytes()))
+          .map_err(|e| AppError::Other(e.into()))?;
+
+      write(&signature_path(&path), signature.as_bytes())?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use anyhow::Result;
+  use wakuchin::result::{Hit, HitCount, WakuchinResult};
+
+  use crate::pipe::BrokenPipeEvent;
+  use crate::workspace::{RunMetadata, RunStatus};
+
+  use super::ExportedRun;
+
+  fn exported_run() -> ExportedRun {
+    ExportedRun {
+      run: RunMetadata {
+        id: "01GZZZZZZZZZZZZZZZZZZZZZZZ".into(),
+        name: Some("brave-wakuchin-42".into()),
+        emoji: None,
+        created_at: "2023-01-01T00:00:00Z".into(),
+        finished_at: Some("2023-01-01T00:01:00Z".into()),
+        status: RunStatus::Finished,
+        tries: 10,
+        times: 1,
+        regex: "WKCN".into(),
+        label: Some("lab machine of alice".into()),
+        host: Some("alice-desktop".into()),
+        config_path: Some(PathBuf::from("/home/alice/wakuchin.toml")),
+        seed: Some(42),
+        symbols: None,
+        throttling: None,
+        broken_pipe: None,
+        deadline: None,
+      },
+      result: WakuchinResult {
+        tries: 10,
+        hits_total: 1,
+        hits: vec![HitCount::new("WKCN", 1)],
+        hits_detail: vec![Hit::new(3, "WKCN")],
+        patterns: Vec::new(),
+        rarity: None,
+        stop_reason: None,
+        failures: Vec::new(),
+      },
+    }
+  }
+
+  #[test]
+  fn test_redact() -> Result<()> {
+    let mut exported = exported_run();
+
+    exported.redact();
+
+    let json = serde_json::to_string(&exported)?;
+
+    assert!(!json.contains("alice"));
+    assert!(json.contains(r#""regex":"WKCN""#));
+    assert!(json.contains(r#""hits_detail":[{"hit_on":3,"chars":"WKCN"}]"#));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_redact_broken_pipe() -> Result<()> {
+    let mut exported = exported_run();
+
+    exported.run.broken_pipe = Some(BrokenPipeEvent {
+      at: "2023-01-01T00:00:30Z".into(),
+      fallback: Some(PathBuf::from("/home/alice/progress.log")),
+    });
+    exported.redact();
+
+    let json = serde_json::to_string(&exported)?;
+
+    assert!(!json.contains("alice"));
+    assert!(json.contains(r#""at":"2023-01-01T00:00:30Z""#));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_check_invariants() {
+    let mut exported = exported_run();
+
+    assert!(exported.check_invariants().is_empty());
+
+    exported.result.hits_total = 2;
+    exported.result.hits_detail.push(Hit::new(12, "WKKN"));
+
+    assert_eq!(
+      exported.check_invariants(),
+      vec![
+        "hit counts sum up to 1 but hits_total is 2",
+        "hit on 12 is out of 10 tries",
+        "'WKKN' is missing in hit counts",
+      ]
+    );
+
+    exported.run.times = 2;
+
+    assert_eq!(exported.check_invariants().len(), 5);
+  }
+}
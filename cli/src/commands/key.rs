@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use clap::Subcommand;
+
+use crate::error::Result;
+use crate::signing::{default_key_path, encode_public_key, generate_key};
+
+#[derive(Subcommand)]
+pub enum KeyCommand {
+  /// Generate a new signing key and print its public key
+  Gen {
+    /// Overwrite the existing key
+    #[arg(long)]
+    force: bool,
+
+    /// Where to save the key, defaults to the config directory
+    #[arg(long, value_name = "FILE")]
+    key: Option<PathBuf>,
+  },
+}
+
+impl KeyCommand {
+  pub fn run(self) -> Result<()> {
+    match self {
+      Self::Gen { force, key } => {
+        let path = key.unwrap_or_else(default_key_path);
+        let key = generate_key(&path, force)?;
+
+        eprintln!("Saved the signing key to {}", path.to_string_lossy());
+        println!("{}", encode_public_key(&key.verifying_key()));
+
+        Ok(())
+      }
+    }
+  }
+}
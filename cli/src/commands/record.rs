@@ -0,0 +1,79 @@
+use std::fs::File;
+use std::io::{stdin, stdout, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, bail};
+use clap::Args;
+
+use crate::error::Result;
+use crate::recording::StreamWriter;
+
+#[derive(Args)]
+pub struct RecordCommand {
+  /// File to record the stream to, e.g. "run.stream"
+  #[arg(short, long, value_name = "FILE")]
+  output: PathBuf,
+
+  /// Command whose stdout to record, e.g. "-- wakuchin --handler msgpack",
+  /// stdin is recorded if omitted
+  #[arg(last = true, value_name = "COMMAND")]
+  command: Vec<String>,
+}
+
+impl RecordCommand {
+  pub fn run(self) -> Result<()> {
+    self.record().map_err(Into::into)
+  }
+
+  /// Copy the source to stdout, recording what it emits with timestamps.
+  fn record(self) -> anyhow::Result<()> {
+    let file = File::create(&self.output)
+      .map_err(|e| anyhow!("'{}': {e}", self.output.display()))?;
+    let mut stream = StreamWriter::new(BufWriter::new(file))?;
+
+    let mut child = match self.command.split_first() {
+      Some((program, args)) => Some(
+        Command::new(program)
+          .args(args)
+          .stdout(Stdio::piped())
+          .spawn()
+          .map_err(|e| anyhow!("cannot start {program}: {e}"))?,
+      ),
+      None => None,
+    };
+    let mut source: Box<dyn Read> = match &mut child {
+      Some(child) => Box::new(child.stdout.take().expect("stdout is piped")),
+      None => Box::new(stdin().lock()),
+    };
+
+    let mut stdout = stdout().lock();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+      let read = match source.read(&mut buf) {
+        Ok(0) => break,
+        Ok(read) => read,
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(e) => return Err(e.into()),
+      };
+
+      // consumers see the progress as soon as it is emitted
+      stdout.write_all(&buf[..read])?;
+      stdout.flush()?;
+      stream.write_chunk(&buf[..read])?;
+    }
+
+    stream.finish()?;
+
+    if let Some(mut child) = child {
+      let status = child.wait()?;
+
+      if !status.success() {
+        bail!("{} exited with {status}", self.command[0]);
+      }
+    }
+
+    Ok(())
+  }
+}
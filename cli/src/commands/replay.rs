@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::{stdout, BufReader, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use clap::Args;
+
+use crate::error::Result;
+use crate::recording::StreamReader;
+
+#[derive(Args)]
+pub struct ReplayCommand {
+  /// Stream recorded by "wakuchin record"
+  #[arg(value_name = "FILE")]
+  stream: PathBuf,
+
+  /// Emit the stream at the pace it was recorded at, the default
+  #[arg(long, conflicts_with = "fast")]
+  realtime: bool,
+
+  /// Emit the stream as fast as possible
+  #[arg(long)]
+  fast: bool,
+
+  /// Emit to a socket instead of stdout, a TCP address like
+  /// "127.0.0.1:9000" or "unix:PATH"
+  #[arg(long, value_name = "ADDR")]
+  connect: Option<String>,
+}
+
+/// Connect to `addr`, a TCP address or "unix:PATH".
+fn connect(addr: &str) -> anyhow::Result<Box<dyn Write>> {
+  if let Some(path) = addr.strip_prefix("unix:") {
+    #[cfg(unix)]
+    return UnixStream::connect(path)
+      .map(|stream| Box::new(stream) as Box<dyn Write>)
+      .map_err(|e| anyhow!("cannot connect to '{path}': {e}"));
+
+    #[cfg(not(unix))]
+    return Err(anyhow!(
+      "cannot connect to '{path}': unix sockets are not supported"
+    ));
+  }
+
+  TcpStream::connect(addr)
+    .map(|stream| Box::new(stream) as Box<dyn Write>)
+    .map_err(|e| anyhow!("cannot connect to {addr}: {e}"))
+}
+
+impl ReplayCommand {
+  pub fn run(self) -> Result<()> {
+    self.replay().map_err(Into::into)
+  }
+
+  fn replay(self) -> anyhow::Result<()> {
+    let file = File::open(&self.stream)
+      .map_err(|e| anyhow!("'{}': {e}", self.stream.display()))?;
+    let chunks = StreamReader::new(BufReader::new(file))
+      .map_err(|e| anyhow!("'{}': {e}", self.stream.display()))?;
+
+    let mut writer = match &self.connect {
+      Some(addr) => connect(addr)?,
+      None => Box::new(stdout().lock()),
+    };
+    let started = Instant::now();
+
+    for chunk in chunks {
+      let chunk =
+        chunk.map_err(|e| anyhow!("'{}': {e}", self.stream.display()))?;
+
+      // --realtime is only there to be explicit
+      if self.realtime || !self.fast {
+        thread::sleep(
+          (started + chunk.elapsed).saturating_duration_since(Instant::now()),
+        );
+      }
+
+      writer.write_all(&chunk.bytes)?;
+      writer.flush()?;
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,167 @@
+use anyhow::anyhow;
+use clap::Subcommand;
+use console::Term;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use human_format::{Formatter, Scales};
+
+use crate::error::Result;
+use crate::registry::{parse_size, Registry, RunEntry};
+
+use super::RegistryArgs;
+
+#[derive(Subcommand)]
+pub enum RunsCommand {
+  /// Summarize disk usage per run
+  Du {
+    #[command(flatten)]
+    registry: RegistryArgs,
+  },
+
+  /// Intern hits of runs created by older versions into the shared
+  /// hit dictionary
+  Migrate {
+    #[command(flatten)]
+    registry: RegistryArgs,
+  },
+
+  /// Remove old runs, newest runs are kept first
+  Gc {
+    /// Keep only the N most recent runs
+    #[arg(long, value_name = "N")]
+    keep_last: Option<usize>,
+
+    /// Keep the total size of runs under SIZE, e.g. "500M", "10G"
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Only show which runs would be removed
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    yes: bool,
+
+    #[command(flatten)]
+    registry: RegistryArgs,
+  },
+}
+
+fn format_size(size: u64) -> String {
+  Formatter::new()
+    .with_scales(Scales::Binary())
+    .with_units("B")
+    .format(size as f64)
+}
+
+fn print_entry(entry: &RunEntry) {
+  println!(
+    "{:<26}  {:<24}  {:<9}  {:>10}  {}{}",
+    entry.metadata.id,
+    entry.metadata.name.as_deref().unwrap_or("-"),
+    if entry.stalled {
+      "stalled".into()
+    } else {
+      format!("{:?}", entry.metadata.status).to_lowercase()
+    },
+    format_size(entry.size),
+    entry.metadata.created_at,
+    if entry.archived { " (archived)" } else { "" }
+  );
+}
+
+impl RunsCommand {
+  pub fn run(self) -> Result<()> {
+    match self {
+      Self::Du { registry } => Self::du(&registry.registry()),
+      Self::Migrate { registry } => {
+        let migrated = registry.registry().migrate()?;
+
+        println!("Migrated {migrated} runs");
+
+        Ok(())
+      }
+      Self::Gc {
+        keep_last,
+        max_size,
+        dry_run,
+        yes,
+        registry,
+      } => Self::gc(&registry.registry(), keep_last, max_size, dry_run, yes),
+    }
+  }
+
+  fn du(registry: &Registry) -> Result<()> {
+    let entries = registry.list()?;
+
+    for entry in &entries {
+      print_entry(entry);
+    }
+
+    println!(
+      "Total: {} runs, {} in {}",
+      entries.len(),
+      format_size(entries.iter().map(|entry| entry.size).sum()),
+      registry.root().to_string_lossy()
+    );
+
+    Ok(())
+  }
+
+  fn gc(
+    registry: &Registry,
+    keep_last: Option<usize>,
+    max_size: Option<u64>,
+    dry_run: bool,
+    yes: bool,
+  ) -> Result<()> {
+    if keep_last.is_none() && max_size.is_none() {
+      return Err(
+        anyhow!("nothing to collect (hint: pass --keep-last or --max-size)")
+          .into(),
+      );
+    }
+
+    let plan = registry.plan_gc(keep_last, max_size)?;
+
+    if plan.remove.is_empty() {
+      println!("Nothing to remove");
+
+      return Ok(());
+    }
+
+    for entry in &plan.remove {
+      print_entry(entry);
+    }
+
+    println!(
+      "Selected {} runs for removal, {} would be freed",
+      plan.remove.len(),
+      format_size(plan.freed())
+    );
+
+    if dry_run {
+      return Ok(());
+    }
+
+    if !yes {
+      if !(atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr)) {
+        return Err(
+          anyhow!("refusing to remove runs in non-interactive mode (hint: pass --yes)")
+            .into(),
+        );
+      }
+
+      let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt("Remove these runs?")
+        .default(false)
+        .interact_on(&Term::stderr())?;
+
+      if !confirmed {
+        return Ok(());
+      }
+    }
+
+    registry.apply_gc(&plan)
+  }
+}
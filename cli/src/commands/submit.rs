@@ -0,0 +1,220 @@
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+use crate::registry::parse_size;
+
+use super::verify::VerifiedRun;
+
+const MAX_RETRIES: usize = 5;
+const RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Args)]
+pub struct SubmitCommand {
+  /// Exported result file to submit, see "wakuchin export --sign"
+  file: PathBuf,
+
+  /// Base URL of the leaderboard API
+  #[arg(long, value_name = "URL")]
+  endpoint: String,
+
+  /// Detached signature of the file, defaults to "<FILE>.sig"
+  #[arg(long, value_name = "FILE")]
+  signature: Option<PathBuf>,
+
+  /// Upload the file in chunks of SIZE, e.g. "64K"
+  #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "64K")]
+  chunk_size: u64,
+
+  /// Only show what would be shared with the leaderboard
+  #[arg(long)]
+  dry_run: bool,
+}
+
+/// Request creating a submission, or looking up the one with the same
+/// signature to resume it.
+#[derive(Serialize)]
+struct CreateSubmission<'a> {
+  size: usize,
+  public_key: &'a str,
+  signature: &'a str,
+}
+
+#[derive(Deserialize)]
+struct Submission {
+  id: String,
+  /// Number of bytes the leaderboard already received
+  offset: usize,
+  /// Public page of the submission, if any
+  #[serde(default)]
+  url: Option<String>,
+}
+
+fn http_error(e: ureq::Error) -> AppError {
+  match e {
+    ureq::Error::Status(status, response) => {
+      let message = response.into_string().unwrap_or_default();
+
+      anyhow!("leaderboard responded with {status}: {}", message.trim()).into()
+    }
+    e => anyhow!(e).into(),
+  }
+}
+
+fn is_transient(e: &ureq::Error) -> bool {
+  match e {
+    ureq::Error::Status(status, _) => *status >= 500,
+    ureq::Error::Transport(_) => true,
+  }
+}
+
+fn print_field(name: &str, value: Option<impl std::fmt::Display>) {
+  match value {
+    Some(value) => println!("  {name:<12} {value}"),
+    None => println!("  {name:<12} (not shared)"),
+  }
+}
+
+impl SubmitCommand {
+  fn preview(&self, verified: &VerifiedRun) {
+    let run = &verified.run.run;
+    let result = &verified.run.result;
+
+    println!(
+      "Would submit {} bytes to {}",
+      verified.contents.len(),
+      self.endpoint
+    );
+    println!("  {:<12} {}", "run", run.id);
+    println!("  {:<12} {}", "created_at", run.created_at);
+    println!("  {:<12} {}", "tries", run.tries);
+    println!("  {:<12} {}", "times", run.times);
+    println!("  {:<12} {}", "regex", run.regex);
+    println!("  {:<12} {}", "hits", result.hits_total);
+    print_field("label", run.label.as_ref());
+    print_field("host", run.host.as_ref());
+    print_field("config_path", run.config_path.as_ref().map(|p| p.display()));
+    println!("  {:<12} {}", "public_key", verified.public_key);
+
+    let fallback = run
+      .broken_pipe
+      .as_ref()
+      .and_then(|broken_pipe| broken_pipe.fallback.as_ref());
+
+    if run.label.is_some()
+      || run.host.is_some()
+      || run.config_path.is_some()
+      || fallback.is_some()
+    {
+      println!(
+        "hint: export with --redact to avoid sharing the label, host and paths"
+      );
+    }
+  }
+
+  fn create(&self, verified: &VerifiedRun) -> Result<Submission> {
+    let url = format!("{}/submissions", self.endpoint.trim_end_matches('/'));
+
+    ureq::post(&url)
+      .send_json(CreateSubmission {
+        size: verified.contents.len(),
+        public_key: &verified.signature.public_key,
+        signature: &verified.signature.signature,
+      })
+      .map_err(http_error)?
+      .into_json()
+      .map_err(|e| AppError::Other(e.into()))
+  }
+
+  /// Send the chunk starting at `submission.offset`, returning the new state
+  /// of the submission.
+  fn send_chunk(
+    &self,
+    submission: &Submission,
+    contents: &[u8],
+  ) -> std::result::Result<Submission, Box<ureq::Error>> {
+    let url = format!(
+      "{}/submissions/{}",
+      self.endpoint.trim_end_matches('/'),
+      submission.id
+    );
+    let end = contents
+      .len()
+      .min(submission.offset + self.chunk_size as usize);
+
+    let response = ureq::request("PATCH", &url)
+      .set("Content-Type", "application/offset+octet-stream")
+      .set("Upload-Offset", &submission.offset.to_string())
+      .send_bytes(&contents[submission.offset..end])?;
+
+    response.into_json().map_err(|e| Box::new(e.into()))
+  }
+
+  fn upload(&self, verified: &VerifiedRun) -> Result<Submission> {
+    let contents = &verified.contents;
+    let mut submission = self.create(verified)?;
+    let mut retries = 0;
+
+    if submission.offset > 0 && submission.offset < contents.len() {
+      eprintln!(
+        "Resuming upload at {} of {} bytes",
+        submission.offset,
+        contents.len()
+      );
+    }
+
+    while submission.offset < contents.len() {
+      match self.send_chunk(&submission, contents) {
+        Ok(next) if next.offset > submission.offset => {
+          submission = next;
+          retries = 0;
+        }
+        Ok(_) => {
+          return Err(anyhow!("leaderboard did not accept the chunk").into())
+        }
+        Err(e) if is_transient(&e) && retries < MAX_RETRIES => {
+          retries += 1;
+
+          eprintln!(
+            "Upload interrupted ({e}), retrying {retries}/{MAX_RETRIES}"
+          );
+          sleep(RETRY_INTERVAL);
+
+          // the leaderboard knows how much it received, continue from there
+          submission = self.create(verified)?;
+        }
+        Err(e) => return Err(http_error(*e)),
+      }
+    }
+
+    Ok(submission)
+  }
+
+  pub fn run(self) -> Result<()> {
+    if self.chunk_size == 0 {
+      return Err(anyhow!("chunk size must be greater than 0").into());
+    }
+
+    let verified = VerifiedRun::load(&self.file, self.signature.as_deref())?;
+
+    if self.dry_run {
+      self.preview(&verified);
+
+      return Ok(());
+    }
+
+    let submission = self.upload(&verified)?;
+
+    match submission.url {
+      Some(url) => println!("Submitted run {}: {url}", verified.run.run.id),
+      None => println!("Submitted run {}", verified.run.run.id),
+    }
+
+    Ok(())
+  }
+}
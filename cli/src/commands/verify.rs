@@ -0,0 +1,86 @@
+use std::fs::read;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use clap::Args;
+
+use crate::error::{AppError, Result};
+use crate::signing::{signature_path, verify, SignatureFile};
+
+use super::export::ExportedRun;
+
+#[derive(Args)]
+pub struct VerifyCommand {
+  /// Exported result file to verify
+  file: PathBuf,
+
+  /// Detached signature of the file, defaults to "<FILE>.sig"
+  #[arg(long, value_name = "FILE")]
+  signature: Option<PathBuf>,
+}
+
+/// Exported result whose invariants and signature have been checked.
+pub struct VerifiedRun {
+  pub run: ExportedRun,
+  /// Exact bytes of the exported file
+  pub contents: Vec<u8>,
+  pub signature: SignatureFile,
+  /// Base64-encoded public key of the signer
+  pub public_key: String,
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+  read(path).map_err(|source| AppError::WorkspaceIoError {
+    path: path.into(),
+    source,
+  })
+}
+
+impl VerifiedRun {
+  /// Load `file` and check it, the signature is read from `signature` or
+  /// `<file>.sig`.
+  pub fn load(file: &Path, signature: Option<&Path>) -> Result<Self> {
+    let contents = read_file(file)?;
+    let run: ExportedRun = serde_json::from_slice(&contents)
+      .map_err(|e| AppError::Other(e.into()))?;
+
+    let violations = run.check_invariants();
+
+    for violation in &violations {
+      eprintln!("invariant violated: {violation}");
+    }
+
+    if !violations.is_empty() {
+      return Err(
+        anyhow!("result has {} invalid entries", violations.len()).into(),
+      );
+    }
+
+    let signature_path = signature
+      .map(PathBuf::from)
+      .unwrap_or_else(|| signature_path(file));
+    let signature: SignatureFile =
+      serde_json::from_slice(&read_file(&signature_path)?)
+        .map_err(|e| AppError::Other(e.into()))?;
+
+    let public_key = verify(&contents, &signature)?;
+
+    Ok(Self {
+      run,
+      contents,
+      signature,
+      public_key,
+    })
+  }
+}
+
+impl VerifyCommand {
+  pub fn run(self) -> Result<()> {
+    let verified = VerifiedRun::load(&self.file, self.signature.as_deref())?;
+
+    println!("Valid result of run {}", verified.run.run.id);
+    println!("Signed by {}", verified.public_key);
+
+    Ok(())
+  }
+}
@@ -1,26 +1,154 @@
+use std::env;
 use std::fs::read_to_string;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{borrow::Borrow, time::Duration};
 
-use clap::ValueEnum;
+use clap::builder::TypedValueParser as _;
+use clap::{value_parser, ValueEnum};
 use clap_serde_derive::ClapSerde;
 use format_serde_error::SerdeError;
 use humantime::DurationError;
 use regex::Regex;
 use serde::Deserialize;
+use wakuchin::handlers::socket::SocketFormat;
+use wakuchin::locale::{Grouping, NumberFormat};
+use wakuchin::math::DEFAULT_NOTABLE_RARITY;
+use wakuchin::protocol::ProtocolVersion;
 use wakuchin::result::ResultOutputFormat;
+use wakuchin::symbol::SymbolSet;
 
+use crate::battery::BatteryPolicy;
 use crate::error::{AppError, Result};
-use crate::handlers::HandlerKind;
+use crate::handlers::{deserialize_handler, HandlerConfig, HandlerKind};
+use crate::pipeline::Stage;
+use crate::workspace::{default_root, RetentionPolicy};
+
+/// Config file names looked up by [`discover_config`], in order of priority.
+const AUTO_CONFIG_FILES: [&str; 5] = [
+  "wakuchin.toml",
+  ".wakuchin/config.toml",
+  ".wakuchin/config.yaml",
+  ".wakuchin/config.yml",
+  ".wakuchin/config.json",
+];
 
 fn default_duration() -> Option<Duration> {
   Some(Duration::from_millis(300))
 }
 
-fn parse_duration(
+fn default_webhook_interval() -> Option<Duration> {
+  Some(Duration::from_secs(300))
+}
+
+fn default_notable_rarity() -> Option<usize> {
+  Some(DEFAULT_NOTABLE_RARITY as usize)
+}
+
+/// Parse fractional durations with a single unit, e.g. "0.3s", "1.5h".
+fn parse_fractional_duration(duration: &str) -> Option<Duration> {
+  let split = duration.find(|c: char| c.is_ascii_alphabetic())?;
+  let (number, unit) = duration.split_at(split);
+
+  let number: f64 = number.trim().parse().ok()?;
+
+  let scale = match unit.trim() {
+    "ns" => 1e-9,
+    "us" => 1e-6,
+    "ms" => 1e-3,
+    "s" | "sec" => 1.0,
+    "m" | "min" => 60.0,
+    "h" | "hr" => 60.0 * 60.0,
+    "d" => 60.0 * 60.0 * 24.0,
+    _ => return None,
+  };
+
+  Duration::try_from_secs_f64(number * scale).ok()
+}
+
+pub(crate) fn parse_duration(
   duration: &str,
 ) -> std::result::Result<Duration, DurationError> {
-  Ok(duration.parse::<humantime::Duration>()?.into())
+  match duration.parse::<humantime::Duration>() {
+    Ok(duration) => Ok(duration.into()),
+    Err(e) => parse_fractional_duration(duration).ok_or(e),
+  }
+}
+
+/// Parse a human-readable count, e.g. "300000000", "300_000_000", "300M" or
+/// "1.5e9".
+pub(crate) fn parse_count(count: &str) -> std::result::Result<usize, String> {
+  let count = count.trim().replace('_', "");
+
+  if let Ok(count) = count.parse::<usize>() {
+    return Ok(count);
+  }
+
+  let split = count
+    .find(|c: char| !c.is_ascii_digit() && !matches!(c, '.' | 'e' | 'E'))
+    .unwrap_or(count.len());
+  let (number, unit) = count.split_at(split);
+
+  let number: f64 = number
+    .parse()
+    .map_err(|_| format!("'{count}': invalid count"))?;
+
+  let scale = match unit.trim().to_ascii_uppercase().as_str() {
+    "" => 1e0,
+    "K" => 1e3,
+    "M" => 1e6,
+    "G" => 1e9,
+    "T" => 1e12,
+    _ => return Err(format!("'{count}': unknown count unit")),
+  };
+
+  let value = number * scale;
+
+  if value < 0.0 || value.fract() != 0.0 || value > usize::MAX as f64 {
+    return Err(format!("'{count}': count must be a non-negative integer"));
+  }
+
+  Ok(value as usize)
+}
+
+/// Deserializers accepting the same human-readable forms as the flags.
+pub(crate) mod human {
+  use std::time::Duration;
+
+  use serde::{de::Error, Deserialize, Deserializer};
+
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Count {
+    Integer(usize),
+    Float(f64),
+    String(String),
+  }
+
+  pub fn count<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let count = match Option::<Count>::deserialize(deserializer)? {
+      Some(Count::Integer(count)) => return Ok(Some(count)),
+      Some(Count::Float(count)) => count.to_string(),
+      Some(Count::String(count)) => count,
+      None => return Ok(None),
+    };
+
+    super::parse_count(&count)
+      .map(Some)
+      .map_err(D::Error::custom)
+  }
+
+  pub fn duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    Option::<String>::deserialize(deserializer)?
+      .map(|duration| super::parse_duration(&duration))
+      .transpose()
+      .map_err(D::Error::custom)
+  }
 }
 
 #[derive(
@@ -48,16 +176,63 @@ impl From<InternalResultOutputFormat> for ResultOutputFormat {
   }
 }
 
+/// Random number generator backend, see [`RngBackend`](wakuchin::rng::RngBackend).
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum InternalRngBackend {
+  #[default]
+  Fastrand,
+  Chacha,
+}
+
+/// Format of --socket, see [`SocketFormat`].
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum InternalSocketFormat {
+  #[default]
+  Msgpack,
+  Json,
+}
+
+impl From<InternalSocketFormat> for SocketFormat {
+  fn from(format: InternalSocketFormat) -> Self {
+    match format {
+      InternalSocketFormat::Msgpack => Self::Msgpack,
+      InternalSocketFormat::Json => Self::Json,
+    }
+  }
+}
+
+/// Regex engine, see [`RegexEngine`](wakuchin::pattern::RegexEngine).
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum InternalRegexEngine {
+  #[default]
+  Regex,
+  Dfa,
+}
+
 #[derive(Clone, Debug, ClapSerde)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
   /// Number of tries
-  #[arg(short = 'i', long, value_name = "N")]
+  ///
+  /// Can be passed as a human-readable count, e.g. "300M", "1.5e9".
+  #[serde(default, deserialize_with = "human::count")]
+  #[arg(short = 'i', long, value_name = "N", value_parser = parse_count)]
   pub tries: usize,
 
   /// Wakuchin times n
   ///
   /// Repeats "わくちん" n times.
-  #[arg(short, long, value_name = "N")]
+  #[serde(default, deserialize_with = "human::count")]
+  #[arg(short, long, value_name = "N", value_parser = parse_count)]
   pub times: usize,
 
   /// Regex to detect hits
@@ -67,6 +242,7 @@ pub struct Config {
   ///   K -> く,
   ///   C -> ち,
   ///   N -> ん
+  /// and hiragana can be written as is, e.g. "^わくちん".
   /// Used to check matches, characters of matches are ignored.
   #[default(Regex::new(r"%default%").unwrap())]
   #[serde(with = "serde_regex")]
@@ -78,11 +254,32 @@ pub struct Config {
   #[arg(short = 'f', long = "format", value_name = "FORMAT", value_enum)]
   pub out: InternalResultOutputFormat,
 
+  /// Locale to format the numbers of the text output for, e.g. "ja" or
+  /// "de", "auto" takes it from LC_ALL, LC_NUMERIC or LANG
+  ///
+  /// Requires wakuchin to be built with the "i18n" feature.
+  #[arg(long, value_name = "LOCALE")]
+  pub lang: String,
+
+  /// Group the digits of the text output by ten thousands like 万 and 億,
+  /// e.g. "3,0000,0000"
+  #[arg(long, value_name = "BOOL")]
+  pub man_grouping: bool,
+
+  /// Flag hits rarer than 1 in N tries as notable in the result
+  ///
+  /// Can be passed as a human-readable count, e.g. "1M", "1e9".
+  #[default(DEFAULT_NOTABLE_RARITY as usize)]
+  #[serde(deserialize_with = "human::count")]
+  #[serde(default = "default_notable_rarity")]
+  #[arg(long, value_name = "N", value_parser = parse_count)]
+  pub notable_rarity: usize,
+
   /// Progress refresh interval
   ///
-  /// Can be passed as a human-readable duration, e.g. "1s", "2m", "3h", "4d".
+  /// Can be passed as a human-readable duration, e.g. "0.3s", "2m", "3h", "4d".
   #[default(Duration::from_millis(300))]
-  #[serde(with = "humantime_serde")]
+  #[serde(deserialize_with = "human::duration")]
   #[serde(default = "default_duration")]
   #[arg(
     short = 'd',
@@ -92,14 +289,49 @@ pub struct Config {
   )]
   pub interval: Duration,
 
+  /// Duration the research is expected to take, 0 means no expectation
+  ///
+  /// A warning is shown once the ETA exceeds it by 10%, and the overrun is
+  /// recorded in the run metadata. Can be passed as a human-readable
+  /// duration, e.g. "90m", "2h".
+  #[serde(default, deserialize_with = "human::duration")]
+  #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+  pub expect_duration: Duration,
+
   /// Progress handler to use
   ///
   /// Available handlers:
   ///  - "console": Prints progress to stderr with pretty progress bar
   ///  - "msgpack": Prints progress to stdout as raw msgpack-encoded data
   ///  - "msgpack-base64": Prints progress to stdout as base64-encoded msgpack data
-  #[arg(short = 'H', long, value_enum, verbatim_doc_comment)]
-  pub handler: HandlerKind,
+  ///  - "csv": Prints a CSV row of the progress per update to stdout, for spreadsheets
+  ///  - "plain": Prints a line of progress every 10 seconds to stderr, for CI logs
+  ///
+  /// Further options can be set in the "[handler]" table of the config file.
+  #[serde(default, deserialize_with = "deserialize_handler")]
+  #[arg(
+    short = 'H',
+    long,
+    value_name = "KIND",
+    value_parser = value_parser!(HandlerKind).map(HandlerConfig::from),
+    verbatim_doc_comment
+  )]
+  pub handler: HandlerConfig,
+
+  /// Layout of the msgpack progress frames
+  ///
+  ///  - 1: Array of the progress fields, for consumers written before 2
+  ///  - 2: Map of the progress fields with the version and a frame number
+  ///  - 3: Map of 2 with the timestamp, the elapsed time, the seed and the
+  ///    workers of the research
+  #[default(ProtocolVersion::LATEST.number())]
+  #[arg(
+    long,
+    value_name = "VERSION",
+    value_parser = value_parser!(u8).range(1..=ProtocolVersion::LATEST.number() as i64),
+    verbatim_doc_comment
+  )]
+  pub protocol_version: u8,
 
   /// Do not show progress, able to use with --handler=console
   #[arg(long, value_name = "BOOL")]
@@ -113,6 +345,263 @@ pub struct Config {
     help = "Number of workers, 0 means number of logical CPUs"
   )]
   pub workers: usize,
+
+  /// Label of the run, recorded in the run metadata
+  #[arg(long, value_name = "LABEL")]
+  pub label: String,
+
+  /// Directory to store run artifacts in
+  ///
+  /// Defaults to the platform data directory, e.g. "~/.local/share/wakuchin/runs".
+  #[arg(long, value_name = "DIR")]
+  pub workspace: PathBuf,
+
+  /// What to do with the run workspace after the research finished
+  ///
+  /// Available policies:
+  ///  - "keep": Leave all artifacts in place
+  ///  - "clean": Remove the workspace of successful runs
+  ///  - "archive": Move the workspace of successful runs into "archive"
+  #[arg(long, value_name = "POLICY", value_enum, verbatim_doc_comment)]
+  pub retention: RetentionPolicy,
+
+  /// Pause while other processes keep the CPUs busy, Linux only
+  #[arg(long, value_name = "BOOL")]
+  pub only_when_idle: bool,
+
+  /// Pause or throttle while on battery below this charge in percent,
+  /// 0 disables it
+  ///
+  /// Requires wakuchin to be built with the "battery" feature.
+  #[arg(long, value_name = "PERCENT", value_parser = value_parser!(u8).range(0..=100))]
+  pub battery_threshold: u8,
+
+  /// What to do below --battery-threshold
+  ///
+  ///  - "pause": Pause until plugged in or charged again
+  ///  - "throttle": Alternately run and pause for a second
+  #[arg(long, value_name = "POLICY", value_enum, verbatim_doc_comment)]
+  pub battery_policy: BatteryPolicy,
+
+  /// File to write a JSON progress snapshot to on SIGUSR1, Unix only
+  ///
+  /// Defaults to stderr.
+  #[arg(long, value_name = "FILE")]
+  pub snapshot_file: PathBuf,
+
+  /// Unix socket, or Windows named pipe, to stream the progress to besides
+  /// the handler, for sidecar UIs
+  ///
+  /// It is connected to again every second while nothing listens on it.
+  #[arg(long, value_name = "PATH")]
+  pub socket: PathBuf,
+
+  /// What to stream to --socket
+  ///
+  ///  - "msgpack": Length-prefixed msgpack frames of --protocol-version
+  ///  - "json": A JSON progress snapshot per line
+  #[arg(long, value_name = "FORMAT", value_enum, verbatim_doc_comment)]
+  pub socket_format: InternalSocketFormat,
+
+  /// Address to serve the progress on as Server-Sent Events besides the
+  /// handler, e.g. "127.0.0.1:8080", for dashboards
+  ///
+  /// Requires wakuchin to be built with the "sse" feature.
+  #[arg(long, value_name = "ADDR")]
+  pub sse: String,
+
+  /// Show a desktop notification once the research finishes
+  ///
+  /// Requires wakuchin to be built with the "notify" feature.
+  #[arg(long, value_name = "BOOL")]
+  pub notify: bool,
+
+  /// Regex of the hits to show a desktop notification of as well, e.g.
+  /// "^WKNCWKNC$", implies --notify
+  ///
+  /// Requires wakuchin to be built with the "notify" feature.
+  #[arg(long, value_name = "REGEX")]
+  pub notify_hits: String,
+
+  /// Webhook URL to POST progress summaries and the result to as JSON
+  ///
+  /// Discord and Slack webhooks get a message of their own format. Requires
+  /// wakuchin to be built with the "webhook" feature.
+  #[arg(long, value_name = "URL")]
+  pub webhook: String,
+
+  /// Time between the progress summaries of --webhook
+  ///
+  /// Can be passed as a human-readable duration, e.g. "30m", "1h".
+  #[default(Duration::from_secs(300))]
+  #[serde(deserialize_with = "human::duration")]
+  #[serde(default = "default_webhook_interval")]
+  #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+  pub webhook_interval: Duration,
+
+  /// SQLite database to record the hits and progress snapshots to, created
+  /// unless it exists, e.g. "studies.sqlite"
+  ///
+  /// Each research is added to the history of the former ones. Requires
+  /// wakuchin to be built with the "sqlite" feature.
+  #[arg(long, value_name = "FILE")]
+  pub sqlite: PathBuf,
+
+  /// Keep researching in the background, detached from the terminal
+  ///
+  /// On Windows, wakuchin has to be started as a service with it, e.g.
+  /// registered with `sc.exe create wakuchin binPath= "wakuchin.exe --daemon
+  /// true"`, and stopping the service stops the research. The console handler
+  /// shows no progress then.
+  #[arg(long, value_name = "BOOL")]
+  pub daemon: bool,
+
+  /// File to write the process ID of --daemon to
+  ///
+  /// Defaults to "wakuchin.pid" in the workspace root.
+  #[arg(long, value_name = "FILE")]
+  pub pid_file: PathBuf,
+
+  /// File to append the output of --daemon to
+  ///
+  /// Defaults to "wakuchin.log" in the workspace root.
+  #[arg(long, value_name = "FILE")]
+  pub log_file: PathBuf,
+
+  /// Checkpoint to resume a research from, e.g. "checkpoints/paused.json" of
+  /// its run workspace
+  ///
+  /// Only the remaining tries are researched, and the hits of the checkpoint
+  /// are added to the result. Seeded researches are resumed with the seed
+  /// offset by the tries of the checkpoint, so those tries are not tried
+  /// again.
+  #[arg(long, value_name = "CHECKPOINT")]
+  pub resume_from: PathBuf,
+
+  /// Seed of the random generator, 0 means a random seed
+  ///
+  /// Runs with the same seed and number of workers produce the same result.
+  #[arg(long, value_name = "N")]
+  pub seed: u64,
+
+  /// Random number generator to shuffle the symbols with
+  ///
+  /// Available backends:
+  ///  - "fastrand": Fastest one
+  ///  - "chacha": ChaCha20, slower but of cryptographic quality
+  ///
+  /// "chacha" requires wakuchin to be built with the "rng-chacha" feature.
+  #[arg(long, value_name = "BACKEND", value_enum, verbatim_doc_comment)]
+  pub rng: InternalRngBackend,
+
+  /// Engine to match the regex with
+  ///
+  /// Available engines:
+  ///  - "regex": Regexes of the regex crate
+  ///  - "dfa": A dense DFA, faster once generating is cheap but slower to build
+  ///
+  /// "dfa" requires wakuchin to be built with the "dfa" feature.
+  #[arg(long, value_name = "ENGINE", value_enum, verbatim_doc_comment)]
+  pub regex_engine: InternalRegexEngine,
+
+  /// Alphabet of the research, only available in config files
+  #[serde(default)]
+  #[arg(skip)]
+  pub symbols: SymbolSet,
+
+  /// Stages of a pipeline, only available in config files
+  #[serde(default)]
+  #[arg(skip)]
+  pub stages: Vec<Stage>,
+}
+
+impl Config {
+  /// Root directory of run workspaces, falling back to the default one.
+  pub fn workspace_root(&self) -> PathBuf {
+    if self.workspace.as_os_str().is_empty() {
+      default_root()
+    } else {
+      self.workspace.clone()
+    }
+  }
+
+  /// Pid and log files of --daemon, falling back to the workspace root.
+  pub fn daemon_files(&self) -> (PathBuf, PathBuf) {
+    let or_root = |path: &PathBuf, name| {
+      if path.as_os_str().is_empty() {
+        self.workspace_root().join(name)
+      } else {
+        path.clone()
+      }
+    };
+
+    (
+      or_root(&self.pid_file, "wakuchin.pid"),
+      or_root(&self.log_file, "wakuchin.log"),
+    )
+  }
+
+  /// Format of the numbers of the text output, from --lang and
+  /// --man-grouping.
+  pub fn number_format(&self) -> anyhow::Result<NumberFormat> {
+    let format = match self.lang.as_str() {
+      "" => NumberFormat::default(),
+      "auto" => ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|locale| !locale.is_empty())
+        .filter(|locale| locale != "C" && locale != "POSIX")
+        .map_or_else(
+          || Ok(NumberFormat::default()),
+          |locale| locale_format(&locale),
+        )?,
+      lang => locale_format(lang)?,
+    };
+
+    Ok(if self.man_grouping {
+      format.with_grouping(Grouping::Man)
+    } else {
+      format
+    })
+  }
+}
+
+/// Format of the numbers of `locale`, unavailable without the "i18n"
+/// feature.
+fn locale_format(locale: &str) -> anyhow::Result<NumberFormat> {
+  #[cfg(feature = "i18n")]
+  return NumberFormat::from_locale(locale).map_err(Into::into);
+
+  #[cfg(not(feature = "i18n"))]
+  Err(anyhow::anyhow!(
+    "--lang {locale} requires wakuchin to be built with the \"i18n\" feature"
+  ))
+}
+
+/// Search `start` and its ancestors for a config file, like cargo does for
+/// `Cargo.toml`. The nearest directory wins.
+pub fn discover_config(start: &Path) -> Option<PathBuf> {
+  start.ancestors().find_map(|dir| {
+    AUTO_CONFIG_FILES
+      .iter()
+      .map(|name| dir.join(name))
+      .find(|path| path.is_file())
+  })
+}
+
+/// Suggest the closest known key for an "unknown field" error of serde.
+fn unknown_field_hint(message: &str) -> Option<String> {
+  let (_, rest) = message.split_once("unknown field `")?;
+  let (field, expected) = rest.split_once('`')?;
+
+  expected
+    .split('`')
+    .skip(1)
+    .step_by(2)
+    .map(|candidate| (strsim::jaro(field, candidate), candidate))
+    .filter(|(confidence, _)| *confidence > 0.7)
+    .max_by(|(a, _), (b, _)| a.total_cmp(b))
+    .map(|(_, candidate)| format!("did you mean `{candidate}`?"))
 }
 
 pub fn load_config(path: &Path) -> Result<Config> {
@@ -132,6 +621,7 @@ pub fn load_config(path: &Path) -> Result<Config> {
         path: path.into(),
         line: Some(e.line()),
         column: Some(e.column()),
+        hint: unknown_field_hint(&e.to_string()),
         source: Box::new(SerdeError::new(contents, e)),
       }
     })?,
@@ -145,6 +635,7 @@ pub fn load_config(path: &Path) -> Result<Config> {
         path: path.into(),
         line,
         column,
+        hint: unknown_field_hint(&e.to_string()),
         source: Box::new(SerdeError::new(contents, e)),
       }
     })?,
@@ -157,6 +648,7 @@ pub fn load_config(path: &Path) -> Result<Config> {
         path: path.into(),
         line,
         column,
+        hint: unknown_field_hint(&e.to_string()),
         source: Box::new(SerdeError::new(contents, e)),
       }
     })?,
@@ -198,6 +690,10 @@ mod test {
     assert_eq!(parse_duration("1m")?, Duration::from_secs(60));
     assert_eq!(parse_duration("1h")?, Duration::from_secs(60 * 60));
     assert_eq!(parse_duration("1d")?, Duration::from_secs(60 * 60 * 24));
+    assert_eq!(parse_duration("0.3s")?, Duration::from_millis(300));
+    assert_eq!(parse_duration("1.5h")?, Duration::from_secs(90 * 60));
+    assert!(parse_duration("1.5x").is_err());
+    assert!(parse_duration("-1s").is_err());
 
     Ok(())
   }
@@ -238,6 +734,20 @@ mod test {
       panic!("Unexpected error: {:?}", invalid_regex_yaml_err);
     }
 
+    let mut unknown_key_toml = PathBuf::from(base_path);
+
+    unknown_key_toml.push("../examples/unknown-key.toml");
+
+    let unknown_key_toml_err =
+      super::load_config(&unknown_key_toml).unwrap_err();
+
+    if let AppError::ConfigDeserializeError { hint, .. } = unknown_key_toml_err
+    {
+      assert_eq!(hint.as_deref(), Some("did you mean `tries`?"));
+    } else {
+      panic!("Unexpected error: {:?}", unknown_key_toml_err);
+    }
+
     let mut correct_toml = PathBuf::from(base_path);
 
     correct_toml.push("../examples/tries-300000000.toml");
@@ -250,8 +760,77 @@ mod test {
 
     assert_eq!(config.out, InternalResultOutputFormat::Text);
     assert_eq!(config.interval, Duration::from_millis(300));
+    assert_eq!(config.expect_duration, Duration::ZERO);
     assert_eq!(config.workers, 0);
-    assert_eq!(config.handler, HandlerKind::Console);
+    assert_eq!(config.handler.kind, HandlerKind::Console);
+    assert_eq!(config.protocol_version, 3);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_parse_count() {
+    use super::parse_count;
+
+    assert_eq!(parse_count("300000000"), Ok(300_000_000));
+    assert_eq!(parse_count("300_000_000"), Ok(300_000_000));
+    assert_eq!(parse_count("300M"), Ok(300_000_000));
+    assert_eq!(parse_count("1.5e9"), Ok(1_500_000_000));
+    assert_eq!(parse_count("2.5k"), Ok(2500));
+    assert_eq!(parse_count("1G"), Ok(1_000_000_000));
+    assert!(parse_count("1.5").is_err());
+    assert!(parse_count("10X").is_err());
+    assert!(parse_count("M").is_err());
+  }
+
+  #[test]
+  fn test_unknown_field_hint() {
+    use super::unknown_field_hint;
+
+    assert_eq!(
+      unknown_field_hint(
+        "unknown field `trise`, expected one of `tries`, `times`, `regex`"
+      ),
+      Some("did you mean `tries`?".into())
+    );
+    assert_eq!(
+      unknown_field_hint("unknown field `foo`, expected `tries` or `times`"),
+      None
+    );
+    assert_eq!(unknown_field_hint("invalid type: string"), None);
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[test]
+  fn test_discover_config() -> Result<()> {
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use super::discover_config;
+
+    let root = temp_dir().join(format!("wakuchin-test-{}", ulid::Ulid::new()));
+    let nested = root.join("a").join("b");
+
+    create_dir_all(&nested)?;
+
+    assert_eq!(discover_config(&nested), None);
+
+    write(root.join("wakuchin.toml"), "tries = 10")?;
+
+    assert_eq!(discover_config(&nested), Some(root.join("wakuchin.toml")));
+
+    create_dir_all(root.join("a").join(".wakuchin"))?;
+    write(
+      root.join("a").join(".wakuchin").join("config.yml"),
+      "tries: 10",
+    )?;
+
+    assert_eq!(
+      discover_config(&nested),
+      Some(root.join("a").join(".wakuchin").join("config.yml"))
+    );
+
+    remove_dir_all(root)?;
 
     Ok(())
   }
@@ -0,0 +1,323 @@
+//! Detaching long researches from the terminal
+//!
+//! On Unix, `--daemon true` forks twice so the research is adopted by init
+//! and never gets a controlling terminal again. On Windows, it runs the
+//! research as a service instead, so the wakuchin executable registered with
+//! the service control manager, e.g. with
+//! `sc.exe create wakuchin binPath= "C:\path\to\wakuchin.exe --daemon true"`,
+//! is started, stopped and restarted by it.
+//!
+//! Either way, the process ID is written to a pid file and the output is
+//! appended to a log file. Daemons keep the working directory, so relative
+//! paths of the config keep working, but services start in the system
+//! directory, so they need absolute ones.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use anyhow::bail;
+use anyhow::{Context, Result};
+
+/// Pid file of the running daemon, removed once it is dropped.
+pub struct Daemon {
+  pid_file: PathBuf,
+}
+
+impl Drop for Daemon {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.pid_file);
+  }
+}
+
+/// Create the directories of `pid_file` and `log_file`, then open the log
+/// file to append to, so it fails on the terminal rather than in the log.
+fn open_log(pid_file: &Path, log_file: &Path) -> Result<File> {
+  for path in [pid_file, log_file] {
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent)?;
+    }
+  }
+
+  OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(log_file)
+    .with_context(|| format!("failed to open {}", log_file.display()))
+}
+
+/// Return the process ID in `pid_file` if that process is still running.
+#[cfg(unix)]
+fn running_pid(pid_file: &Path) -> Option<libc::pid_t> {
+  let pid = fs::read_to_string(pid_file).ok()?.trim().parse().ok()?;
+
+  // signal 0 only checks whether the process exists
+  (unsafe { libc::kill(pid, 0) } == 0).then_some(pid)
+}
+
+#[cfg(unix)]
+fn fork() -> io::Result<libc::pid_t> {
+  match unsafe { libc::fork() } {
+    -1 => Err(io::Error::last_os_error()),
+    pid => Ok(pid),
+  }
+}
+
+#[cfg(unix)]
+fn redirect(from: &File, to: libc::c_int) -> io::Result<()> {
+  if unsafe { libc::dup2(from.as_raw_fd(), to) } == -1 {
+    return Err(io::Error::last_os_error());
+  }
+
+  Ok(())
+}
+
+/// Keep running in the background, writing the pid to `pid_file` and the
+/// output to `log_file`.
+///
+/// Only the daemon returns, the calling process exits once the daemon is
+/// started. It has to be called before any thread is spawned, as only the
+/// calling thread survives forking.
+#[cfg(unix)]
+pub fn daemonize(pid_file: &Path, log_file: &Path) -> Result<Daemon> {
+  if let Some(pid) = running_pid(pid_file) {
+    bail!(
+      "wakuchin is already running as {pid}, see {}",
+      pid_file.display()
+    );
+  }
+
+  let log = open_log(pid_file, log_file)?;
+  let null = File::open("/dev/null")?;
+
+  let child = fork()?;
+
+  if child != 0 {
+    let mut status = 0;
+
+    unsafe { libc::waitpid(child, &mut status, 0) };
+
+    if !libc::WIFEXITED(status) || libc::WEXITSTATUS(status) != 0 {
+      bail!("failed to start the daemon");
+    }
+
+    let pid = fs::read_to_string(pid_file)?;
+
+    eprintln!(
+      "Started as {}, logging to {}",
+      pid.trim(),
+      log_file.display()
+    );
+
+    std::process::exit(0);
+  }
+
+  // leave the process group of the terminal
+  if unsafe { libc::setsid() } == -1 {
+    unsafe { libc::_exit(1) };
+  }
+
+  // not a session leader anymore, so a terminal can never be acquired
+  match fork() {
+    Ok(0) => {}
+    Ok(pid) => {
+      let status = i32::from(fs::write(pid_file, format!("{pid}\n")).is_err());
+
+      unsafe { libc::_exit(status) };
+    }
+    Err(_) => unsafe { libc::_exit(1) },
+  }
+
+  redirect(&null, libc::STDIN_FILENO)?;
+  redirect(&log, libc::STDOUT_FILENO)?;
+  redirect(&log, libc::STDERR_FILENO)?;
+
+  Ok(Daemon {
+    pid_file: pid_file.to_path_buf(),
+  })
+}
+
+/// Research run by the service once it is started, along with the log file
+/// its output goes to.
+#[cfg(windows)]
+type ServiceResearch = (Box<dyn FnOnce() -> Result<()> + Send>, File);
+
+#[cfg(windows)]
+static SERVICE_RESEARCH: std::sync::Mutex<Option<ServiceResearch>> =
+  std::sync::Mutex::new(None);
+
+#[cfg(windows)]
+static STOP_TOKEN: std::sync::OnceLock<wakuchin::cancel::CancellationToken> =
+  std::sync::OnceLock::new();
+
+/// Return the token stopping the research once the service is stopped, or
+/// the system shuts down.
+///
+/// The research returns the result of the tries done so far then, so the
+/// run is recorded as usual.
+#[cfg(windows)]
+pub fn stop_token() -> wakuchin::cancel::CancellationToken {
+  STOP_TOKEN.get_or_init(Default::default).clone()
+}
+
+#[cfg(windows)]
+fn redirect(
+  from: &File,
+  to: windows_sys::Win32::System::Console::STD_HANDLE,
+) -> io::Result<()> {
+  use std::os::windows::io::AsRawHandle;
+
+  use windows_sys::Win32::System::Console::SetStdHandle;
+
+  if unsafe { SetStdHandle(to, from.as_raw_handle() as isize) } == 0 {
+    return Err(io::Error::last_os_error());
+  }
+
+  Ok(())
+}
+
+#[cfg(windows)]
+windows_service::define_windows_service!(ffi_service_main, service_main);
+
+/// Run `research` as a Windows service, writing the pid to `pid_file` and
+/// the output to `log_file`.
+///
+/// The process has to be started by the service control manager, it returns
+/// once the service stopped.
+#[cfg(windows)]
+pub fn run_service(
+  pid_file: &Path,
+  log_file: &Path,
+  research: impl FnOnce() -> Result<()> + Send + 'static,
+) -> Result<()> {
+  let log = open_log(pid_file, log_file)?;
+
+  *SERVICE_RESEARCH.lock().unwrap() = Some((Box::new(research), log));
+
+  fs::write(pid_file, format!("{}\n", std::process::id()))?;
+
+  let _daemon = Daemon {
+    pid_file: pid_file.to_path_buf(),
+  };
+
+  // the name is ignored for services of their own process
+  windows_service::service_dispatcher::start("wakuchin", ffi_service_main)
+    .context(
+      "--daemon has to be started by the service control manager, e.g. with \
+       sc.exe",
+    )
+}
+
+/// Main of the service, called on a thread of its own by the service
+/// control manager.
+#[cfg(windows)]
+fn service_main(_arguments: Vec<std::ffi::OsString>) {
+  use std::time::Duration;
+
+  use windows_service::service::{
+    ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState,
+    ServiceStatus, ServiceType,
+  };
+  use windows_service::service_control_handler::{
+    self, ServiceControlHandlerResult,
+  };
+  use windows_sys::Win32::System::Console::{
+    STD_ERROR_HANDLE, STD_OUTPUT_HANDLE,
+  };
+
+  let Some((research, log)) = SERVICE_RESEARCH.lock().unwrap().take() else {
+    return;
+  };
+
+  let handler = |control: ServiceControl| match control {
+    ServiceControl::Stop | ServiceControl::Shutdown => {
+      stop_token().stop();
+
+      ServiceControlHandlerResult::NoError
+    }
+    ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+    _ => ServiceControlHandlerResult::NotImplemented,
+  };
+
+  let Ok(status) = service_control_handler::register("wakuchin", handler)
+  else {
+    return;
+  };
+
+  let set_state = |state, controls_accepted, exit_code| {
+    let _ = status.set_service_status(ServiceStatus {
+      service_type: ServiceType::OWN_PROCESS,
+      current_state: state,
+      controls_accepted,
+      exit_code,
+      checkpoint: 0,
+      wait_hint: Duration::default(),
+      process_id: None,
+    });
+  };
+
+  set_state(
+    ServiceState::Running,
+    ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+    ServiceExitCode::Win32(0),
+  );
+
+  let result = redirect(&log, STD_OUTPUT_HANDLE)
+    .and_then(|()| redirect(&log, STD_ERROR_HANDLE))
+    .map_err(anyhow::Error::from)
+    .and_then(|()| research());
+
+  let exit_code = match result {
+    Ok(()) => ServiceExitCode::Win32(0),
+    Err(err) => {
+      eprintln!("error: {err}");
+
+      ServiceExitCode::ServiceSpecific(1)
+    }
+  };
+
+  set_state(
+    ServiceState::Stopped,
+    ServiceControlAccept::empty(),
+    exit_code,
+  );
+}
+
+#[cfg(all(test, unix))]
+mod test {
+  use std::env::temp_dir;
+  use std::fs;
+
+  use anyhow::Result;
+  use ulid::Ulid;
+
+  use super::running_pid;
+
+  #[test]
+  fn test_running_pid() -> Result<()> {
+    let dir = temp_dir().join(format!("wakuchin-test-{}", Ulid::new()));
+    let pid_file = dir.join("wakuchin.pid");
+
+    fs::create_dir_all(&dir)?;
+
+    assert_eq!(running_pid(&pid_file), None);
+
+    fs::write(&pid_file, format!("{}\n", std::process::id()))?;
+
+    assert_eq!(
+      running_pid(&pid_file),
+      Some(std::process::id() as libc::pid_t)
+    );
+
+    fs::write(&pid_file, "wakuchin")?;
+
+    assert_eq!(running_pid(&pid_file), None);
+
+    fs::remove_dir_all(dir)?;
+
+    Ok(())
+  }
+}
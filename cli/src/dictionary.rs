@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::fs::{read_to_string, remove_file, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::process;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use wakuchin::fs::atomic_write;
+use wakuchin::result::{Hit, HitCount, WakuchinResult};
+
+use crate::error::{AppError, Result};
+
+/// Name of the dictionary file shared by all runs under a registry root.
+pub const DICTIONARY_FILE: &str = "dictionary.json";
+
+/// Name of the file holding the interned hits of a run.
+pub const HITS_FILE: &str = "hits.json";
+
+const LOCK_FILE: &str = "dictionary.lock";
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Hit strings shared across runs, referenced by their index.
+///
+/// The dictionary is append-only, so ids stay valid forever.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HitDictionary {
+  entries: Vec<String>,
+  #[serde(skip)]
+  ids: HashMap<String, u32>,
+}
+
+/// Hits of a run with their chars replaced by dictionary ids.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InternedHits {
+  /// `(chars id, hits)` pairs
+  pub hits: Vec<(u32, usize)>,
+  /// `(hit_on, chars id)` pairs
+  pub hits_detail: Vec<(usize, u32)>,
+}
+
+impl InternedHits {
+  /// Turn the interned `hits` back into hit counts.
+  pub fn resolve_hits(
+    &self,
+    dictionary: &HitDictionary,
+  ) -> Result<Vec<HitCount>> {
+    self
+      .hits
+      .iter()
+      .map(|(id, hits)| {
+        dictionary
+          .try_resolve(*id)
+          .map(|chars| HitCount::new(chars.to_string(), *hits))
+      })
+      .collect()
+  }
+
+  /// Turn the interned `hits_detail` back into hits.
+  pub fn resolve_detail(&self, dictionary: &HitDictionary) -> Result<Vec<Hit>> {
+    self
+      .hits_detail
+      .iter()
+      .map(|(hit_on, id)| {
+        dictionary
+          .try_resolve(*id)
+          .map(|chars| Hit::new(*hit_on, chars))
+      })
+      .collect()
+  }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> AppError + '_ {
+  move |source| AppError::WorkspaceIoError {
+    path: path.into(),
+    source,
+  }
+}
+
+/// Exclusive lock on the dictionary of a registry, released on drop.
+///
+/// The lock file holds the process ID of its holder, so a lock left behind by
+/// a crashed process is taken over without waiting for it to time out.
+struct DictionaryLock<'a> {
+  path: &'a Path,
+}
+
+impl<'a> DictionaryLock<'a> {
+  fn acquire(path: &'a Path) -> Result<Self> {
+    let started = SystemTime::now();
+
+    loop {
+      match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(mut file) => {
+          let lock = Self { path };
+
+          write!(file, "{}", process::id()).map_err(io_error(path))?;
+
+          return Ok(lock);
+        }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+          if let Some(holder) = stale_holder(path) {
+            // another process may have taken over the same lock meanwhile
+            if read_to_string(path).ok().as_deref() == Some(holder.as_str()) {
+              let _ = remove_file(path);
+            }
+
+            continue;
+          }
+
+          if started.elapsed().unwrap_or_default() > LOCK_TIMEOUT {
+            return Err(io_error(path)(io::Error::new(
+              ErrorKind::WouldBlock,
+              "timed out waiting for the hit dictionary lock",
+            )));
+          }
+
+          sleep(LOCK_RETRY_INTERVAL);
+        }
+        Err(e) => return Err(io_error(path)(e)),
+      }
+    }
+  }
+}
+
+impl Drop for DictionaryLock<'_> {
+  fn drop(&mut self) {
+    let _ = remove_file(self.path);
+  }
+}
+
+/// Return the contents of the lock file at `path` if its holder is gone.
+///
+/// A lock without a process ID is only stale once it is older than
+/// [`LOCK_STALE_AFTER`], as its holder may not have written it yet.
+fn stale_holder(path: &Path) -> Option<String> {
+  let contents = read_to_string(path).ok()?;
+
+  match contents.trim().parse() {
+    Ok(pid) => (!is_running(pid)).then_some(contents),
+    Err(_) => path
+      .metadata()
+      .and_then(|m| m.modified())
+      .ok()
+      .and_then(|modified| modified.elapsed().ok())
+      .is_some_and(|age| age > LOCK_STALE_AFTER)
+      .then_some(contents),
+  }
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+  let Ok(pid) = libc::pid_t::try_from(pid) else {
+    return false;
+  };
+
+  // signal 0 only checks whether the process exists, EPERM means it does but
+  // belongs to another user
+  (unsafe { libc::kill(pid, 0) } == 0)
+    || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+  // without a way to tell, the holder is assumed to be alive
+  true
+}
+
+impl HitDictionary {
+  /// Load the dictionary of a registry root, empty if there is none yet.
+  pub fn load(root: &Path) -> Result<Self> {
+    let path = root.join(DICTIONARY_FILE);
+
+    let mut dictionary: Self = match read_to_string(&path) {
+      Ok(contents) => serde_json::from_str(&contents)
+        .map_err(|e| AppError::Other(e.into()))?,
+      Err(e) if e.kind() == ErrorKind::NotFound => Self::default(),
+      Err(e) => return Err(io_error(&path)(e)),
+    };
+
+    dictionary.ids = dictionary
+      .entries
+      .iter()
+      .enumerate()
+      .map(|(id, chars)| (chars.clone(), id as u32))
+      .collect();
+
+    Ok(dictionary)
+  }
+
+  fn save(&self, root: &Path) -> Result<()> {
+    let path = root.join(DICTIONARY_FILE);
+    let contents =
+      serde_json::to_string(self).map_err(|e| AppError::Other(e.into()))?;
+
+    atomic_write(&path, contents).map_err(io_error(&path))
+  }
+
+  /// Load, modify and save the dictionary of a registry root while holding
+  /// its lock, so concurrent runs never hand out the same id twice.
+  pub fn update<R>(root: &Path, f: impl FnOnce(&mut Self) -> R) -> Result<R> {
+    let lock_path = root.join(LOCK_FILE);
+    let _lock = DictionaryLock::acquire(&lock_path)?;
+
+    let mut dictionary = Self::load(root)?;
+    let len = dictionary.entries.len();
+    let ret = f(&mut dictionary);
+
+    if dictionary.entries.len() != len {
+      dictionary.save(root)?;
+    }
+
+    Ok(ret)
+  }
+
+  /// Return the id of `chars`, adding it to the dictionary if needed.
+  pub fn intern(&mut self, chars: &str) -> u32 {
+    if let Some(id) = self.ids.get(chars) {
+      return *id;
+    }
+
+    let id = self.entries.len() as u32;
+
+    self.entries.push(chars.to_string());
+    self.ids.insert(chars.to_string(), id);
+
+    id
+  }
+
+  #[inline]
+  pub fn resolve(&self, id: u32) -> Option<&str> {
+    self.entries.get(id as usize).map(String::as_str)
+  }
+
+  fn try_resolve(&self, id: u32) -> Result<&str> {
+    self.resolve(id).ok_or_else(|| {
+      AppError::Other(anyhow::anyhow!(
+        "hit dictionary has no entry for id {id}"
+      ))
+    })
+  }
+
+  /// Intern all hits of a result.
+  pub fn intern_result(&mut self, result: &WakuchinResult) -> InternedHits {
+    InternedHits {
+      hits: result
+        .hits
+        .iter()
+        .map(|count| (self.intern(&count.chars), count.hits))
+        .collect(),
+      hits_detail: result
+        .hits_detail
+        .iter()
+        .map(|hit| (hit.hit_on, self.intern(&hit.chars)))
+        .collect(),
+    }
+  }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+  use std::env::temp_dir;
+  use std::fs::{create_dir_all, remove_dir_all, write};
+  use std::process::{self, Command};
+
+  use anyhow::Result;
+  use ulid::Ulid;
+  use wakuchin::result::{Hit, HitCount, WakuchinResult};
+
+  use super::{stale_holder, HitDictionary, LOCK_FILE};
+
+  #[test]
+  fn test_hit_dictionary() -> Result<()> {
+    let root = temp_dir().join(format!("wakuchin-test-{}", Ulid::new()));
+
+    create_dir_all(&root)?;
+
+    let result = WakuchinResult {
+      tries: 10,
+      hits_total: 3,
+      hits: vec![HitCount::new("WKCN", 2), HitCount::new("WKNC", 1)],
+      hits_detail: vec![
+        Hit::new(0, "WKCN"),
+        Hit::new(4, "WKNC"),
+        Hit::new(7, "WKCN"),
+      ],
+      patterns: Vec::new(),
+      rarity: None,
+      stop_reason: None,
+      failures: Vec::new(),
+    };
+
+    let interned =
+      HitDictionary::update(&root, |dict| dict.intern_result(&result))?;
+
+    assert_eq!(interned.hits, vec![(0, 2), (1, 1)]);
+    assert_eq!(interned.hits_detail, vec![(0, 0), (4, 1), (7, 0)]);
+
+    // ids are stable across runs
+    let id = HitDictionary::update(&root, |dict| dict.intern("WKNC"))?;
+
+    assert_eq!(id, 1);
+
+    let dictionary = HitDictionary::load(&root)?;
+
+    assert_eq!(dictionary.entries, vec!["WKCN", "WKNC"]);
+    assert_eq!(dictionary.resolve(1), Some("WKNC"));
+    assert_eq!(dictionary.resolve(2), None);
+
+    assert_eq!(interned.resolve_hits(&dictionary)?, result.hits);
+
+    let detail = interned.resolve_detail(&dictionary)?;
+
+    assert_eq!(detail.len(), 3);
+    assert_eq!(detail[1].hit_on, 4);
+    assert_eq!(detail[1].chars, "WKNC");
+
+    remove_dir_all(root)?;
+
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_stale_lock() -> Result<()> {
+    let root = temp_dir().join(format!("wakuchin-test-{}", Ulid::new()));
+    let lock_path = root.join(LOCK_FILE);
+
+    create_dir_all(&root)?;
+
+    // a lock held by a running process is not taken over
+    write(&lock_path, process::id().to_string())?;
+
+    assert!(stale_holder(&lock_path).is_none());
+
+    // a fresh lock without a process ID may still be being written
+    write(&lock_path, "")?;
+
+    assert!(stale_holder(&lock_path).is_none());
+
+    let mut child = Command::new("true").spawn()?;
+    let pid = child.id();
+
+    child.wait()?;
+    write(&lock_path, pid.to_string())?;
+
+    assert_eq!(stale_holder(&lock_path), Some(pid.to_string()));
+
+    let id = HitDictionary::update(&root, |dict| dict.intern("WKCN"))?;
+
+    assert_eq!(id, 0);
+    assert!(!lock_path.exists());
+
+    remove_dir_all(root)?;
+
+    Ok(())
+  }
+}
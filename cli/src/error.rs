@@ -16,18 +16,26 @@ pub enum AppError {
   #[error("'{}': Not supported config type", .path.to_string_lossy())]
   ConfigTypeNotSupported { path: Box<Path> },
   #[error("error when parsing config file:
-   {} {}{line}{column}{source}",
+   {} {}{line}{column}{source}{hint}",
     "-->".blue().bold(),
     .path.to_string_lossy(),
     line = .line.map(|l| format!(":{l}")).unwrap_or_default(),
-    column = .column.map(|c| format!(":{c}")).unwrap_or_default())]
+    column = .column.map(|c| format!(":{c}")).unwrap_or_default(),
+    hint = .hint.as_ref().map(|h| format!("\n   {} {h}", "= help:".blue().bold())).unwrap_or_default())]
   ConfigDeserializeError {
     path: Box<Path>,
     line: Option<usize>,
     column: Option<usize>,
+    hint: Option<String>,
     #[source]
     source: Box<SerdeError>,
   },
+  #[error("'{}': {source}", .path.to_string_lossy())]
+  WorkspaceIoError {
+    path: Box<Path>,
+    #[source]
+    source: io::Error,
+  },
   #[error(transparent)]
   Other(anyhow::Error),
 }
@@ -1,22 +1,46 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, stderr, stdout, BufReader, Write};
 use std::ops::{Bound, RangeBounds};
-use std::time::Duration;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use anyhow::bail;
 use clap::ValueEnum;
 use console::Term;
 use owo_colors::OwoColorize as _;
-use serde::{Deserialize, Serialize};
-use wakuchin::convert::chars_to_wakuchin;
-use wakuchin::handlers::ProgressHandler;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, IntoDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use wakuchin::convert::to_external;
+use wakuchin::handlers::csv::CsvProgressHandler;
+use wakuchin::handlers::deadline::DeadlineOverrun;
+use wakuchin::handlers::msgpack::{
+  Acks, Compression, Framing, MsgpackBase64ProgressHandler,
+  MsgpackProgressHandler, DEFAULT_ACK_WINDOW,
+};
+use wakuchin::handlers::throttle::Throttled;
+use wakuchin::handlers::{ControlFlow, ProgressHandler, RunInfo};
 use wakuchin::progress::{
   DoneDetail, IdleDetail, ProcessingDetail, Progress, ProgressKind,
+  StalledDetail,
 };
+use wakuchin::protocol::ProtocolVersion;
 use wakuchin::result::HitCount;
+use wakuchin::symbol::SymbolSet;
+
+use crate::error::AppError;
 
-const DEFAULT_TERMINAL_WIDTH: u16 = 33;
+pub(crate) const DEFAULT_TERMINAL_WIDTH: u16 = 33;
 const DEFAULT_TERMINAL_HEIGHT: u16 = 20;
 
 const PROGRESS_BAR_WIDTH_MAX: u16 = DEFAULT_TERMINAL_WIDTH;
 
+/// Time between the lines of the plain handler.
+const PLAIN_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(
   Clone,
   Debug,
@@ -33,6 +57,8 @@ pub enum HandlerKind {
   Console,
   Msgpack,
   MsgpackBase64,
+  Csv,
+  Plain,
 }
 
 impl Default for HandlerKind {
@@ -41,6 +67,332 @@ impl Default for HandlerKind {
   }
 }
 
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum InternalFraming {
+  #[default]
+  None,
+  LengthPrefixed,
+  Newline,
+}
+
+impl From<InternalFraming> for Framing {
+  fn from(framing: InternalFraming) -> Self {
+    match framing {
+      InternalFraming::None => Self::None,
+      InternalFraming::LengthPrefixed => Self::LengthPrefixed,
+      InternalFraming::Newline => Self::Newline,
+    }
+  }
+}
+
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum InternalCompression {
+  #[default]
+  None,
+  Zstd,
+}
+
+/// Level of zstd unless `compression_level` is set.
+#[cfg(feature = "zstd")]
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Options of the progress handler, the `[handler]` table of the config.
+///
+/// A plain string is accepted as well and taken as `kind`, e.g.
+/// `handler = "msgpack"`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HandlerConfig {
+  pub kind: HandlerKind,
+  /// File to write progress to instead of stdout
+  pub output: Option<PathBuf>,
+  /// Encode msgpack frames with base64
+  pub base64: bool,
+  pub frame: InternalFraming,
+  /// File to write progress to once stdout is closed, e.g. by `head`,
+  /// instead of giving it up
+  pub fallback_output: Option<PathBuf>,
+  /// File, e.g. a FIFO, the consumer writes the seq number of each frame it
+  /// read to, one per line, so frames are skipped while it lags behind
+  pub acks: Option<PathBuf>,
+  /// Unacknowledged frames after which frames are skipped
+  pub ack_window: Option<u64>,
+  /// Compression of each frame, for consumers on slow links
+  pub compression: InternalCompression,
+  pub compression_level: Option<i32>,
+  /// Hit chars the csv handler writes a column of hits for
+  pub chars: Vec<String>,
+}
+
+impl From<HandlerKind> for HandlerConfig {
+  fn from(kind: HandlerKind) -> Self {
+    Self {
+      kind,
+      ..Default::default()
+    }
+  }
+}
+
+/// Deserialize either a handler kind or a `[handler]` table.
+pub(crate) fn deserialize_handler<'de, D>(
+  deserializer: D,
+) -> Result<Option<HandlerConfig>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  struct HandlerVisitor;
+
+  impl<'de> Visitor<'de> for HandlerVisitor {
+    type Value = HandlerConfig;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      f.write_str("a handler kind or a handler table")
+    }
+
+    fn visit_str<E: de::Error>(self, kind: &str) -> Result<Self::Value, E> {
+      HandlerKind::deserialize(kind.into_deserializer()).map(Into::into)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+      self,
+      map: A,
+    ) -> Result<Self::Value, A::Error> {
+      HandlerConfig::deserialize(MapAccessDeserializer::new(map))
+    }
+  }
+
+  deserializer.deserialize_any(HandlerVisitor).map(Some)
+}
+
+impl HandlerConfig {
+  /// Create the progress handler described by this config, for a research
+  /// resuming `baseline` tries done before.
+  pub fn build(
+    &self,
+    no_progress: bool,
+    tries: usize,
+    times: usize,
+    symbols: &SymbolSet,
+    protocol_version: ProtocolVersion,
+    baseline: usize,
+  ) -> anyhow::Result<Box<dyn ProgressHandler>> {
+    if self.kind == HandlerKind::Console {
+      if self.output.is_some()
+        || self.base64
+        || Framing::from(self.frame) != Framing::None
+        || self.fallback_output.is_some()
+        || self.acks.is_some()
+        || self.ack_window.is_some()
+        || self.compression != InternalCompression::None
+        || self.compression_level.is_some()
+        || !self.chars.is_empty()
+      {
+        bail!("console handler does not support 'output', 'base64', 'frame', 'fallback_output', 'acks', 'ack_window', 'compression', 'compression_level' or 'chars'");
+      }
+
+      return Ok(Box::new(
+        ConsoleProgressHandler::new(no_progress, tries, times)
+          .with_symbols(symbols.clone())
+          .with_baseline(baseline),
+      ));
+    }
+
+    if self.kind == HandlerKind::Csv {
+      if self.base64
+        || Framing::from(self.frame) != Framing::None
+        || self.fallback_output.is_some()
+        || self.acks.is_some()
+        || self.ack_window.is_some()
+        || self.compression != InternalCompression::None
+        || self.compression_level.is_some()
+      {
+        bail!("csv handler does not support 'base64', 'frame', 'fallback_output', 'acks', 'ack_window', 'compression' or 'compression_level'");
+      }
+
+      return Ok(Box::new(
+        CsvProgressHandler::new(self.writer()?).with_chars(self.chars.clone()),
+      ));
+    }
+
+    if self.kind == HandlerKind::Plain {
+      if self.base64
+        || Framing::from(self.frame) != Framing::None
+        || self.fallback_output.is_some()
+        || self.acks.is_some()
+        || self.ack_window.is_some()
+        || self.compression != InternalCompression::None
+        || self.compression_level.is_some()
+        || !self.chars.is_empty()
+      {
+        bail!("plain handler does not support 'base64', 'frame', 'fallback_output', 'acks', 'ack_window', 'compression', 'compression_level' or 'chars'");
+      }
+
+      let writer: Arc<Mutex<dyn Write + Send>> = match self.output {
+        Some(_) => self.writer()?,
+        None => Arc::new(Mutex::new(stderr())),
+      };
+      let handler = PlainProgressHandler::new(no_progress, tries, writer)
+        .with_symbols(symbols.clone())
+        .with_baseline(baseline);
+
+      return Ok(Box::new(Throttled::new(handler, PLAIN_INTERVAL)));
+    }
+
+    self.check_msgpack()?;
+
+    self.build_msgpack_with(
+      tries,
+      self.writer()?,
+      protocol_version,
+      self.read_acks(),
+    )
+  }
+
+  /// Open the output of the handler, stdout unless configured.
+  fn writer(&self) -> anyhow::Result<Arc<Mutex<dyn Write + Send>>> {
+    Ok(match &self.output {
+      Some(path) => {
+        Arc::new(Mutex::new(File::create(path).map_err(|source| {
+          AppError::ConfigIoError {
+            path: path.as_path().into(),
+            source,
+          }
+        })?))
+      }
+      None => Arc::new(Mutex::new(stdout())),
+    })
+  }
+
+  /// Start reading the acknowledgements of the consumer, if configured.
+  fn read_acks(&self) -> Option<Acks> {
+    let path = self.acks.clone()?;
+    let acks = Acks::new(self.ack_window.unwrap_or(DEFAULT_ACK_WINDOW));
+
+    thread::spawn({
+      let acks = acks.clone();
+
+      move || {
+        // opening a FIFO blocks until the consumer opens it
+        let result = File::open(&path)
+          .and_then(|file| acks.read_lines(BufReader::new(file)));
+
+        if let Err(e) = result {
+          eprintln!(
+            "warning: failed to read acknowledgements from {}: {e}",
+            path.display()
+          );
+        }
+      }
+    });
+
+    Some(acks)
+  }
+
+  /// Create the msgpack progress handler writing to `fallback_output`
+  /// instead of its output.
+  pub fn build_fallback(
+    &self,
+    tries: usize,
+    protocol_version: ProtocolVersion,
+  ) -> anyhow::Result<Box<dyn ProgressHandler>> {
+    let Some(path) = &self.fallback_output else {
+      bail!("no fallback output is configured");
+    };
+    let file =
+      File::create(path).map_err(|source| AppError::ConfigIoError {
+        path: path.as_path().into(),
+        source,
+      })?;
+
+    self.build_msgpack(tries, Arc::new(Mutex::new(file)), protocol_version)
+  }
+
+  #[inline]
+  fn is_base64(&self) -> bool {
+    self.base64 || self.kind == HandlerKind::MsgpackBase64
+  }
+
+  /// Convert the compression, unavailable without the "zstd" feature.
+  pub fn compression(&self) -> anyhow::Result<Compression> {
+    match self.compression {
+      InternalCompression::None => Ok(Compression::None),
+      #[cfg(feature = "zstd")]
+      InternalCompression::Zstd => Ok(Compression::Zstd(
+        self.compression_level.unwrap_or(DEFAULT_ZSTD_LEVEL),
+      )),
+      #[cfg(not(feature = "zstd"))]
+      InternalCompression::Zstd => bail!(
+        "zstd compression requires wakuchin to be built with the \"zstd\" feature"
+      ),
+    }
+  }
+
+  /// Check that the msgpack frames can be delimited as configured.
+  pub fn check_msgpack(&self) -> anyhow::Result<()> {
+    if !self.chars.is_empty() {
+      bail!("'chars' is only supported by the csv handler");
+    }
+
+    if !self.is_base64() && Framing::from(self.frame) == Framing::Newline {
+      bail!(
+        "newline framing requires base64, msgpack frames may contain newlines"
+      );
+    }
+
+    Ok(())
+  }
+
+  /// Create the msgpack progress handler described by this config, writing
+  /// to `writer` instead of its output.
+  pub fn build_msgpack(
+    &self,
+    tries: usize,
+    writer: Arc<Mutex<dyn Write + Send>>,
+    protocol_version: ProtocolVersion,
+  ) -> anyhow::Result<Box<dyn ProgressHandler>> {
+    self.build_msgpack_with(tries, writer, protocol_version, None)
+  }
+
+  fn build_msgpack_with(
+    &self,
+    tries: usize,
+    writer: Arc<Mutex<dyn Write + Send>>,
+    protocol_version: ProtocolVersion,
+    acks: Option<Acks>,
+  ) -> anyhow::Result<Box<dyn ProgressHandler>> {
+    let framing = Framing::from(self.frame);
+    let compression = self.compression()?;
+
+    if self.is_base64() {
+      let handler = MsgpackBase64ProgressHandler::new(tries, writer)
+        .with_framing(framing)
+        .with_protocol_version(protocol_version)
+        .with_compression(compression);
+
+      Ok(match acks {
+        Some(acks) => Box::new(handler.with_acks(acks)),
+        None => Box::new(handler),
+      })
+    } else {
+      let handler = MsgpackProgressHandler::new(tries, writer)
+        .with_framing(framing)
+        .with_protocol_version(protocol_version)
+        .with_compression(compression);
+
+      Ok(match acks {
+        Some(acks) => Box::new(handler.with_acks(acks)),
+        None => Box::new(handler),
+      })
+    }
+  }
+}
+
 pub struct ConsoleProgressHandler {
   no_progress: bool,
   handler_height: usize,
@@ -48,7 +400,11 @@ pub struct ConsoleProgressHandler {
   tries: usize,
   tries_string: String,
   times: usize,
+  symbols: SymbolSet,
   total_workers: usize,
+  baseline: usize,
+  /// Warning to print above the progress once it is redrawn
+  warning: Option<String>,
 }
 
 impl ConsoleProgressHandler {
@@ -60,10 +416,27 @@ impl ConsoleProgressHandler {
       tries,
       tries_string: tries.to_string(),
       times,
+      symbols: SymbolSet::default(),
       total_workers: 0,
+      baseline: 0,
+      warning: None,
     }
   }
 
+  /// Display hits with the external symbols of `symbols`.
+  pub fn with_symbols(mut self, symbols: SymbolSet) -> Self {
+    self.symbols = symbols;
+    self
+  }
+
+  /// Count `baseline` tries done before a resumed run in the status, so it
+  /// shows the progress of the whole research.
+  pub fn with_baseline(mut self, baseline: usize) -> Self {
+    self.baseline = baseline;
+    self.tries_string = (baseline + self.tries).to_string();
+    self
+  }
+
   /// Append a worker ID to the base string.
   /// If the ID is 0, return true and the base string.
   ///
@@ -150,18 +523,19 @@ impl ConsoleProgressHandler {
     buf: &mut itoa::Buffer,
     id_width: usize,
     hit_counts: &[HitCount],
-  ) -> usize {
+  ) -> io::Result<usize> {
     let mut current_hit_total = 0;
 
     let tries_width = self.tries_string.len();
 
     for hit_count in hit_counts {
-      let chars = chars_to_wakuchin(&hit_count.chars);
+      let chars = to_external(&hit_count.chars, &self.symbols);
       let count = hit_count.hits;
 
       current_hit_total += count;
 
-      eprintln!(
+      writeln!(
+        &self.term,
         "      {} {}: {:<} ({:.3}%)",
         Self::pad_id(
           self.total_workers,
@@ -171,10 +545,11 @@ impl ConsoleProgressHandler {
         chars.dimmed(),
         buf.format(count).bold(),
         count as f64 / self.tries as f64 * 100.0,
-      );
+      )?;
     }
 
-    eprintln!(
+    writeln!(
+      &self.term,
       "{} {:<tries_width$} / {tries} ({:.3}%)",
       Self::pad_id(
         self.total_workers,
@@ -184,9 +559,9 @@ impl ConsoleProgressHandler {
       buf.format(current_hit_total).bold(),
       current_hit_total as f64 / self.tries as f64 * 100.0,
       tries = self.tries
-    );
+    )?;
 
-    current_hit_total
+    Ok(current_hit_total)
   }
 
   fn render_workers(
@@ -194,7 +569,7 @@ impl ConsoleProgressHandler {
     buf: &mut itoa::Buffer,
     progresses: &[Progress],
     terminal_height: u16,
-  ) -> usize {
+  ) -> io::Result<usize> {
     // truncate all progress with one line if the terminal height is too small
     if self.handler_height > terminal_height.into() {
       // collect total processing workers
@@ -203,7 +578,10 @@ impl ConsoleProgressHandler {
         .filter_map(|progress| {
           (
             matches!(progress, Progress(ProgressKind::Idle(_))),
-            matches!(progress, Progress(ProgressKind::Processing(_))),
+            matches!(
+              progress,
+              Progress(ProgressKind::Processing(_) | ProgressKind::Stalled(_))
+            ),
             matches!(progress, Progress(ProgressKind::Done(_))),
           )
             .into()
@@ -275,9 +653,9 @@ impl ConsoleProgressHandler {
         .to_string(),
       );
 
-      eprintln!("{}", appended_string);
+      writeln!(&self.term, "{appended_string}")?;
 
-      return processing_workers + done_workers;
+      return Ok(processing_workers + done_workers);
     }
 
     let mut current_total = 0;
@@ -304,11 +682,35 @@ impl ConsoleProgressHandler {
             format!(
               "{} {} • {:<tries_width$} / {total}",
               "Processing".blue(),
-              chars_to_wakuchin(wakuchin).dimmed(),
+              to_external(wakuchin, &self.symbols).dimmed(),
               buf.format(*current)
             ),
           )
         }
+        Progress(ProgressKind::Stalled(StalledDetail {
+          id,
+          current,
+          stalled_for,
+        })) => {
+          current_total += current;
+
+          Self::append_id(
+            *id,
+            id_width,
+            format!(
+              "{} {:<tries_width$} {}",
+              "Stalled   ".red(),
+              buf.format(*current),
+              format!(
+                "for {}",
+                humantime::format_duration(Duration::from_secs(
+                  stalled_for.as_secs()
+                ))
+              )
+              .dimmed()
+            ),
+          )
+        }
         Progress(ProgressKind::Done(DoneDetail { id, total })) => {
           current_total += total;
 
@@ -318,23 +720,31 @@ impl ConsoleProgressHandler {
             format!(
               "{} {}",
               "Done      ".green(),
-              " ".repeat(self.times * 8 + self.tries_string.len() * 2 + 5)
+              " ".repeat(
+                self.times
+                  * console::measure_text_width(
+                    &self.symbols.external().iter().collect::<String>()
+                  )
+                  + self.tries_string.len() * 2
+                  + 5
+              )
             ),
           )
         }
       };
 
       if sequential {
-        eprintln!(
+        writeln!(
+          &self.term,
           "{}",
           Self::pad_id(1, self.total_workers.to_string().len(), body)
-        );
+        )?;
       } else {
-        eprintln!("{body}");
+        writeln!(&self.term, "{body}")?;
       }
     }
 
-    current_total
+    Ok(current_total)
   }
 
   /// Use blue bar to indicate progress that is processing.
@@ -346,7 +756,7 @@ impl ConsoleProgressHandler {
     elapsed_time: Duration,
     current_diff: usize,
     terminal_width: u16,
-  ) {
+  ) -> io::Result<()> {
     let tries_width = self.tries_string.len();
 
     let possible_bar_width = {
@@ -366,34 +776,43 @@ impl ConsoleProgressHandler {
     };
 
     let id_width = self.total_workers.to_string().len();
-    let percentage = current as f64 / self.tries as f64 * 100.0;
+    let total = self.baseline + self.tries;
+    let percentage = (self.baseline + current) as f64 / total as f64 * 100.0;
     let bar = Self::render_progress_segment(bar_width.into(), percentage);
     let rate = current_diff as f64 / elapsed_time.as_secs_f64();
     let eta = (self.tries - current) as f64 / rate;
+    let resumed = if self.baseline == 0 {
+      String::new()
+    } else {
+      format!(
+        ", resumed at {:.0}%",
+        self.baseline as f64 / total as f64 * 100.0
+      )
+    };
 
-    eprintln!(
-      "{} {bar} • {}: {:<tries_width$} / {tries} ({percentage:.0}%, {rate}/sec, eta: {eta:>3.0}sec)   ",
+    writeln!(
+      &self.term,
+      "{} {bar} • {}: {:<tries_width$} / {total} ({percentage:.0}%{resumed}, {rate}/sec, eta: {eta:>3.0}sec)   ",
       Self::pad_id(self.total_workers, id_width, "Status".bold().to_string()),
       "total".green().underline(),
-      buf.format(current).bold(),
-      tries = self.tries,
+      buf.format(self.baseline + current).bold(),
       rate = human_format::Formatter::new().format(rate),
-    );
+    )
   }
 }
 
 impl ProgressHandler for ConsoleProgressHandler {
-  fn before_start(&mut self, total_workers: usize) -> anyhow::Result<()> {
+  fn before_start(&mut self, info: &RunInfo) -> anyhow::Result<()> {
     if self.no_progress {
       return Ok(());
     }
 
-    eprint!("Spawning workers...");
+    write!(&self.term, "Spawning workers...")?;
 
     self.term.hide_cursor()?;
     self.term.move_cursor_left(u16::MAX as usize)?;
 
-    self.total_workers = total_workers;
+    self.total_workers = info.workers;
 
     Ok(())
   }
@@ -405,9 +824,9 @@ impl ProgressHandler for ConsoleProgressHandler {
     elapsed_time: Duration,
     current_diff: usize,
     all_done: bool,
-  ) -> anyhow::Result<()> {
+  ) -> anyhow::Result<ControlFlow> {
     if self.no_progress {
-      return Ok(());
+      return Ok(ControlFlow::Continue);
     }
 
     if self.handler_height == 0 {
@@ -419,13 +838,19 @@ impl ProgressHandler for ConsoleProgressHandler {
         .move_cursor_up(self.handler_height as u16 as usize)?;
     }
 
+    // pushes the progress down a line for good
+    if let Some(warning) = self.warning.take() {
+      self.term.clear_line()?;
+      writeln!(&self.term, "{warning}")?;
+    }
+
     let mut itoa_buf = itoa::Buffer::new();
 
     self.render_hit_counts(
       &mut itoa_buf,
       self.total_workers.to_string().len(),
       hit_counts,
-    );
+    )?;
 
     let size = self.term.size_checked();
 
@@ -434,13 +859,19 @@ impl ProgressHandler for ConsoleProgressHandler {
       None => (DEFAULT_TERMINAL_HEIGHT, DEFAULT_TERMINAL_WIDTH),
     };
 
-    let current_total = self.render_workers(&mut itoa_buf, progresses, height);
+    let current_total =
+      self.render_workers(&mut itoa_buf, progresses, height)?;
 
     if all_done {
       self.term.clear_line()?;
-      eprintln!("{} {}", "Status".bold(), "All Done".bold().green());
-
-      return Ok(());
+      writeln!(
+        &self.term,
+        "{} {}",
+        "Status".bold(),
+        "All Done".bold().green()
+      )?;
+
+      return Ok(ControlFlow::Continue);
     }
 
     self.render_progress_bar(
@@ -449,8 +880,54 @@ impl ProgressHandler for ConsoleProgressHandler {
       elapsed_time,
       current_diff,
       width,
+    )?;
+
+    Ok(ControlFlow::Continue)
+  }
+
+  fn on_pause(&mut self) -> anyhow::Result<()> {
+    if !self.no_progress && self.handler_height != 0 {
+      // replace the progress bar, the next progress redraws it
+      self.term.clear_last_lines(1)?;
+      writeln!(
+        &self.term,
+        "{} {}",
+        "Status".bold(),
+        "Paused".bold().yellow()
+      )?;
+    }
+
+    self.term.show_cursor()?;
+
+    Ok(())
+  }
+
+  fn on_resume(&mut self) -> anyhow::Result<()> {
+    if !self.no_progress {
+      self.term.hide_cursor()?;
+    }
+
+    Ok(())
+  }
+
+  fn on_deadline_overrun(
+    &mut self,
+    overrun: &DeadlineOverrun,
+  ) -> anyhow::Result<()> {
+    let round = |duration: Duration| Duration::from_secs(duration.as_secs());
+    let warning = format!(
+      "{} expected to take {}, but estimated to take {}",
+      "Warning:".bold().yellow(),
+      humantime::format_duration(round(overrun.expected)),
+      humantime::format_duration(round(overrun.estimated)),
     );
 
+    if self.no_progress {
+      writeln!(&self.term, "{warning}")?;
+    } else {
+      self.warning = Some(warning);
+    }
+
     Ok(())
   }
 
@@ -468,3 +945,379 @@ impl ProgressHandler for ConsoleProgressHandler {
     Ok(())
   }
 }
+
+/// Handler printing a line of progress at a time, without colors nor
+/// cursor movements, for CI logs and `nohup` output.
+pub struct PlainProgressHandler {
+  no_progress: bool,
+  tries: usize,
+  symbols: SymbolSet,
+  baseline: usize,
+  writer: Arc<Mutex<dyn Write + Send>>,
+  started: Instant,
+}
+
+impl PlainProgressHandler {
+  pub fn new(
+    no_progress: bool,
+    tries: usize,
+    writer: Arc<Mutex<dyn Write + Send>>,
+  ) -> Self {
+    Self {
+      no_progress,
+      tries,
+      symbols: SymbolSet::default(),
+      baseline: 0,
+      writer,
+      started: Instant::now(),
+    }
+  }
+
+  /// Print hits with the external symbols of `symbols`.
+  pub fn with_symbols(mut self, symbols: SymbolSet) -> Self {
+    self.symbols = symbols;
+    self
+  }
+
+  /// Count `baseline` tries done before a resumed run, so the lines show
+  /// the progress of the whole research.
+  pub fn with_baseline(mut self, baseline: usize) -> Self {
+    self.baseline = baseline;
+    self
+  }
+
+  fn println(&self, line: fmt::Arguments) -> io::Result<()> {
+    if self.no_progress {
+      return Ok(());
+    }
+
+    self.write_line(line)
+  }
+
+  /// Print `line` prefixed with the elapsed time, even without progress.
+  fn write_line(&self, line: fmt::Arguments) -> io::Result<()> {
+    let elapsed = Duration::from_secs(self.started.elapsed().as_secs());
+    let mut writer = self.writer.lock().unwrap();
+
+    writeln!(writer, "[{}] {line}", humantime::format_duration(elapsed))?;
+    writer.flush()
+  }
+}
+
+impl ProgressHandler for PlainProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> anyhow::Result<()> {
+    self.started = Instant::now();
+
+    self.println(format_args!(
+      "Started {} tries on {} workers",
+      self.baseline + self.tries,
+      info.workers
+    ))?;
+
+    Ok(())
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> anyhow::Result<ControlFlow> {
+    let current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>()
+      .min(self.tries);
+    let hits = hit_counts
+      .iter()
+      .map(|count| {
+        format!(
+          "{} {}",
+          to_external(&count.chars, &self.symbols),
+          count.hits
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(", ");
+    let total = self.baseline + self.tries;
+    let percentage =
+      (self.baseline + current) as f64 / total.max(1) as f64 * 100.0;
+
+    if all_done {
+      self.println(format_args!(
+        "All done: {} / {total} tries, hits: {}",
+        self.baseline + current,
+        if hits.is_empty() { "none" } else { &hits }
+      ))?;
+
+      return Ok(ControlFlow::Continue);
+    }
+
+    let rate = current_diff as f64 / elapsed_time.as_secs_f64();
+    let eta = (self.tries - current) as f64 / rate;
+    let eta = if eta.is_finite() {
+      humantime::format_duration(Duration::from_secs(eta as u64)).to_string()
+    } else {
+      "unknown".to_string()
+    };
+
+    self.println(format_args!(
+      "{} / {total} tries ({percentage:.1}%), {}/sec, eta {eta}, hits: {}",
+      self.baseline + current,
+      human_format::Formatter::new()
+        .with_separator("")
+        .format(if rate.is_finite() { rate } else { 0.0 }),
+      if hits.is_empty() { "none" } else { &hits }
+    ))?;
+
+    Ok(ControlFlow::Continue)
+  }
+
+  fn on_pause(&mut self) -> anyhow::Result<()> {
+    self.println(format_args!("Paused"))?;
+
+    Ok(())
+  }
+
+  fn on_resume(&mut self) -> anyhow::Result<()> {
+    self.println(format_args!("Resumed"))?;
+
+    Ok(())
+  }
+
+  fn on_deadline_overrun(
+    &mut self,
+    overrun: &DeadlineOverrun,
+  ) -> anyhow::Result<()> {
+    let round = |duration: Duration| Duration::from_secs(duration.as_secs());
+
+    // warned even without progress, like the console handler does
+    self.write_line(format_args!(
+      "Warning: expected to take {}, but estimated to take {}",
+      humantime::format_duration(round(overrun.expected)),
+      humantime::format_duration(round(overrun.estimated)),
+    ))?;
+
+    Ok(())
+  }
+
+  fn on_accidential_stop(&mut self) -> anyhow::Result<()> {
+    self.println(format_args!("Stopped"))?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::path::PathBuf;
+
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use serde::Deserialize;
+
+  use wakuchin::handlers::{ProgressHandler, RunInfo};
+  use wakuchin::progress::{
+    DoneDetail, ProcessingDetail, Progress, ProgressKind,
+  };
+  use wakuchin::protocol::ProtocolVersion;
+  use wakuchin::result::HitCount;
+  use wakuchin::symbol::SymbolSet;
+
+  use super::{
+    HandlerConfig, HandlerKind, InternalCompression, InternalFraming,
+    PlainProgressHandler,
+  };
+
+  #[derive(Deserialize)]
+  struct Config {
+    #[serde(default, deserialize_with = "super::deserialize_handler")]
+    handler: Option<HandlerConfig>,
+  }
+
+  #[test]
+  fn test_deserialize_handler() -> Result<()> {
+    let config: Config = toml::from_str(r#"handler = "msgpack_base64""#)?;
+
+    assert_eq!(config.handler, Some(HandlerKind::MsgpackBase64.into()));
+
+    let config: Config = toml::from_str(
+      r#"
+      [handler]
+      kind = "msgpack"
+      output = "/tmp/p.bin"
+      base64 = true
+      frame = "length-prefixed"
+      "#,
+    )?;
+
+    assert_eq!(
+      config.handler,
+      Some(HandlerConfig {
+        kind: HandlerKind::Msgpack,
+        output: Some(PathBuf::from("/tmp/p.bin")),
+        base64: true,
+        frame: InternalFraming::LengthPrefixed,
+        fallback_output: None,
+        acks: None,
+        ack_window: None,
+        compression: InternalCompression::None,
+        compression_level: None,
+        chars: Vec::new(),
+      })
+    );
+
+    let config: Config = toml::from_str("")?;
+
+    assert_eq!(config.handler, None);
+
+    let err = toml::from_str::<Config>("[handler]\nkinds = \"msgpack\"")
+      .err()
+      .unwrap();
+
+    assert!(err.to_string().contains("unknown field `kinds`"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_build_handler() {
+    let console = HandlerConfig {
+      base64: true,
+      ..Default::default()
+    };
+
+    assert!(console
+      .build(
+        false,
+        10,
+        1,
+        &SymbolSet::default(),
+        ProtocolVersion::LATEST,
+        0
+      )
+      .is_err());
+
+    let newline = HandlerConfig {
+      kind: HandlerKind::Msgpack,
+      frame: InternalFraming::Newline,
+      ..Default::default()
+    };
+
+    assert!(newline
+      .build(
+        false,
+        10,
+        1,
+        &SymbolSet::default(),
+        ProtocolVersion::LATEST,
+        0
+      )
+      .is_err());
+    assert!(HandlerConfig {
+      base64: true,
+      ..newline
+    }
+    .build(
+      false,
+      10,
+      1,
+      &SymbolSet::default(),
+      ProtocolVersion::LATEST,
+      0
+    )
+    .is_ok());
+
+    let csv = HandlerConfig {
+      kind: HandlerKind::Csv,
+      chars: vec!["WKCN".into()],
+      ..Default::default()
+    };
+    let build = |config: &HandlerConfig| {
+      config.build(
+        false,
+        10,
+        1,
+        &SymbolSet::default(),
+        ProtocolVersion::LATEST,
+        0,
+      )
+    };
+
+    assert!(build(&csv).is_ok());
+    assert!(build(&HandlerConfig {
+      base64: true,
+      ..csv.clone()
+    })
+    .is_err());
+    assert!(build(&HandlerConfig {
+      kind: HandlerKind::Msgpack,
+      ..csv.clone()
+    })
+    .is_err());
+    assert!(build(&HandlerConfig {
+      kind: HandlerKind::Plain,
+      ..csv
+    })
+    .is_err());
+    assert!(build(&HandlerKind::Plain.into()).is_ok());
+  }
+
+  #[test]
+  fn test_plain_handler() -> Result<()> {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut handler = PlainProgressHandler::new(false, 100, output.clone());
+
+    handler.before_start(&RunInfo::new(2))?;
+    handler.handle(
+      &[Progress(ProgressKind::Processing(ProcessingDetail {
+        id: 1,
+        current: 50,
+        total: 100,
+        wakuchin: "WKCN".into(),
+      }))],
+      &[HitCount::new("WKNC", 1)],
+      Duration::from_secs(1),
+      50,
+      false,
+    )?;
+    handler.handle(
+      &[Progress(ProgressKind::Done(DoneDetail {
+        id: 1,
+        total: 100,
+      }))],
+      &[HitCount::new("WKNC", 2)],
+      Duration::from_secs(1),
+      50,
+      true,
+    )?;
+
+    let output = String::from_utf8(output.lock().unwrap().clone())?;
+
+    assert_eq!(
+      output.lines().collect::<Vec<_>>(),
+      [
+        "[0s] Started 100 tries on 2 workers",
+        "[0s] 50 / 100 tries (50.0%), 50.00/sec, eta 1s, hits: わくんち 1",
+        "[0s] All done: 100 / 100 tries, hits: わくんち 2",
+      ]
+    );
+    assert!(!output.contains('\x1b'));
+
+    Ok(())
+  }
+}
@@ -0,0 +1,182 @@
+//! Heartbeats of running researches, telling a stalled run from a slow one
+//!
+//! A run writes its heartbeat to its workspace whenever its workers made
+//! progress, at most every [`HEARTBEAT_INTERVAL`]. A run which is still
+//! running but has not beaten for [`STALL_TIMEOUT`] is stalled, e.g. its
+//! workers are deadlocked or its process was killed.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use wakuchin::fs::atomic_write;
+use wakuchin::handlers::{ControlFlow, ProgressHandler, RunInfo};
+use wakuchin::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use wakuchin::result::HitCount;
+
+/// Name of the heartbeat file placed in each run directory.
+pub const HEARTBEAT_FILE: &str = "heartbeat.json";
+
+/// Least time between two heartbeats.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time without heartbeats after which a running run is stalled.
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeat {
+  /// When the workers last made progress
+  pub at: String,
+  /// Tries done by then
+  pub current: usize,
+  /// Paused runs make no progress on purpose, so they never stall
+  #[serde(default)]
+  pub paused: bool,
+}
+
+impl Heartbeat {
+  fn now(current: usize, paused: bool) -> Self {
+    Self {
+      at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+      current,
+      paused,
+    }
+  }
+
+  /// Return whether the run has not beaten for [`STALL_TIMEOUT`] at `now`.
+  pub fn is_stalled(&self, now: SystemTime) -> bool {
+    let Ok(at) = humantime::parse_rfc3339(&self.at) else {
+      return false;
+    };
+
+    !self.paused && at + STALL_TIMEOUT < now
+  }
+}
+
+/// Progress handler writing the heartbeats of a run, wrapping another one.
+pub struct HeartbeatProgressHandler {
+  inner: Box<dyn ProgressHandler>,
+  path: PathBuf,
+  current: usize,
+  last_beat: Instant,
+}
+
+impl HeartbeatProgressHandler {
+  /// Write heartbeats to `path`, forwarding everything to `inner`.
+  pub fn new(inner: Box<dyn ProgressHandler>, path: PathBuf) -> Self {
+    Self {
+      inner,
+      path,
+      current: 0,
+      last_beat: Instant::now(),
+    }
+  }
+
+  fn beat(&mut self, paused: bool) -> Result<()> {
+    let heartbeat = Heartbeat::now(self.current, paused);
+
+    atomic_write(&self.path, serde_json::to_vec(&heartbeat)?)?;
+    self.last_beat = Instant::now();
+
+    Ok(())
+  }
+}
+
+impl ProgressHandler for HeartbeatProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.beat(false)?;
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    let current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>();
+
+    // the render keeps calling handlers while the workers are stuck, so
+    // only progress counts as a sign of life
+    if current > self.current {
+      self.current = current;
+
+      if self.last_beat.elapsed() >= HEARTBEAT_INTERVAL {
+        self.beat(false)?;
+      }
+    }
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    self.beat(true)?;
+
+    self.inner.on_pause()
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    self.beat(false)?;
+
+    self.inner.on_resume()
+  }
+
+  wakuchin::forward_to_inner!(
+    after_finish,
+    on_hit,
+    on_notable_hit,
+    on_deadline_overrun,
+    on_accidential_stop
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::{Duration, SystemTime};
+
+  use super::{Heartbeat, STALL_TIMEOUT};
+
+  #[test]
+  fn test_is_stalled() {
+    let heartbeat = Heartbeat {
+      at: "2023-01-01T00:00:00Z".into(),
+      current: 42,
+      paused: false,
+    };
+    let at = humantime::parse_rfc3339("2023-01-01T00:00:00Z").unwrap();
+
+    assert!(!heartbeat.is_stalled(at + Duration::from_secs(30)));
+    assert!(heartbeat.is_stalled(at + STALL_TIMEOUT * 2));
+    assert!(!Heartbeat {
+      paused: true,
+      ..heartbeat
+    }
+    .is_stalled(at + STALL_TIMEOUT * 2));
+    assert!(!Heartbeat::now(0, false).is_stalled(SystemTime::now()));
+  }
+}
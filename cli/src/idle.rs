@@ -0,0 +1,220 @@
+//! Research only with spare CPU cycles, `--only-when-idle`
+
+use std::fs::read_to_string;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::pausing::PauseReasons;
+
+const REASON: &str = "busy";
+
+/// Interval between two CPU samples.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Pause when other processes use more than this fraction of the CPUs.
+const BUSY_THRESHOLD: f64 = 0.5;
+
+/// Resume when other processes use less than this fraction of the CPUs.
+const IDLE_THRESHOLD: f64 = 0.25;
+
+/// Number of consecutive samples needed to switch between paused and
+/// running, so short bursts of activity do not cause thrashing.
+const STREAK: usize = 3;
+
+/// CPU time counters, in clock ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CpuTimes {
+  /// Busy time of all CPUs
+  busy: u64,
+  /// Busy and idle time of all CPUs
+  total: u64,
+  /// Time spent by this process
+  own: u64,
+}
+
+impl CpuTimes {
+  fn sample() -> Result<Self> {
+    let (busy, total) = parse_proc_stat(&read_to_string("/proc/stat")?)
+      .ok_or_else(|| anyhow!("cannot parse /proc/stat"))?;
+    let own = parse_self_stat(&read_to_string("/proc/self/stat")?)
+      .ok_or_else(|| anyhow!("cannot parse /proc/self/stat"))?;
+
+    Ok(Self { busy, total, own })
+  }
+
+  /// Fraction of the CPUs used by other processes since `prev`.
+  fn others_load(&self, prev: &Self) -> f64 {
+    let total = self.total.saturating_sub(prev.total);
+
+    if total == 0 {
+      return 0.0;
+    }
+
+    let busy = self.busy.saturating_sub(prev.busy);
+    let own = self.own.saturating_sub(prev.own);
+
+    busy.saturating_sub(own) as f64 / total as f64
+  }
+}
+
+/// Parse the busy and total ticks from the `cpu` line of `/proc/stat`.
+fn parse_proc_stat(contents: &str) -> Option<(u64, u64)> {
+  let mut fields = contents
+    .lines()
+    .next()?
+    .strip_prefix("cpu ")?
+    .split_whitespace()
+    .map(|field| field.parse::<u64>().ok());
+
+  let mut next = || fields.next().flatten();
+
+  let (user, nice, system, idle) = (next()?, next()?, next()?, next()?);
+  let (iowait, irq, softirq) = (next()?, next()?, next()?);
+  let steal = next().unwrap_or(0);
+
+  let busy = user + nice + system + irq + softirq + steal;
+
+  Some((busy, busy + idle + iowait))
+}
+
+/// Parse `utime + stime` from `/proc/self/stat`.
+fn parse_self_stat(contents: &str) -> Option<u64> {
+  // the command name may contain spaces and parentheses
+  let mut fields = contents.rsplit_once(')')?.1.split_whitespace().skip(11);
+
+  let utime = fields.next()?.parse::<u64>().ok()?;
+  let stime = fields.next()?.parse::<u64>().ok()?;
+
+  Some(utime + stime)
+}
+
+/// Decides whether to pause with hysteresis.
+#[derive(Debug, Default)]
+struct Scheduler {
+  paused: bool,
+  streak: usize,
+}
+
+impl Scheduler {
+  /// Feed the load of other processes, returning the new state if it should
+  /// change.
+  fn update(&mut self, load: f64) -> Option<bool> {
+    let wants_change = if self.paused {
+      load < IDLE_THRESHOLD
+    } else {
+      load > BUSY_THRESHOLD
+    };
+
+    if !wants_change {
+      self.streak = 0;
+
+      return None;
+    }
+
+    self.streak += 1;
+
+    if self.streak < STREAK {
+      return None;
+    }
+
+    self.streak = 0;
+    self.paused = !self.paused;
+
+    Some(self.paused)
+  }
+}
+
+/// Background thread pausing the research while the system is busy.
+pub struct IdleMonitor {
+  stop_tx: Sender<()>,
+  thread: JoinHandle<()>,
+}
+
+impl IdleMonitor {
+  pub fn spawn(reasons: PauseReasons) -> Result<Self> {
+    let mut prev = CpuTimes::sample()
+      .map_err(|e| anyhow!("--only-when-idle is not supported here: {e}"))?;
+
+    let (stop_tx, stop_rx) = channel();
+
+    let thread = thread::spawn(move || {
+      let mut scheduler = Scheduler::default();
+
+      while let Err(RecvTimeoutError::Timeout) =
+        stop_rx.recv_timeout(SAMPLE_INTERVAL)
+      {
+        let Ok(times) = CpuTimes::sample() else {
+          continue;
+        };
+
+        if let Some(paused) = scheduler.update(times.others_load(&prev)) {
+          reasons.set(REASON, paused);
+        }
+
+        prev = times;
+      }
+
+      reasons.set(REASON, false);
+    });
+
+    Ok(Self { stop_tx, thread })
+  }
+
+  pub fn stop(self) {
+    let _ = self.stop_tx.send(());
+    let _ = self.thread.join();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{parse_proc_stat, parse_self_stat, Scheduler, STREAK};
+
+  #[test]
+  fn test_parse_stat() {
+    assert_eq!(
+      parse_proc_stat("cpu  100 5 20 800 10 1 2 3 0 0\ncpu0 1 2 3 4"),
+      Some((131, 941))
+    );
+    assert_eq!(parse_proc_stat("intr 1 2 3"), None);
+
+    assert_eq!(
+      parse_self_stat(
+        "42 (wa (ku) chin) R 1 42 1 0 -1 4194304 86 0 0 0 150 30 0 0 20 0"
+      ),
+      Some(180)
+    );
+  }
+
+  #[test]
+  fn test_scheduler() {
+    let mut scheduler = Scheduler::default();
+
+    for _ in 1..STREAK {
+      assert_eq!(scheduler.update(0.9), None);
+    }
+
+    // a single idle sample resets the streak
+    assert_eq!(scheduler.update(0.0), None);
+
+    for _ in 1..STREAK {
+      assert_eq!(scheduler.update(0.9), None);
+    }
+
+    assert_eq!(scheduler.update(0.9), Some(true));
+
+    // between the thresholds, stay paused
+    for _ in 0..STREAK * 2 {
+      assert_eq!(scheduler.update(0.4), None);
+    }
+
+    for _ in 1..STREAK {
+      assert_eq!(scheduler.update(0.1), None);
+    }
+
+    assert_eq!(scheduler.update(0.1), Some(false));
+  }
+}
@@ -1,22 +1,37 @@
 mod app;
+mod battery;
+mod commands;
 mod config;
+#[cfg(any(unix, windows))]
+mod daemon;
+mod dictionary;
 mod error;
 mod handlers;
-
-use std::io::stdout;
-use std::panic;
-use std::sync::{Arc, Mutex};
+mod heartbeat;
+#[cfg(not(target_arch = "wasm32"))]
+mod idle;
+mod pausing;
+mod pipe;
+mod pipeline;
+mod recording;
+mod registry;
+mod signing;
+#[cfg(unix)]
+mod snapshot;
+mod thermal;
+mod workspace;
+
+use std::io::{stdout, ErrorKind, Write};
+use std::path::Path;
 
 use anyhow::Result;
 use owo_colors::OwoColorize as _;
-use wakuchin::builder::ResearchBuilder;
 use wakuchin::error::WakuchinError;
-use wakuchin::handlers::msgpack::{
-  MsgpackBase64ProgressHandler, MsgpackProgressHandler,
-};
+use wakuchin::locale::NumberFormat;
 
 use crate::app::App;
-use crate::handlers::{ConsoleProgressHandler, HandlerKind};
+use crate::config::Config;
+use crate::handlers::HandlerKind;
 
 #[cfg(all(
   not(target_os = "android"),
@@ -43,53 +58,71 @@ async fn main() {
 fn try_main() -> Result<()> {
   let mut app = App::new();
 
+  if let Some(command) = app.take_command() {
+    return command.run().map_err(Into::into);
+  }
+
   app.setup_config()?;
 
-  let config = app.config;
+  let config_path = app.config_path().map(Path::to_path_buf);
+  let mut config = app.config;
+  // fail before the research instead of after it
+  let numbers = config.number_format()?;
 
-  let default_hook = App::set_panic_hook();
+  // nobody watches the progress of a daemon
+  if config.daemon && config.handler.kind == HandlerKind::Console {
+    config.no_progress = true;
+  }
 
-  let builder = ResearchBuilder::new()
-    .tries(config.tries)
-    .times(config.times)
-    .regex(config.regex)
-    .progress_interval(config.interval);
+  #[cfg(unix)]
+  let _daemon = config
+    .daemon
+    .then(|| {
+      let (pid_file, log_file) = config.daemon_files();
 
-  let builder = {
-    match config.handler {
-      HandlerKind::Console => {
-        builder.progress_handler(Box::new(ConsoleProgressHandler::new(
-          config.no_progress,
-          config.tries,
-          config.times,
-        )))
-      }
-      HandlerKind::Msgpack => {
-        builder.progress_handler(Box::new(MsgpackProgressHandler::new(
-          config.tries,
-          Arc::new(Mutex::new(stdout())),
-        )))
-      }
-      HandlerKind::MsgpackBase64 => {
-        builder.progress_handler(Box::new(MsgpackBase64ProgressHandler::new(
-          config.tries,
-          Arc::new(Mutex::new(stdout())),
-        )))
-      }
-    }
-  };
+      daemon::daemonize(&pid_file, &log_file)
+    })
+    .transpose()?;
 
-  #[cfg(not(any(feature = "sequential", target_arch = "wasm32")))]
-  let result = builder.workers(config.workers).run_par();
+  #[cfg(windows)]
+  if config.daemon {
+    let (pid_file, log_file) = config.daemon_files();
 
-  #[cfg(any(feature = "sequential", target_arch = "wasm32"))]
-  let result = builder.run_seq();
+    return daemon::run_service(&pid_file, &log_file, move || {
+      research(&config, config_path.as_deref(), &numbers)
+    });
+  }
 
-  let result = result?;
+  #[cfg(not(any(unix, windows)))]
+  if config.daemon {
+    anyhow::bail!("--daemon is only available on Unix and Windows");
+  }
 
-  panic::set_hook(default_hook);
+  research(&config, config_path.as_deref(), &numbers)
+}
+
+/// Run the research or pipeline of `config` and print its result.
+fn research(
+  config: &Config,
+  config_path: Option<&Path>,
+  numbers: &NumberFormat,
+) -> Result<()> {
+  let result = if config.stages.is_empty() {
+    Some(pipeline::run_research(config, config_path)?.result)
+  } else {
+    pipeline::run_pipeline(config, config_path)?
+  };
 
-  println!("{}", result.out(config.out.into())?);
+  if let Some(result) = result {
+    let out = result.out_with(config.out.clone().into(), numbers)?;
+
+    // the result is stored in the run workspace anyway
+    if let Err(err) = writeln!(stdout(), "{out}") {
+      if err.kind() != ErrorKind::BrokenPipe {
+        return Err(err.into());
+      }
+    }
+  }
 
   Ok(())
 }
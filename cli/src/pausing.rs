@@ -0,0 +1,63 @@
+//! Pausing the research for several reasons at once
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use wakuchin::pause::PauseHandle;
+
+/// Pauses the research while at least one reason is active, so monitors do
+/// not resume a research paused by another one.
+#[derive(Clone)]
+pub struct PauseReasons {
+  handle: PauseHandle,
+  active: Arc<Mutex<HashSet<&'static str>>>,
+}
+
+impl PauseReasons {
+  pub fn new(handle: PauseHandle) -> Self {
+    Self {
+      handle,
+      active: Arc::default(),
+    }
+  }
+
+  /// Activate or deactivate `reason`.
+  pub fn set(&self, reason: &'static str, paused: bool) {
+    let mut active = self.active.lock().unwrap();
+
+    if paused {
+      active.insert(reason);
+    } else {
+      active.remove(reason);
+    }
+
+    if active.is_empty() {
+      self.handle.resume();
+    } else {
+      self.handle.pause();
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use wakuchin::pause::PauseHandle;
+
+  use super::PauseReasons;
+
+  #[test]
+  fn test_pause_reasons() {
+    let handle = PauseHandle::new();
+    let reasons = PauseReasons::new(handle.clone());
+
+    reasons.set("idle", true);
+    reasons.set("battery", true);
+    reasons.set("idle", false);
+
+    assert!(handle.is_paused());
+
+    reasons.set("battery", false);
+
+    assert!(!handle.is_paused());
+  }
+}
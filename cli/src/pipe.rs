@@ -0,0 +1,237 @@
+//! Graceful degradation when the consumer of the progress goes away
+//!
+//! Writing to a closed pipe, e.g. `wakuchin --handler msgpack | head -c 100`,
+//! fails with `EPIPE`. Instead of failing the research, the progress handler
+//! gives up its output, or switches to a fallback file, and the event is
+//! recorded in the metadata of the run.
+
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use wakuchin::handlers::deadline::DeadlineOverrun;
+use wakuchin::handlers::{ControlFlow, ProgressHandler, RunInfo};
+use wakuchin::progress::Progress;
+use wakuchin::result::{Hit, HitCount, NotableHit};
+
+/// Builder of the handler to switch to once the pipe is broken.
+type Fallback = Box<dyn FnOnce() -> Result<Box<dyn ProgressHandler>> + Send>;
+
+/// When the output of a run broke, stored in `run.json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokenPipeEvent {
+  pub at: String,
+  /// File the progress was written to since then, if any
+  #[serde(default)]
+  pub fallback: Option<PathBuf>,
+}
+
+/// Return whether `err` was caused by writing to a closed pipe.
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+  err.chain().any(|cause| {
+    cause
+      .downcast_ref::<io::Error>()
+      .is_some_and(|e| e.kind() == ErrorKind::BrokenPipe)
+  })
+}
+
+/// Progress handler wrapping another one, which is dropped once its output
+/// is a closed pipe.
+pub struct PipeGuardProgressHandler {
+  inner: Option<Box<dyn ProgressHandler>>,
+  fallback: Option<(PathBuf, Fallback)>,
+  event: Arc<Mutex<Option<BrokenPipeEvent>>>,
+  /// Info of the run, to start the fallback with
+  info: Option<RunInfo>,
+}
+
+impl PipeGuardProgressHandler {
+  pub fn new(inner: Box<dyn ProgressHandler>) -> Self {
+    Self {
+      inner: Some(inner),
+      fallback: None,
+      event: Arc::default(),
+      info: None,
+    }
+  }
+
+  /// Switch to the handler built by `fallback`, writing to `path`, once the
+  /// pipe is broken instead of giving up the progress.
+  pub fn with_fallback(
+    mut self,
+    path: PathBuf,
+    fallback: impl FnOnce() -> Result<Box<dyn ProgressHandler>> + Send + 'static,
+  ) -> Self {
+    self.fallback = Some((path, Box::new(fallback)));
+    self
+  }
+
+  /// Return a handle to the broken pipe event, set once it happened.
+  pub fn event(&self) -> Arc<Mutex<Option<BrokenPipeEvent>>> {
+    self.event.clone()
+  }
+
+  fn break_pipe(&mut self) -> Result<()> {
+    self.inner = None;
+
+    let fallback = self.fallback.take();
+
+    *self.event.lock().unwrap() = Some(BrokenPipeEvent {
+      at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+      fallback: fallback.as_ref().map(|(path, _)| path.clone()),
+    });
+
+    if let Some((_, fallback)) = fallback {
+      let mut inner = fallback()?;
+
+      if let Some(info) = &self.info {
+        inner.before_start(info)?;
+      }
+
+      self.inner = Some(inner);
+    }
+
+    Ok(())
+  }
+
+  /// Call `f` with the inner handler, retrying with the fallback if the pipe
+  /// is broken, and return `default` without any.
+  fn call<T>(
+    &mut self,
+    default: T,
+    mut f: impl FnMut(&mut dyn ProgressHandler) -> Result<T>,
+  ) -> Result<T> {
+    let Some(inner) = &mut self.inner else {
+      return Ok(default);
+    };
+
+    match f(inner.as_mut()) {
+      Err(err) if is_broken_pipe(&err) => {
+        self.break_pipe()?;
+
+        match &mut self.inner {
+          Some(inner) => f(inner.as_mut()),
+          None => Ok(default),
+        }
+      }
+      result => result,
+    }
+  }
+}
+
+impl ProgressHandler for PipeGuardProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.info = Some(info.clone());
+
+    self.call((), |inner| inner.before_start(info))
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    self.call(ControlFlow::Continue, |inner| {
+      inner.handle(progresses, hit_counts, elapsed_time, current_diff, all_done)
+    })
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.call((), |inner| inner.after_finish())
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    self.call((), |inner| inner.on_pause())
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    self.call((), |inner| inner.on_resume())
+  }
+
+  fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+    self.call((), |inner| inner.on_hit(hit))
+  }
+
+  fn on_notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+    self.call((), |inner| inner.on_notable_hit(hit))
+  }
+
+  fn on_deadline_overrun(&mut self, overrun: &DeadlineOverrun) -> Result<()> {
+    self.call((), |inner| inner.on_deadline_overrun(overrun))
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.call((), |inner| inner.on_accidential_stop())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::{self, ErrorKind, Write};
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use wakuchin::handlers::msgpack::MsgpackProgressHandler;
+  use wakuchin::handlers::{ControlFlow, ProgressHandler};
+
+  use super::PipeGuardProgressHandler;
+
+  /// Writer of a pipe closed by its reader.
+  struct ClosedPipe;
+
+  impl Write for ClosedPipe {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+      Err(ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_pipe_guard() -> Result<()> {
+    let closed = Box::new(MsgpackProgressHandler::new(
+      10,
+      Arc::new(Mutex::new(ClosedPipe)),
+    ));
+    let mut handler = PipeGuardProgressHandler::new(closed);
+    let event = handler.event();
+
+    assert_eq!(
+      handler.handle(&[], &[], Duration::from_secs(1), 0, false)?,
+      ControlFlow::Continue
+    );
+    assert_eq!(event.lock().unwrap().as_ref().unwrap().fallback, None);
+    assert!(handler.inner.is_none());
+
+    let fallback = Arc::new(Mutex::new(Vec::new()));
+    let writer = fallback.clone();
+    let closed = Box::new(MsgpackProgressHandler::new(
+      10,
+      Arc::new(Mutex::new(ClosedPipe)),
+    ));
+    let mut handler = PipeGuardProgressHandler::new(closed)
+      .with_fallback("progress.bin".into(), move || {
+        Ok(Box::new(MsgpackProgressHandler::new(10, writer)))
+      });
+
+    handler.handle(&[], &[], Duration::from_secs(1), 0, false)?;
+    handler.handle(&[], &[], Duration::from_secs(1), 0, true)?;
+
+    assert!(!fallback.lock().unwrap().is_empty());
+    assert_eq!(
+      handler.event().lock().unwrap().as_ref().unwrap().fallback,
+      Some("progress.bin".into())
+    );
+
+    Ok(())
+  }
+}
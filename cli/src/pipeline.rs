@@ -0,0 +1,997 @@
+use std::collections::HashSet;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use regex::Regex;
+use serde::Deserialize;
+use wakuchin::builder::ResearchBuilder;
+use wakuchin::error::WakuchinError;
+use wakuchin::fs::atomic_write;
+use wakuchin::handlers::deadline::DeadlineProgressHandler;
+#[cfg(feature = "notify")]
+use wakuchin::handlers::notify::NotifyProgressHandler;
+use wakuchin::handlers::snapshot::ProgressSnapshot;
+#[cfg(unix)]
+use wakuchin::handlers::snapshot::SnapshotProgressHandler;
+use wakuchin::handlers::socket::SocketProgressHandler;
+#[cfg(feature = "sqlite")]
+use wakuchin::handlers::sqlite::SqliteProgressHandler;
+#[cfg(feature = "sse")]
+use wakuchin::handlers::sse::SseProgressHandler;
+#[cfg(feature = "webhook")]
+use wakuchin::handlers::webhook::WebhookProgressHandler;
+use wakuchin::handlers::ProgressHandler;
+use wakuchin::hit::HitCounter;
+use wakuchin::pattern::{Matchers, RegexEngine};
+use wakuchin::pause::PauseHandle;
+use wakuchin::protocol::ProtocolVersion;
+use wakuchin::result::WakuchinResult;
+use wakuchin::rng::RngBackend;
+use wakuchin::symbol::SymbolSet;
+
+use crate::app::App;
+#[cfg(feature = "battery")]
+use crate::battery::BatteryMonitor;
+use crate::commands::export::ExportedRun;
+use crate::config::{human, Config, InternalRegexEngine, InternalRngBackend};
+use crate::error::{AppError, Result};
+use crate::handlers::HandlerKind;
+use crate::heartbeat::{HeartbeatProgressHandler, HEARTBEAT_FILE};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::idle::IdleMonitor;
+use crate::pausing::PauseReasons;
+use crate::pipe::PipeGuardProgressHandler;
+#[cfg(unix)]
+use crate::snapshot::SnapshotListener;
+#[cfg(feature = "thermal")]
+use crate::thermal::ThermalMonitor;
+#[cfg(unix)]
+use crate::workspace::Artifact;
+use crate::workspace::{
+  DeadlineSummary, RetentionPolicy, RunMetadata, RunStatus, RunWorkspace,
+};
+
+/// Stage of a pipeline, the `[[stages]]` array of the config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Stage {
+  /// Run a research, parameters default to the top-level ones
+  Research {
+    name: String,
+    #[serde(default, deserialize_with = "human::count")]
+    tries: Option<usize>,
+    #[serde(default, deserialize_with = "human::count")]
+    times: Option<usize>,
+    #[serde(default, with = "serde_regex")]
+    regex: Option<Regex>,
+    /// Run for about this long, estimating the tries from the throughput
+    /// of the stage `from`
+    #[serde(default, deserialize_with = "human::duration")]
+    duration: Option<Duration>,
+    #[serde(default)]
+    from: Option<String>,
+  },
+  /// Export the result of the stage `from`, like `wakuchin export`
+  Export {
+    name: String,
+    from: String,
+    output: PathBuf,
+    #[serde(default)]
+    redact: bool,
+  },
+}
+
+impl Stage {
+  #[inline]
+  pub fn name(&self) -> &str {
+    match self {
+      Self::Research { name, .. } | Self::Export { name, .. } => name,
+    }
+  }
+
+  #[inline]
+  fn from(&self) -> Option<&str> {
+    match self {
+      Self::Research { from, .. } => from.as_deref(),
+      Self::Export { from, .. } => Some(from),
+    }
+  }
+}
+
+/// Check that stage names are unique and stages only reference prior
+/// research stages, before anything is run.
+pub fn validate_stages(stages: &[Stage]) -> Result<()> {
+  let mut researches = HashSet::new();
+  let mut names = HashSet::new();
+
+  for stage in stages {
+    if !names.insert(stage.name()) {
+      return Err(anyhow!("stage '{}' is defined twice", stage.name()).into());
+    }
+
+    if let Some(from) = stage.from() {
+      if !researches.contains(from) {
+        return Err(
+          anyhow!(
+            "stage '{}' references '{from}', which is not a prior research stage",
+            stage.name()
+          )
+          .into(),
+        );
+      }
+    }
+
+    if let Stage::Research { duration, from, .. } = stage {
+      if duration.is_some() != from.is_some() {
+        return Err(
+          anyhow!(
+            "stage '{}' needs both 'duration' and 'from' to estimate its tries",
+            stage.name()
+          )
+          .into(),
+        );
+      }
+
+      researches.insert(stage.name());
+    }
+  }
+
+  Ok(())
+}
+
+/// Outcome of a finished research.
+pub struct Research {
+  pub run: RunMetadata,
+  pub result: WakuchinResult,
+  pub elapsed: Duration,
+}
+
+impl Research {
+  /// Tries per second of the research.
+  #[inline]
+  fn rate(&self) -> f64 {
+    self.result.tries as f64 / self.elapsed.as_secs_f64()
+  }
+}
+
+/// Convert the `--rng` backend, unavailable without the "rng-chacha" feature.
+fn rng_backend(backend: InternalRngBackend) -> anyhow::Result<RngBackend> {
+  match backend {
+    InternalRngBackend::Fastrand => Ok(RngBackend::Fastrand),
+    #[cfg(feature = "rng-chacha")]
+    InternalRngBackend::Chacha => Ok(RngBackend::ChaCha),
+    #[cfg(not(feature = "rng-chacha"))]
+    InternalRngBackend::Chacha => Err(anyhow!(
+      "--rng chacha requires wakuchin to be built with the \"rng-chacha\" feature"
+    )),
+  }
+}
+
+/// Convert the `--regex-engine`, unavailable without the "dfa" feature.
+fn regex_engine(engine: InternalRegexEngine) -> anyhow::Result<RegexEngine> {
+  match engine {
+    InternalRegexEngine::Regex => Ok(RegexEngine::Regex),
+    #[cfg(feature = "dfa")]
+    InternalRegexEngine::Dfa => Ok(RegexEngine::Dfa),
+    #[cfg(not(feature = "dfa"))]
+    InternalRegexEngine::Dfa => Err(anyhow!(
+      "--regex-engine dfa requires wakuchin to be built with the \"dfa\" feature"
+    )),
+  }
+}
+
+/// Match `regex` with an Aho-Corasick automaton when it is an alternation of
+/// literals as long as a try, e.g. "(WKNCWKNC|WCKNWCKN)".
+///
+/// A try hits at most one of the literals then, so the hits are the same as
+/// those of the regex, only counted by literal.
+fn matchers(regex: &Regex, times: usize, symbols: &SymbolSet) -> Matchers {
+  let source = regex.as_str();
+  let source = source.strip_prefix('^').unwrap_or(source);
+  let source = source.strip_suffix('$').unwrap_or(source);
+  let alternation = source
+    .strip_prefix("(?:")
+    .or_else(|| source.strip_prefix('('))
+    .and_then(|source| source.strip_suffix(')'))
+    .unwrap_or(source);
+  let literals = alternation.split('|').collect::<Vec<_>>();
+
+  let is_literal = |literal: &&str| {
+    literal.chars().count() == symbols.len() * times
+      && regex::escape(literal) == *literal
+  };
+
+  if literals.iter().all(is_literal)
+    && literals.iter().collect::<HashSet<_>>().len() == literals.len()
+  {
+    return Matchers::Literals(literals.into_iter().map(Into::into).collect());
+  }
+
+  Matchers::Regex(vec![regex.clone()])
+}
+
+/// Print a failure the research goes on despite.
+fn warn(message: &str) {
+  eprintln!("warning: {message}");
+}
+
+/// Run a single research in its own run workspace.
+pub fn run_research(
+  config: &Config,
+  config_path: Option<&Path>,
+) -> anyhow::Result<Research> {
+  #[cfg(not(feature = "battery"))]
+  if config.battery_threshold != 0 {
+    return Err(anyhow!(
+      "--battery-threshold requires wakuchin to be built with the \"battery\" feature"
+    ));
+  }
+
+  #[cfg(not(feature = "notify"))]
+  if config.notify || !config.notify_hits.is_empty() {
+    return Err(anyhow!(
+      "--notify requires wakuchin to be built with the \"notify\" feature"
+    ));
+  }
+
+  #[cfg(not(feature = "sqlite"))]
+  if !config.sqlite.as_os_str().is_empty() {
+    return Err(anyhow!(
+      "--sqlite requires wakuchin to be built with the \"sqlite\" feature"
+    ));
+  }
+
+  #[cfg(not(feature = "sse"))]
+  if !config.sse.is_empty() {
+    return Err(anyhow!(
+      "--sse requires wakuchin to be built with the \"sse\" feature"
+    ));
+  }
+
+  #[cfg(not(feature = "webhook"))]
+  if !config.webhook.is_empty() {
+    return Err(anyhow!(
+      "--webhook requires wakuchin to be built with the \"webhook\" feature"
+    ));
+  }
+
+  let checkpoint = load_checkpoint(config)?;
+  let baseline = checkpoint
+    .as_ref()
+    .map_or(0, |checkpoint| checkpoint.current);
+  let tries = config.tries - baseline;
+
+  let handler = config.handler.build(
+    config.no_progress,
+    tries,
+    config.times,
+    &config.symbols,
+    ProtocolVersion::try_from(config.protocol_version)?,
+    baseline,
+  )?;
+
+  let mut handler = PipeGuardProgressHandler::new(handler);
+
+  if config.handler.fallback_output.is_some() {
+    let handler_config = config.handler.clone();
+    let protocol_version = ProtocolVersion::try_from(config.protocol_version)?;
+
+    handler = handler.with_fallback(
+      config.handler.fallback_output.clone().unwrap_or_default(),
+      move || handler_config.build_fallback(tries, protocol_version),
+    );
+  }
+
+  let broken_pipe = handler.event();
+
+  let (handler, deadline): (Box<dyn ProgressHandler>, _) =
+    if config.expect_duration.is_zero() {
+      (Box::new(handler), None)
+    } else {
+      let handler = DeadlineProgressHandler::new(
+        tries,
+        config.expect_duration,
+        Box::new(handler),
+      );
+      let warning = handler.warning();
+
+      (Box::new(handler), Some(warning))
+    };
+
+  #[cfg(feature = "notify")]
+  let handler: Box<dyn ProgressHandler> =
+    if config.notify || !config.notify_hits.is_empty() {
+      let mut handler =
+        NotifyProgressHandler::new(handler).with_warning_callback(warn);
+
+      if !config.notify_hits.is_empty() {
+        handler = handler.with_hit_pattern(Regex::new(&config.notify_hits)?);
+      }
+
+      Box::new(handler)
+    } else {
+      handler
+    };
+
+  #[cfg(feature = "webhook")]
+  let handler: Box<dyn ProgressHandler> = if config.webhook.is_empty() {
+    handler
+  } else {
+    Box::new(
+      WebhookProgressHandler::new(config.webhook.clone(), handler)
+        .with_interval(config.webhook_interval)
+        .with_warning_callback(warn),
+    )
+  };
+
+  #[cfg(feature = "sqlite")]
+  let handler: Box<dyn ProgressHandler> =
+    if config.sqlite.as_os_str().is_empty() {
+      handler
+    } else {
+      Box::new(
+        SqliteProgressHandler::open(&config.sqlite, handler).map_err(|e| {
+          anyhow!("cannot open {}: {e}", config.sqlite.display())
+        })?,
+      )
+    };
+
+  let handler: Box<dyn ProgressHandler> =
+    if config.socket.as_os_str().is_empty() {
+      handler
+    } else {
+      Box::new(
+        SocketProgressHandler::new(config.socket.clone(), handler)
+          .with_format(config.socket_format.into())
+          .with_protocol_version(ProtocolVersion::try_from(
+            config.protocol_version,
+          )?),
+      )
+    };
+
+  #[cfg(feature = "sse")]
+  let handler: Box<dyn ProgressHandler> = if config.sse.is_empty() {
+    handler
+  } else {
+    Box::new(
+      SseProgressHandler::bind(config.sse.as_str(), handler)
+        .map_err(|e| anyhow!("cannot serve on {}: {e}", config.sse))?,
+    )
+  };
+
+  let root = config.workspace_root();
+  // the research goes on anyway, it is only not recorded
+  let mut workspace = RunWorkspace::create(&root, config, config_path)
+    .unwrap_or_else(|e| {
+      eprintln!("warning: the run is not recorded: {e}");
+
+      RunWorkspace::unrecorded(&root, config, config_path)
+    });
+
+  if workspace.is_recorded()
+    && config.handler.kind == HandlerKind::Console
+    && !config.no_progress
+  {
+    eprintln!("Run {}", workspace.metadata().display_name());
+  }
+
+  let handler: Box<dyn ProgressHandler> = if workspace.is_recorded() {
+    Box::new(HeartbeatProgressHandler::new(
+      handler,
+      workspace.dir().join(HEARTBEAT_FILE),
+    ))
+  } else {
+    handler
+  };
+
+  #[cfg(feature = "thermal")]
+  let thermal_monitor = ThermalMonitor::spawn();
+
+  // progress is checkpointed when suspended with SIGTSTP
+  #[cfg(unix)]
+  let (handler, listener) = {
+    let mut handler = SnapshotProgressHandler::new(tries, handler)
+      .with_baseline(baseline)
+      .with_baseline_hits(
+        checkpoint
+          .as_ref()
+          .map(|checkpoint| checkpoint.hits.clone())
+          .unwrap_or_default(),
+      );
+
+    if workspace.is_recorded() {
+      handler = handler.with_checkpoint(
+        workspace
+          .artifact_dir(Artifact::Checkpoints)
+          .join("paused.json"),
+      );
+    }
+    let path = Some(config.snapshot_file.clone())
+      .filter(|path| !path.as_os_str().is_empty());
+
+    #[cfg(feature = "thermal")]
+    let thermal = Some(thermal_monitor.reader());
+    #[cfg(not(feature = "thermal"))]
+    let thermal = None;
+
+    let listener = SnapshotListener::spawn(handler.reader(), thermal, path)?;
+
+    (Box::new(handler), listener)
+  };
+
+  let pause_handle = PauseHandle::new();
+  let reasons = PauseReasons::new(pause_handle.clone());
+
+  #[cfg(not(target_arch = "wasm32"))]
+  let idle_monitor = config
+    .only_when_idle
+    .then(|| IdleMonitor::spawn(reasons.clone()))
+    .transpose()?;
+
+  #[cfg(feature = "battery")]
+  let battery_monitor = (config.battery_threshold != 0)
+    .then(|| {
+      BatteryMonitor::spawn(
+        reasons.clone(),
+        config.battery_threshold,
+        config.battery_policy,
+      )
+    })
+    .transpose()?;
+
+  let default_hook = App::set_panic_hook();
+
+  let builder = ResearchBuilder::new()
+    .tries(tries)
+    .times(config.times)
+    .matchers(matchers(&config.regex, config.times, &config.symbols))
+    .progress_interval(config.interval)
+    .progress_handler(handler)
+    .seed(resumed_seed(config.seed, baseline))
+    .notable_rarity(config.notable_rarity as u128)
+    .rng(rng_backend(config.rng)?)
+    .regex_engine(regex_engine(config.regex_engine)?)
+    .symbols(config.symbols.clone())
+    .pause_handle(pause_handle)
+    .on_warning(warn);
+
+  // stopped with the service, keeping the tries done so far
+  #[cfg(windows)]
+  let builder = builder.cancellation_token(crate::daemon::stop_token());
+
+  let started = Instant::now();
+
+  #[cfg(not(any(feature = "sequential", target_arch = "wasm32")))]
+  let result = builder.workers(config.workers).run_par();
+
+  #[cfg(any(feature = "sequential", target_arch = "wasm32"))]
+  let result = builder.run_seq();
+
+  let elapsed = started.elapsed();
+
+  #[cfg(unix)]
+  listener.stop();
+
+  #[cfg(not(target_arch = "wasm32"))]
+  if let Some(idle_monitor) = idle_monitor {
+    idle_monitor.stop();
+  }
+
+  #[cfg(feature = "battery")]
+  if let Some(battery_monitor) = battery_monitor {
+    battery_monitor.stop();
+  }
+
+  #[cfg(feature = "thermal")]
+  workspace.set_throttling(thermal_monitor.stop());
+
+  workspace.set_broken_pipe(broken_pipe.lock().unwrap().take());
+  workspace.set_deadline(deadline.map(|warning| {
+    DeadlineSummary::new(
+      config.expect_duration,
+      elapsed,
+      warning.lock().unwrap().take(),
+    )
+  }));
+
+  let result = match result {
+    Ok(result) => match checkpoint {
+      Some(checkpoint) => resumed_result(result, checkpoint),
+      None => result,
+    },
+    Err(err) => {
+      let status = match err {
+        WakuchinError::Cancelled => RunStatus::Cancelled,
+        _ => RunStatus::Failed,
+      };
+
+      finish_run(workspace, status, config.retention);
+
+      return Err(err.into());
+    }
+  };
+
+  panic::set_hook(default_hook);
+
+  // the result is reported even if it cannot be stored
+  if let Err(e) = workspace.store_result(&result) {
+    eprintln!("warning: failed to store the result of the run: {e}");
+  }
+
+  let run = finish_run(workspace, RunStatus::Finished, config.retention);
+
+  Ok(Research {
+    run,
+    result,
+    elapsed,
+  })
+}
+
+/// Record the end of the run of `workspace` as `status`, returning its
+/// metadata.
+///
+/// Failing to record it is only warned about, so the outcome of the
+/// research is reported anyway.
+fn finish_run(
+  workspace: RunWorkspace,
+  status: RunStatus,
+  retention: RetentionPolicy,
+) -> RunMetadata {
+  let mut run = workspace.metadata().clone();
+
+  match workspace.finish(status, retention) {
+    Ok(run) => run,
+    Err(e) => {
+      eprintln!("warning: failed to record the run: {e}");
+
+      run.status = status;
+      run
+    }
+  }
+}
+
+/// Read the checkpoint given by `--resume-from`, if any.
+fn load_checkpoint(
+  config: &Config,
+) -> anyhow::Result<Option<ProgressSnapshot>> {
+  let path = &config.resume_from;
+
+  if path.as_os_str().is_empty() {
+    return Ok(None);
+  }
+
+  let contents =
+    fs::read_to_string(path).map_err(|source| AppError::WorkspaceIoError {
+      path: path.as_path().into(),
+      source,
+    })?;
+  let checkpoint: ProgressSnapshot = serde_json::from_str(&contents)
+    .map_err(|e| anyhow!("'{}': {e}", path.display()))?;
+
+  if checkpoint.tries != config.tries || checkpoint.current > checkpoint.tries {
+    return Err(anyhow!(
+      "'{}': checkpoint of a research of {} tries, not {}",
+      path.display(),
+      checkpoint.tries,
+      config.tries
+    ));
+  }
+
+  Ok(Some(checkpoint))
+}
+
+/// Return the seed of a research resumed after `baseline` tries of a research
+/// seeded with `seed`.
+///
+/// Chunks are seeded from the first one again, so the seed is offset by the
+/// baseline not to regenerate the tries of the checkpoint, whose hits are
+/// already counted.
+fn resumed_seed(seed: u64, baseline: usize) -> u64 {
+  seed.wrapping_add(baseline as u64)
+}
+
+/// Add the tries and hits of `checkpoint` to the `result` of the research
+/// resuming it, whose hits are on the tries after those of the checkpoint.
+fn resumed_result(
+  result: WakuchinResult,
+  checkpoint: ProgressSnapshot,
+) -> WakuchinResult {
+  let hits = result
+    .hits
+    .into_iter()
+    .chain(checkpoint.hit_counts)
+    .collect::<HitCounter>();
+  let hits = hits.snapshot();
+  let mut patterns = result.patterns;
+
+  for pattern_hits in &mut patterns {
+    pattern_hits.hits_total = 0;
+  }
+
+  for hit_count in &hits {
+    if let Some(pattern_hits) = patterns.get_mut(hit_count.pattern) {
+      pattern_hits.hits_total += hit_count.hits;
+    }
+  }
+
+  let mut hits_detail = checkpoint.hits;
+
+  hits_detail.extend(result.hits_detail.into_iter().map(|mut hit| {
+    hit.hit_on += checkpoint.current;
+    hit
+  }));
+
+  WakuchinResult {
+    tries: checkpoint.current + result.tries,
+    hits_total: hits.iter().map(|hit_count| hit_count.hits).sum(),
+    hits,
+    hits_detail,
+    patterns,
+    rarity: result.rarity,
+    stop_reason: result.stop_reason,
+    failures: result.failures,
+  }
+}
+
+/// Run the stages of `config` in order, returning the result of the last
+/// research stage.
+///
+/// Each research stage is a run of its own, sharing the workspace root.
+/// Stage `n` is seeded with the seed of the config plus `n`, so the whole
+/// pipeline is reproducible from a single seed.
+pub fn run_pipeline(
+  config: &Config,
+  config_path: Option<&Path>,
+) -> anyhow::Result<Option<WakuchinResult>> {
+  validate_stages(&config.stages)?;
+
+  if !config.resume_from.as_os_str().is_empty() {
+    return Err(anyhow!("pipelines cannot be resumed with --resume-from"));
+  }
+
+  let mut researches: Vec<(&str, Research)> = Vec::new();
+
+  let find = |researches: &[(&str, Research)], name: &str| {
+    researches
+      .iter()
+      .position(|(research, _)| *research == name)
+      .expect("stages are validated")
+  };
+
+  for (index, stage) in config.stages.iter().enumerate() {
+    match stage {
+      Stage::Research {
+        name,
+        tries,
+        times,
+        regex,
+        duration,
+        from,
+      } => {
+        let mut stage_config = config.clone();
+
+        stage_config.seed = config.seed.wrapping_add(index as u64);
+        stage_config.label = if config.label.is_empty() {
+          name.clone()
+        } else {
+          format!("{}/{name}", config.label)
+        };
+
+        if let Some(tries) = tries {
+          stage_config.tries = *tries;
+        }
+
+        if let Some(times) = times {
+          stage_config.times = *times;
+        }
+
+        if let Some(regex) = regex {
+          stage_config.regex = regex.clone();
+        }
+
+        if let (Some(duration), Some(from)) = (duration, from) {
+          let rate = researches[find(&researches, from)].1.rate();
+
+          if !rate.is_normal() {
+            return Err(anyhow!(
+              "stage '{from}' is too short to estimate the tries of '{name}'"
+            ));
+          }
+
+          stage_config.tries = (rate * duration.as_secs_f64()).ceil() as usize;
+          // warn if the estimate turns out too optimistic
+          stage_config.expect_duration = *duration;
+        }
+
+        eprintln!("Stage '{name}': researching {} tries", stage_config.tries);
+
+        let research = run_research(&stage_config, config_path)?;
+
+        eprintln!(
+          "Stage '{name}': finished in {}",
+          humantime::format_duration(Duration::from_secs(
+            research.elapsed.as_secs()
+          ))
+        );
+
+        researches.push((name, research));
+      }
+      Stage::Export {
+        name,
+        from,
+        output,
+        redact,
+      } => {
+        let research = &researches[find(&researches, from)].1;
+        let mut exported = ExportedRun {
+          run: research.run.clone(),
+          result: research.result.clone(),
+        };
+
+        if *redact {
+          exported.redact();
+        }
+
+        let contents = serde_json::to_string_pretty(&exported)
+          .map_err(|e| AppError::Other(e.into()))?;
+
+        atomic_write(output, contents).map_err(|source| {
+          AppError::WorkspaceIoError {
+            path: output.as_path().into(),
+            source,
+          }
+        })?;
+
+        eprintln!(
+          "Stage '{name}': exported '{from}' to {}",
+          output.to_string_lossy()
+        );
+      }
+    }
+  }
+
+  Ok(researches.pop().map(|(_, research)| research.result))
+}
+
+#[cfg(test)]
+mod test {
+  use std::env::temp_dir;
+  use std::fs;
+  use std::time::Duration;
+
+  use regex::Regex;
+  use serde::Deserialize;
+  use wakuchin::builder::ResearchBuilder;
+  use wakuchin::handlers::snapshot::ProgressSnapshot;
+  use wakuchin::pattern::Matchers;
+  use wakuchin::result::{Hit, HitCount, WakuchinResult};
+  use wakuchin::symbol::SymbolSet;
+
+  use crate::config::Config;
+
+  use super::{
+    matchers, resumed_result, resumed_seed, run_research, validate_stages,
+    Stage,
+  };
+
+  #[derive(Deserialize)]
+  struct Pipeline {
+    stages: Vec<Stage>,
+  }
+
+  fn stages(toml: &str) -> Vec<Stage> {
+    toml::from_str::<Pipeline>(toml).unwrap().stages
+  }
+
+  #[test]
+  fn test_validate_stages() {
+    let valid = stages(
+      r#"
+      [[stages]]
+      kind = "research"
+      name = "calibrate"
+      tries = "1M"
+
+      [[stages]]
+      kind = "research"
+      name = "full"
+      duration = "10m"
+      from = "calibrate"
+
+      [[stages]]
+      kind = "export"
+      name = "report"
+      from = "full"
+      output = "report.json"
+      "#,
+    );
+
+    assert!(validate_stages(&valid).is_ok());
+    assert!(matches!(
+      valid[0],
+      Stage::Research {
+        tries: Some(1_000_000),
+        ..
+      }
+    ));
+
+    let forward = stages(
+      r#"
+      [[stages]]
+      kind = "export"
+      name = "report"
+      from = "full"
+      output = "report.json"
+
+      [[stages]]
+      kind = "research"
+      name = "full"
+      "#,
+    );
+
+    assert!(validate_stages(&forward).is_err());
+
+    let duplicated = stages(
+      r#"
+      [[stages]]
+      kind = "research"
+      name = "full"
+
+      [[stages]]
+      kind = "research"
+      name = "full"
+      "#,
+    );
+
+    assert!(validate_stages(&duplicated).is_err());
+
+    let no_duration = stages(
+      r#"
+      [[stages]]
+      kind = "research"
+      name = "calibrate"
+
+      [[stages]]
+      kind = "research"
+      name = "full"
+      from = "calibrate"
+      "#,
+    );
+
+    assert!(validate_stages(&no_duration).is_err());
+  }
+
+  #[test]
+  fn test_resumed_result() {
+    let checkpoint = ProgressSnapshot {
+      tries: 100,
+      current: 40,
+      baseline: 0,
+      rate: 0.0,
+      elapsed: Duration::from_secs(1),
+      hits_total: 3,
+      hit_counts: vec![HitCount::new("WKCN", 2), HitCount::new("NCWK", 1)],
+      progresses: Vec::new(),
+      all_done: false,
+      paused: true,
+      hits: vec![
+        Hit::new(3, "WKCN"),
+        Hit::new(11, "NCWK"),
+        Hit::new(20, "WKCN"),
+      ],
+    };
+    let result = WakuchinResult {
+      tries: 60,
+      hits_total: 1,
+      hits: vec![HitCount::new("WKCN", 1)],
+      hits_detail: vec![Hit::new(7, "WKCN")],
+      patterns: Vec::new(),
+      rarity: None,
+      stop_reason: None,
+      failures: Vec::new(),
+    };
+
+    let resumed = resumed_result(result, checkpoint);
+
+    assert_eq!(resumed.tries, 100);
+    assert_eq!(resumed.hits_total, 4);
+    assert_eq!(
+      resumed.hits,
+      [HitCount::new("NCWK", 1), HitCount::new("WKCN", 3)]
+    );
+    // the hits of the checkpoint are kept, so verifying the result passes
+    assert_eq!(
+      resumed.hits_detail,
+      [
+        Hit::new(3, "WKCN"),
+        Hit::new(11, "NCWK"),
+        Hit::new(20, "WKCN"),
+        Hit::new(47, "WKCN"),
+      ]
+    );
+    assert_eq!(
+      resumed
+        .hits_detail
+        .iter()
+        .map(|hit| hit.matches)
+        .sum::<usize>(),
+      resumed.hits_total
+    );
+  }
+
+  #[test]
+  fn test_resumed_seed() -> anyhow::Result<()> {
+    let research = |tries, seed| {
+      ResearchBuilder::new()
+        .tries(tries)
+        .times(2)
+        .regex(Regex::new(r"^WKCN").unwrap())
+        .seed(seed)
+        .run_seq()
+    };
+
+    let checkpointed = research(1000, 42)?;
+
+    assert_eq!(resumed_seed(42, 0), 42);
+
+    // resumed with the seed of the checkpoint, the same tries would hit again
+    assert_eq!(research(1000, 42)?.hits_detail, checkpointed.hits_detail);
+    assert_ne!(
+      research(1000, resumed_seed(42, 1000))?.hits_detail,
+      checkpointed.hits_detail
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_unrecorded_run() -> anyhow::Result<()> {
+    // a file where the workspace root should be, so nothing can be recorded
+    let root = temp_dir().join(format!("wakuchin-root-{}", fastrand::u64(..)));
+
+    fs::write(&root, "")?;
+
+    let config = Config {
+      tries: 100,
+      times: 1,
+      regex: Regex::new("^WKNC$")?,
+      no_progress: true,
+      workers: 1,
+      workspace: root.clone(),
+      ..Config::default()
+    };
+    let research = run_research(&config, None)?;
+
+    assert_eq!(research.result.tries, 100);
+    assert!(research.run.name.is_none());
+
+    fs::remove_file(root)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_matchers() {
+    let symbols = SymbolSet::default();
+    let literals =
+      |regex: &str| match matchers(&Regex::new(regex).unwrap(), 2, &symbols) {
+        Matchers::Literals(literals) => Some(literals),
+        _ => None,
+      };
+
+    assert_eq!(
+      literals("(WKNCWKNC|WCKNWCKN)"),
+      Some(vec!["WKNCWKNC".into(), "WCKNWCKN".into()])
+    );
+    assert_eq!(literals("^WKNCWKNC$"), Some(vec!["WKNCWKNC".into()]));
+    assert_eq!(literals("(?:WKNCWKNC|WCKNWCKN)").map(|l| l.len()), Some(2));
+    // shorter literals may hit together, unlike alternatives of the regex
+    assert_eq!(literals("(WKNC|WCKN)"), None);
+    assert_eq!(literals("(WKNCWKNC|WKNCWKNC)"), None);
+    assert_eq!(literals("(?i)WKNCWKNC"), None);
+    assert_eq!(literals("WKNCWKN."), None);
+  }
+}
@@ -0,0 +1,192 @@
+//! Progress streams recorded by `wakuchin record` and replayed by
+//! `wakuchin replay`
+//!
+//! A stream starts with [`MAGIC`] and a format version byte, followed by the
+//! chunks as emitted, each of them the microseconds elapsed since the start
+//! of the recording as a little-endian `u64`, the length of the chunk as a
+//! little-endian `u32` and its bytes. Chunks are written as they were read,
+//! so they do not need to align with msgpack frames.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+/// First bytes of a stream.
+pub const MAGIC: &[u8; 8] = b"WKSTREAM";
+
+/// Version of the stream format.
+pub const VERSION: u8 = 1;
+
+/// Bytes emitted at once, `elapsed` after the start of the recording.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Chunk {
+  pub elapsed: Duration,
+  pub bytes: Vec<u8>,
+}
+
+/// Writer of a stream, timestamping chunks from its creation.
+pub struct StreamWriter<W: Write> {
+  writer: W,
+  started: Instant,
+}
+
+impl<W: Write> StreamWriter<W> {
+  /// Write the header of a stream to `writer`.
+  pub fn new(mut writer: W) -> io::Result<Self> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+
+    Ok(Self {
+      writer,
+      started: Instant::now(),
+    })
+  }
+
+  /// Record `bytes` as emitted now.
+  #[inline]
+  pub fn write_chunk(&mut self, bytes: &[u8]) -> io::Result<()> {
+    let elapsed = self.started.elapsed();
+
+    self.write_chunk_at(elapsed, bytes)
+  }
+
+  fn write_chunk_at(
+    &mut self,
+    elapsed: Duration,
+    bytes: &[u8],
+  ) -> io::Result<()> {
+    let len = u32::try_from(bytes.len()).map_err(|_| {
+      io::Error::new(ErrorKind::InvalidInput, "chunk is too long")
+    })?;
+
+    self
+      .writer
+      .write_all(&(elapsed.as_micros() as u64).to_le_bytes())?;
+    self.writer.write_all(&len.to_le_bytes())?;
+    self.writer.write_all(bytes)
+  }
+
+  /// Flush the stream, returning its writer.
+  pub fn finish(mut self) -> io::Result<W> {
+    self.writer.flush()?;
+
+    Ok(self.writer)
+  }
+}
+
+/// Reader of the chunks of a stream, in order.
+pub struct StreamReader<R: Read> {
+  reader: R,
+}
+
+impl<R: Read> StreamReader<R> {
+  /// Read and check the header of a stream from `reader`.
+  pub fn new(mut reader: R) -> io::Result<Self> {
+    let mut header = [0; MAGIC.len() + 1];
+
+    reader.read_exact(&mut header).map_err(|e| match e.kind() {
+      ErrorKind::UnexpectedEof => invalid_data("not a recorded stream"),
+      _ => e,
+    })?;
+
+    if &header[..MAGIC.len()] != MAGIC {
+      return Err(invalid_data("not a recorded stream"));
+    }
+
+    if header[MAGIC.len()] != VERSION {
+      return Err(invalid_data(format!(
+        "unsupported stream version {}, expected {VERSION}",
+        header[MAGIC.len()]
+      )));
+    }
+
+    Ok(Self { reader })
+  }
+
+  fn read_chunk(&mut self) -> io::Result<Option<Chunk>> {
+    let mut elapsed = [0; 8];
+
+    // the stream may only end between chunks
+    match self.reader.read(&mut elapsed[..1])? {
+      0 => return Ok(None),
+      _ => self.reader.read_exact(&mut elapsed[1..])?,
+    }
+
+    let mut len = [0; 4];
+
+    self.reader.read_exact(&mut len)?;
+
+    let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+
+    self.reader.read_exact(&mut bytes)?;
+
+    Ok(Some(Chunk {
+      elapsed: Duration::from_micros(u64::from_le_bytes(elapsed)),
+      bytes,
+    }))
+  }
+}
+
+impl<R: Read> Iterator for StreamReader<R> {
+  type Item = io::Result<Chunk>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.read_chunk().transpose()
+  }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+  io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::ErrorKind;
+  use std::time::Duration;
+
+  use super::{Chunk, StreamReader, StreamWriter};
+
+  #[test]
+  fn test_stream() -> std::io::Result<()> {
+    let mut writer = StreamWriter::new(Vec::new())?;
+
+    writer.write_chunk_at(Duration::from_millis(5), b"\x93\x00")?;
+    writer.write_chunk_at(Duration::from_secs(2), b"")?;
+    writer.write_chunk_at(Duration::from_secs(3), b"WKCN")?;
+
+    let stream = writer.finish()?;
+    let chunks = StreamReader::new(stream.as_slice())?
+      .collect::<std::io::Result<Vec<_>>>()?;
+
+    assert_eq!(
+      chunks,
+      [
+        Chunk {
+          elapsed: Duration::from_millis(5),
+          bytes: b"\x93\x00".to_vec(),
+        },
+        Chunk {
+          elapsed: Duration::from_secs(2),
+          bytes: Vec::new(),
+        },
+        Chunk {
+          elapsed: Duration::from_secs(3),
+          bytes: b"WKCN".to_vec(),
+        },
+      ]
+    );
+
+    // truncated in the middle of a chunk
+    let truncated = StreamReader::new(&stream[..stream.len() - 2])?
+      .collect::<std::io::Result<Vec<_>>>();
+
+    assert_eq!(truncated.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(
+      StreamReader::new(&b"WKCNWKCN\x01"[..])
+        .err()
+        .map(|e| e.kind()),
+      Some(ErrorKind::InvalidData)
+    );
+
+    Ok(())
+  }
+}
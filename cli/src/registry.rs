@@ -0,0 +1,591 @@
+use std::collections::HashSet;
+use std::fs::{read_dir, read_to_string, remove_dir_all};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use wakuchin::result::{Hit, HitCount, WakuchinResult};
+
+use crate::dictionary::{HitDictionary, InternedHits, HITS_FILE};
+use crate::error::{AppError, Result};
+use crate::heartbeat::{Heartbeat, HEARTBEAT_FILE};
+use crate::workspace::{
+  petname, write_json, Artifact, ResultSummary, RunMetadata, RunStatus,
+  ARCHIVE_DIR, METADATA_FILE, REPORT_FILE,
+};
+
+/// A run found in the registry.
+#[derive(Clone, Debug)]
+pub struct RunEntry {
+  pub metadata: RunMetadata,
+  pub dir: PathBuf,
+  pub size: u64,
+  pub archived: bool,
+  /// Still running but without a heartbeat for a while, see
+  /// [`Heartbeat::is_stalled`]
+  pub stalled: bool,
+}
+
+impl RunEntry {
+  fn report_path(&self) -> PathBuf {
+    self
+      .dir
+      .join(Artifact::Reports.dir_name())
+      .join(REPORT_FILE)
+  }
+
+  /// Load the stored result of the run, resolving interned hits.
+  pub fn load_result(
+    &self,
+    dictionary: &HitDictionary,
+  ) -> Result<WakuchinResult> {
+    let report_path = self.report_path();
+    let contents = read_to_string(&report_path).map_err(|source| {
+      AppError::WorkspaceIoError {
+        path: report_path.as_path().into(),
+        source,
+      }
+    })?;
+
+    // runs which are not migrated yet still carry full hits
+    if let Ok(legacy) = serde_json::from_str::<LegacyResult>(&contents) {
+      return Ok(legacy.into());
+    }
+
+    let summary: ResultSummary =
+      serde_json::from_str(&contents).map_err(|e| AppError::Other(e.into()))?;
+
+    let hits_path = self.dir.join(HITS_FILE);
+    let hits: InternedHits =
+      serde_json::from_str(&read_to_string(&hits_path).map_err(|source| {
+        AppError::WorkspaceIoError {
+          path: hits_path.as_path().into(),
+          source,
+        }
+      })?)
+      .map_err(|e| AppError::Other(e.into()))?;
+
+    Ok(WakuchinResult {
+      tries: summary.tries,
+      hits_total: summary.hits_total,
+      hits: hits.resolve_hits(dictionary)?,
+      hits_detail: hits.resolve_detail(dictionary)?,
+      patterns: summary.patterns,
+      rarity: summary.rarity,
+      stop_reason: summary.stop_reason,
+      failures: summary.failures,
+    })
+  }
+}
+
+/// Runs selected for removal by [`Registry::plan_gc`].
+#[derive(Debug, Default)]
+pub struct GcPlan {
+  pub remove: Vec<RunEntry>,
+  pub keep: Vec<RunEntry>,
+}
+
+impl GcPlan {
+  #[inline]
+  pub fn freed(&self) -> u64 {
+    self.remove.iter().map(|entry| entry.size).sum()
+  }
+}
+
+/// Result report written before hits were interned into the dictionary.
+#[derive(Deserialize)]
+struct LegacyResult {
+  tries: usize,
+  hits_total: usize,
+  hits: Vec<HitCount>,
+  hits_detail: Vec<LegacyHit>,
+}
+
+#[derive(Deserialize)]
+struct LegacyHit {
+  hit_on: usize,
+  chars: String,
+}
+
+impl From<LegacyResult> for WakuchinResult {
+  fn from(legacy: LegacyResult) -> Self {
+    Self {
+      tries: legacy.tries,
+      hits_total: legacy.hits_total,
+      hits: legacy.hits,
+      hits_detail: legacy
+        .hits_detail
+        .into_iter()
+        .map(|hit| Hit::new(hit.hit_on, hit.chars))
+        .collect(),
+      patterns: Vec::new(),
+      rarity: None,
+      stop_reason: None,
+      failures: Vec::new(),
+    }
+  }
+}
+
+/// View over all run workspaces under a root directory.
+pub struct Registry {
+  root: PathBuf,
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+  let mut size = 0;
+
+  for entry in read_dir(path)? {
+    let entry = entry?;
+    let metadata = entry.metadata()?;
+
+    if metadata.is_dir() {
+      size += dir_size(&entry.path())?;
+    } else {
+      size += metadata.len();
+    }
+  }
+
+  Ok(size)
+}
+
+impl Registry {
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    Self { root: root.into() }
+  }
+
+  #[inline]
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  fn io_error(&self) -> impl FnOnce(io::Error) -> AppError + '_ {
+    move |source| AppError::WorkspaceIoError {
+      path: self.root.as_path().into(),
+      source,
+    }
+  }
+
+  /// Read the metadata of the runs in `dir`, without their size.
+  fn read_metadata(&self, dir: &Path) -> Result<Vec<(PathBuf, RunMetadata)>> {
+    let mut runs = Vec::new();
+
+    if !dir.is_dir() {
+      return Ok(runs);
+    }
+
+    for entry in read_dir(dir).map_err(self.io_error())? {
+      let path = entry.map_err(self.io_error())?.path();
+      let metadata_path = path.join(METADATA_FILE);
+
+      // skip anything that is not a run directory, e.g. the archive itself
+      let Ok(contents) = read_to_string(&metadata_path) else {
+        continue;
+      };
+
+      // a broken run must not hide all the others
+      match serde_json::from_str::<RunMetadata>(&contents) {
+        Ok(metadata) => runs.push((path, metadata)),
+        Err(e) => eprintln!(
+          "warning: skipping {}: {e}",
+          metadata_path.to_string_lossy()
+        ),
+      }
+    }
+
+    Ok(runs)
+  }
+
+  fn collect(
+    &self,
+    dir: &Path,
+    archived: bool,
+    entries: &mut Vec<RunEntry>,
+  ) -> Result<()> {
+    let now = SystemTime::now();
+
+    for (path, metadata) in self.read_metadata(dir)? {
+      // runs created by older versions have no heartbeat
+      let stalled = metadata.status == RunStatus::Running
+        && read_to_string(path.join(HEARTBEAT_FILE))
+          .ok()
+          .and_then(|contents| {
+            serde_json::from_str::<Heartbeat>(&contents).ok()
+          })
+          .is_some_and(|heartbeat| heartbeat.is_stalled(now));
+
+      entries.push(RunEntry {
+        metadata,
+        stalled,
+        size: dir_size(&path).map_err(self.io_error())?,
+        dir: path,
+        archived,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Generate a run name which no run of the registry has, archived ones
+  /// included.
+  pub fn unique_name(&self) -> Result<String> {
+    let taken = [self.root.clone(), self.root.join(ARCHIVE_DIR)]
+      .iter()
+      .map(|dir| self.read_metadata(dir))
+      .collect::<Result<Vec<_>>>()?
+      .into_iter()
+      .flatten()
+      .filter_map(|(_, metadata)| metadata.name)
+      .collect::<HashSet<_>>();
+    let mut max_number = 99;
+
+    // numbers get longer while names keep colliding
+    loop {
+      for _ in 0..16 {
+        let name = petname(max_number);
+
+        if !taken.contains(&name) {
+          return Ok(name);
+        }
+      }
+
+      max_number = max_number.saturating_mul(10).saturating_add(9);
+    }
+  }
+
+  /// List all runs, newest first.
+  pub fn list(&self) -> Result<Vec<RunEntry>> {
+    let mut entries = Vec::new();
+
+    self.collect(&self.root, false, &mut entries)?;
+    self.collect(&self.root.join(ARCHIVE_DIR), true, &mut entries)?;
+
+    // run ids are ULIDs, so they sort by creation time
+    entries.sort_by(|a, b| b.metadata.id.cmp(&a.metadata.id));
+
+    Ok(entries)
+  }
+
+  /// Find a run by its name, its id or a prefix of it, the latest run if
+  /// `id` is `None`.
+  pub fn find(&self, id: Option<&str>) -> Result<RunEntry> {
+    let mut entries = self.list()?;
+
+    if let Some(index) = id.and_then(|name| {
+      entries
+        .iter()
+        .position(|entry| entry.metadata.name.as_deref() == Some(name))
+    }) {
+      return Ok(entries.swap_remove(index));
+    }
+
+    let mut entries = entries.into_iter();
+
+    let found = match id {
+      Some(id) => {
+        let mut matched =
+          entries.filter(|entry| entry.metadata.id.starts_with(id));
+        let found = matched.next();
+
+        if found.is_some() && matched.next().is_some() {
+          return Err(AppError::Other(anyhow::anyhow!(
+            "'{id}': ambiguous run id"
+          )));
+        }
+
+        found
+      }
+      None => entries.next(),
+    };
+
+    found.ok_or_else(|| {
+      AppError::Other(anyhow::anyhow!(
+        "'{}': no such run",
+        id.unwrap_or("latest")
+      ))
+    })
+  }
+
+  /// Select runs to remove so that at most `keep_last` runs remain and they
+  /// take up at most `max_size` bytes in total.
+  ///
+  /// The newest runs are kept, so once a run does not fit in `max_size`, it
+  /// is removed along with every older one. Runs which are still running are
+  /// never selected, unless they stalled.
+  pub fn plan_gc(
+    &self,
+    keep_last: Option<usize>,
+    max_size: Option<u64>,
+  ) -> Result<GcPlan> {
+    let mut plan = GcPlan::default();
+    let mut kept_size = 0;
+    let mut over_size = false;
+
+    for entry in self.list()? {
+      let over_count = keep_last.is_some_and(|n| plan.keep.len() >= n);
+
+      over_size |= max_size.is_some_and(|max| kept_size + entry.size > max);
+
+      let collectable =
+        entry.metadata.status != RunStatus::Running || entry.stalled;
+
+      if collectable && (over_count || over_size) {
+        plan.remove.push(entry);
+      } else {
+        kept_size += entry.size;
+        plan.keep.push(entry);
+      }
+    }
+
+    Ok(plan)
+  }
+
+  /// Intern the hits of runs which still store full hit chars in their
+  /// report into the shared dictionary.
+  ///
+  /// Returns the number of migrated runs.
+  pub fn migrate(&self) -> Result<usize> {
+    let mut migrated = 0;
+
+    for entry in self.list()? {
+      let hits_path = entry.dir.join(HITS_FILE);
+      let report_path = entry
+        .dir
+        .join(Artifact::Reports.dir_name())
+        .join(REPORT_FILE);
+
+      if hits_path.exists() {
+        continue;
+      }
+
+      let Ok(contents) = read_to_string(&report_path) else {
+        continue;
+      };
+
+      let Ok(legacy) = serde_json::from_str::<LegacyResult>(&contents) else {
+        continue;
+      };
+
+      let result = WakuchinResult::from(legacy);
+      let hits =
+        HitDictionary::update(&self.root, |dict| dict.intern_result(&result))?;
+
+      write_json(&hits_path, &hits)?;
+      write_json(&report_path, &ResultSummary::from(&result))?;
+
+      migrated += 1;
+    }
+
+    Ok(migrated)
+  }
+
+  /// Remove the runs selected by the plan.
+  pub fn apply_gc(&self, plan: &GcPlan) -> Result<()> {
+    for entry in &plan.remove {
+      remove_dir_all(&entry.dir).map_err(|source| {
+        AppError::WorkspaceIoError {
+          path: entry.dir.as_path().into(),
+          source,
+        }
+      })?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Parse a human-readable byte size, e.g. "512", "20M", "10G".
+pub fn parse_size(size: &str) -> std::result::Result<u64, String> {
+  let size = size.trim();
+  let split = size
+    .find(|c: char| !c.is_ascii_digit() && c != '.')
+    .unwrap_or(size.len());
+  let (number, unit) = size.split_at(split);
+
+  let number: f64 = number
+    .parse()
+    .map_err(|_| format!("'{size}': invalid size"))?;
+
+  let scale: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+    "" | "B" => 1,
+    "K" | "KB" | "KIB" => 1 << 10,
+    "M" | "MB" | "MIB" => 1 << 20,
+    "G" | "GB" | "GIB" => 1 << 30,
+    "T" | "TB" | "TIB" => 1 << 40,
+    _ => return Err(format!("'{size}': unknown size unit")),
+  };
+
+  Ok((number * scale as f64) as u64)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+  use std::collections::HashSet;
+  use std::env::temp_dir;
+  use std::fs::{
+    create_dir_all, read_to_string, remove_dir_all, remove_file, write,
+  };
+  use std::thread::sleep;
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use ulid::Ulid;
+  use wakuchin::result::HitCount;
+
+  use crate::config::Config;
+  use crate::dictionary::HitDictionary;
+  use crate::heartbeat::HEARTBEAT_FILE;
+  use crate::workspace::{
+    RetentionPolicy, RunStatus, RunWorkspace, METADATA_FILE,
+  };
+
+  use super::{parse_size, Registry};
+
+  #[test]
+  fn test_parse_size() {
+    assert_eq!(parse_size("512"), Ok(512));
+    assert_eq!(parse_size("20K"), Ok(20 * 1024));
+    assert_eq!(parse_size("1.5M"), Ok(3 * 512 * 1024));
+    assert_eq!(parse_size("10G"), Ok(10 * 1024 * 1024 * 1024));
+    assert_eq!(parse_size("10gb"), Ok(10 * 1024 * 1024 * 1024));
+    assert!(parse_size("10X").is_err());
+    assert!(parse_size("G").is_err());
+  }
+
+  #[test]
+  fn test_plan_gc() -> Result<()> {
+    let root = temp_dir().join(format!("wakuchin-test-{}", Ulid::new()));
+    let config = Config::default();
+
+    let mut ids = Vec::new();
+
+    for _ in 0..3 {
+      let workspace = RunWorkspace::create(&root, &config, None)?;
+
+      workspace.write_report("padding.txt", &"0".repeat(1000))?;
+      ids.push(workspace.id().to_string());
+      workspace.finish(RunStatus::Finished, RetentionPolicy::Keep)?;
+
+      // make sure ids differ in their timestamp part
+      sleep(Duration::from_millis(2));
+    }
+
+    // still running, must not be collected until it stalls
+    let running = RunWorkspace::create(&root, &config, None)?;
+
+    // a half-written run is skipped instead of failing the listing
+    create_dir_all(root.join("broken"))?;
+    write(root.join("broken").join(METADATA_FILE), r#"{"id":"#)?;
+
+    let registry = Registry::new(&root);
+
+    assert_eq!(registry.list()?.len(), 4);
+
+    let names = registry
+      .list()?
+      .into_iter()
+      .filter_map(|entry| entry.metadata.name)
+      .collect::<HashSet<_>>();
+
+    assert_eq!(names.len(), 4);
+
+    let name = running.metadata().name.as_deref().unwrap_or_default();
+
+    assert_eq!(registry.find(Some(name))?.metadata.id, running.id());
+    assert!(!registry.find(None)?.stalled);
+    assert_eq!(registry.plan_gc(Some(0), None)?.keep.len(), 1);
+
+    write(
+      running.dir().join(HEARTBEAT_FILE),
+      r#"{"at":"2023-01-01T00:00:00Z","current":42}"#,
+    )?;
+
+    assert!(registry.find(None)?.stalled);
+    assert!(registry.plan_gc(Some(0), None)?.keep.is_empty());
+
+    let plan = registry.plan_gc(Some(2), None)?;
+
+    assert_eq!(plan.keep.len(), 2);
+    assert_eq!(plan.keep[0].metadata.id, running.id());
+    assert_eq!(plan.remove.len(), 2);
+    assert_eq!(plan.remove[0].metadata.id, ids[1]);
+
+    // a large new run does not make room for smaller older ones
+    let padding = root.join(&ids[2]).join("padding.txt");
+
+    write(&padding, "0".repeat(3000))?;
+
+    let sizes = registry
+      .list()?
+      .into_iter()
+      .map(|entry| entry.size)
+      .collect::<Vec<_>>();
+    let plan = registry.plan_gc(None, Some(sizes[0] + sizes[2] + sizes[3]))?;
+
+    assert_eq!(plan.keep.len(), 1);
+    assert_eq!(plan.remove.len(), 3);
+    assert_eq!(plan.remove[0].metadata.id, ids[2]);
+
+    remove_file(padding)?;
+
+    let plan = registry.plan_gc(None, Some(1800))?;
+
+    assert_eq!(plan.keep.len(), 2);
+    assert_eq!(plan.keep[1].metadata.id, ids[2]);
+
+    registry.apply_gc(&plan)?;
+
+    assert_eq!(registry.list()?.len(), 2);
+
+    write(root.join("stray-file"), "")?;
+
+    assert_eq!(registry.list()?.len(), 2);
+
+    remove_dir_all(root)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_migrate() -> Result<()> {
+    let root = temp_dir().join(format!("wakuchin-test-{}", Ulid::new()));
+    let config = Config::default();
+
+    let workspace = RunWorkspace::create(&root, &config, None)?;
+    let dir = root.join(workspace.id());
+
+    workspace.write_report(
+      "result.json",
+      r#"{"tries":10,"hits_total":2,"hits":[{"chars":"WKCN","hits":2}],"hits_detail":[{"hit_on":3,"chars":"WKCN"},{"hit_on":8,"chars":"WKCN"}]}"#,
+    )?;
+    workspace.finish(RunStatus::Finished, RetentionPolicy::Keep)?;
+
+    let registry = Registry::new(&root);
+
+    assert_eq!(registry.migrate()?, 1);
+    assert_eq!(registry.migrate()?, 0);
+
+    assert_eq!(
+      read_to_string(dir.join("hits.json"))?,
+      r#"{"hits":[[0,2]],"hits_detail":[[3,0],[8,0]]}"#
+    );
+    assert_eq!(
+      read_to_string(dir.join("reports").join("result.json"))?,
+      r#"{"tries":10,"hits_total":2}"#
+    );
+    assert_eq!(
+      read_to_string(root.join("dictionary.json"))?,
+      r#"{"entries":["WKCN"]}"#
+    );
+
+    let result =
+      registry.list()?[0].load_result(&HitDictionary::load(&root)?)?;
+
+    assert_eq!(result.hits, vec![HitCount::new("WKCN", 2)]);
+    assert_eq!(result.hits_detail.len(), 2);
+
+    remove_dir_all(root)?;
+
+    Ok(())
+  }
+}
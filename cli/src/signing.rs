@@ -0,0 +1,179 @@
+use std::fs::{create_dir_all, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use base64::{engine::general_purpose, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use wakuchin::fs::atomic_write_with_mode;
+
+use crate::error::{AppError, Result};
+
+/// Extension appended to a signed file to get its detached signature.
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Detached signature of a result file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureFile {
+  /// Base64-encoded ed25519 public key of the signer
+  pub public_key: String,
+  /// Base64-encoded ed25519 signature over the exact bytes of the file
+  pub signature: String,
+}
+
+/// Default path of the signing key, e.g. `~/.config/wakuchin/signing.key`.
+pub fn default_key_path() -> PathBuf {
+  dirs::config_dir()
+    .unwrap_or_else(|| PathBuf::from(".wakuchin"))
+    .join("wakuchin")
+    .join("signing.key")
+}
+
+/// Path of the detached signature of `path`, e.g. `result.json.sig`.
+pub fn signature_path(path: &Path) -> PathBuf {
+  let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+
+  file_name.push(".");
+  file_name.push(SIGNATURE_EXTENSION);
+
+  path.with_file_name(file_name)
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> AppError + '_ {
+  move |source| AppError::ConfigIoError {
+    path: path.into(),
+    source,
+  }
+}
+
+fn decode<const N: usize>(encoded: &str, what: &str) -> Result<[u8; N]> {
+  general_purpose::STANDARD
+    .decode(encoded.trim())
+    .ok()
+    .and_then(|bytes| bytes.try_into().ok())
+    .ok_or_else(|| anyhow!("malformed {what}").into())
+}
+
+#[inline]
+pub fn encode_public_key(key: &VerifyingKey) -> String {
+  general_purpose::STANDARD.encode(key.as_bytes())
+}
+
+/// Generate a new signing key from the OS random source and save it to
+/// `path`, readable only by the current user.
+pub fn generate_key(path: &Path, force: bool) -> Result<SigningKey> {
+  if path.exists() && !force {
+    return Err(
+      anyhow!(
+        "'{}': key already exists (hint: pass --force to overwrite)",
+        path.to_string_lossy()
+      )
+      .into(),
+    );
+  }
+
+  let mut secret = [0; 32];
+
+  getrandom::getrandom(&mut secret).map_err(|e| anyhow!(e))?;
+
+  let key = SigningKey::from_bytes(&secret);
+
+  if let Some(parent) = path.parent() {
+    create_dir_all(parent).map_err(io_error(parent))?;
+  }
+
+  // created readable only by the user before the key is written to it
+  atomic_write_with_mode(path, general_purpose::STANDARD.encode(secret), 0o600)
+    .map_err(io_error(path))?;
+
+  Ok(key)
+}
+
+pub fn load_key(path: &Path) -> Result<SigningKey> {
+  let encoded = read_to_string(path).map_err(io_error(path))?;
+
+  Ok(SigningKey::from_bytes(&decode(&encoded, "signing key")?))
+}
+
+pub fn sign(key: &SigningKey, contents: &[u8]) -> SignatureFile {
+  SignatureFile {
+    public_key: encode_public_key(&key.verifying_key()),
+    signature: general_purpose::STANDARD.encode(key.sign(contents).to_bytes()),
+  }
+}
+
+/// Verify the signature of `contents`, returning the public key of the signer.
+pub fn verify(contents: &[u8], signature: &SignatureFile) -> Result<String> {
+  let key =
+    VerifyingKey::from_bytes(&decode(&signature.public_key, "public key")?)
+      .map_err(|e| anyhow!(e))?;
+  let signature =
+    Signature::from_bytes(&decode(&signature.signature, "signature")?);
+
+  key
+    .verify(contents, &signature)
+    .map_err(|_| anyhow!("signature does not match the contents"))?;
+
+  Ok(encode_public_key(&key))
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+  use std::env::temp_dir;
+  use std::fs::remove_file;
+  use std::path::Path;
+
+  use anyhow::Result;
+  use ulid::Ulid;
+
+  use super::{generate_key, load_key, sign, signature_path, verify};
+
+  #[test]
+  fn test_signature_path() {
+    assert_eq!(
+      signature_path(Path::new("out/result.json")),
+      Path::new("out/result.json.sig")
+    );
+  }
+
+  #[test]
+  fn test_sign_and_verify() -> Result<()> {
+    let path = temp_dir().join(format!("wakuchin-key-{}", Ulid::new()));
+
+    let key = generate_key(&path, false)?;
+
+    assert!(generate_key(&path, false).is_err());
+    assert_eq!(load_key(&path)?.to_bytes(), key.to_bytes());
+
+    let signature = sign(&key, b"{\"tries\":10}");
+
+    assert_eq!(verify(b"{\"tries\":10}", &signature)?, signature.public_key);
+    assert!(verify(b"{\"tries\":11}", &signature).is_err());
+
+    remove_file(path)?;
+
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_key_mode() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = temp_dir().join(format!("wakuchin-key-{}", Ulid::new()));
+
+    generate_key(&path, false)?;
+
+    assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+
+    // overwriting keeps the key private too
+    generate_key(&path, true)?;
+
+    assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+
+    remove_file(path)?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,80 @@
+use std::io;
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+
+use serde::Serialize;
+use signal_hook::consts::SIGUSR1;
+use signal_hook::iterator::{Handle, Signals};
+use wakuchin::fs::atomic_write;
+use wakuchin::handlers::snapshot::{ProgressSnapshot, SnapshotReader};
+
+use crate::thermal::{ThermalReader, ThermalStatus};
+
+/// Dumps the latest progress snapshot as JSON whenever the process receives
+/// SIGUSR1, e.g. `kill -USR1 <pid>`.
+pub struct SnapshotListener {
+  handle: Handle,
+  thread: JoinHandle<()>,
+}
+
+/// Snapshot annotated with the state of the machine.
+#[derive(Serialize)]
+struct AnnotatedSnapshot {
+  #[serde(flatten)]
+  snapshot: ProgressSnapshot,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  thermal: Option<ThermalStatus>,
+}
+
+fn dump(
+  reader: &SnapshotReader,
+  thermal: Option<&ThermalReader>,
+  path: Option<&PathBuf>,
+) -> io::Result<()> {
+  let snapshot =
+    serde_json::to_string_pretty(&reader.latest().map(|snapshot| {
+      AnnotatedSnapshot {
+        snapshot,
+        thermal: thermal.and_then(ThermalReader::latest),
+      }
+    }))?;
+
+  match path {
+    Some(path) => atomic_write(path, snapshot),
+    None => {
+      eprintln!("{snapshot}");
+
+      Ok(())
+    }
+  }
+}
+
+impl SnapshotListener {
+  /// Start listening, snapshots are written to `path` or stderr if `None`.
+  ///
+  /// Snapshots include the thermal state if `thermal` is given.
+  pub fn spawn(
+    reader: SnapshotReader,
+    thermal: Option<ThermalReader>,
+    path: Option<PathBuf>,
+  ) -> io::Result<Self> {
+    let mut signals = Signals::new([SIGUSR1])?;
+    let handle = signals.handle();
+
+    let thread = thread::spawn(move || {
+      for _ in signals.forever() {
+        if let Err(e) = dump(&reader, thermal.as_ref(), path.as_ref()) {
+          eprintln!("warning: failed to write progress snapshot: {e}");
+        }
+      }
+    });
+
+    Ok(Self { handle, thread })
+  }
+
+  pub fn stop(self) {
+    self.handle.close();
+
+    let _ = self.thread.join();
+  }
+}
@@ -0,0 +1,312 @@
+//! Detecting thermal throttling of the CPUs, to explain throughput drops
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+/// Throttling observed during a run, stored in the run metadata.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThrottlingSummary {
+  /// Number of samples taken
+  pub samples: usize,
+  /// Number of samples in which the CPUs were throttled
+  pub throttled_samples: usize,
+  /// Number of times throttling lasted for several samples in a row
+  pub sustained_episodes: usize,
+  /// Highest temperature seen, in degrees Celsius
+  pub max_temperature: Option<f64>,
+  /// Lowest ratio of the current to the maximum CPU frequency seen
+  pub min_frequency_ratio: Option<f64>,
+}
+
+/// Latest thermal state, added to progress snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct ThermalStatus {
+  pub throttled: bool,
+  /// Whether throttling lasted for several samples in a row
+  pub sustained: bool,
+  pub temperature: Option<f64>,
+  pub frequency_ratio: Option<f64>,
+}
+
+/// Cloneable handle to read the latest [`ThermalStatus`].
+#[derive(Clone, Default)]
+pub struct ThermalReader {
+  latest: Arc<Mutex<Option<ThermalStatus>>>,
+}
+
+impl ThermalReader {
+  pub fn latest(&self) -> Option<ThermalStatus> {
+    *self.latest.lock().unwrap()
+  }
+}
+
+#[cfg(feature = "thermal")]
+pub use self::monitor::ThermalMonitor;
+
+#[cfg(feature = "thermal")]
+mod monitor {
+  use std::fs::{read_dir, read_to_string};
+  use std::path::Path;
+  use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+  use std::thread::{self, JoinHandle};
+  use std::time::Duration;
+
+  use super::{ThermalReader, ThermalStatus, ThrottlingSummary};
+
+  /// Interval between two samples.
+  const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+  /// A sample is throttled when the frequency drops below this fraction of
+  /// the highest frequency seen during the run.
+  const FREQUENCY_DROP: f64 = 0.8;
+
+  /// Number of throttled samples in a row to consider throttling sustained.
+  const STREAK: usize = 3;
+
+  /// Readings of a sample, `None` if unavailable on this system.
+  #[derive(Clone, Copy, Debug, Default, PartialEq)]
+  struct Sample {
+    /// Highest temperature of the thermal zones, in degrees Celsius
+    temperature: Option<f64>,
+    /// Average ratio of the current to the maximum frequency of the CPUs
+    frequency_ratio: Option<f64>,
+    /// Total of the throttle counters of the CPUs
+    throttle_count: Option<u64>,
+  }
+
+  fn read_number(path: &Path) -> Option<f64> {
+    read_to_string(path).ok()?.trim().parse().ok()
+  }
+
+  impl Sample {
+    fn take() -> Self {
+      let temperature = read_dir("/sys/class/thermal")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|zone| {
+          zone
+            .file_name()
+            .to_string_lossy()
+            .starts_with("thermal_zone")
+        })
+        .filter_map(|zone| read_number(&zone.path().join("temp")))
+        .map(|millis| millis / 1000.0)
+        .reduce(f64::max);
+
+      let cpus = read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|cpu| {
+          let name = cpu.file_name().to_string_lossy().into_owned();
+
+          matches!(
+            name.strip_prefix("cpu"),
+            Some(id) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit())
+          )
+        })
+        .map(|cpu| cpu.path())
+        .collect::<Vec<_>>();
+
+      let ratios = cpus
+        .iter()
+        .filter_map(|cpu| {
+          let current = read_number(&cpu.join("cpufreq/scaling_cur_freq"))?;
+          let max = read_number(&cpu.join("cpufreq/cpuinfo_max_freq"))?;
+
+          (max > 0.0).then(|| current / max)
+        })
+        .collect::<Vec<_>>();
+
+      let frequency_ratio = (!ratios.is_empty())
+        .then(|| ratios.iter().sum::<f64>() / ratios.len() as f64);
+
+      let throttle_count = cpus
+        .iter()
+        .filter_map(|cpu| {
+          read_to_string(cpu.join("thermal_throttle/core_throttle_count"))
+            .ok()?
+            .trim()
+            .parse::<u64>()
+            .ok()
+        })
+        .reduce(|a, b| a + b);
+
+      Self {
+        temperature,
+        frequency_ratio,
+        throttle_count,
+      }
+    }
+  }
+
+  /// Detects throttling from consecutive samples.
+  #[derive(Debug, Default)]
+  struct Tracker {
+    summary: ThrottlingSummary,
+    status: ThermalStatus,
+    streak: usize,
+    max_frequency_ratio: f64,
+    throttle_count: Option<u64>,
+  }
+
+  impl Tracker {
+    fn update(&mut self, sample: Sample) -> ThermalStatus {
+      let counted = match (self.throttle_count, sample.throttle_count) {
+        (Some(prev), Some(count)) => count > prev,
+        _ => false,
+      };
+
+      let dropped = match sample.frequency_ratio {
+        Some(ratio) => {
+          self.max_frequency_ratio = self.max_frequency_ratio.max(ratio);
+
+          ratio < self.max_frequency_ratio * FREQUENCY_DROP
+        }
+        None => false,
+      };
+
+      let throttled = counted || dropped;
+
+      self.throttle_count = sample.throttle_count;
+      self.streak = if throttled { self.streak + 1 } else { 0 };
+
+      let summary = &mut self.summary;
+
+      summary.samples += 1;
+      summary.throttled_samples += usize::from(throttled);
+      summary.sustained_episodes += usize::from(self.streak == STREAK);
+      summary.max_temperature =
+        merge(summary.max_temperature, sample.temperature, f64::max);
+      summary.min_frequency_ratio = merge(
+        summary.min_frequency_ratio,
+        sample.frequency_ratio,
+        f64::min,
+      );
+
+      self.status = ThermalStatus {
+        throttled,
+        sustained: self.streak >= STREAK,
+        temperature: sample.temperature,
+        frequency_ratio: sample.frequency_ratio,
+      };
+
+      self.status
+    }
+
+    fn into_summary(self) -> Option<ThrottlingSummary> {
+      let summary = self.summary;
+
+      (summary.max_temperature.is_some()
+        || summary.min_frequency_ratio.is_some()
+        || summary.throttled_samples > 0)
+        .then_some(summary)
+    }
+  }
+
+  fn merge(
+    a: Option<f64>,
+    b: Option<f64>,
+    f: fn(f64, f64) -> f64,
+  ) -> Option<f64> {
+    match (a, b) {
+      (Some(a), Some(b)) => Some(f(a, b)),
+      (a, b) => a.or(b),
+    }
+  }
+
+  /// Background thread sampling CPU temperatures and frequencies.
+  pub struct ThermalMonitor {
+    stop_tx: Sender<()>,
+    thread: JoinHandle<Option<ThrottlingSummary>>,
+    reader: ThermalReader,
+  }
+
+  impl ThermalMonitor {
+    pub fn spawn() -> Self {
+      let reader = ThermalReader::default();
+      let (stop_tx, stop_rx) = channel();
+
+      let thread = thread::spawn({
+        let reader = reader.clone();
+
+        move || {
+          let mut tracker = Tracker::default();
+
+          loop {
+            let status = tracker.update(Sample::take());
+
+            *reader.latest.lock().unwrap() = Some(status);
+
+            if !matches!(
+              stop_rx.recv_timeout(SAMPLE_INTERVAL),
+              Err(RecvTimeoutError::Timeout)
+            ) {
+              break;
+            }
+          }
+
+          tracker.into_summary()
+        }
+      });
+
+      Self {
+        stop_tx,
+        thread,
+        reader,
+      }
+    }
+
+    #[inline]
+    pub fn reader(&self) -> ThermalReader {
+      self.reader.clone()
+    }
+
+    /// Stop sampling, returning the summary if anything could be sampled.
+    pub fn stop(self) -> Option<ThrottlingSummary> {
+      let _ = self.stop_tx.send(());
+
+      self.thread.join().ok().flatten()
+    }
+  }
+
+  #[cfg(test)]
+  mod test {
+    use super::{Sample, Tracker, STREAK};
+
+    #[test]
+    fn test_tracker() {
+      let mut tracker = Tracker::default();
+
+      let sample = |frequency_ratio, throttle_count| Sample {
+        temperature: Some(60.0),
+        frequency_ratio: Some(frequency_ratio),
+        throttle_count: Some(throttle_count),
+      };
+
+      assert!(!tracker.update(sample(1.0, 0)).throttled);
+
+      // counter increased without a frequency drop
+      assert!(tracker.update(sample(1.0, 5)).throttled);
+
+      for _ in 1..STREAK {
+        assert!(tracker.update(sample(0.5, 5)).throttled);
+      }
+
+      assert!(tracker.status.sustained);
+      assert!(!tracker.update(sample(0.9, 5)).throttled);
+
+      let summary = tracker.into_summary().unwrap();
+
+      assert_eq!(summary.samples, STREAK + 2);
+      assert_eq!(summary.throttled_samples, STREAK);
+      assert_eq!(summary.sustained_episodes, 1);
+      assert_eq!(summary.max_temperature, Some(60.0));
+      assert_eq!(summary.min_frequency_ratio, Some(0.5));
+
+      assert_eq!(Tracker::default().into_summary(), None);
+    }
+  }
+}
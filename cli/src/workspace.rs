@@ -0,0 +1,564 @@
+use std::fs::{create_dir_all, remove_dir_all, rename};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+use wakuchin::fs::atomic_write;
+use wakuchin::handlers::deadline::DeadlineOverrun;
+use wakuchin::math::Rarity;
+use wakuchin::result::{
+  PatternHits, StopReason, WakuchinResult, WorkerFailure,
+};
+use wakuchin::symbol::SymbolSet;
+
+use crate::config::Config;
+use crate::dictionary::{HitDictionary, HITS_FILE};
+use crate::error::{AppError, Result};
+use crate::pipe::BrokenPipeEvent;
+use crate::registry::Registry;
+use crate::thermal::ThrottlingSummary;
+
+/// Name of the metadata file placed in each run directory.
+pub const METADATA_FILE: &str = "run.json";
+
+/// Name of the result report placed in the `reports` directory.
+pub const REPORT_FILE: &str = "result.json";
+
+/// Name of the directory archived runs are moved into.
+pub const ARCHIVE_DIR: &str = "archive";
+
+const ADJECTIVES: &[&str] = &[
+  "brave", "bright", "calm", "clever", "cosmic", "curious", "daring", "eager",
+  "fancy", "fuzzy", "gentle", "giddy", "happy", "jolly", "kind", "lively",
+  "lucky", "merry", "mighty", "nimble", "noble", "plucky", "proud", "quick",
+  "quiet", "shiny", "silly", "sleepy", "snappy", "sunny", "swift", "witty",
+];
+
+const NOUNS: &[&str] = &[
+  "wakuchin", "daruma", "dango", "kappa", "kitsune", "koi", "maneki", "mikan",
+  "mochi", "onigiri", "panda", "ramen", "sakura", "shiba", "sushi", "tanuki",
+  "tengu", "tofu", "tsubame", "udon", "usagi", "yokai", "yuzu", "zori",
+];
+
+const EMOJIS: &[&str] = &[
+  "🦊", "🦝", "🐼", "🐰", "🐸", "🐙", "🐳", "🐢", "🦉", "🐝", "🌸", "🍡", "🍙",
+  "🍜", "🍣", "🍊", "🎏", "🎎", "🗻", "🌙", "⭐", "🌈", "🍀", "🔮",
+];
+
+/// Generate a human-friendly run name like "brave-wakuchin-42", numbered up
+/// to `max_number`.
+pub(crate) fn petname(max_number: u64) -> String {
+  format!(
+    "{}-{}-{}",
+    ADJECTIVES[fastrand::usize(..ADJECTIVES.len())],
+    NOUNS[fastrand::usize(..NOUNS.len())],
+    fastrand::u64(1..=max_number)
+  )
+}
+
+#[derive(
+  Clone,
+  Copy,
+  Debug,
+  Default,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  Serialize,
+  Deserialize,
+  ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionPolicy {
+  #[default]
+  Keep,
+  Clean,
+  Archive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+  Running,
+  Finished,
+  Cancelled,
+  Failed,
+}
+
+/// Kind of artifacts a run can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Artifact {
+  Journal,
+  Checkpoints,
+  Samples,
+  Reports,
+}
+
+impl Artifact {
+  const ALL: [Self; 4] = [
+    Self::Journal,
+    Self::Checkpoints,
+    Self::Samples,
+    Self::Reports,
+  ];
+
+  pub(crate) const fn dir_name(self) -> &'static str {
+    match self {
+      Self::Journal => "journal",
+      Self::Checkpoints => "checkpoints",
+      Self::Samples => "samples",
+      Self::Reports => "reports",
+    }
+  }
+}
+
+/// Metadata of a run, stored as `run.json` in the run directory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RunMetadata {
+  pub id: String,
+  /// Human-friendly name of the run, unique in its registry, `None` for
+  /// runs created by older versions
+  #[serde(default)]
+  pub name: Option<String>,
+  #[serde(default)]
+  pub emoji: Option<String>,
+  pub created_at: String,
+  pub finished_at: Option<String>,
+  pub status: RunStatus,
+  pub tries: usize,
+  pub times: usize,
+  pub regex: String,
+  #[serde(default)]
+  pub label: Option<String>,
+  #[serde(default)]
+  pub host: Option<String>,
+  #[serde(default)]
+  pub config_path: Option<PathBuf>,
+  #[serde(default)]
+  pub seed: Option<u64>,
+  /// Symbol set of the run, `None` for the default one
+  #[serde(default)]
+  pub symbols: Option<SymbolSet>,
+  /// Thermal throttling observed during the run, if it could be sampled
+  #[serde(default)]
+  pub throttling: Option<ThrottlingSummary>,
+  /// When the progress output was closed by its consumer, if it was
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub broken_pipe: Option<BrokenPipeEvent>,
+  /// How the run went compared to `--expect-duration`, if it was set
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub deadline: Option<DeadlineSummary>,
+}
+
+/// Expected duration of a run against the time it took, durations in
+/// seconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeadlineSummary {
+  pub expected_secs: f64,
+  pub elapsed_secs: f64,
+  /// How much longer than expected the run took, 0 if it did not
+  pub overrun_secs: f64,
+  /// Time elapsed when the ETA exceeded the expected duration by the
+  /// margin, if it did
+  #[serde(default)]
+  pub warned_after_secs: Option<f64>,
+  /// ETA of then
+  #[serde(default)]
+  pub estimated_secs: Option<f64>,
+}
+
+impl DeadlineSummary {
+  pub fn new(
+    expected: Duration,
+    elapsed: Duration,
+    warning: Option<DeadlineOverrun>,
+  ) -> Self {
+    Self {
+      expected_secs: expected.as_secs_f64(),
+      elapsed_secs: elapsed.as_secs_f64(),
+      overrun_secs: elapsed.saturating_sub(expected).as_secs_f64(),
+      warned_after_secs: warning
+        .as_ref()
+        .map(|warning| warning.elapsed.as_secs_f64()),
+      estimated_secs: warning.map(|warning| warning.estimated.as_secs_f64()),
+    }
+  }
+}
+
+impl RunMetadata {
+  /// Return the name of the run with its emoji, or its id if it has none.
+  pub fn display_name(&self) -> String {
+    match (&self.name, &self.emoji) {
+      (Some(name), Some(emoji)) => format!("{emoji} {name}"),
+      (Some(name), None) => name.clone(),
+      (None, _) => self.id.clone(),
+    }
+  }
+
+  /// Strip everything identifying the user or their machine, keeping the
+  /// parameters needed to interpret the result.
+  pub fn redact(&mut self) {
+    self.label = None;
+    self.host = None;
+    self.config_path = None;
+
+    if let Some(broken_pipe) = &mut self.broken_pipe {
+      broken_pipe.fallback = None;
+    }
+  }
+}
+
+/// Result report stored in a run workspace.
+///
+/// Neither `hits` nor `hits_detail` are included, they are stored in
+/// `hits.json` by dictionary ids instead.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResultSummary {
+  pub tries: usize,
+  pub hits_total: usize,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub patterns: Vec<PatternHits>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub rarity: Option<Rarity>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub stop_reason: Option<StopReason>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub failures: Vec<WorkerFailure>,
+}
+
+impl From<&WakuchinResult> for ResultSummary {
+  fn from(result: &WakuchinResult) -> Self {
+    Self {
+      tries: result.tries,
+      hits_total: result.hits_total,
+      patterns: result.patterns.clone(),
+      rarity: result.rarity,
+      stop_reason: result.stop_reason.clone(),
+      failures: result.failures.clone(),
+    }
+  }
+}
+
+/// Owns the directory all artifacts of a single run are written to.
+///
+/// Layout:
+///
+/// ```txt
+/// <root>/<run-id>/
+///   run.json
+///   hits.json
+///   journal/
+///   checkpoints/
+///   samples/
+///   reports/
+/// ```
+pub struct RunWorkspace {
+  root: PathBuf,
+  dir: PathBuf,
+  metadata: RunMetadata,
+  /// Whether the run is recorded to `dir`, which does not exist otherwise
+  recorded: bool,
+}
+
+/// Default root directory of run workspaces, e.g.
+/// `~/.local/share/wakuchin/runs`.
+pub fn default_root() -> PathBuf {
+  dirs::data_dir()
+    .unwrap_or_else(|| PathBuf::from(".wakuchin"))
+    .join("wakuchin")
+    .join("runs")
+}
+
+pub(crate) fn hostname() -> Option<String> {
+  #[cfg(not(target_arch = "wasm32"))]
+  {
+    hostname::get()
+      .ok()
+      .map(|name| name.to_string_lossy().into_owned())
+  }
+
+  #[cfg(target_arch = "wasm32")]
+  {
+    None
+  }
+}
+
+fn now() -> String {
+  humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
+
+pub(crate) fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+  let contents =
+    serde_json::to_string(value).map_err(|e| AppError::Other(e.into()))?;
+
+  atomic_write(path, contents).map_err(io_error(path))
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> AppError + '_ {
+  move |source| AppError::WorkspaceIoError {
+    path: path.into(),
+    source,
+  }
+}
+
+impl RunWorkspace {
+  /// Create a new run directory under `root`.
+  pub fn create(
+    root: &Path,
+    config: &Config,
+    config_path: Option<&Path>,
+  ) -> Result<Self> {
+    let mut workspace = Self::unrecorded(root, config, config_path);
+
+    workspace.metadata.name = Some(Registry::new(root).unique_name()?);
+
+    for artifact in Artifact::ALL {
+      let artifact_dir = workspace.dir.join(artifact.dir_name());
+
+      create_dir_all(&artifact_dir).map_err(io_error(&artifact_dir))?;
+    }
+
+    workspace.recorded = true;
+    workspace.write_metadata()?;
+
+    Ok(workspace)
+  }
+
+  /// Describe a new run under `root` without recording anything, e.g. once
+  /// [`create`](Self::create) failed, so the research still goes on.
+  ///
+  /// Its directory is never created, and storing its result or finishing it
+  /// does nothing.
+  pub fn unrecorded(
+    root: &Path,
+    config: &Config,
+    config_path: Option<&Path>,
+  ) -> Self {
+    let id = Ulid::new().to_string();
+
+    Self {
+      root: root.into(),
+      dir: root.join(&id),
+      recorded: false,
+      metadata: RunMetadata {
+        id,
+        name: None,
+        emoji: Some(EMOJIS[fastrand::usize(..EMOJIS.len())].into()),
+        created_at: now(),
+        finished_at: None,
+        status: RunStatus::Running,
+        tries: config.tries,
+        times: config.times,
+        regex: config.regex.to_string(),
+        label: Some(config.label.clone()).filter(|label| !label.is_empty()),
+        host: hostname(),
+        config_path: config_path.map(Into::into),
+        seed: Some(config.seed).filter(|seed| *seed != 0),
+        symbols: Some(config.symbols.clone())
+          .filter(|symbols| *symbols != SymbolSet::default()),
+        throttling: None,
+        broken_pipe: None,
+        deadline: None,
+      },
+    }
+  }
+
+  /// Record the thermal throttling observed during the run.
+  #[cfg(feature = "thermal")]
+  #[inline]
+  pub fn set_throttling(&mut self, throttling: Option<ThrottlingSummary>) {
+    self.metadata.throttling = throttling;
+  }
+
+  /// Record that the progress output was closed during the run.
+  #[inline]
+  pub fn set_broken_pipe(&mut self, broken_pipe: Option<BrokenPipeEvent>) {
+    self.metadata.broken_pipe = broken_pipe;
+  }
+
+  /// Record how the run went compared to its expected duration.
+  #[inline]
+  pub fn set_deadline(&mut self, deadline: Option<DeadlineSummary>) {
+    self.metadata.deadline = deadline;
+  }
+
+  #[inline]
+  pub fn id(&self) -> &str {
+    &self.metadata.id
+  }
+
+  #[inline]
+  pub fn metadata(&self) -> &RunMetadata {
+    &self.metadata
+  }
+
+  #[inline]
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// Return whether the run is recorded, see [`unrecorded`](Self::unrecorded).
+  #[inline]
+  pub fn is_recorded(&self) -> bool {
+    self.recorded
+  }
+
+  /// Return the directory for the specified artifact.
+  #[inline]
+  pub fn artifact_dir(&self, artifact: Artifact) -> PathBuf {
+    self.dir.join(artifact.dir_name())
+  }
+
+  /// Write a report file into the `reports` directory.
+  pub fn write_report(&self, name: &str, contents: &str) -> Result<PathBuf> {
+    let path = self.artifact_dir(Artifact::Reports).join(name);
+
+    atomic_write(&path, contents).map_err(io_error(&path))?;
+
+    Ok(path)
+  }
+
+  /// Store the result of the run.
+  ///
+  /// Hit chars are interned into the dictionary shared by all runs of the
+  /// registry, so only ids are stored per run.
+  pub fn store_result(&self, result: &WakuchinResult) -> Result<()> {
+    if !self.recorded {
+      return Ok(());
+    }
+
+    let hits =
+      HitDictionary::update(&self.root, |dict| dict.intern_result(result))?;
+
+    write_json(&self.dir.join(HITS_FILE), &hits)?;
+
+    let summary = serde_json::to_string(&ResultSummary::from(result))
+      .map_err(|e| AppError::Other(e.into()))?;
+
+    self.write_report(REPORT_FILE, &summary)?;
+
+    Ok(())
+  }
+
+  fn write_metadata(&self) -> Result<()> {
+    if !self.recorded {
+      return Ok(());
+    }
+
+    let path = self.dir.join(METADATA_FILE);
+    let contents = serde_json::to_string_pretty(&self.metadata)
+      .map_err(|e| AppError::Other(e.into()))?;
+
+    atomic_write(&path, contents).map_err(io_error(&path))
+  }
+
+  /// Record the final status of the run and apply the retention policy,
+  /// returning the final metadata.
+  ///
+  /// Unsuccessful runs are always kept so that they can be inspected.
+  pub fn finish(
+    mut self,
+    status: RunStatus,
+    retention: RetentionPolicy,
+  ) -> Result<RunMetadata> {
+    self.metadata.status = status;
+    self.metadata.finished_at = Some(now());
+
+    self.write_metadata()?;
+
+    if status != RunStatus::Finished || !self.recorded {
+      return Ok(self.metadata);
+    }
+
+    match retention {
+      RetentionPolicy::Keep => {}
+      RetentionPolicy::Clean => {
+        remove_dir_all(&self.dir).map_err(io_error(&self.dir))?;
+      }
+      RetentionPolicy::Archive => {
+        let archive = self.root.join(ARCHIVE_DIR);
+
+        create_dir_all(&archive).map_err(io_error(&archive))?;
+        rename(&self.dir, archive.join(self.id()))
+          .map_err(io_error(&self.dir))?;
+      }
+    }
+
+    Ok(self.metadata)
+  }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+  use std::env::temp_dir;
+  use std::fs::{read_to_string, remove_dir_all};
+  use std::path::PathBuf;
+
+  use anyhow::Result;
+  use ulid::Ulid;
+
+  use crate::config::Config;
+
+  use super::{
+    Artifact, RetentionPolicy, RunMetadata, RunStatus, RunWorkspace,
+    ARCHIVE_DIR, METADATA_FILE,
+  };
+
+  fn temp_root() -> PathBuf {
+    temp_dir().join(format!("wakuchin-test-{}", Ulid::new()))
+  }
+
+  #[test]
+  fn test_run_workspace() -> Result<()> {
+    let root = temp_root();
+    let config = Config::default();
+
+    let workspace = RunWorkspace::create(&root, &config, None)?;
+    let id = workspace.id().to_string();
+    let dir = root.join(&id);
+
+    assert!(workspace.artifact_dir(Artifact::Checkpoints).is_dir());
+    assert!(workspace.artifact_dir(Artifact::Journal).is_dir());
+
+    let report = workspace.write_report("result.txt", "ok")?;
+
+    assert_eq!(read_to_string(report)?, "ok");
+
+    workspace.finish(RunStatus::Cancelled, RetentionPolicy::Clean)?;
+
+    let metadata: RunMetadata =
+      serde_json::from_str(&read_to_string(dir.join(METADATA_FILE))?)?;
+
+    assert_eq!(metadata.id, id);
+    assert_eq!(metadata.status, RunStatus::Cancelled);
+    assert!(metadata
+      .name
+      .is_some_and(|name| name.split('-').count() == 3));
+
+    let workspace = RunWorkspace::create(&root, &config, None)?;
+    let id = workspace.id().to_string();
+
+    workspace.finish(RunStatus::Finished, RetentionPolicy::Archive)?;
+
+    assert!(!root.join(&id).exists());
+    assert!(root
+      .join(ARCHIVE_DIR)
+      .join(&id)
+      .join(METADATA_FILE)
+      .is_file());
+
+    let workspace = RunWorkspace::create(&root, &config, None)?;
+    let dir = root.join(workspace.id());
+
+    workspace.finish(RunStatus::Finished, RetentionPolicy::Clean)?;
+
+    assert!(!dir.exists());
+
+    remove_dir_all(root)?;
+
+    Ok(())
+  }
+}
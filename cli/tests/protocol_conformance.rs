@@ -0,0 +1,100 @@
+use std::env::temp_dir;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::Result;
+use ulid::Ulid;
+
+/// Run a research with the msgpack handler configured by `handler`, piping
+/// its progress to the reference consumer run with `consumer_args`, and
+/// return whether it conforms.
+fn conforms(
+  dir: &Path,
+  protocol_version: u8,
+  handler: &str,
+  consumer_args: &[&str],
+) -> Result<bool> {
+  let config = dir.join(format!("{}.toml", Ulid::new()));
+
+  fs::write(
+    &config,
+    format!(
+      r#"
+        tries = "200k"
+        times = 2
+        regex = "^(WK|CN)+$"
+        interval = "10ms"
+        workspace = "{}"
+        protocol_version = {protocol_version}
+
+        [handler]
+        kind = "msgpack"
+        {handler}
+      "#,
+      dir.join("workspace").display()
+    ),
+  )?;
+
+  let mut research = Command::new(env!("CARGO_BIN_EXE_wakuchin"))
+    .arg("--no-auto-config")
+    .arg(&config)
+    .stdout(Stdio::piped())
+    .spawn()?;
+  let progress = research.stdout.take().expect("stdout is piped");
+  let consumer = Command::new(env!("CARGO_BIN_EXE_protocol-conformance"))
+    .args(consumer_args)
+    .stdin(progress)
+    .status()?;
+
+  assert!(research.wait()?.success());
+
+  Ok(consumer.success())
+}
+
+#[test]
+fn test_protocol_conformance() -> Result<()> {
+  let dir = temp_dir().join(format!("wakuchin-test-{}", Ulid::new()));
+
+  fs::create_dir_all(&dir)?;
+
+  assert!(conforms(&dir, 1, "", &["--protocol-version", "1"])?);
+  assert!(conforms(&dir, 2, "", &["--protocol-version", "2"])?);
+  assert!(conforms(&dir, 3, "", &[])?);
+  assert!(conforms(
+    &dir,
+    3,
+    r#"frame = "length-prefixed""#,
+    &["--frame", "length-prefixed"]
+  )?);
+  assert!(conforms(
+    &dir,
+    3,
+    "base64 = true\nframe = \"newline\"",
+    &["--frame", "newline", "--base64"]
+  )?);
+  assert!(conforms(
+    &dir,
+    1,
+    "base64 = true\nframe = \"length-prefixed\"",
+    &[
+      "--frame",
+      "length-prefixed",
+      "--base64",
+      "--protocol-version",
+      "1"
+    ]
+  )?);
+
+  // the consumer notices when it is not read as written
+  assert!(!conforms(
+    &dir,
+    3,
+    r#"frame = "length-prefixed""#,
+    &["--frame", "length-prefixed", "--protocol-version", "1"]
+  )?);
+
+  fs::remove_dir_all(dir)?;
+
+  Ok(())
+}
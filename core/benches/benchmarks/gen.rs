@@ -0,0 +1,56 @@
+use criterion::{criterion_group, Criterion};
+
+use wakuchin::{
+  gen, gen_batch, gen_bytes_into, gen_into, gen_vec, gen_vec_par,
+};
+
+const BATCH: usize = 1024;
+
+fn speed_gen(c: &mut Criterion) {
+  c.bench_function("generation speed one by one", |b| {
+    b.iter(|| {
+      for _ in 0..BATCH {
+        gen(2);
+      }
+    });
+  });
+
+  c.bench_function("generation speed into a buffer", |b| {
+    let mut buf = String::new();
+
+    b.iter(|| {
+      for _ in 0..BATCH {
+        gen_into(2, &mut buf);
+      }
+    });
+  });
+
+  c.bench_function("generation speed into bytes", |b| {
+    let mut buf = Vec::new();
+
+    b.iter(|| {
+      for _ in 0..BATCH {
+        gen_bytes_into(2, &mut buf);
+      }
+    });
+  });
+
+  c.bench_function("generation speed in batches", |b| {
+    let mut batch = Vec::with_capacity(BATCH);
+
+    b.iter(|| {
+      batch.clear();
+      gen_batch(BATCH, 2, &mut batch);
+    });
+  });
+
+  c.bench_function("generation speed of a vector", |b| {
+    b.iter(|| gen_vec(BATCH * 64, 2));
+  });
+
+  c.bench_function("generation speed of a vector in parallel", |b| {
+    b.iter(|| gen_vec_par(BATCH * 64, 2, 0).unwrap());
+  });
+}
+
+criterion_group!(gens, speed_gen);
@@ -6,6 +6,7 @@ use criterion::{criterion_group, Criterion};
 use regex::Regex;
 
 use wakuchin::handlers::empty::EmptyProgressHandler;
+use wakuchin::pattern::Matchers;
 use wakuchin::worker::run_par;
 use wakuchin::worker::run_seq;
 
@@ -58,4 +59,33 @@ fn speed_seq(c: &mut Criterion) {
   });
 }
 
-criterion_group!(runs, speed_par, speed_seq);
+fn speed_literals(c: &mut Criterion) {
+  let regex = Regex::new(r"(WKNCWKNC|WCKNWCKN)").unwrap();
+  let literals = Matchers::Literals(vec!["WKNCWKNC".into(), "WCKNWCKN".into()]);
+
+  c.bench_function("sequential processing speed with regex alternation", |b| {
+    b.iter(|| {
+      run_seq(
+        20000,
+        2,
+        &regex,
+        Box::new(EmptyProgressHandler::new()),
+        Duration::from_millis(20),
+      )
+    });
+  });
+
+  c.bench_function("sequential processing speed with literals", |b| {
+    b.iter(|| {
+      run_seq(
+        20000,
+        2,
+        &literals,
+        Box::new(EmptyProgressHandler::new()),
+        Duration::from_millis(20),
+      )
+    });
+  });
+}
+
+criterion_group!(runs, speed_par, speed_seq, speed_literals);
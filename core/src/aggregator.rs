@@ -0,0 +1,531 @@
+//! Aggregation of the progress of workers for progress handlers, usable by
+//! custom workers outside researches
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+use itertools::Itertools;
+use tokio::sync::watch;
+
+use crate::channel;
+use crate::handlers::{ControlFlow, ProgressHandler, RunInfo};
+use crate::hit::HitCounter;
+use crate::progress::{Progress, ProgressIntervalPolicy, WorkerProgress};
+use crate::result::{Hit, NotableHit};
+use crate::sync::spin_loop;
+use crate::utils::DiffStore;
+
+/// Collects the progress of workers and drives a [`ProgressHandler`] at an
+/// interval, the same way researches do.
+///
+/// Each worker reports through its own [`ProgressReporter`], and hits are
+/// counted in a shared [`HitCounter`].
+///
+/// # Examples
+///
+/// ```rust
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// use regex::Regex;
+///
+/// use wakuchin::aggregator::ProgressAggregator;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::{check, gen};
+///
+/// let regex = Regex::new(r"^WK")?;
+/// let mut aggregator = ProgressAggregator::new(
+///   Box::new(EmptyProgressHandler::new()),
+///   Duration::from_millis(100),
+/// );
+///
+/// let handles = (0..2)
+///   .map(|_| {
+///     let mut reporter = aggregator.add_worker(500);
+///     let hit_counter = aggregator.hit_counter().clone();
+///     let regex = regex.clone();
+///
+///     thread::spawn(move || {
+///       for current in 0..500 {
+///         let wakuchin = gen(1);
+///
+///         if check(&wakuchin, &regex) {
+///           hit_counter.add(wakuchin.to_string());
+///         }
+///
+///         reporter.report(wakuchin.as_bytes(), current);
+///       }
+///
+///       reporter.done();
+///     })
+///   })
+///   .collect::<Vec<_>>();
+///
+/// aggregator.run()?;
+///
+/// for handle in handles {
+///   handle.join().unwrap();
+/// }
+///
+/// assert_eq!(aggregator.current(), 1000);
+/// assert!(aggregator.hit_counter().total() > 0);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ProgressAggregator {
+  progress_handler: Box<dyn ProgressHandler>,
+  interval_policy: ProgressIntervalPolicy,
+  hit_counter: Arc<HitCounter>,
+  workers: Vec<watch::Receiver<WorkerProgress>>,
+  /// Tries of all workers, as they were added
+  tries: usize,
+  run_info: Option<RunInfo>,
+  /// Tries of each worker when they last changed, and when
+  activity: Vec<(usize, Instant)>,
+  stall_timeout: Option<Duration>,
+  /// Time of the start, which adaptive intervals grow with
+  started: Instant,
+  start_time: Instant,
+  /// Whether a hit is waiting to be rendered right away
+  flush: bool,
+  current_diff: DiffStore<usize>,
+}
+
+impl ProgressAggregator {
+  /// Create an aggregator rendering to `progress_handler` every `interval`.
+  pub fn new(
+    progress_handler: Box<dyn ProgressHandler>,
+    interval: Duration,
+  ) -> Self {
+    Self {
+      progress_handler,
+      interval_policy: interval.into(),
+      hit_counter: Arc::default(),
+      workers: Vec::new(),
+      tries: 0,
+      run_info: None,
+      activity: Vec::new(),
+      stall_timeout: None,
+      started: Instant::now(),
+      start_time: Instant::now(),
+      flush: false,
+      current_diff: DiffStore::new(0),
+    }
+  }
+
+  /// Render the hits of `hit_counter`, instead of a new counter.
+  pub fn with_hit_counter(mut self, hit_counter: Arc<HitCounter>) -> Self {
+    self.hit_counter = hit_counter;
+    self
+  }
+
+  /// Render at the intervals of `policy`, instead of a fixed interval.
+  pub fn with_interval_policy(
+    mut self,
+    policy: ProgressIntervalPolicy,
+  ) -> Self {
+    self.interval_policy = policy;
+    self
+  }
+
+  /// Pass `info` to the handler on start, instead of the tries and the
+  /// number of the workers added.
+  pub fn with_run_info(mut self, info: RunInfo) -> Self {
+    self.run_info = Some(info);
+    self
+  }
+
+  /// Render the workers which made no progress for `timeout` as
+  /// [`Stalled`](crate::progress::ProgressKind::Stalled).
+  pub fn with_stall_timeout(mut self, timeout: Duration) -> Self {
+    self.stall_timeout = Some(timeout);
+    self
+  }
+
+  /// Add a worker processing `total` tries, returning its reporter.
+  ///
+  /// Workers are numbered from 1 in the order they are added.
+  pub fn add_worker(&mut self, total: usize) -> ProgressReporter {
+    let id = self.workers.len() + 1;
+    let (tx, rx) = channel::watch(WorkerProgress::Idle { id });
+
+    self.workers.push(rx);
+    self.tries = self.tries.saturating_add(total);
+    self.activity.push((0, Instant::now()));
+
+    ProgressReporter { id, total, tx }
+  }
+
+  /// Counter of the hits rendered with the progress.
+  #[inline]
+  pub fn hit_counter(&self) -> &Arc<HitCounter> {
+    &self.hit_counter
+  }
+
+  /// Return the number of tries processed by all workers.
+  pub fn current(&self) -> usize {
+    self.workers.iter().map(|rx| rx.borrow().current()).sum()
+  }
+
+  /// Return whether every worker is done, or dropped its reporter.
+  pub fn is_finished(&self) -> bool {
+    self
+      .workers
+      .iter()
+      .all(|rx| rx.has_changed().is_err() || rx.borrow().is_done())
+  }
+
+  /// Notify the handler of the start, once all workers are added.
+  pub fn start(&mut self) -> Result<()> {
+    self.started = Instant::now();
+    self.start_time = Instant::now();
+    self.reset_activity();
+
+    let info = self.run_info.clone().unwrap_or_else(|| RunInfo {
+      tries: self.tries,
+      ..RunInfo::new(self.workers.len())
+    });
+
+    self.progress_handler.before_start(&info)
+  }
+
+  /// Return the current interval between renderings.
+  #[inline]
+  pub fn interval(&self) -> Duration {
+    self.interval_policy.interval(self.started.elapsed())
+  }
+
+  /// Notify that a hit was counted, so it is rendered by the next
+  /// [`poll`](ProgressAggregator::poll) if the interval policy flushes on
+  /// hits.
+  #[inline]
+  pub fn hit(&mut self) {
+    self.flush |= self.interval_policy.flushes_on_hit();
+  }
+
+  /// Render the progress if the interval elapsed since the last rendering,
+  /// or a hit is to be flushed, returning whether the handler lets the
+  /// workers go on.
+  pub fn poll(&mut self) -> Result<ControlFlow> {
+    let interval = self.interval();
+
+    if !self.flush && self.start_time.elapsed() < interval {
+      return Ok(ControlFlow::Continue);
+    }
+
+    let progresses = self.watch_progresses();
+    let current = progresses.iter().map(|progress| progress.current()).sum();
+
+    let flow = self.progress_handler.handle(
+      &progresses,
+      &self.hit_counter.get_all().into_hit_counts(),
+      interval,
+      self.current_diff.update(current),
+      false,
+    )?;
+
+    self.start_time = Instant::now();
+    self.flush = false;
+
+    Ok(flow)
+  }
+
+  /// Return the progress of each worker, marking the workers without
+  /// progress for the stall timeout as stalled.
+  fn watch_progresses(&mut self) -> Vec<Progress> {
+    let stall_timeout = self.stall_timeout;
+
+    self
+      .workers
+      .iter()
+      .zip(&mut self.activity)
+      .map(|(rx, (last, since))| {
+        let progress = rx.borrow();
+        let current = progress.current();
+
+        if current != *last {
+          *last = current;
+          *since = Instant::now();
+        }
+
+        match stall_timeout {
+          Some(timeout)
+            if !progress.is_done() && since.elapsed() >= timeout =>
+          {
+            progress.to_stalled(since.elapsed())
+          }
+          _ => progress.to_progress(),
+        }
+      })
+      .collect_vec()
+  }
+
+  fn reset_activity(&mut self) {
+    for (_, since) in &mut self.activity {
+      *since = Instant::now();
+    }
+  }
+
+  /// Notify the handler of a pause, calling `wait` to block until resumed.
+  pub fn pause(&mut self, wait: impl FnOnce()) -> Result<()> {
+    self.progress_handler.on_pause()?;
+    wait();
+    self.progress_handler.on_resume()?;
+
+    self.start_time = Instant::now();
+    // paused workers are not stuck
+    self.reset_activity();
+
+    Ok(())
+  }
+
+  /// Render the final progress and notify the handler of the end, it is too
+  /// late to stop then.
+  pub fn finish(&mut self) -> Result<()> {
+    let progresses = self
+      .workers
+      .iter()
+      .map(|rx| rx.borrow().to_progress())
+      .collect_vec();
+
+    self.progress_handler.handle(
+      &progresses,
+      &self.hit_counter.get_all().into_hit_counts(),
+      self.interval(),
+      0,
+      true,
+    )?;
+
+    self.progress_handler.after_finish()
+  }
+
+  /// Pass a hit to the handler right away, see
+  /// [`ProgressHandler::on_hit`].
+  #[inline]
+  pub fn handle_hit(&mut self, hit: &Hit) -> Result<()> {
+    self.progress_handler.on_hit(hit)
+  }
+
+  /// Pass a notable hit to the handler right away.
+  #[inline]
+  pub fn notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+    self.progress_handler.on_notable_hit(hit)
+  }
+
+  /// Notify the handler that the workers stopped before finishing.
+  #[inline]
+  pub fn stop(&mut self) -> Result<()> {
+    self.progress_handler.on_accidential_stop()
+  }
+
+  /// Render until all workers are finished, see [`is_finished`].
+  ///
+  /// If the handler asks to stop, it is notified with
+  /// [`on_accidential_stop`](ProgressHandler::on_accidential_stop) and
+  /// [`ControlFlow::Stop`] is returned, so the workers can be stopped.
+  ///
+  /// [`is_finished`]: ProgressAggregator::is_finished
+  pub fn run(&mut self) -> Result<ControlFlow> {
+    self.start()?;
+
+    while !self.is_finished() {
+      if self.poll()? == ControlFlow::Stop {
+        self.stop()?;
+
+        return Ok(ControlFlow::Stop);
+      }
+
+      spin_loop();
+    }
+
+    self.finish()?;
+
+    Ok(ControlFlow::Continue)
+  }
+}
+
+/// Reports the progress of a worker to its [`ProgressAggregator`].
+pub struct ProgressReporter {
+  id: usize,
+  total: usize,
+  tx: watch::Sender<WorkerProgress>,
+}
+
+impl ProgressReporter {
+  /// Worker id, 1-indexed.
+  #[inline]
+  pub fn id(&self) -> usize {
+    self.id
+  }
+
+  /// Report that the worker is processing the UTF-8 bytes of `chars`, the
+  /// `current`th of its tries.
+  pub fn report(&mut self, chars: &[u8], current: usize) {
+    if self.tx.is_closed() {
+      return;
+    }
+
+    // update in place to reuse the allocation of the previous chars
+    self.tx.send_modify(|progress| match progress {
+      WorkerProgress::Processing {
+        chars: previous_chars,
+        current: previous,
+        total,
+        ..
+      } => {
+        previous_chars.clear();
+        previous_chars.extend_from_slice(chars);
+        *previous = current;
+        *total = self.total;
+      }
+      _ => {
+        *progress = WorkerProgress::Processing {
+          id: self.id,
+          chars: chars.to_vec(),
+          current,
+          total: self.total,
+        };
+      }
+    });
+  }
+
+  /// Change the number of tries of the worker, for workers which take more
+  /// tries as they go, reported from the next progress on.
+  #[inline]
+  pub fn set_total(&mut self, total: usize) {
+    self.total = total;
+  }
+
+  /// Report that the worker processed all of its tries.
+  pub fn done(&mut self) {
+    // not sent if the aggregator is gone
+    let _ = self.tx.send(WorkerProgress::Done {
+      id: self.id,
+      total: self.total,
+    });
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::{Arc, Mutex};
+  use std::thread;
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::{ControlFlow, ProgressHandler};
+  use crate::progress::{Progress, ProgressKind};
+  use crate::result::HitCount;
+
+  use super::ProgressAggregator;
+
+  /// Progresses, hit counts and whether all done of a call to `handle`.
+  type Handled = (Vec<Progress>, Vec<HitCount>, bool);
+
+  /// Keeps the arguments of the last call to `handle`.
+  #[derive(Clone, Default)]
+  struct LastHandler(Arc<Mutex<Option<Handled>>>);
+
+  impl ProgressHandler for LastHandler {
+    fn handle(
+      &mut self,
+      progresses: &[Progress],
+      hit_counts: &[HitCount],
+      _: Duration,
+      _: usize,
+      all_done: bool,
+    ) -> Result<ControlFlow> {
+      *self.0.lock().unwrap() =
+        Some((progresses.to_vec(), hit_counts.to_vec(), all_done));
+
+      Ok(ControlFlow::Continue)
+    }
+  }
+
+  #[test]
+  fn test_run() -> Result<()> {
+    let handler = LastHandler::default();
+    let mut aggregator =
+      ProgressAggregator::new(Box::new(handler.clone()), Duration::ZERO);
+
+    let mut finished = aggregator.add_worker(3);
+    let dropped = aggregator.add_worker(3);
+    let hit_counter = aggregator.hit_counter().clone();
+
+    assert!(!aggregator.is_finished());
+
+    let handle = thread::spawn(move || {
+      for current in 0..3 {
+        finished.report(b"WKCN", current);
+      }
+
+      hit_counter.add("WKCN");
+      finished.done();
+
+      // a worker which gave up still lets the aggregator finish
+      drop(dropped);
+    });
+
+    aggregator.run()?;
+    handle.join().unwrap();
+
+    let (progresses, hit_counts, all_done) =
+      handler.0.lock().unwrap().take().unwrap();
+
+    assert!(all_done);
+    assert!(matches!(
+      &progresses[..],
+      [
+        Progress(ProgressKind::Done(done)),
+        Progress(ProgressKind::Idle(idle)),
+      ] if done.id == 1 && done.total == 3 && idle.id == 2
+    ));
+    assert_eq!(hit_counts, [HitCount::new("WKCN", 1)]);
+    assert_eq!(aggregator.current(), 3);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_stall() -> Result<()> {
+    let handler = LastHandler::default();
+    let mut aggregator =
+      ProgressAggregator::new(Box::new(handler.clone()), Duration::ZERO)
+        .with_stall_timeout(Duration::from_millis(50));
+    let mut reporter = aggregator.add_worker(10);
+    let last_progress = || handler.0.lock().unwrap().take().unwrap().0;
+
+    aggregator.start()?;
+    reporter.report(b"WKCN", 3);
+    aggregator.poll()?;
+
+    assert!(matches!(
+      &last_progress()[..],
+      [Progress(ProgressKind::Processing(processing))] if processing.current == 3
+    ));
+
+    thread::sleep(Duration::from_millis(80));
+    aggregator.poll()?;
+
+    assert!(matches!(
+      &last_progress()[..],
+      [Progress(ProgressKind::Stalled(stalled))]
+        if stalled.current == 3
+          && stalled.stalled_for >= Duration::from_millis(50)
+    ));
+
+    // back to normal once it goes on
+    reporter.report(b"WKCN", 4);
+    aggregator.poll()?;
+
+    assert!(matches!(
+      &last_progress()[..],
+      [Progress(ProgressKind::Processing(_))]
+    ));
+
+    Ok(())
+  }
+}
@@ -1,12 +1,34 @@
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 use regex::Regex;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::sync::oneshot;
 
+use crate::cancel::CancellationToken;
+use crate::checkpoint::Checkpoint;
 use crate::error::WakuchinError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::future::{HitStream, ResearchFuture};
+use crate::generator::WakuchinGenerator;
 use crate::handlers::empty::EmptyProgressHandler;
-use crate::handlers::ProgressHandler;
-use crate::result::WakuchinResult;
-use crate::worker::{run_par, run_seq};
+use crate::handlers::snapshot::SnapshotReader;
+use crate::handlers::{HandlerErrorPolicy, ProgressHandler};
+use crate::pattern::{MatchMode, Matcher, Matchers, Patterns, RegexEngine};
+use crate::pause::PauseHandle;
+use crate::progress::ProgressIntervalPolicy;
+use crate::prune::PruneRule;
+use crate::result::{Hit, WakuchinResult};
+use crate::rng::RngBackend;
+use crate::session::ResearchSession;
+use crate::symbol::SymbolSet;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::threads;
+use crate::threads::ThreadConfig;
+use crate::worker::{
+  get_total_workers, run_par_with_options, run_seq_with_options, RunOptions,
+};
 
 type Result<T> = std::result::Result<T, WakuchinError>;
 
@@ -15,8 +37,9 @@ pub struct ResearchBuilder<Tries, Times, TRegex> {
   times: Times,
   regex: TRegex,
   progress_handler: Box<dyn ProgressHandler>,
-  progress_interval: Duration,
+  progress_interval: ProgressIntervalPolicy,
   workers: usize,
+  options: RunOptions,
 }
 
 impl ResearchBuilder<(), (), ()> {
@@ -27,8 +50,11 @@ impl ResearchBuilder<(), (), ()> {
       times: (),
       regex: (),
       progress_handler: Box::new(EmptyProgressHandler::new()),
-      progress_interval: Duration::from_millis(500),
+      progress_interval: ProgressIntervalPolicy::Fixed(Duration::from_millis(
+        500,
+      )),
       workers: 0,
+      options: RunOptions::default(),
     }
   }
 }
@@ -48,6 +74,7 @@ impl<Tries, Times, TRegex> ResearchBuilder<Tries, Times, TRegex> {
       progress_handler: self.progress_handler,
       progress_interval: self.progress_interval,
       workers: self.workers,
+      options: self.options,
     }
   }
 
@@ -59,6 +86,7 @@ impl<Tries, Times, TRegex> ResearchBuilder<Tries, Times, TRegex> {
       progress_handler: self.progress_handler,
       progress_interval: self.progress_interval,
       workers: self.workers,
+      options: self.options,
     }
   }
 
@@ -70,6 +98,59 @@ impl<Tries, Times, TRegex> ResearchBuilder<Tries, Times, TRegex> {
       progress_handler: self.progress_handler,
       progress_interval: self.progress_interval,
       workers: self.workers,
+      options: self.options,
+    }
+  }
+
+  /// Detect hits of each of `regexes`, counting their hits separately.
+  pub fn regexes(
+    self,
+    regexes: impl IntoIterator<Item = Regex>,
+  ) -> ResearchBuilder<Tries, Times, Vec<Regex>> {
+    ResearchBuilder {
+      tries: self.tries,
+      times: self.times,
+      regex: regexes.into_iter().collect(),
+      progress_handler: self.progress_handler,
+      progress_interval: self.progress_interval,
+      workers: self.workers,
+      options: self.options,
+    }
+  }
+
+  /// Detect hits with `matchers`, e.g. literals of
+  /// [`Matchers::Literals`] which are faster to match than regexes.
+  pub fn matchers(
+    self,
+    matchers: Matchers,
+  ) -> ResearchBuilder<Tries, Times, Matchers> {
+    ResearchBuilder {
+      tries: self.tries,
+      times: self.times,
+      regex: matchers,
+      progress_handler: self.progress_handler,
+      progress_interval: self.progress_interval,
+      workers: self.workers,
+      options: self.options,
+    }
+  }
+
+  /// Detect hits with a [`Matcher`] of its own, e.g. a closure.
+  ///
+  /// Regexes passed to [`regex`](Self::regex) are faster, as they match the
+  /// tries as generated instead of as `&str`.
+  pub fn matcher(
+    self,
+    matcher: impl Matcher + 'static,
+  ) -> ResearchBuilder<Tries, Times, Matchers> {
+    ResearchBuilder {
+      tries: self.tries,
+      times: self.times,
+      regex: Matchers::custom(matcher),
+      progress_handler: self.progress_handler,
+      progress_interval: self.progress_interval,
+      workers: self.workers,
+      options: self.options,
     }
   }
 
@@ -82,8 +163,149 @@ impl<Tries, Times, TRegex> ResearchBuilder<Tries, Times, TRegex> {
     self
   }
 
+  /// Record the latest progress of the research to `reader`, so any thread
+  /// holding a clone of it can poll the tries, the rate and the hits instead
+  /// of implementing a [`ProgressHandler`].
+  ///
+  /// Snapshots are taken as the progress is rendered, every
+  /// [`progress_interval`](Self::progress_interval), and the progress
+  /// handler of the research is still called.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  /// use wakuchin::builder::ResearchBuilder;
+  /// use wakuchin::handlers::snapshot::SnapshotReader;
+  ///
+  /// let reader = SnapshotReader::new();
+  /// let research = ResearchBuilder::new()
+  ///   .tries(10_000)
+  ///   .times(1)
+  ///   .regex(Regex::new("^WK")?)
+  ///   .progress_snapshots(reader.clone());
+  ///
+  /// // e.g. polled by the UI thread while the research runs
+  /// assert!(reader.latest().is_none());
+  ///
+  /// let result = research.run_par()?;
+  /// let snapshot = reader.latest().unwrap();
+  ///
+  /// assert!(snapshot.all_done);
+  /// assert_eq!(snapshot.current, 10_000);
+  /// assert_eq!(snapshot.hits_total, result.hits_total);
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn progress_snapshots(mut self, reader: SnapshotReader) -> Self {
+    self.options.snapshots = Some(reader);
+
+    self
+  }
+
+  /// Decide what the research does once its progress handler fails, so a
+  /// broken pipe to the consumer of the progress does not end a long
+  /// research. The research fails with the error by default, see
+  /// [`HandlerErrorPolicy::Abort`].
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use std::time::Duration;
+  ///
+  /// use anyhow::bail;
+  /// use regex::Regex;
+  /// use wakuchin::builder::ResearchBuilder;
+  /// use wakuchin::handlers::{ControlFlow, HandlerErrorPolicy, ProgressHandler};
+  /// use wakuchin::progress::Progress;
+  /// use wakuchin::result::HitCount;
+  ///
+  /// struct Broken;
+  ///
+  /// impl ProgressHandler for Broken {
+  ///   fn handle(
+  ///     &mut self,
+  ///     _: &[Progress],
+  ///     _: &[HitCount],
+  ///     _: Duration,
+  ///     _: usize,
+  ///     _: bool,
+  ///   ) -> anyhow::Result<ControlFlow> {
+  ///     bail!("broken pipe")
+  ///   }
+  /// }
+  ///
+  /// let research = || {
+  ///   ResearchBuilder::new()
+  ///     .tries(1000)
+  ///     .times(1)
+  ///     .regex(Regex::new("^WK").unwrap())
+  ///     .progress_handler(Box::new(Broken))
+  /// };
+  ///
+  /// assert!(research().run_seq().is_err());
+  ///
+  /// let result = research()
+  ///   .handler_error_policy(HandlerErrorPolicy::DisableHandler)
+  ///   .run_seq()?;
+  ///
+  /// assert_eq!(result.tries, 1000);
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn handler_error_policy(mut self, policy: HandlerErrorPolicy) -> Self {
+    self.options.handler_error_policy = policy;
+
+    self
+  }
+
+  /// Call `callback` with the errors of the progress handler the research
+  /// goes on despite, see [`handler_error_policy`](Self::handler_error_policy).
+  ///
+  /// wakuchin never prints on its own, so they are not reported otherwise.
+  pub fn on_warning<F>(mut self, callback: F) -> Self
+  where
+    F: Fn(&str) + Send + Sync + 'static,
+  {
+    self.options.on_warning = Some(Arc::new(callback));
+
+    self
+  }
+
   pub fn progress_interval(mut self, progress_interval: Duration) -> Self {
-    self.progress_interval = progress_interval;
+    self.progress_interval = progress_interval.into();
+
+    self
+  }
+
+  /// Render the progress at the intervals of `policy`, for example more
+  /// often at the start and as soon as a hit is found, instead of every
+  /// [`progress_interval`](ResearchBuilder::progress_interval).
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  ///
+  /// use wakuchin::builder::ResearchBuilder;
+  /// use wakuchin::progress::ProgressIntervalPolicy;
+  ///
+  /// let result = ResearchBuilder::new()
+  ///   .tries(1000)
+  ///   .times(1)
+  ///   .regex(Regex::new(r"^WK")?)
+  ///   .progress_interval_policy(ProgressIntervalPolicy::adaptive())
+  ///   .run_seq()?;
+  ///
+  /// assert_eq!(result.tries, 1000);
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn progress_interval_policy(
+    mut self,
+    policy: ProgressIntervalPolicy,
+  ) -> Self {
+    self.progress_interval = policy;
 
     self
   }
@@ -93,29 +315,1627 @@ impl<Tries, Times, TRegex> ResearchBuilder<Tries, Times, TRegex> {
 
     self
   }
+
+  /// Report the workers which made no progress for `timeout` to the progress
+  /// handler as [`Stalled`](crate::progress::ProgressKind::Stalled), so
+  /// deadlocked or descheduled workers of long researches are noticed. The
+  /// handler can stop the research then.
+  ///
+  /// Sequential researches are rendered by their only worker, so they never
+  /// report it.
+  pub fn stall_timeout(mut self, timeout: Duration) -> Self {
+    self.options.stall_timeout = Some(timeout);
+
+    self
+  }
+
+  /// Set the priority of the threads of the research and pin its workers to
+  /// cores, see [`threads`](crate::threads).
+  pub fn thread_config(mut self, config: ThreadConfig) -> Self {
+    self.options.threads = config;
+
+    self
+  }
+
+  /// Seed the random generators to make the research reproducible.
+  ///
+  /// Tries are seeded in chunks, so the hits do not depend on the number of
+  /// workers nor on which worker took which chunk.
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.options.seed = Some(seed);
+
+    self
+  }
+
+  /// Generate strings of `symbols` instead of "わくちん".
+  pub fn symbols(mut self, symbols: SymbolSet) -> Self {
+    self.options.symbols = symbols;
+
+    self
+  }
+
+  /// Shuffle the symbols with a generator of `backend`, defaults to
+  /// [`RngBackend::Fastrand`].
+  ///
+  /// It has no effect on generators set with [`generator`](Self::generator).
+  pub fn rng(mut self, backend: RngBackend) -> Self {
+    self.options.rng = backend;
+
+    self
+  }
+
+  /// Use `handle` to pause and resume the research from another thread.
+  pub fn pause_handle(mut self, handle: PauseHandle) -> Self {
+    self.options.pause = handle;
+
+    self
+  }
+
+  /// Use `token` to cancel or stop the research from another thread.
+  pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+    self.options.cancel = token;
+
+    self
+  }
+
+  /// Write a [`Checkpoint`] of the research to `path` at most every `every`,
+  /// and once its workers stopped, e.g. after Ctrl-C, so it can be resumed
+  /// with [`run_par_resume`](Self::run_par_resume).
+  pub fn checkpoint(
+    mut self,
+    path: impl Into<PathBuf>,
+    every: Duration,
+  ) -> Self {
+    self.options.checkpoint = Some((path.into(), every));
+
+    self
+  }
+
+  /// Generate the tried strings with `generator` instead of shuffling the
+  /// symbols, each chunk of tries using its own clone.
+  pub fn generator<G>(mut self, generator: G) -> Self
+  where
+    G: WakuchinGenerator + Clone + Sync + 'static,
+  {
+    self.options.generator =
+      Some(Arc::new(move || Box::new(generator.clone())));
+
+    self
+  }
+
+  /// Give up waiting for the progress handler and the hit counter `grace`
+  /// after all workers finished, failing with
+  /// [`WakuchinError::ShutdownTimeout`](crate::error::WakuchinError::ShutdownTimeout)
+  /// instead of hanging.
+  ///
+  /// Defaults to [`DEFAULT_SHUTDOWN_GRACE`](crate::worker::DEFAULT_SHUTDOWN_GRACE).
+  pub fn shutdown_grace(mut self, grace: Duration) -> Self {
+    self.options.shutdown_grace = Some(grace);
+
+    self
+  }
+
+  /// Locate the match of each hit, setting [`Hit::span`] and
+  /// [`Hit::groups`].
+  ///
+  /// Every hit is matched once more to locate it, so it slows down
+  /// researches with many hits.
+  ///
+  /// [`Hit::span`]: crate::result::Hit::span
+  /// [`Hit::groups`]: crate::result::Hit::groups
+  pub fn capture_matches(mut self, capture: bool) -> Self {
+    self.options.capture_matches = capture;
+
+    self
+  }
+
+  /// Keep every hit in [`WakuchinResult::hits_detail`], or only count them,
+  /// so researches with many hits do not run out of memory.
+  ///
+  /// Defaults to `true`, see [`max_hit_details`](Self::max_hit_details).
+  pub fn collect_details(mut self, collect: bool) -> Self {
+    self.options.max_hit_details = (!collect).then_some(0);
+
+    self
+  }
+
+  /// Keep only the first `max` hits in [`WakuchinResult::hits_detail`],
+  /// counting the others in [`WakuchinResult::hits`] only.
+  pub fn max_hit_details(mut self, max: usize) -> Self {
+    self.options.max_hit_details = Some(max);
+
+    self
+  }
+
+  /// Match the regexes with `engine`, defaults to [`RegexEngine::Regex`].
+  pub fn regex_engine(mut self, engine: RegexEngine) -> Self {
+    self.options.regex_engine = engine;
+
+    self
+  }
+
+  /// Count every non-overlapping match of the patterns in a try, setting
+  /// [`Hit::matches`] and summing them into the hit counts and
+  /// `hits_total`, instead of counting tries which match.
+  ///
+  /// Like [`capture_matches`](Self::capture_matches), every hit is matched
+  /// once more to count its matches.
+  ///
+  /// [`Hit::matches`]: crate::result::Hit::matches
+  pub fn match_mode(mut self, mode: MatchMode) -> Self {
+    self.options.match_mode = mode;
+
+    self
+  }
+
+  /// Stop the research as soon as the pattern at `pattern` has `hits` hits,
+  /// even if other patterns have not reached theirs.
+  ///
+  /// Patterns without a target are counted until then but never stop the
+  /// research, so it can be called for the patterns which matter most. The
+  /// result has the tries done by then, its
+  /// [`stop_reason`](crate::result::WakuchinResult::stop_reason) and the
+  /// target of each pattern in
+  /// [`patterns`](crate::result::WakuchinResult::patterns).
+  ///
+  /// Workers notice the target a little after it is reached, so patterns
+  /// may end with a few more hits than their target.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use wakuchin::builder::ResearchBuilder;
+  /// use wakuchin::pattern::Matchers;
+  /// use wakuchin::result::StopReason;
+  ///
+  /// let result = ResearchBuilder::new()
+  ///   .tries(1_000_000)
+  ///   .times(1)
+  ///   .matchers(Matchers::Literals(vec!["WKCN".into(), "NCWK".into()]))
+  ///   .stop_at_hits(0, 10)
+  ///   .run_seq()?;
+  ///
+  /// assert!(result.tries < 1_000_000);
+  /// assert_eq!(result.patterns[0].hits_total, 10);
+  /// assert_eq!(
+  ///   result.stop_reason,
+  ///   Some(StopReason::PatternTarget { pattern: 0, hits: 10 })
+  /// );
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn stop_at_hits(mut self, pattern: usize, hits: usize) -> Self {
+    self.options.hit_targets.insert(pattern, hits);
+
+    self
+  }
+
+  /// Stop the research once `hits` hits of any patterns are found, with
+  /// [`StopReason::HitLimit`](crate::result::StopReason::HitLimit).
+  ///
+  /// Like [`stop_at_hits`](Self::stop_at_hits), the result may have a few
+  /// more hits than the limit.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  /// use wakuchin::builder::ResearchBuilder;
+  /// use wakuchin::result::StopReason;
+  ///
+  /// let result = ResearchBuilder::new()
+  ///   .tries(1_000_000)
+  ///   .times(1)
+  ///   .regex(Regex::new(r"^WK")?)
+  ///   .stop_after_hits(100)
+  ///   .run_seq()?;
+  ///
+  /// assert!(result.tries < 1_000_000);
+  /// assert_eq!(result.hits_total, 100);
+  /// assert_eq!(result.stop_reason, Some(StopReason::HitLimit { hits: 100 }));
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn stop_after_hits(mut self, hits: usize) -> Self {
+    self.options.hit_limit = Some(hits);
+
+    self
+  }
+
+  /// Stop the research once the interval of the probability of a try hitting
+  /// any pattern at `confidence`, e.g. 0.95, is narrower than `margin`, with
+  /// [`StopReason::Confident`](crate::result::StopReason::Confident).
+  ///
+  /// Workers report their tries every 4096 of them, so short researches may
+  /// never stop early, and `tries` only caps researches whose hits are too
+  /// rare for the margin. Both are between 0 and 1, or the research fails.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  /// use wakuchin::builder::ResearchBuilder;
+  /// use wakuchin::result::StopReason;
+  ///
+  /// let result = ResearchBuilder::new()
+  ///   .tries(10_000_000)
+  ///   .times(1)
+  ///   .regex(Regex::new(r"^WK")?)
+  ///   .stop_when_confident(0.95, 0.01)
+  ///   .run_seq()?;
+  ///
+  /// assert!(result.tries < 10_000_000);
+  /// assert_eq!(
+  ///   result.stop_reason,
+  ///   Some(StopReason::Confident { confidence: 0.95, margin: 0.01 })
+  /// );
+  ///
+  /// // 2 of the 24 strings start with WK
+  /// let probability = result.hits_total as f64 / result.tries as f64;
+  ///
+  /// assert!((probability - 1.0 / 12.0).abs() < 0.01);
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn stop_when_confident(mut self, confidence: f64, margin: f64) -> Self {
+    self.options.confidence_target = Some((confidence, margin));
+
+    self
+  }
+
+  /// Stop the research once it ran for `limit`, with
+  /// [`StopReason::TimeLimit`](crate::result::StopReason::TimeLimit).
+  ///
+  /// Paused researches are stopped as well. The limit is watched by the
+  /// signal thread, so it has no effect on `wasm32`.
+  pub fn time_limit(mut self, limit: Duration) -> Self {
+    self.options.time_limit = Some(limit);
+
+    self
+  }
+
+  /// Stop matching the patterns whose hit rate is surely above or below the
+  /// bound of `rule`, so the other patterns of the research are matched
+  /// faster.
+  ///
+  /// Retired patterns keep the hits found until then, and
+  /// [`PatternHits::retired_at`] tells about the tries they are a rate of. The
+  /// last pattern and patterns of [`stop_at_hits`](Self::stop_at_hits) are
+  /// never retired, nor are patterns of researches of a single one.
+  ///
+  /// [`PatternHits::retired_at`]: crate::result::PatternHits::retired_at
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  ///
+  /// use wakuchin::builder::ResearchBuilder;
+  /// use wakuchin::prune::PruneRule;
+  ///
+  /// let result = ResearchBuilder::new()
+  ///   .tries(200_000)
+  ///   .times(2)
+  ///   .regexes([Regex::new(r"^W")?, Regex::new(r"^WKCNWKCN$")?])
+  ///   .prune_patterns(PruneRule::new(0.01, 0.99).with_min_tries(50_000))
+  ///   .run_seq()?;
+  ///
+  /// // a quarter of the tries start with "W"
+  /// assert!(result.patterns[0].retired_at.is_some());
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn prune_patterns(mut self, rule: PruneRule) -> Self {
+    self.options.prune = Some(rule);
+
+    self
+  }
+
+  /// Flag hits rarer than 1 in `one_in` tries as notable, see
+  /// [`Rarity`](crate::math::Rarity).
+  ///
+  /// Defaults to [`DEFAULT_NOTABLE_RARITY`](crate::math::DEFAULT_NOTABLE_RARITY).
+  pub fn notable_rarity(mut self, one_in: u128) -> Self {
+    self.options.notable_rarity = Some(one_in);
+
+    self
+  }
+
+  /// Pass hits also matching `patterns` to
+  /// [`ProgressHandler::on_notable_hit`] as soon as they are found, like
+  /// hits rarer than [`notable_rarity`](Self::notable_rarity).
+  ///
+  /// Only the hits of the patterns of the research are checked, e.g.
+  /// `"^(WKCN){3}"` among the hits of `"^WKCN"`.
+  pub fn notable_patterns(mut self, patterns: impl Patterns) -> Self {
+    self.options.notable_patterns = Some(patterns.to_matchers().into_owned());
+
+    self
+  }
+
+  /// Call `callback` with every hit as soon as it is found, instead of
+  /// waiting for the result, e.g. to send hits through a channel.
+  ///
+  /// The callback is called on the worker which found the hit, so it should
+  /// return quickly. Callbacks are called in the order they were added.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  /// use wakuchin::builder::ResearchBuilder;
+  ///
+  /// let (tx, rx) = flume::unbounded();
+  ///
+  /// let result = ResearchBuilder::new()
+  ///   .tries(10_000)
+  ///   .times(1)
+  ///   .regex(Regex::new("^WK")?)
+  ///   .on_hit(move |hit| tx.send(hit.clone()).unwrap())
+  ///   .run_par()?;
+  ///
+  /// assert_eq!(rx.drain().count(), result.hits_total);
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn on_hit<F>(mut self, callback: F) -> Self
+  where
+    F: Fn(&Hit) + Send + Sync + 'static,
+  {
+    self.options.on_hit.push(Arc::new(callback));
+
+    self
+  }
+
+  /// Call `callback` every `tries` tries of sequential researches, so the
+  /// application gets the calling thread back for a moment, e.g. to pump the
+  /// event loop of a GUI or to check for user input.
+  ///
+  /// Parallel researches leave the calling thread to their workers and never
+  /// call it. On `wasm32`, where nothing else runs on the thread until the
+  /// research returns, run sequential researches from a Web Worker instead
+  /// if the page has to stay responsive. `tries` cannot be 0, or the
+  /// research fails.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use std::sync::atomic::{AtomicUsize, Ordering};
+  /// use std::sync::Arc;
+  ///
+  /// use regex::Regex;
+  /// use wakuchin::builder::ResearchBuilder;
+  ///
+  /// let yields = Arc::new(AtomicUsize::new(0));
+  /// let counter = yields.clone();
+  ///
+  /// ResearchBuilder::new()
+  ///   .tries(10_000)
+  ///   .times(1)
+  ///   .regex(Regex::new("^WK")?)
+  ///   .yield_every(1000, move || {
+  ///     counter.fetch_add(1, Ordering::Relaxed);
+  ///   })
+  ///   .run_seq()?;
+  ///
+  /// assert_eq!(yields.load(Ordering::Relaxed), 10);
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  pub fn yield_every<F>(mut self, tries: usize, callback: F) -> Self
+  where
+    F: Fn() + Send + Sync + 'static,
+  {
+    self.options.yield_every = Some((tries, Arc::new(callback)));
+
+    self
+  }
+}
+
+impl<Tries, Times, TRegex: Patterns> ResearchBuilder<Tries, Times, TRegex> {
+  /// Fail with [`WakuchinError::ImpossiblePattern`] if a pattern can never
+  /// match the generated strings, unless they come from a custom generator.
+  fn validate(&self, matchers: &Matchers) -> Result<()> {
+    if self.options.generator.is_some() {
+      return Ok(());
+    }
+
+    matchers.validate_alphabet(&self.options.symbols)
+  }
+}
+
+impl<Tries, TRegex: Patterns> ResearchBuilder<Tries, usize, TRegex> {
+  /// Compile the patterns and spawn the workers once for many researches,
+  /// whose tries are given to [`ResearchSession::run`], see
+  /// [`session`](crate::session).
+  pub fn session(self) -> Result<ResearchSession> {
+    let matchers = self.regex.to_matchers();
+
+    self.validate(&matchers)?;
+
+    ResearchSession::new(
+      self.times,
+      matchers.into_owned(),
+      self.progress_handler,
+      self.progress_interval,
+      get_total_workers(self.workers)?,
+      self.options,
+    )
+  }
 }
 
-impl ResearchBuilder<usize, usize, Regex> {
+impl<TRegex: Patterns> ResearchBuilder<usize, usize, TRegex> {
   #[inline]
   pub fn run_par(self) -> Result<WakuchinResult> {
-    run_par(
+    let matchers = self.regex.to_matchers();
+
+    self.validate(&matchers)?;
+
+    run_par_with_options(
       self.tries,
       self.times,
-      &self.regex,
+      &matchers,
       self.progress_handler,
       self.progress_interval,
       self.workers,
+      self.options,
+    )
+  }
+
+  /// Resume the research of `checkpoint` in parallel, with as many workers
+  /// as it was checkpointed with, and return the result of the whole
+  /// research.
+  ///
+  /// The options deciding the tries and hits have to be the ones of the
+  /// checkpointed research, or it fails with
+  /// [`WakuchinError::CheckpointMismatch`]. The hits of the tries before the
+  /// checkpoint are in the result, but the progress handler only sees the
+  /// tries left.
+  pub fn run_par_resume(
+    mut self,
+    checkpoint: Checkpoint,
+  ) -> Result<WakuchinResult> {
+    self.workers = checkpoint.workers.len();
+    self.options.resume = Some(checkpoint);
+
+    self.run_par()
+  }
+
+  /// Run the research in parallel on a thread of its own, returning a future
+  /// of its result and a stream of its hits as they are found, so async
+  /// applications are never blocked, see [`future`](crate::future).
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  /// use wakuchin::builder::ResearchBuilder;
+  ///
+  /// # #[tokio::main(flavor = "current_thread")]
+  /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+  /// let (result, mut hits) = ResearchBuilder::new()
+  ///   .tries(10_000)
+  ///   .times(1)
+  ///   .regex(Regex::new("^WK")?)
+  ///   .run_par_async();
+  /// let mut found = 0;
+  ///
+  /// while let Some(hit) = hits.next().await {
+  ///   assert!(hit.chars.starts_with("WK"));
+  ///
+  ///   found += 1;
+  /// }
+  ///
+  /// assert_eq!(result.await?.hits_total, found);
+  /// # Ok(())
+  /// # }
+  /// ```
+  #[cfg(not(target_arch = "wasm32"))]
+  pub fn run_par_async(mut self) -> (ResearchFuture, HitStream)
+  where
+    TRegex: Send + 'static,
+  {
+    let (hit_tx, hit_rx) = flume::unbounded();
+    let (result_tx, result_rx) = oneshot::channel();
+
+    self = self.on_hit(move |hit| {
+      // the stream may have been dropped
+      let _ = hit_tx.send(hit.clone());
+    });
+
+    let thread = threads::spawn("research", move || {
+      // the future may have been dropped
+      let _ = result_tx.send(self.run_par());
+    });
+
+    (
+      ResearchFuture::new(result_rx, thread),
+      HitStream::new(hit_rx),
     )
   }
 
   #[inline]
   pub fn run_seq(self) -> Result<WakuchinResult> {
-    run_seq(
+    let matchers = self.regex.to_matchers();
+
+    self.validate(&matchers)?;
+
+    run_seq_with_options(
       self.tries,
       self.times,
-      &self.regex,
+      &matchers,
       self.progress_handler,
       self.progress_interval,
+      self.options,
     )
   }
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+  use std::borrow::Cow;
+  use std::collections::BTreeSet;
+  use std::env::temp_dir;
+  use std::fs::remove_file;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::{Arc, Mutex};
+  use std::thread;
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use regex::bytes::{Regex as BytesRegex, RegexBuilder};
+  use regex::Regex;
+
+  use crate::cancel::CancellationToken;
+  use crate::checkpoint::Checkpoint;
+  use crate::error::WakuchinError;
+  use crate::generator::WakuchinGenerator;
+  use crate::handlers::snapshot::SnapshotReader;
+  use crate::handlers::{ControlFlow, HandlerErrorPolicy, ProgressHandler};
+  use crate::hit::HitCounter;
+  use crate::pattern::{MatchMode, Matchers};
+  use crate::pause::PauseHandle;
+  use crate::progress::Progress;
+  use crate::prune::PruneRule;
+  use crate::result::{Hit, HitCount, NotableHit, StopReason, WakuchinResult};
+  use crate::threads::{ThreadConfig, ThreadPriority};
+
+  use super::ResearchBuilder;
+
+  #[test]
+  fn test_seed() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(1000)
+        .times(2)
+        .regex(Regex::new(r"^WKCN").unwrap())
+        .workers(3)
+        .seed(42)
+    };
+
+    let first = research().run_par()?;
+    let second = research().run_par()?;
+
+    assert!(first.hits_total > 0);
+    assert_eq!(first.hits_detail, second.hits_detail);
+
+    let first = research().run_seq()?;
+    let second = research().run_seq()?;
+
+    assert_eq!(first.hits_detail, second.hits_detail);
+
+    Ok(())
+  }
+
+  #[cfg(feature = "rng-chacha")]
+  #[test]
+  fn test_rng() -> Result<(), WakuchinError> {
+    use crate::rng::RngBackend;
+
+    let research = |rng| {
+      ResearchBuilder::new()
+        .tries(1000)
+        .times(2)
+        .regex(Regex::new(r"^WKCN").unwrap())
+        .workers(3)
+        .seed(42)
+        .rng(rng)
+        .run_par()
+    };
+
+    let first = research(RngBackend::ChaCha)?;
+    let second = research(RngBackend::ChaCha)?;
+
+    assert!(first.hits_total > 0);
+    assert_eq!(first.hits_detail, second.hits_detail);
+    assert_ne!(
+      first.hits_detail,
+      research(RngBackend::Fastrand)?.hits_detail
+    );
+
+    Ok(())
+  }
+
+  #[derive(Clone)]
+  struct ConstantGenerator;
+
+  impl WakuchinGenerator for ConstantGenerator {
+    fn next(&mut self, times: usize) -> Cow<'_, [u8]> {
+      b"WKCN".repeat(times).into()
+    }
+  }
+
+  #[test]
+  fn test_generator() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(100)
+        .times(2)
+        .regex(Regex::new(r"^WKCNWKCN$").unwrap())
+        .workers(3)
+        .generator(ConstantGenerator)
+    };
+
+    assert_eq!(research().run_par()?.hits_total, 100);
+    assert_eq!(research().run_seq()?.hits_total, 100);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_regexes() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(100)
+        .times(2)
+        .regexes([
+          Regex::new(r"^WKCN").unwrap(),
+          Regex::new(r"CN$").unwrap(),
+          Regex::new(r"^NC").unwrap(),
+        ])
+        .workers(3)
+        .generator(ConstantGenerator)
+    };
+
+    for result in [research().run_par()?, research().run_seq()?] {
+      assert_eq!(result.hits_total, 200);
+      assert_eq!(
+        result
+          .patterns
+          .iter()
+          .map(|pattern| pattern.hits_total)
+          .collect::<Vec<_>>(),
+        [100, 100, 0]
+      );
+      assert!(result
+        .hits_detail
+        .iter()
+        .all(|hit| hit.pattern < 2 && hit.chars.as_ref() == "WKCNWKCN"));
+    }
+
+    assert!(matches!(
+      research().regexes([]).run_seq(),
+      Err(WakuchinError::NoPatterns)
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_matcher() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(100)
+        .times(2)
+        .matcher(|chars: &str| chars.ends_with("CN"))
+        .workers(3)
+        .generator(ConstantGenerator)
+    };
+
+    for result in [research().run_par()?, research().run_seq()?] {
+      assert_eq!(result.hits_total, 100);
+      assert!(result.patterns.is_empty());
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_capture_matches() -> Result<(), WakuchinError> {
+    let research = |capture| {
+      ResearchBuilder::new()
+        .tries(10)
+        .times(2)
+        .regex(Regex::new(r"(?P<last>CN)$").unwrap())
+        .workers(2)
+        .generator(ConstantGenerator)
+        .capture_matches(capture)
+    };
+
+    for result in [research(true).run_par()?, research(true).run_seq()?] {
+      assert_eq!(result.hits_detail.len(), 10);
+      assert!(result.hits_detail.iter().all(|hit| {
+        hit.span == Some(6..8)
+          && hit.groups.get("last").map(String::as_str) == Some("CN")
+      }));
+    }
+
+    let result = research(false).run_seq()?;
+
+    assert!(result
+      .hits_detail
+      .iter()
+      .all(|hit| hit.span.is_none() && hit.groups.is_empty()));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_match_mode() -> Result<(), WakuchinError> {
+    let research = |mode| {
+      ResearchBuilder::new()
+        .tries(10)
+        .times(3)
+        .matchers(Matchers::Literals(vec!["WKCN".into(), "NW".into()]))
+        .workers(2)
+        .generator(ConstantGenerator)
+        .match_mode(mode)
+    };
+
+    for result in [
+      research(MatchMode::CountAll).run_par()?,
+      research(MatchMode::CountAll).run_seq()?,
+    ] {
+      assert_eq!(result.hits_detail.len(), 20);
+      assert_eq!(result.hits_total, 50);
+      assert_eq!(result.patterns[0].hits_total, 30);
+      assert_eq!(result.patterns[1].hits_total, 20);
+    }
+
+    assert_eq!(research(MatchMode::Any).run_seq()?.hits_total, 20);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_bytes_matchers() -> Result<()> {
+    let research = || {
+      Ok::<_, regex::Error>(
+        ResearchBuilder::new()
+          .tries(10)
+          .times(3)
+          .matchers(Matchers::Bytes(vec![
+            RegexBuilder::new(r"wkcn").case_insensitive(true).build()?,
+            BytesRegex::new(r"wkcn")?,
+          ]))
+          .workers(2)
+          .generator(ConstantGenerator)
+          .match_mode(MatchMode::CountAll),
+      )
+    };
+
+    // the case of the first pattern is ignored as it was built
+    for result in [research()?.run_par()?, research()?.run_seq()?] {
+      assert_eq!(result.hits_detail.len(), 10);
+      assert_eq!(result.patterns[0].hits_total, 30);
+      assert_eq!(result.patterns[1].hits_total, 0);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_stop_at_hits() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(1000)
+        .times(1)
+        .matchers(Matchers::Literals(vec!["WK".into(), "NW".into()]))
+        .workers(2)
+        .generator(ConstantGenerator)
+        .stop_at_hits(1, 5)
+    };
+
+    // "WKCN" never hits "NW"
+    let result = research().run_seq()?;
+
+    assert_eq!(result.tries, 1000);
+    assert_eq!(result.stop_reason, None);
+    assert_eq!(result.patterns[1].target, Some(5));
+    assert!(!result.patterns[1].is_reached());
+
+    for result in [
+      research().stop_at_hits(0, 10).run_par()?,
+      research().stop_at_hits(0, 10).run_seq()?,
+    ] {
+      assert!(result.tries < 1000);
+      assert_eq!(result.hits_detail.len(), result.tries);
+      assert!(result.patterns[0].is_reached());
+      assert_eq!(
+        result.stop_reason,
+        Some(StopReason::PatternTarget {
+          pattern: 0,
+          hits: 10
+        })
+      );
+    }
+
+    assert!(research().stop_at_hits(2, 1).run_seq().is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_checkpoint() -> Result<(), WakuchinError> {
+    let path = temp_dir()
+      .join(format!("wakuchin-checkpoint-{}.json", fastrand::u64(..)));
+    let research = || {
+      ResearchBuilder::new()
+        .tries(100_000)
+        .times(2)
+        .matchers(Matchers::Literals(vec!["WKCN".into()]))
+        .seed(42)
+        .workers(2)
+    };
+
+    let whole = research().run_par()?;
+
+    // stopped early, like a reboot
+    let partial = research()
+      .stop_at_hits(0, 100)
+      .checkpoint(&path, Duration::ZERO)
+      .run_par()?;
+    let checkpoint = Checkpoint::load(&path)?;
+
+    assert!(partial.tries < 100_000);
+    assert_eq!(checkpoint.current(), partial.tries);
+    assert_eq!(checkpoint.hits().total(), partial.hits_total);
+
+    let resumed = research().run_par_resume(checkpoint.clone())?;
+    let sorted = |result: WakuchinResult| {
+      result.hits.into_iter().collect::<HitCounter>().snapshot()
+    };
+
+    assert_eq!(resumed.tries, 100_000);
+    assert_eq!(resumed.hits_total, whole.hits_total);
+    assert_eq!(resumed.hits_detail, whole.hits_detail);
+    assert_eq!(sorted(resumed), sorted(whole));
+
+    assert!(matches!(
+      research().seed(43).run_par_resume(checkpoint),
+      Err(WakuchinError::CheckpointMismatch)
+    ));
+
+    remove_file(path).unwrap();
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_stop_limits() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(usize::MAX)
+        .times(1)
+        .matchers(Matchers::Literals(vec!["WK".into()]))
+        .workers(2)
+        .generator(ConstantGenerator)
+    };
+
+    for result in [
+      research().stop_after_hits(10).run_par()?,
+      research().stop_after_hits(10).run_seq()?,
+    ] {
+      assert!(result.hits_total >= 10);
+      assert_eq!(result.hits_total, result.tries);
+      assert_eq!(result.stop_reason, Some(StopReason::HitLimit { hits: 10 }));
+    }
+
+    for result in [
+      research().stop_when_confident(0.99, 0.01).run_par()?,
+      research().stop_when_confident(0.99, 0.01).run_seq()?,
+    ] {
+      assert!(result.tries < usize::MAX);
+      assert_eq!(
+        result.stop_reason,
+        Some(StopReason::Confident {
+          confidence: 0.99,
+          margin: 0.01
+        })
+      );
+    }
+
+    assert!(research().stop_when_confident(1.0, 0.01).run_seq().is_err());
+    assert!(research().stop_when_confident(0.99, 0.0).run_seq().is_err());
+
+    let limit = Duration::from_millis(100);
+    let pause = PauseHandle::new();
+
+    pause.pause();
+
+    for result in [
+      research().time_limit(limit).run_par()?,
+      research().time_limit(limit).run_seq()?,
+      // paused researches are stopped too
+      research().time_limit(limit).pause_handle(pause).run_par()?,
+    ] {
+      assert!(result.tries < usize::MAX);
+      assert_eq!(result.stop_reason, Some(StopReason::TimeLimit { limit }));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_on_hit() -> Result<(), WakuchinError> {
+    let research = |hits: &Arc<Mutex<Vec<usize>>>| {
+      let hits = hits.clone();
+
+      ResearchBuilder::new()
+        .tries(10_000)
+        .times(2)
+        .regex(Regex::new(r"^WKCN").unwrap())
+        .workers(2)
+        .on_hit(move |hit| hits.lock().unwrap().push(hit.hit_on))
+    };
+
+    let par = Arc::default();
+    let seq = Arc::default();
+
+    for (result, hits) in [
+      (research(&par).run_par()?, par),
+      (research(&seq).run_seq()?, seq),
+    ] {
+      let mut hits = hits.lock().unwrap().clone();
+      let mut found = result
+        .hits_detail
+        .iter()
+        .map(|hit| hit.hit_on)
+        .collect::<Vec<_>>();
+
+      assert!(!found.is_empty());
+
+      hits.sort_unstable();
+      found.sort_unstable();
+
+      assert_eq!(hits, found);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_handler_on_hit() -> Result<(), WakuchinError> {
+    /// Handler recording the index of each hit and the hits counted by the
+    /// final progress.
+    #[derive(Clone, Default)]
+    struct HitHandler(Arc<Mutex<(Vec<usize>, usize)>>);
+
+    impl ProgressHandler for HitHandler {
+      fn handle(
+        &mut self,
+        _: &[Progress],
+        hit_counts: &[HitCount],
+        _: Duration,
+        _: usize,
+        all_done: bool,
+      ) -> Result<ControlFlow> {
+        if all_done {
+          self.0.lock().unwrap().1 =
+            hit_counts.iter().map(|count| count.hits).sum();
+        }
+
+        Ok(ControlFlow::Continue)
+      }
+
+      fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+        self.0.lock().unwrap().0.push(hit.hit_on);
+
+        Ok(())
+      }
+    }
+
+    let research = |handler: &HitHandler| {
+      ResearchBuilder::new()
+        .tries(10_000)
+        .times(2)
+        .regex(Regex::new(r"^WKCN").unwrap())
+        .workers(2)
+        .progress_handler(Box::new(handler.clone()))
+    };
+
+    let par = HitHandler::default();
+    let seq = HitHandler::default();
+
+    for (result, handler) in [
+      (research(&par).run_par()?, par),
+      (research(&seq).run_seq()?, seq),
+    ] {
+      let (mut hits, counted) = handler.0.lock().unwrap().clone();
+      let mut found = result
+        .hits_detail
+        .iter()
+        .map(|hit| hit.hit_on)
+        .collect::<Vec<_>>();
+
+      assert!(!found.is_empty());
+      assert_eq!(
+        counted,
+        result.hits.iter().map(|count| count.hits).sum::<usize>()
+      );
+
+      hits.sort_unstable();
+      found.sort_unstable();
+
+      assert_eq!(hits, found);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_progress_snapshots() -> Result<(), WakuchinError> {
+    let reader = SnapshotReader::new();
+    let poller = thread::spawn({
+      let reader = reader.clone();
+
+      move || {
+        let mut seen = Vec::new();
+
+        while !seen.last().is_some_and(|(_, all_done)| *all_done) {
+          if let Some(snapshot) = reader.latest() {
+            seen.push((snapshot.current, snapshot.all_done));
+          }
+
+          thread::yield_now();
+        }
+
+        seen
+      }
+    });
+
+    let result = ResearchBuilder::new()
+      .tries(100_000)
+      .times(1)
+      .regex(Regex::new(r"^WK").unwrap())
+      .progress_interval(Duration::ZERO)
+      .progress_snapshots(reader.clone())
+      .run_seq()?;
+    let seen = poller.join().unwrap();
+
+    assert!(seen.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+    assert_eq!(seen.last(), Some(&(100_000, true)));
+    assert_eq!(reader.latest().unwrap().hits_total, result.hits_total);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_handler_error_policy() -> Result<(), WakuchinError> {
+    /// Handler failing on every call, counting them.
+    #[derive(Clone, Default)]
+    struct FailingHandler(Arc<AtomicUsize>);
+
+    impl ProgressHandler for FailingHandler {
+      fn handle(
+        &mut self,
+        _: &[Progress],
+        _: &[HitCount],
+        _: Duration,
+        _: usize,
+        _: bool,
+      ) -> Result<ControlFlow> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+
+        anyhow::bail!("broken pipe")
+      }
+    }
+
+    let research = |handler: &FailingHandler| {
+      ResearchBuilder::new()
+        .tries(100_000)
+        .times(1)
+        .regex(Regex::new(r"^WK").unwrap())
+        .workers(2)
+        .progress_interval(Duration::ZERO)
+        .progress_handler(Box::new(handler.clone()))
+    };
+
+    for policy in [
+      HandlerErrorPolicy::LogAndContinue,
+      HandlerErrorPolicy::DisableHandler,
+    ] {
+      for run_seq in [true, false] {
+        let handler = FailingHandler::default();
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let research =
+          research(&handler).handler_error_policy(policy).on_warning({
+            let warnings = warnings.clone();
+
+            move |message| warnings.lock().unwrap().push(message.to_string())
+          });
+        let result = if run_seq {
+          research.run_seq()?
+        } else {
+          research.run_par()?
+        };
+        let calls = handler.0.load(Ordering::Relaxed);
+
+        let warnings = warnings.lock().unwrap().len();
+
+        assert_eq!(result.tries, 100_000);
+
+        match policy {
+          HandlerErrorPolicy::DisableHandler => {
+            assert_eq!(calls, 1);
+            assert_eq!(warnings, 1);
+          }
+          _ => {
+            assert!(calls > 1);
+            assert!((1..=calls).contains(&warnings));
+          }
+        }
+      }
+    }
+
+    assert!(research(&FailingHandler::default()).run_seq().is_err());
+    assert!(research(&FailingHandler::default()).run_par().is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_yield_every() -> Result<(), WakuchinError> {
+    let research = |yields: &Arc<AtomicUsize>| {
+      let yields = yields.clone();
+
+      ResearchBuilder::new()
+        .tries(10_000)
+        .times(1)
+        .regex(Regex::new(r"^WK").unwrap())
+        .workers(2)
+        .yield_every(1024, move || {
+          yields.fetch_add(1, Ordering::Relaxed);
+        })
+    };
+
+    let yields = Arc::default();
+
+    research(&yields).run_seq()?;
+    assert_eq!(yields.load(Ordering::Relaxed), 9);
+
+    // parallel researches do not block the workers on the calling thread
+    let yields = Arc::default();
+
+    research(&yields).run_par()?;
+    assert_eq!(yields.load(Ordering::Relaxed), 0);
+
+    assert!(research(&yields).yield_every(0, || {}).run_seq().is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_thread_config() -> Result<(), WakuchinError> {
+    let names = Arc::new(Mutex::new(BTreeSet::new()));
+    let result = ResearchBuilder::new()
+      .tries(1000)
+      .times(1)
+      .regex(Regex::new(r"^WK").unwrap())
+      .workers(2)
+      .thread_config(
+        ThreadConfig::new()
+          .with_priority(ThreadPriority::Low)
+          .with_cores([0]),
+      )
+      .on_hit({
+        let names = names.clone();
+
+        move |_| {
+          let name = thread::current().name().map(str::to_owned);
+
+          names.lock().unwrap().insert(name);
+        }
+      })
+      .run_par()?;
+
+    assert!(result.hits_total > 0);
+
+    // hits are reported from the workers
+    for name in names.lock().unwrap().iter() {
+      assert!(
+        matches!(
+          name.as_deref(),
+          Some("wakuchin-worker-1" | "wakuchin-worker-2")
+        ),
+        "{name:?}"
+      );
+    }
+
+    Ok(())
+  }
+
+  /// Generates "WKCN" until the string `at` of all of its clones, on which it
+  /// panics.
+  #[derive(Clone)]
+  struct PanickingGenerator {
+    generated: Arc<AtomicUsize>,
+    at: usize,
+  }
+
+  impl WakuchinGenerator for PanickingGenerator {
+    fn next(&mut self, times: usize) -> Cow<'_, [u8]> {
+      if self.generated.fetch_add(1, Ordering::Relaxed) == self.at {
+        panic!("broken generator");
+      }
+
+      b"WKCN".repeat(times).into()
+    }
+  }
+
+  #[test]
+  fn test_worker_panic() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(10_000)
+        .times(1)
+        .regex(Regex::new(r"^WKCN$").unwrap())
+        .workers(4)
+        .generator(PanickingGenerator {
+          generated: Arc::default(),
+          at: 5_000,
+        })
+    };
+
+    let par = research().run_par()?;
+    let seq = research().run_seq()?;
+
+    // the other workers take the chunks left, only the rest of the chunk of
+    // the failed worker is skipped
+    assert!(par.tries > 9_900 && par.tries < 10_000);
+    assert_eq!(seq.tries, 5_000);
+
+    for result in [par, seq] {
+      assert_eq!(result.failures.len(), 1);
+      assert_eq!(result.failures[0].message, "broken generator");
+      assert_eq!(result.tries + result.failures[0].untried, 10_000);
+      assert_eq!(result.hits_total, result.tries);
+      assert_eq!(result.hits_detail.len(), result.tries);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_max_hit_details() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(10_000)
+        .times(2)
+        .regex(Regex::new(r"^WK").unwrap())
+        .workers(3)
+        .seed(1)
+    };
+
+    let all = research().run_par()?;
+
+    for (result, max) in [
+      (research().max_hit_details(10).run_par()?, 10),
+      (research().max_hit_details(10).run_seq()?, 10),
+      (research().collect_details(false).run_par()?, 0),
+    ] {
+      assert_eq!(result.hits_total, all.hits_total);
+      assert_eq!(result.hits_detail[..], all.hits_detail[..max]);
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_session() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .times(2)
+        .regex(Regex::new(r"^WKCN").unwrap())
+        .workers(3)
+        .seed(42)
+    };
+
+    let mut session = research().session()?;
+
+    assert_eq!(session.workers(), 3);
+
+    for tries in [0, 1, 100, 10_000, 100] {
+      let result = session.run(tries)?;
+      let expected = research().tries(tries).run_par()?;
+
+      assert_eq!(result.tries, tries);
+      assert_eq!(result.hits_detail, expected.hits_detail);
+    }
+
+    assert!(matches!(
+      research().times(0).session(),
+      Err(WakuchinError::TimesIsZero)
+    ));
+
+    Ok(())
+  }
+
+  #[tokio::test(flavor = "current_thread")]
+  async fn test_run_par_async() -> Result<(), WakuchinError> {
+    let (result, mut hits) = ResearchBuilder::new()
+      .tries(10_000)
+      .times(2)
+      .regex(Regex::new(r"^WKCN").unwrap())
+      .workers(2)
+      .run_par_async();
+    let mut streamed = Vec::new();
+
+    while let Some(hit) = hits.next().await {
+      streamed.push(hit.hit_on);
+    }
+
+    let result = result.await?;
+    let mut found = result
+      .hits_detail
+      .iter()
+      .map(|hit| hit.hit_on)
+      .collect::<Vec<_>>();
+
+    assert!(!found.is_empty());
+
+    streamed.sort_unstable();
+    found.sort_unstable();
+
+    assert_eq!(streamed, found);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_cancellation_token() -> Result<(), WakuchinError> {
+    let research = |token: &CancellationToken| {
+      ResearchBuilder::new()
+        .tries(usize::MAX)
+        .times(1)
+        .matchers(Matchers::Literals(vec!["WK".into()]))
+        .workers(2)
+        .generator(ConstantGenerator)
+        .cancellation_token(token.clone())
+    };
+
+    for run_par in [true, false] {
+      let token = CancellationToken::new();
+      let stopper = thread::spawn({
+        let token = token.clone();
+
+        move || {
+          thread::sleep(Duration::from_millis(100));
+          token.stop();
+        }
+      });
+
+      let result = if run_par {
+        research(&token).run_par()?
+      } else {
+        research(&token).run_seq()?
+      };
+
+      stopper.join().unwrap();
+
+      assert!(result.tries > 0 && result.tries < usize::MAX);
+      assert_eq!(result.hits_total, result.tries);
+      assert_eq!(result.stop_reason, Some(StopReason::Requested));
+    }
+
+    let token = CancellationToken::new();
+    let pause = PauseHandle::new();
+
+    pause.pause();
+    token.cancel();
+
+    assert!(matches!(
+      research(&token).pause_handle(pause).run_par(),
+      Err(WakuchinError::Cancelled)
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_prune_patterns() -> Result<(), WakuchinError> {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(20_000)
+        .times(1)
+        .matchers(Matchers::Literals(vec!["WK".into(), "NW".into()]))
+        .workers(2)
+        .generator(ConstantGenerator)
+        .prune_patterns(PruneRule::new(0.5, 0.99).with_min_tries(5000))
+    };
+
+    for result in [research().run_par()?, research().run_seq()?] {
+      let retired_at = result.patterns[0].retired_at.unwrap();
+
+      // other workers notice it at their next report
+      assert!(result.patterns[0].hits_total >= retired_at);
+      assert!(result.patterns[0].hits_total < result.tries);
+      // the last pattern is kept
+      assert_eq!(result.patterns[1].retired_at, None);
+    }
+
+    let result = research().run_seq()?;
+
+    assert_eq!(
+      result.patterns[0].retired_at,
+      Some(result.patterns[0].hits_total)
+    );
+
+    let result = research().stop_at_hits(0, 20_000).run_seq()?;
+
+    assert_eq!(result.patterns[0].retired_at, None);
+    assert_eq!(result.patterns[1].retired_at, Some(8192));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_impossible_pattern() {
+    let research = || {
+      ResearchBuilder::new()
+        .tries(10)
+        .times(1)
+        .regex(Regex::new(r"^wk").unwrap())
+    };
+
+    assert!(matches!(
+      research().run_seq(),
+      Err(WakuchinError::ImpossiblePattern { .. })
+    ));
+    assert!(matches!(
+      research().run_par(),
+      Err(WakuchinError::ImpossiblePattern { .. })
+    ));
+    // custom generators may generate anything
+    assert!(research().generator(ConstantGenerator).run_seq().is_ok());
+  }
+
+  #[test]
+  fn test_fuzzy() -> Result<(), WakuchinError> {
+    let result = ResearchBuilder::new()
+      .tries(10)
+      .times(3)
+      .matchers(Matchers::fuzzy("WKCNWKCNWKWW", 2))
+      .generator(ConstantGenerator)
+      .run_seq()?;
+
+    assert_eq!(result.hits_total, 10);
+    assert!(result.hits_detail.iter().all(|hit| hit.distance == Some(2)));
+
+    Ok(())
+  }
+
+  #[derive(Clone, Default)]
+  struct NotableHandler(Arc<Mutex<Vec<NotableHit>>>);
+
+  impl ProgressHandler for NotableHandler {
+    fn handle(
+      &mut self,
+      _: &[Progress],
+      _: &[HitCount],
+      _: Duration,
+      _: usize,
+      _: bool,
+    ) -> Result<ControlFlow> {
+      Ok(ControlFlow::Continue)
+    }
+
+    fn on_notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+      self.0.lock().unwrap().push(hit.clone());
+
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_notable_hits() -> Result<(), WakuchinError> {
+    let research = |handler: &NotableHandler, notable: &str| {
+      ResearchBuilder::new()
+        .tries(10)
+        .times(2)
+        .regex(Regex::new(r"^WK").unwrap())
+        .notable_patterns(Regex::new(notable).unwrap())
+        .workers(2)
+        .seed(7)
+        .generator(ConstantGenerator)
+        .progress_handler(Box::new(handler.clone()))
+    };
+
+    for parallel in [true, false] {
+      let handler = NotableHandler::default();
+      let notable_research = research(&handler, r"CN$");
+
+      if parallel {
+        notable_research.run_par()?;
+      } else {
+        notable_research.run_seq()?;
+      }
+
+      let mut notable = handler.0.lock().unwrap().clone();
+
+      notable.sort_by_key(|notable| notable.hit.hit_on);
+
+      assert_eq!(notable.len(), 10);
+      assert!(notable.iter().enumerate().all(|(i, notable)| {
+        notable.hit.hit_on == i
+          && notable.hit.chars.as_ref() == "WKCNWKCN"
+          && notable.seed == Some(7)
+          && notable.rarity.is_none()
+      }));
+
+      let handler = NotableHandler::default();
+
+      research(&handler, r"^NC").run_seq()?;
+
+      assert!(handler.0.lock().unwrap().is_empty());
+    }
+
+    // every hit is notable when rarer than the threshold
+    let handler = NotableHandler::default();
+    let result = ResearchBuilder::new()
+      .tries(1000)
+      .times(1)
+      .regex(Regex::new(r"^WKCN$").unwrap())
+      .notable_rarity(10)
+      .progress_handler(Box::new(handler.clone()))
+      .run_seq()?;
+
+    assert_eq!(handler.0.lock().unwrap().len(), result.hits_total);
+    assert!(handler
+      .0
+      .lock()
+      .unwrap()
+      .iter()
+      .all(|notable| notable.rarity.map(|rarity| rarity.one_in) == Some(24)));
+
+    Ok(())
+  }
+
+  struct HangingProgressHandler;
+
+  impl ProgressHandler for HangingProgressHandler {
+    fn handle(
+      &mut self,
+      _: &[Progress],
+      _: &[HitCount],
+      _: Duration,
+      _: usize,
+      _: bool,
+    ) -> Result<ControlFlow> {
+      Ok(ControlFlow::Continue)
+    }
+
+    fn after_finish(&mut self) -> Result<()> {
+      thread::sleep(Duration::from_secs(5));
+
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_shutdown_grace() {
+    let result = ResearchBuilder::new()
+      .tries(10)
+      .times(1)
+      .regex(Regex::new(r"^WKCN").unwrap())
+      .workers(2)
+      .progress_handler(Box::new(HangingProgressHandler))
+      .shutdown_grace(Duration::from_millis(100))
+      .run_par();
+
+    assert!(matches!(
+      result,
+      Err(WakuchinError::ShutdownTimeout(stuck)) if stuck == ["render"]
+    ));
+  }
+}
@@ -0,0 +1,111 @@
+//! Cancelling researches from embedding applications
+
+use std::sync::Arc;
+
+use crate::sync::{AtomicBool, AtomicUsize, Mutex, Ordering};
+
+#[derive(Default)]
+struct CancelState {
+  cancelled: AtomicBool,
+  stopped: AtomicBool,
+  /// Woken up on cancel and stop, one for each research using the token
+  watchers: Mutex<Vec<(usize, flume::Sender<()>)>>,
+  next_watcher: AtomicUsize,
+}
+
+/// Token to end a research from another thread, e.g. the UI thread of a GUI
+/// or the request handler of a server, see
+/// [`ResearchBuilder::cancellation_token`](crate::builder::ResearchBuilder::cancellation_token).
+///
+/// [`cancel`](Self::cancel) makes the research fail with
+/// [`WakuchinError::Cancelled`](crate::error::WakuchinError::Cancelled),
+/// like Ctrl-C, while [`stop`](Self::stop) lets it return the result of the
+/// tries done so far, with
+/// [`StopReason::Requested`](crate::result::StopReason::Requested).
+///
+/// Paused researches are woken up to end. The token is watched by the signal
+/// thread, so it has no effect on `wasm32`.
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::cancel::CancellationToken;
+/// use wakuchin::error::WakuchinError;
+///
+/// let token = CancellationToken::new();
+///
+/// token.cancel();
+///
+/// let result = ResearchBuilder::new()
+///   .tries(usize::MAX)
+///   .times(2)
+///   .regex(Regex::new("^(WK)+$").unwrap())
+///   .cancellation_token(token.clone())
+///   .run_par();
+///
+/// assert!(matches!(result, Err(WakuchinError::Cancelled)));
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+  state: Arc<CancelState>,
+}
+
+impl CancellationToken {
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Cancel the research, discarding its result.
+  #[inline]
+  pub fn cancel(&self) {
+    self.state.cancelled.store(true, Ordering::SeqCst);
+    self.wake();
+  }
+
+  /// Stop the research, keeping the result of the tries done so far.
+  #[inline]
+  pub fn stop(&self) {
+    self.state.stopped.store(true, Ordering::SeqCst);
+    self.wake();
+  }
+
+  #[inline]
+  pub fn is_cancelled(&self) -> bool {
+    self.state.cancelled.load(Ordering::Relaxed)
+  }
+
+  #[inline]
+  pub fn is_stopped(&self) -> bool {
+    self.state.stopped.load(Ordering::Relaxed)
+  }
+
+  /// Wake up `watcher` on cancel and stop, until unwatched with the
+  /// returned id.
+  #[cfg(not(target_arch = "wasm32"))]
+  pub(crate) fn watch(&self, watcher: flume::Sender<()>) -> usize {
+    let id = self.state.next_watcher.fetch_add(1, Ordering::Relaxed);
+
+    self.state.watchers.lock().unwrap().push((id, watcher));
+
+    id
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  pub(crate) fn unwatch(&self, id: usize) {
+    self
+      .state
+      .watchers
+      .lock()
+      .unwrap()
+      .retain(|(other, _)| *other != id);
+  }
+
+  fn wake(&self) {
+    for (_, watcher) in self.state.watchers.lock().unwrap().iter() {
+      let _ = watcher.try_send(());
+    }
+  }
+}
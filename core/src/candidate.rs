@@ -0,0 +1,240 @@
+//! String type of the candidates flowing through progresses and hits
+
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use arrayvec::ArrayString;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Length in bytes up to which candidates are stored inline, e.g. wakuchin
+/// strings of `times` up to 8.
+pub const INLINE_CAPACITY: usize = 32;
+
+/// Chars of a candidate, stored inline up to [`INLINE_CAPACITY`] bytes and
+/// on the heap above, so short candidates are passed around without
+/// allocating.
+///
+/// It dereferences to [`str`], and is serialized as a string.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::candidate::Candidate;
+///
+/// let candidate = Candidate::from("WKCNWKCN");
+///
+/// assert!(candidate.is_inline());
+/// assert_eq!(candidate, "WKCNWKCN");
+/// assert!(!Candidate::from("WKCN".repeat(10)).is_inline());
+/// ```
+#[derive(Clone)]
+pub struct Candidate(Repr);
+
+#[derive(Clone)]
+enum Repr {
+  Inline(ArrayString<INLINE_CAPACITY>),
+  Heap(String),
+}
+
+impl Candidate {
+  /// Create a candidate from UTF-8 bytes, replacing invalid sequences with
+  /// `U+FFFD` like [`String::from_utf8_lossy`].
+  pub fn from_utf8_lossy(bytes: &[u8]) -> Self {
+    match String::from_utf8_lossy(bytes) {
+      Cow::Borrowed(chars) => chars.into(),
+      Cow::Owned(chars) => chars.into(),
+    }
+  }
+
+  #[inline]
+  pub fn as_str(&self) -> &str {
+    match &self.0 {
+      Repr::Inline(chars) => chars,
+      Repr::Heap(chars) => chars,
+    }
+  }
+
+  /// Return whether the chars are stored inline.
+  #[inline]
+  pub fn is_inline(&self) -> bool {
+    matches!(self.0, Repr::Inline(_))
+  }
+
+  pub fn into_string(self) -> String {
+    match self.0 {
+      Repr::Inline(chars) => chars.to_string(),
+      Repr::Heap(chars) => chars,
+    }
+  }
+}
+
+impl Default for Candidate {
+  fn default() -> Self {
+    Self(Repr::Inline(ArrayString::new()))
+  }
+}
+
+impl Deref for Candidate {
+  type Target = str;
+
+  #[inline]
+  fn deref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl AsRef<str> for Candidate {
+  #[inline]
+  fn as_ref(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl Borrow<str> for Candidate {
+  #[inline]
+  fn borrow(&self) -> &str {
+    self.as_str()
+  }
+}
+
+impl From<&str> for Candidate {
+  fn from(chars: &str) -> Self {
+    match ArrayString::from(chars) {
+      Ok(inline) => Self(Repr::Inline(inline)),
+      Err(_) => Self(Repr::Heap(chars.to_owned())),
+    }
+  }
+}
+
+impl From<String> for Candidate {
+  fn from(chars: String) -> Self {
+    match ArrayString::from(&chars) {
+      Ok(inline) => Self(Repr::Inline(inline)),
+      // keep the allocation
+      Err(_) => Self(Repr::Heap(chars)),
+    }
+  }
+}
+
+impl From<Cow<'_, str>> for Candidate {
+  fn from(chars: Cow<'_, str>) -> Self {
+    match chars {
+      Cow::Borrowed(chars) => chars.into(),
+      Cow::Owned(chars) => chars.into(),
+    }
+  }
+}
+
+impl From<Candidate> for String {
+  #[inline]
+  fn from(candidate: Candidate) -> Self {
+    candidate.into_string()
+  }
+}
+
+impl From<Candidate> for Cow<'static, str> {
+  #[inline]
+  fn from(candidate: Candidate) -> Self {
+    Cow::Owned(candidate.into_string())
+  }
+}
+
+impl PartialEq for Candidate {
+  #[inline]
+  fn eq(&self, other: &Self) -> bool {
+    self.as_str() == other.as_str()
+  }
+}
+
+impl Eq for Candidate {}
+
+impl PartialEq<str> for Candidate {
+  #[inline]
+  fn eq(&self, other: &str) -> bool {
+    self.as_str() == other
+  }
+}
+
+impl PartialEq<&str> for Candidate {
+  #[inline]
+  fn eq(&self, other: &&str) -> bool {
+    self.as_str() == *other
+  }
+}
+
+impl PartialOrd for Candidate {
+  #[inline]
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Candidate {
+  #[inline]
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.as_str().cmp(other.as_str())
+  }
+}
+
+impl Hash for Candidate {
+  #[inline]
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.as_str().hash(state);
+  }
+}
+
+impl fmt::Debug for Candidate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Debug::fmt(self.as_str(), f)
+  }
+}
+
+impl fmt::Display for Candidate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Display::fmt(self.as_str(), f)
+  }
+}
+
+impl Serialize for Candidate {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+impl<'de> Deserialize<'de> for Candidate {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    Cow::<'de, str>::deserialize(deserializer).map(Into::into)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::result::Hit;
+
+  use super::{Candidate, INLINE_CAPACITY};
+
+  #[test]
+  fn test_candidate() -> Result<(), serde_json::Error> {
+    let inline = Candidate::from("W".repeat(INLINE_CAPACITY));
+    let heap = Candidate::from("W".repeat(INLINE_CAPACITY + 1));
+
+    assert!(inline.is_inline());
+    assert!(!heap.is_inline());
+    assert!(inline < heap);
+    assert_eq!(heap.len(), INLINE_CAPACITY + 1);
+    assert_eq!(Candidate::from_utf8_lossy(b"WK\xffCN"), "WK\u{fffd}CN");
+
+    let hit = Hit::new(3, "WKCN");
+    let json = serde_json::to_string(&hit)?;
+
+    assert_eq!(json, r#"{"hit_on":3,"chars":"WKCN"}"#);
+    assert_eq!(serde_json::from_str::<Hit>(&json)?, hit);
+
+    Ok(())
+  }
+}
@@ -1,7 +1,15 @@
+#[cfg(not(wakuchin_loom))]
 use flume::unbounded;
 use tokio::sync::watch;
 
-pub fn channel<T>() -> (flume::Sender<T>, flume::Receiver<T>) {
+#[cfg(not(wakuchin_loom))]
+pub use flume::{Receiver, Sender};
+
+#[cfg(wakuchin_loom)]
+pub use self::loom::{channel, Receiver, Sender};
+
+#[cfg(not(wakuchin_loom))]
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
   let (tx, rx) = unbounded();
 
   (tx, rx)
@@ -12,3 +20,111 @@ pub fn watch<T>(initial_value: T) -> (watch::Sender<T>, watch::Receiver<T>) {
 
   (tx, rx)
 }
+
+/// Unbounded channel built on the primitives of loom, since flume is opaque
+/// to it and the channel of loom does not disconnect when the senders are
+/// dropped, which is what stops the hit counter.
+#[cfg(wakuchin_loom)]
+mod loom {
+  use std::collections::VecDeque;
+  use std::sync::Arc;
+
+  use crate::sync::{Condvar, Mutex};
+
+  struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+  }
+
+  struct Shared<T> {
+    state: Mutex<State<T>>,
+    changed: Condvar,
+  }
+
+  pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+  }
+
+  #[derive(Clone)]
+  pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+  }
+
+  pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+      state: Mutex::new(State {
+        queue: VecDeque::new(),
+        senders: 1,
+      }),
+      changed: Condvar::new(),
+    });
+
+    (
+      Sender {
+        shared: shared.clone(),
+      },
+      Receiver { shared },
+    )
+  }
+
+  impl<T> Sender<T> {
+    pub fn send(&self, value: T) -> Result<(), T> {
+      self.shared.state.lock().unwrap().queue.push_back(value);
+      self.shared.changed.notify_all();
+
+      Ok(())
+    }
+  }
+
+  impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+      self.shared.state.lock().unwrap().senders += 1;
+
+      Self {
+        shared: self.shared.clone(),
+      }
+    }
+  }
+
+  impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+      self.shared.state.lock().unwrap().senders -= 1;
+      self.shared.changed.notify_all();
+    }
+  }
+
+  impl<T> Receiver<T> {
+    /// Wait for a value, `None` once empty and all senders are dropped.
+    fn recv(&self) -> Option<T> {
+      let mut state = self.shared.state.lock().unwrap();
+
+      loop {
+        if let Some(value) = state.queue.pop_front() {
+          return Some(value);
+        }
+
+        if state.senders == 0 {
+          return None;
+        }
+
+        state = self.shared.changed.wait(state).unwrap();
+      }
+    }
+
+    /// Take the values sent so far, without waiting for more.
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+      std::iter::from_fn(move || {
+        self.shared.state.lock().unwrap().queue.pop_front()
+      })
+    }
+  }
+
+  impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Box<dyn Iterator<Item = T> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+      Box::new(std::iter::from_fn(move || self.recv()))
+    }
+  }
+}
@@ -0,0 +1,281 @@
+//! Checkpointing researches to disk, so long researches survive reboots
+//!
+//! A research with
+//! [`ResearchBuilder::checkpoint`](crate::builder::ResearchBuilder::checkpoint)
+//! writes the state of each worker to a file every now and then: the tries
+//! it did, the state of its generator and the hits it found.
+//! [`ResearchBuilder::run_par_resume`](crate::builder::ResearchBuilder::run_par_resume)
+//! continues from such a [`Checkpoint`], each worker from its own tries, and
+//! returns the result of the whole research.
+//!
+//! Workers restore the thread-local generator of `fastrand`, so a seeded
+//! research of [`RngBackend::Fastrand`](crate::rng::RngBackend::Fastrand)
+//! tries the same strings as if it was never interrupted.
+
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Source;
+use crate::error::WakuchinError;
+use crate::fs::atomic_write;
+use crate::hit::HitCounter;
+use crate::pattern::Matchers;
+use crate::result::Hit;
+use crate::sync::Mutex;
+use crate::worker::RunOptions;
+
+type Result<T> = std::result::Result<T, WakuchinError>;
+
+/// Tries after which workers record their state to the checkpoint.
+pub(crate) const UPDATE_EVERY: usize = 4096;
+
+/// State of a research written to disk, see the [module](self).
+///
+/// # Examples
+///
+/// ```rust
+/// use std::env::temp_dir;
+/// use std::time::Duration;
+///
+/// use regex::Regex;
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::checkpoint::Checkpoint;
+///
+/// let path = temp_dir().join("wakuchin-checkpoint-example.json");
+/// let research = || {
+///   ResearchBuilder::new()
+///     .tries(10_000)
+///     .times(1)
+///     .regex(Regex::new("^WK").unwrap())
+///     .seed(42)
+///     .workers(2)
+/// };
+///
+/// let result = research()
+///   .checkpoint(&path, Duration::from_secs(60))
+///   .run_par()?;
+/// let checkpoint = Checkpoint::load(&path)?;
+///
+/// assert_eq!(checkpoint.current(), 10_000);
+///
+/// // nothing is left to try
+/// let resumed = research().run_par_resume(checkpoint)?;
+///
+/// assert_eq!(resumed.tries, result.tries);
+/// assert_eq!(resumed.hits_total, result.hits_total);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+  /// Hash of the options of the research, which have to be the same to
+  /// resume it
+  pub config_hash: u64,
+  pub tries: usize,
+  pub workers: Vec<WorkerCheckpoint>,
+}
+
+/// State of a worker of a [`Checkpoint`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkerCheckpoint {
+  /// Tries of the worker
+  pub range: Range<usize>,
+  /// Number of tries done from the start of `range`
+  pub done: usize,
+  /// Seed of the thread-local generator of `fastrand` by then
+  pub rng_state: u64,
+  /// Hits of the tries done
+  pub hits: HitCounter,
+  /// First hits of the tries done, as many as
+  /// [`ResearchBuilder::max_hit_details`](crate::builder::ResearchBuilder::max_hit_details)
+  /// allows, to be added to the `hits_detail` of the resumed research
+  #[serde(default)]
+  pub hits_detail: Vec<Hit>,
+}
+
+impl Checkpoint {
+  /// Read a checkpoint written by a research.
+  pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+    let contents =
+      fs::read(path.as_ref()).map_err(WakuchinError::CheckpointIoError)?;
+
+    serde_json::from_slice(&contents)
+      .map_err(|e| WakuchinError::CheckpointIoError(e.into()))
+  }
+
+  /// Return the number of tries done by all workers.
+  pub fn current(&self) -> usize {
+    self.workers.iter().map(|worker| worker.done).sum()
+  }
+
+  /// Return the hits of all workers.
+  pub fn hits(&self) -> HitCounter {
+    let hits = HitCounter::new();
+
+    for worker in &self.workers {
+      hits.merge(&worker.hits);
+    }
+
+    hits
+  }
+
+  /// Return the first hits of all workers in the order of their tries, at
+  /// most `max` of them.
+  pub(crate) fn hits_detail(&self, max: usize) -> Vec<Hit> {
+    let mut hits_detail = self
+      .workers
+      .iter()
+      .flat_map(|worker| worker.hits_detail.iter().cloned())
+      .collect::<Vec<_>>();
+
+    hits_detail.sort_by_key(|hit| hit.hit_on);
+    hits_detail.truncate(max);
+    hits_detail
+  }
+
+  /// Return the tries left to each worker.
+  pub(crate) fn remaining(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+    self
+      .workers
+      .iter()
+      .map(|worker| worker.range.start + worker.done..worker.range.end)
+  }
+}
+
+/// Hash the options of a research which decide its tries and hits with
+/// FNV-1a, stable across builds unlike the hashers of `std`.
+pub(crate) fn config_hash(
+  tries: usize,
+  source: Source,
+  patterns: &Matchers,
+  options: &RunOptions,
+) -> u64 {
+  let source = match source {
+    Source::Random { times } => format!("random {times}"),
+    Source::List(candidates) => format!("list {candidates:?}"),
+  };
+  let config = format!(
+    "{tries}\0{source}\0{:?}\0{:?}\0{:?}\0{:?}\0{:?}",
+    patterns.patterns().collect::<Vec<_>>(),
+    options.symbols,
+    options.seed,
+    options.rng,
+    options.match_mode,
+  );
+
+  config.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+    (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+  })
+}
+
+/// Writer of the checkpoint of a running research, updated by its workers.
+pub(crate) struct Checkpointer {
+  path: PathBuf,
+  every: Duration,
+  max_hit_details: usize,
+  /// Latest state of the workers, when it was last serialized to be written
+  /// and how many times it was
+  state: Mutex<(Checkpoint, Instant, u64)>,
+  /// Number of the serialization last written, so a worker late to write
+  /// does not write over a later state
+  written: Mutex<u64>,
+}
+
+impl Checkpointer {
+  /// Write `checkpoint` to `path` as its workers go, at most every `every`,
+  /// with the first `max_hit_details` hits of each worker.
+  pub fn new(
+    path: PathBuf,
+    every: Duration,
+    max_hit_details: usize,
+    checkpoint: Checkpoint,
+  ) -> Self {
+    Self {
+      path,
+      every,
+      max_hit_details,
+      state: Mutex::new((checkpoint, Instant::now(), 0)),
+      written: Mutex::new(0),
+    }
+  }
+
+  /// Record that worker `id` did `done` tries of its range, with the `hits`
+  /// found since its previous update, writing the checkpoint if it is due.
+  pub fn update(
+    &self,
+    id: usize,
+    done: usize,
+    rng_state: u64,
+    hits: &[Hit],
+  ) -> Result<()> {
+    let serialized = {
+      let mut state = self.state.lock().unwrap();
+      let (checkpoint, serialized_at, serial) = &mut *state;
+      let worker = &mut checkpoint.workers[id];
+
+      worker.done = done;
+      worker.rng_state = rng_state;
+
+      for hit in hits {
+        worker
+          .hits
+          .add_count_for(hit.pattern, hit.chars.clone(), hit.matches);
+
+        if worker.hits_detail.len() < self.max_hit_details {
+          worker.hits_detail.push(hit.clone());
+        }
+      }
+
+      if serialized_at.elapsed() < self.every {
+        return Ok(());
+      }
+
+      *serialized_at = Instant::now();
+      *serial += 1;
+
+      (*serial, serialize(checkpoint)?)
+    };
+
+    // written without holding up the other workers
+    self.write_serialized(serialized)
+  }
+
+  /// Write the latest state of the workers.
+  pub fn write(&self) -> Result<()> {
+    let serialized = {
+      let mut state = self.state.lock().unwrap();
+
+      state.2 += 1;
+
+      (state.2, serialize(&state.0)?)
+    };
+
+    self.write_serialized(serialized)
+  }
+
+  /// Write the `contents` of the serialization `serial`, unless a later one
+  /// was already written.
+  fn write_serialized(&self, (serial, contents): (u64, Vec<u8>)) -> Result<()> {
+    let mut written = self.written.lock().unwrap();
+
+    if serial <= *written {
+      return Ok(());
+    }
+
+    atomic_write(&self.path, contents)
+      .map_err(WakuchinError::CheckpointIoError)?;
+    *written = serial;
+
+    Ok(())
+  }
+}
+
+fn serialize(checkpoint: &Checkpoint) -> Result<Vec<u8>> {
+  serde_json::to_vec(checkpoint)
+    .map_err(|e| WakuchinError::CheckpointIoError(e.into()))
+}
@@ -0,0 +1,93 @@
+//! Console close events of Windows, which the CRT raises no signal for, so
+//! researches are stopped as on Ctrl-C when their console is closed
+
+use std::io;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CTRL_CLOSE_EVENT: u32 = 2;
+const CTRL_LOGOFF_EVENT: u32 = 5;
+const CTRL_SHUTDOWN_EVENT: u32 = 6;
+
+/// How long the process is kept alive for the researches to stop, Windows
+/// ending it 5 seconds after the console is closed anyway.
+const GRACE: Duration = Duration::from_millis(4500);
+
+type HandlerRoutine = unsafe extern "system" fn(u32) -> i32;
+
+#[link(name = "kernel32")]
+extern "system" {
+  fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+}
+
+struct Researches {
+  installed: bool,
+  next_id: usize,
+  /// Stop channels of the running researches
+  stops: Vec<(usize, flume::Sender<()>)>,
+}
+
+static RESEARCHES: Mutex<Researches> = Mutex::new(Researches {
+  installed: false,
+  next_id: 0,
+  stops: Vec::new(),
+});
+
+fn researches() -> std::sync::MutexGuard<'static, Researches> {
+  // never panic in the handler, the lock only guards a list
+  RESEARCHES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+unsafe extern "system" fn on_event(event: u32) -> i32 {
+  if !matches!(
+    event,
+    CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT
+  ) {
+    return 0;
+  }
+
+  for (_, stop) in &researches().stops {
+    let _ = stop.try_send(());
+  }
+
+  // the process ends as soon as the handler returns
+  let started = Instant::now();
+
+  while started.elapsed() < GRACE && !researches().stops.is_empty() {
+    thread::sleep(Duration::from_millis(10));
+  }
+
+  1
+}
+
+/// Registration of a research to stop through `stop` once the console is
+/// closed, until it is unregistered.
+pub(crate) struct CloseHandler {
+  id: usize,
+}
+
+impl CloseHandler {
+  pub(crate) fn register(stop: flume::Sender<()>) -> io::Result<Self> {
+    let mut researches = researches();
+
+    if !researches.installed {
+      if unsafe { SetConsoleCtrlHandler(Some(on_event), 1) } == 0 {
+        return Err(io::Error::last_os_error());
+      }
+
+      researches.installed = true;
+    }
+
+    let id = researches.next_id;
+
+    researches.next_id += 1;
+    researches.stops.push((id, stop));
+
+    Ok(Self { id })
+  }
+
+  pub(crate) fn unregister(&self) {
+    researches().stops.retain(|(id, _)| *id != self.id);
+  }
+}
@@ -1,6 +1,8 @@
 //! Wakuchin conversion functions
 
-use crate::symbol;
+use std::borrow::Cow;
+
+use crate::symbol::{self, SymbolSet};
 
 fn char_to_wakuchin(char: char) -> char {
   match char {
@@ -70,8 +72,76 @@ pub fn wakuchin_to_chars(chars: &str) -> String {
   chars.chars().map(self::wakuchin_to_char).collect()
 }
 
+/// Convert internal symbols to the external symbols of `symbols`, unknown
+/// chars become `'\0'`.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::convert::{to_external, to_internal};
+/// use wakuchin::symbol::SymbolSet;
+///
+/// let symbols = SymbolSet::new("ABCDE", "あいうえお")?;
+///
+/// assert_eq!(to_external("EDCBA", &symbols), "おえういあ");
+/// assert_eq!(to_internal("おえういあ", &symbols), "EDCBA");
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[inline]
+pub fn to_external(chars: &str, symbols: &SymbolSet) -> String {
+  chars
+    .chars()
+    .map(|c| symbols.to_external(c).unwrap_or('\0'))
+    .collect()
+}
+
+/// Convert external symbols of `symbols` to internal symbols, the inverse
+/// of `to_external`.
+#[inline]
+pub fn to_internal(chars: &str, symbols: &SymbolSet) -> String {
+  chars
+    .chars()
+    .map(|c| symbols.to_internal(c).unwrap_or('\0'))
+    .collect()
+}
+
+/// Convert the external symbols of `symbols` in a regex or a literal to
+/// internal symbols, leaving other chars as they are, so patterns can be
+/// written like "^わくちん" as well as "^WKCN".
+///
+/// Chars which are internal symbols too are not converted.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::convert::pattern_to_internal;
+/// use wakuchin::symbol::SymbolSet;
+///
+/// let symbols = SymbolSet::default();
+///
+/// assert_eq!(pattern_to_internal("^(わくちん|WKNC)+$", &symbols), "^(WKCN|WKNC)+$");
+/// assert_eq!(pattern_to_internal("[わく]{2}", &symbols), "[WK]{2}");
+/// ```
+pub fn pattern_to_internal<'a>(
+  pattern: &'a str,
+  symbols: &SymbolSet,
+) -> Cow<'a, str> {
+  let convert = |c: char| match symbols.to_internal(c) {
+    Some(internal) if symbols.to_external(c).is_none() => internal,
+    _ => c,
+  };
+
+  if pattern.chars().all(|c| convert(c) == c) {
+    Cow::Borrowed(pattern)
+  } else {
+    Cow::Owned(pattern.chars().map(convert).collect())
+  }
+}
+
 #[cfg(test)]
 mod test {
+  use crate::symbol::SymbolSet;
   use crate::{convert, symbol};
 
   #[test]
@@ -125,4 +195,22 @@ mod test {
   fn test_wakuchin_to_chars() {
     assert_eq!(convert::wakuchin_to_chars("わくんちわくんち"), "WKNCWKNC");
   }
+
+  #[test]
+  fn test_pattern_to_internal() -> Result<(), crate::error::WakuchinError> {
+    let symbols = SymbolSet::default();
+
+    assert_eq!(convert::pattern_to_internal("^わくちん", &symbols), "^WKCN");
+    assert!(matches!(
+      convert::pattern_to_internal("^WKCN", &symbols),
+      std::borrow::Cow::Borrowed("^WKCN")
+    ));
+
+    // "B" is both symbols, so it stays an internal one
+    let symbols = SymbolSet::new("AB", "BC")?;
+
+    assert_eq!(convert::pattern_to_internal("BC", &symbols), "BB");
+
+    Ok(())
+  }
 }
@@ -0,0 +1,2111 @@
+//! Research engine shared by `run_par` and `run_seq`, so features are only
+//! implemented once and only the way workers run differs
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread::{self, scope, Scope};
+use std::time::{Duration, Instant};
+
+use divide_range::RangeDivisions;
+use instant::SystemTime;
+use itertools::Either;
+
+use crate::aggregator::{ProgressAggregator, ProgressReporter};
+use crate::cancel::CancellationToken;
+use crate::candidate::Candidate;
+use crate::channel::{self, Sender};
+use crate::checkpoint::{
+  config_hash, Checkpoint, Checkpointer, WorkerCheckpoint, UPDATE_EVERY,
+};
+use crate::error::WakuchinError;
+use crate::generator::{
+  new_generator, GeneratorFactory, ListGenerator, WakuchinGenerator,
+};
+use crate::handlers::snapshot::SnapshotProgressHandler;
+use crate::handlers::{
+  ControlFlow, GuardedProgressHandler, HandlerErrorPolicy, ProgressHandler,
+  RunInfo,
+};
+use crate::hit::counter::ThreadHitCounter;
+use crate::hit::HitCounter;
+use crate::math::{wilson_interval, z_score, Rarity, DEFAULT_NOTABLE_RARITY};
+use crate::pattern::{Locator, MatchMode, Matchers, PatternSet, RegexEngine};
+use crate::pause::PauseState;
+use crate::progress::{
+  DoneDetail, IdleDetail, ProcessingDetail, Progress, ProgressIntervalPolicy,
+  ProgressKind,
+};
+use crate::prune::{Pruner, REPORT_EVERY};
+use crate::render::{Render, ThreadRender};
+use crate::result::{
+  Hit, HitCount, NotableHit, PatternHits, StopReason, WakuchinResult,
+  WorkerFailure,
+};
+use crate::rng::RngBackend;
+use crate::shutdown::{Shutdown, Steps};
+use crate::symbol::SymbolSet;
+use crate::sync::{AtomicBool, AtomicUsize, Mutex, Ordering};
+use crate::threads::{self, ThreadConfig};
+use crate::worker::{
+  HitCallback, RunOptions, YieldCallback, DEFAULT_SHUTDOWN_GRACE,
+};
+
+type Result<T> = std::result::Result<T, WakuchinError>;
+
+/// How the workers of a research run.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Executor<'a> {
+  /// Workers on their own threads, with threads for the render and the hit
+  /// counter
+  Threads(usize),
+  /// Workers on the threads of a pool, one for each of its threads, kept
+  /// between researches
+  Pool(&'a rayon_core::ThreadPool),
+  /// A single worker on the calling thread, rendering inline
+  Inline,
+}
+
+/// Where the tries of a research come from.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Source<'a> {
+  /// Generated strings of `times`
+  Random { times: usize },
+  /// Strings of a list, one try each
+  List(&'a [String]),
+}
+
+/// Arguments of a research.
+pub(crate) struct Research<'a> {
+  pub tries: usize,
+  pub source: Source<'a>,
+  pub patterns: &'a Matchers,
+  pub progress_handler: Box<dyn ProgressHandler>,
+  pub progress_interval: ProgressIntervalPolicy,
+  pub options: RunOptions,
+  /// Matchers compiled ahead, compiled for the research if `None`
+  pub compiled: Option<&'a Compiled>,
+}
+
+/// Matchers of a research, compiled once for all runs of a session.
+pub(crate) struct Compiled {
+  patterns: PatternSet,
+  /// Locator of the matches of hits, if they are captured or counted
+  locator: Option<Locator>,
+  notable_patterns: Option<PatternSet>,
+}
+
+impl Compiled {
+  pub(crate) fn new(patterns: &Matchers, options: &RunOptions) -> Result<Self> {
+    let RunOptions {
+      symbols,
+      regex_engine,
+      capture_matches,
+      match_mode,
+      notable_patterns,
+      ..
+    } = options;
+
+    Ok(Self {
+      // strings are matched as generated, without going through `String`
+      patterns: PatternSet::new(patterns, symbols, *regex_engine)?,
+      locator: (*capture_matches || *match_mode == MatchMode::CountAll)
+        .then(|| Locator::new(patterns, symbols))
+        .transpose()?,
+      notable_patterns: notable_patterns
+        .as_ref()
+        .map(|notable_patterns| {
+          PatternSet::new(notable_patterns, symbols, *regex_engine)
+        })
+        .transpose()?,
+    })
+  }
+}
+
+/// State shared by the workers of a running research.
+struct Context<'a> {
+  tries: usize,
+  source: Source<'a>,
+  matchers: &'a Matchers,
+  patterns: &'a PatternSet,
+  regex_engine: RegexEngine,
+  /// Locator of the matches of hits, if they are captured or counted
+  locator: Option<&'a Locator>,
+  capture_matches: bool,
+  match_mode: MatchMode,
+  notable_patterns: Option<&'a PatternSet>,
+  rarity: Option<Rarity>,
+  progress_interval: ProgressIntervalPolicy,
+  seed: Option<u64>,
+  symbols: &'a SymbolSet,
+  rng: RngBackend,
+  generator: Option<&'a GeneratorFactory>,
+  shutdown_grace: Duration,
+  is_stopped: &'a Arc<AtomicBool>,
+  pause: &'a Arc<PauseState>,
+  targets: &'a HitTargets,
+  early_stop: &'a EarlyStop,
+  confidence: Option<&'a ConfidenceTarget>,
+  pruner: Option<&'a Pruner>,
+  work: &'a Work,
+  resume: Option<&'a Checkpoint>,
+  checkpointer: Option<&'a Checkpointer>,
+  on_hit: &'a [HitCallback],
+  /// Hits kept in the details by each worker
+  max_hit_details: usize,
+  threads: &'a ThreadConfig,
+  stall_timeout: Option<Duration>,
+  /// Tries between calls to the callback, only for inline researches
+  yield_every: Option<(usize, &'a YieldCallback)>,
+}
+
+/// Number of chunks the tries of a research are divided into, unless they
+/// would be too small or too large.
+const CHUNKS: usize = 1024;
+
+/// Fewest tries of a chunk, so workers rarely take another.
+const MIN_CHUNK: usize = 64;
+
+/// Most tries of a chunk, so workers run out of them at about the same
+/// time.
+const MAX_CHUNK: usize = 1 << 16;
+
+/// Return the tries of each chunk of a research of `tries`, the same
+/// whatever the number of workers, so seeded researches try the same strings
+/// with any of them.
+fn chunk_size(tries: usize) -> usize {
+  (tries / CHUNKS).clamp(MIN_CHUNK, MAX_CHUNK)
+}
+
+/// Divide the chunks of `tries` evenly into a range for each of `workers`.
+fn divide_chunks(
+  tries: usize,
+  chunk: usize,
+  workers: usize,
+) -> Vec<Range<usize>> {
+  (0..tries.div_ceil(chunk))
+    .divide_evenly_into(workers)
+    .map(|chunks| {
+      chunks.start * chunk..chunks.end.saturating_mul(chunk).min(tries)
+    })
+    .collect()
+}
+
+/// Tries taken by a worker at once.
+struct Chunk {
+  /// Index of the chunk, which seeds the generator of its tries
+  index: usize,
+  tries: Range<usize>,
+  /// Whether the chunk goes on from where a resumed worker left off
+  continued: bool,
+}
+
+/// How the tries of a research are handed out to its workers.
+enum Work {
+  /// A fixed range of chunks for each worker, the tries left to it when
+  /// resumed, as checkpoints record the progress of each worker
+  Ranges {
+    ranges: Vec<Range<usize>>,
+    chunk: usize,
+  },
+  /// Chunks taken by whichever worker is free, so fast workers are not held
+  /// up by slow ones
+  Shared {
+    next: AtomicUsize,
+    tries: usize,
+    chunk: usize,
+    workers: usize,
+  },
+}
+
+impl Work {
+  fn workers(&self) -> usize {
+    match self {
+      Self::Ranges { ranges, .. } => ranges.len(),
+      Self::Shared { workers, .. } => *workers,
+    }
+  }
+
+  /// Return the chunks taken by the worker `id`, one after another.
+  fn chunks(&self, id: usize) -> impl Iterator<Item = Chunk> + '_ {
+    match self {
+      Self::Ranges { ranges, chunk } => {
+        let range = ranges[id].clone();
+        let mut start = range.start;
+
+        Either::Left(std::iter::from_fn(move || {
+          if start >= range.end {
+            return None;
+          }
+
+          let end = (start / chunk + 1).saturating_mul(*chunk).min(range.end);
+          let taken = Chunk {
+            index: start / chunk,
+            tries: start..end,
+            continued: start % chunk != 0,
+          };
+
+          start = end;
+
+          Some(taken)
+        }))
+      }
+      Self::Shared {
+        next, tries, chunk, ..
+      } => Either::Right(std::iter::from_fn(move || {
+        let start = next
+          .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |start| {
+            (start < *tries).then(|| start.saturating_add(*chunk))
+          })
+          .ok()?;
+
+        Some(Chunk {
+          index: start / chunk,
+          tries: start..start.saturating_add(*chunk).min(*tries),
+          continued: false,
+        })
+      })),
+    }
+  }
+
+  /// Return the tries the worker `id` left undone when it stopped after
+  /// `tally`, which the other workers never take over.
+  fn untried(&self, id: usize, tally: &Tally) -> usize {
+    match self {
+      Self::Ranges { ranges, .. } => ranges[id].len(),
+      // the other workers only take the chunks it has not taken
+      Self::Shared { .. } => tally.taken,
+    }
+    .saturating_sub(tally.current)
+  }
+
+  /// Estimate the tries of the worker `id`, which did `done` of them and
+  /// took `chunk` next.
+  fn estimate(&self, id: usize, done: usize, chunk: &Range<usize>) -> usize {
+    match self {
+      Self::Ranges { ranges, .. } => done + (ranges[id].end - chunk.start),
+      Self::Shared {
+        next,
+        tries,
+        workers,
+        ..
+      } => {
+        let left = tries.saturating_sub(next.load(Ordering::Relaxed));
+
+        done + chunk.len() + left / workers
+      }
+    }
+  }
+
+  /// Estimate the tries of the worker `id` before it starts.
+  fn initial_estimate(&self, id: usize) -> usize {
+    match self {
+      Self::Ranges { ranges, .. } => ranges[id].len(),
+      Self::Shared { tries, workers, .. } => tries.div_ceil(*workers),
+    }
+  }
+}
+
+/// Hit targets of the patterns and of all of them, the first one reached
+/// stopping the research early.
+struct HitTargets {
+  targets: BTreeMap<usize, usize>,
+  /// Hits of each pattern with a target, in the order of `targets`
+  hits: Vec<AtomicUsize>,
+  limit: Option<usize>,
+  /// Hits of all patterns, only counted with a limit
+  total: AtomicUsize,
+}
+
+impl HitTargets {
+  fn new(targets: BTreeMap<usize, usize>, limit: Option<usize>) -> Self {
+    Self {
+      hits: targets.keys().map(|_| AtomicUsize::new(0)).collect(),
+      targets,
+      limit,
+      total: AtomicUsize::new(0),
+    }
+  }
+
+  /// Count `hits` more hits of `pattern`, stopping the research at its
+  /// target or at the limit.
+  #[inline]
+  fn add(&self, pattern: usize, hits: usize, early_stop: &EarlyStop) {
+    if let Some(limit) = self.limit {
+      if self.total.fetch_add(hits, Ordering::Relaxed) + hits >= limit {
+        early_stop.stop(StopReason::HitLimit { hits: limit });
+      }
+    }
+
+    let Some(index) = self.targets.keys().position(|p| *p == pattern) else {
+      return;
+    };
+
+    let target = self.targets[&pattern];
+
+    if self.hits[index].fetch_add(hits, Ordering::Relaxed) + hits >= target {
+      early_stop.stop(StopReason::PatternTarget {
+        pattern,
+        hits: target,
+      });
+    }
+  }
+}
+
+/// Precision of the hit probability which stops the research, once the
+/// interval of the tries and hits reported by the workers is narrow enough.
+struct ConfidenceTarget {
+  confidence: f64,
+  margin: f64,
+  z: f64,
+  tries: AtomicUsize,
+  /// Tries which hit any pattern, so the probability is at most 1
+  hits: AtomicUsize,
+}
+
+impl ConfidenceTarget {
+  fn new((confidence, margin): (f64, f64)) -> Self {
+    Self {
+      confidence,
+      margin,
+      z: z_score(confidence),
+      tries: AtomicUsize::new(0),
+      hits: AtomicUsize::new(0),
+    }
+  }
+
+  /// Add the `tries` and `hits` of a worker since its last report, stopping
+  /// the research once the interval is narrower than the margin.
+  ///
+  /// `hits` are reset, so the worker can count its next hits in place.
+  fn report(&self, tries: usize, hits: &mut usize, early_stop: &EarlyStop) {
+    let hits = std::mem::take(hits);
+    let hits = self.hits.fetch_add(hits, Ordering::Relaxed) + hits;
+    let tries = self.tries.fetch_add(tries, Ordering::Relaxed) + tries;
+    let (low, high) = wilson_interval(hits, tries, self.z);
+
+    if high - low < self.margin {
+      early_stop.stop(StopReason::Confident {
+        confidence: self.confidence,
+        margin: self.margin,
+      });
+    }
+  }
+}
+
+/// Why the workers stop before all of their tries, keeping their results.
+struct EarlyStop {
+  is_set: AtomicBool,
+  reason: Mutex<Option<StopReason>>,
+}
+
+impl EarlyStop {
+  fn new() -> Self {
+    Self {
+      is_set: AtomicBool::new(false),
+      reason: Mutex::new(None),
+    }
+  }
+
+  /// Stop the workers for `reason`, unless they are stopping for another.
+  fn stop(&self, reason: StopReason) {
+    let mut current = self.reason.lock().unwrap();
+
+    // only the first reason is reported
+    if current.is_none() {
+      *current = Some(reason);
+      self.is_set.store(true, Ordering::SeqCst);
+    }
+  }
+
+  /// Cheap check to call on every iteration.
+  #[inline]
+  fn is_set(&self) -> bool {
+    self.is_set.load(Ordering::Relaxed)
+  }
+
+  fn reason(&self) -> Option<StopReason> {
+    self.reason.lock().unwrap().clone()
+  }
+}
+
+/// Run `research` with `executor`.
+pub(crate) fn run(
+  research: Research,
+  executor: Executor,
+) -> Result<WakuchinResult> {
+  let Research {
+    tries,
+    source,
+    patterns,
+    progress_handler,
+    progress_interval,
+    options,
+    compiled,
+  } = research;
+
+  // only shuffled symbols are uniform over the strings
+  let rarity = match source {
+    Source::Random { times } if options.generator.is_none() => Rarity::of(
+      times,
+      &options.symbols,
+      options.notable_rarity.unwrap_or(DEFAULT_NOTABLE_RARITY),
+    ),
+    _ => None,
+  };
+
+  if let Some(pattern) = options
+    .hit_targets
+    .keys()
+    .find(|pattern| **pattern >= patterns.len())
+  {
+    return Err(anyhow::anyhow!("no pattern {pattern} to stop at").into());
+  }
+
+  if let Some((0, _)) = options.yield_every {
+    return Err(anyhow::anyhow!("cannot yield every 0 tries").into());
+  }
+
+  if let Some((confidence, margin)) = options.confidence_target {
+    if !(0.0 < confidence && confidence < 1.0) {
+      return Err(
+        anyhow::anyhow!("confidence {confidence} is not between 0 and 1")
+          .into(),
+      );
+    }
+
+    if !(0.0 < margin && margin <= 1.0) {
+      return Err(
+        anyhow::anyhow!("margin {margin} is not between 0 and 1").into(),
+      );
+    }
+  }
+
+  let config_hash = (options.checkpoint.is_some() || options.resume.is_some())
+    .then(|| config_hash(tries, source, patterns, &options));
+
+  if let Some(resume) = &options.resume {
+    if Some(resume.config_hash) != config_hash || resume.tries != tries {
+      return Err(WakuchinError::CheckpointMismatch);
+    }
+
+    if let Executor::Inline = executor {
+      return Err(
+        anyhow::anyhow!("checkpoints are resumed in parallel").into(),
+      );
+    }
+  }
+
+  if tries == 0 {
+    return Ok(WakuchinResult {
+      tries: 0,
+      hits_total: 0,
+      hits: Vec::new(),
+      hits_detail: Vec::new(),
+      patterns: pattern_hits(patterns, &[], &options.hit_targets),
+      rarity,
+      stop_reason: None,
+      failures: Vec::new(),
+    });
+  }
+
+  if let Source::Random { times: 0 } = source {
+    return Err(WakuchinError::TimesIsZero);
+  }
+
+  let owned;
+  let compiled = match compiled {
+    Some(compiled) => compiled,
+    None => {
+      owned = Compiled::new(patterns, &options)?;
+      &owned
+    }
+  };
+
+  let RunOptions {
+    seed,
+    pause,
+    cancel,
+    symbols,
+    rng,
+    generator,
+    shutdown_grace,
+    capture_matches,
+    match_mode,
+    regex_engine,
+    notable_rarity: _,
+    notable_patterns: _,
+    hit_targets,
+    hit_limit,
+    confidence_target,
+    time_limit,
+    prune,
+    checkpoint,
+    resume,
+    on_hit,
+    max_hit_details,
+    threads,
+    stall_timeout,
+    yield_every,
+    snapshots,
+    handler_error_policy,
+    on_warning,
+  } = options;
+  let max_hit_details = max_hit_details.unwrap_or(usize::MAX);
+
+  let progress_handler: Box<dyn ProgressHandler> = match handler_error_policy {
+    HandlerErrorPolicy::Abort => progress_handler,
+    policy => Box::new(GuardedProgressHandler::new(
+      progress_handler,
+      policy,
+      on_warning,
+    )),
+  };
+  // snapshots are recorded even if the handler fails
+  let progress_handler: Box<dyn ProgressHandler> = match snapshots {
+    Some(reader) => {
+      // the handler only sees the tries left of resumed researches
+      let baseline = resume.as_ref().map_or(0, Checkpoint::current);
+
+      Box::new(
+        SnapshotProgressHandler::new(tries - baseline, progress_handler)
+          .with_baseline(baseline)
+          .with_reader(reader),
+      )
+    }
+    None => progress_handler,
+  };
+
+  let is_stopped = Arc::new(AtomicBool::new(false));
+  let pause = pause.into_state();
+  // patterns with a target have to be matched until they reach it
+  let pruner = prune.filter(|_| patterns.len() > 1).map(|rule| {
+    Pruner::new(
+      rule,
+      (0..patterns.len())
+        .map(|pattern| !hit_targets.contains_key(&pattern))
+        .collect(),
+    )
+  });
+  let targets = HitTargets::new(hit_targets, hit_limit);
+  let early_stop = EarlyStop::new();
+  let confidence = confidence_target.map(ConfidenceTarget::new);
+
+  let workers = match executor {
+    Executor::Threads(workers) => workers,
+    Executor::Pool(pool) => pool.current_num_threads(),
+    Executor::Inline => 1,
+  };
+  let chunk = chunk_size(tries);
+  let ranges = match &resume {
+    Some(resume) => resume
+      .workers
+      .iter()
+      .map(|worker| worker.range.clone())
+      .collect(),
+    None => divide_chunks(tries, chunk, workers),
+  };
+  let work = match &resume {
+    Some(resume) => Work::Ranges {
+      ranges: resume.remaining().collect(),
+      chunk,
+    },
+    None if checkpoint.is_some() || workers == 1 => Work::Ranges {
+      ranges: ranges.clone(),
+      chunk,
+    },
+    None => Work::Shared {
+      next: AtomicUsize::new(0),
+      tries,
+      chunk,
+      workers,
+    },
+  };
+  let checkpointer = checkpoint.map(|(path, every)| {
+    let checkpoint = resume.clone().unwrap_or_else(|| Checkpoint {
+      config_hash: config_hash.unwrap_or_default(),
+      tries,
+      workers: ranges
+        .iter()
+        .enumerate()
+        .map(|(id, range)| WorkerCheckpoint {
+          range: range.clone(),
+          done: 0,
+          rng_state: seed.map_or(0, |seed| worker_seed(seed, id)),
+          hits: HitCounter::new(),
+          hits_detail: Vec::new(),
+        })
+        .collect(),
+    });
+
+    Checkpointer::new(path, every, max_hit_details, checkpoint)
+  });
+
+  let context = Context {
+    tries,
+    source,
+    matchers: patterns,
+    patterns: &compiled.patterns,
+    regex_engine,
+    locator: compiled.locator.as_ref(),
+    capture_matches,
+    match_mode,
+    notable_patterns: compiled.notable_patterns.as_ref(),
+    rarity,
+    progress_interval,
+    seed,
+    symbols: &symbols,
+    rng,
+    generator: generator.as_ref(),
+    shutdown_grace: shutdown_grace.unwrap_or(DEFAULT_SHUTDOWN_GRACE),
+    is_stopped: &is_stopped,
+    pause: &pause,
+    targets: &targets,
+    early_stop: &early_stop,
+    confidence: confidence.as_ref(),
+    pruner: pruner.as_ref(),
+    work: &work,
+    resume: resume.as_ref(),
+    checkpointer: checkpointer.as_ref(),
+    on_hit: &on_hit,
+    max_hit_details,
+    threads: &threads,
+    stall_timeout,
+    yield_every: yield_every
+      .as_ref()
+      .map(|(every, callback)| (*every, callback)),
+  };
+
+  let signals = Signals::register()?;
+
+  // watched by the signal thread
+  #[cfg(target_arch = "wasm32")]
+  drop((cancel, time_limit));
+
+  let outcome = scope(|s| {
+    #[cfg(not(target_arch = "wasm32"))]
+    let signal_handle =
+      threads::spawn_scoped(s, threads::name("signal"), || {
+        signals.watch(&is_stopped, &pause, &cancel, &early_stop, time_limit);
+      });
+
+    // last step of the shutdown
+    let unregister = || {
+      // let the signal thread go, even if the counter is stuck
+      is_stopped.store(true, Ordering::SeqCst);
+      #[cfg(not(target_arch = "wasm32"))]
+      signals.wake();
+
+      #[cfg(not(target_arch = "wasm32"))]
+      signal_handle.join().unwrap_or_else(|e| resume_unwind(e));
+
+      signals.unregister();
+    };
+
+    match executor {
+      Executor::Threads(_) => {
+        run_threads(s, None, &context, progress_handler, unregister)
+      }
+      Executor::Pool(pool) => {
+        run_threads(s, Some(pool), &context, progress_handler, unregister)
+      }
+      Executor::Inline => run_inline(&context, progress_handler, unregister),
+    }
+  });
+
+  // even after Ctrl-C, so the research can be resumed from its last tries
+  if let Some(checkpointer) = &checkpointer {
+    checkpointer.write()?;
+  }
+
+  let Outcome {
+    mut hits_detail,
+    hits,
+    tries_done,
+    failures,
+  } = outcome?;
+
+  // along with the hits of the tries before the checkpoint
+  if let Some(resume) = &resume {
+    hits_detail.extend(resume.hits_detail(max_hit_details));
+    hits_detail.sort_by_key(|hit| hit.hit_on);
+  }
+
+  // the first hits of all workers, as they are sorted
+  hits_detail.truncate(max_hit_details);
+  let hits = match &resume {
+    Some(resume) => {
+      let all = resume.hits();
+
+      all.merge(&hits.into_iter().collect());
+      all.snapshot()
+    }
+    None => hits,
+  };
+  let baseline = resume.as_ref().map_or(0, Checkpoint::current);
+  let hits_total = hits.iter().map(|c| c.hits).sum::<usize>();
+  let stop_reason = early_stop.reason();
+  let mut pattern_hits = pattern_hits(patterns, &hits, &targets.targets);
+
+  if let Some(pruner) = &pruner {
+    for (pattern_hits, retired_at) in
+      pattern_hits.iter_mut().zip(pruner.retired_at())
+    {
+      pattern_hits.retired_at = retired_at;
+    }
+  }
+
+  Ok(WakuchinResult {
+    tries: if stop_reason.is_some() || !failures.is_empty() {
+      baseline + tries_done
+    } else {
+      tries
+    },
+    hits_total,
+    patterns: pattern_hits,
+    hits,
+    hits_detail,
+    rarity,
+    stop_reason,
+    failures,
+  })
+}
+
+/// Sum `hits` by pattern with the `targets` of the patterns, or nothing for
+/// a single pattern as its hits are the total.
+fn pattern_hits(
+  patterns: &Matchers,
+  hits: &[HitCount],
+  targets: &BTreeMap<usize, usize>,
+) -> Vec<PatternHits> {
+  if patterns.len() < 2 {
+    return Vec::new();
+  }
+
+  let mut pattern_hits = patterns
+    .patterns()
+    .enumerate()
+    .map(|(index, pattern)| PatternHits {
+      pattern: pattern.to_owned(),
+      hits_total: 0,
+      target: targets.get(&index).copied(),
+      retired_at: None,
+    })
+    .collect::<Vec<_>>();
+
+  for hit_count in hits {
+    pattern_hits[hit_count.pattern].hits_total += hit_count.hits;
+  }
+
+  pattern_hits
+}
+
+/// What the workers of a research did, whether they finished or not.
+struct Outcome {
+  hits_detail: Vec<Hit>,
+  hits: Vec<HitCount>,
+  tries_done: usize,
+  failures: Vec<WorkerFailure>,
+}
+
+/// Return the message of the panic with `payload`.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+  payload
+    .downcast_ref::<&str>()
+    .map(|message| (*message).to_owned())
+    .or_else(|| payload.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "unknown panic".into())
+}
+
+/// Run the workers on threads of `s`, or of `pool` if any.
+fn run_threads<'scope, 'env>(
+  s: &'scope Scope<'scope, 'env>,
+  pool: Option<&rayon_core::ThreadPool>,
+  context: &'env Context<'env>,
+  progress_handler: Box<dyn ProgressHandler>,
+  unregister: impl FnOnce(),
+) -> Result<Outcome> {
+  let (hit_tx, hit_rx) = channel::channel();
+  let (notable_tx, notable_rx) = channel::channel();
+  let (counted_tx, counted_rx) = channel::channel();
+
+  let counter = ThreadHitCounter::new(hit_rx).with_counted(counted_tx);
+
+  let mut aggregator =
+    ProgressAggregator::new(progress_handler, Duration::ZERO)
+      .with_interval_policy(context.progress_interval)
+      .with_hit_counter(counter.hit_counter())
+      .with_run_info(context.run_info());
+
+  if let Some(timeout) = context.stall_timeout {
+    aggregator = aggregator.with_stall_timeout(timeout);
+  }
+
+  let reporters = (0..context.work.workers())
+    .map(|id| aggregator.add_worker(context.work.initial_estimate(id)))
+    .collect::<Vec<_>>();
+
+  let mut render = ThreadRender::new(
+    context.is_stopped.clone(),
+    context.pause.clone(),
+    counter.clone(),
+    aggregator,
+  )
+  .with_notable_hits(notable_rx)
+  .with_hits(counted_rx);
+
+  // not scoped, so the research can give up on them if they hang at
+  // shutdown
+
+  // hit handler
+  let hit_handle = threads::spawn("hits", {
+    let counter = counter.clone();
+    let config = context.threads.clone();
+
+    move || -> Result<()> {
+      config.apply(None);
+      counter.run();
+
+      Ok(())
+    }
+  });
+
+  // progress reporter
+  let config = context.threads.clone();
+  let ui_handle = threads::spawn("render", move || -> Result<()> {
+    config.apply(None);
+    render.run()?;
+
+    Ok(())
+  });
+
+  let workers = reporters
+    .into_iter()
+    .enumerate()
+    .map(|(id, reporter)| {
+      let mut sink = ThreadSink {
+        reporter,
+        hit_tx: hit_tx.clone(),
+        notable_tx: notable_tx.clone(),
+      };
+
+      move || {
+        context.threads.apply(Some(id));
+
+        let mut tally = Tally::default();
+        let result = catch_unwind(AssertUnwindSafe(|| {
+          work(context, id, &mut sink, &mut tally)
+        }));
+
+        (tally, result)
+      }
+    })
+    .collect::<Vec<_>>();
+  let outcomes = if pool.is_some() || threads::POOLED {
+    let outcomes = Mutex::new(Vec::new());
+
+    // returns once every worker is done
+    match pool {
+      Some(pool) => pool.scope(|ps| spawn_pooled(ps, workers, &outcomes)),
+      // on the Web Workers of the application
+      None => rayon_core::scope(|ps| spawn_pooled(ps, workers, &outcomes)),
+    }
+
+    let mut outcomes = outcomes.into_inner().unwrap();
+
+    outcomes.sort_by_key(|(id, _)| *id);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+  } else {
+    workers
+      .into_iter()
+      .enumerate()
+      .map(|(id, worker)| {
+        threads::spawn_scoped(s, threads::worker_name(id), worker)
+      })
+      .collect::<Vec<_>>()
+      .into_iter()
+      .map(|handle| handle.join().unwrap_or_else(|e| resume_unwind(e)))
+      .collect::<Vec<_>>()
+  };
+
+  // keep joining on errors, the render has to stop before returning
+  let mut workers_result = Ok(());
+  let mut hits_detail = Vec::new();
+  let mut tries_done = 0;
+  let mut failures = Vec::new();
+
+  for (id, (tally, result)) in outcomes.into_iter().enumerate() {
+    match result {
+      Ok(Ok(())) => {}
+      Ok(Err(err)) => workers_result = workers_result.and(Err(err)),
+      // the other workers go on, but only take over its chunks if they share
+      // them, see `Work::untried`
+      Err(panic) => failures.push(WorkerFailure {
+        id: id + 1,
+        message: panic_message(panic.as_ref()),
+        untried: context.work.untried(id, &tally),
+      }),
+    }
+
+    hits_detail.extend(tally.hits);
+    tries_done += tally.current;
+  }
+
+  Shutdown::after_workers(workers_result).run(Steps {
+    close_hits: move || drop(hit_tx),
+    // after all workers have finished, wait for ui and hit threads to finish
+    stop_counter: || {
+      join_within(
+        vec![("hit counter", hit_handle), ("render", ui_handle)],
+        context.shutdown_grace,
+      )?
+      .into_iter()
+      .collect()
+    },
+    // rendered by the ui thread, joined with the hit thread
+    render: |_| Ok(()),
+    unregister,
+  })?;
+
+  // workers take chunks in any order
+  hits_detail.sort_by_key(|hit| hit.hit_on);
+
+  Ok(Outcome {
+    hits_detail,
+    hits: counter.get_all().into_hit_counts(),
+    tries_done,
+    failures,
+  })
+}
+
+fn run_inline(
+  context: &Context,
+  progress_handler: Box<dyn ProgressHandler>,
+  unregister: impl FnOnce(),
+) -> Result<Outcome> {
+  let mut render = Render::new(progress_handler);
+
+  let started =
+    render
+      .invoke_before_start(&context.run_info())
+      .and_then(|()| {
+        render.render_progress(context.progress_interval, || {
+          Progress(ProgressKind::Idle(IdleDetail { id: 0 }))
+        })
+      });
+  let flow = match started {
+    Ok(flow) => flow,
+    Err(e) => {
+      // the signal thread is let go like at shutdown
+      unregister();
+
+      return Err(e.into());
+    }
+  };
+
+  if flow == ControlFlow::Stop {
+    context.is_stopped.store(true, Ordering::SeqCst);
+  }
+
+  let mut sink = InlineSink {
+    render: &mut render,
+    interval: context.progress_interval,
+    total: context.tries,
+    yield_every: context.yield_every,
+  };
+
+  let mut tally = Tally::default();
+  let mut failures = Vec::new();
+  let result =
+    catch_unwind(AssertUnwindSafe(|| work(context, 0, &mut sink, &mut tally)));
+  let workers_result = match result {
+    Ok(result) => result,
+    Err(panic) => {
+      failures.push(WorkerFailure {
+        id: 1,
+        message: panic_message(panic.as_ref()),
+        untried: context.work.untried(0, &tally),
+      });
+
+      Ok(())
+    }
+  };
+  let Tally {
+    hits: hits_detail,
+    current: tries_done,
+    ..
+  } = tally;
+
+  Shutdown::after_workers(workers_result).run(Steps {
+    // hits are counted by the render
+    close_hits: || {},
+    stop_counter: || Ok(()),
+    render: |stopped| {
+      if stopped {
+        render.invoke_on_accidential_stop()?;
+
+        return Ok(());
+      }
+
+      render.render_done(
+        context.progress_interval,
+        Progress(ProgressKind::Done(DoneDetail {
+          id: 0,
+          total: tries_done,
+        })),
+      )?;
+
+      render.invoke_after_finish()?;
+
+      Ok(())
+    },
+    unregister,
+  })?;
+
+  Ok(Outcome {
+    hits_detail,
+    hits: render.hits(),
+    tries_done,
+    failures,
+  })
+}
+
+/// Where a worker reports its progress and hits.
+trait Sink {
+  /// Report the progress, returning whether the handler lets the worker go
+  /// on.
+  fn progress(
+    &mut self,
+    wakuchin: &[u8],
+    current: usize,
+  ) -> Result<ControlFlow>;
+
+  fn hit(&mut self, hit: &Hit) -> Result<()>;
+
+  /// Report the estimated number of tries of the worker, as it takes more.
+  fn total(&mut self, total: usize);
+
+  fn notable_hit(&mut self, hit: NotableHit) -> Result<()>;
+
+  /// Block while `pause` is paused.
+  fn pause(&mut self, pause: &PauseState) -> Result<()>;
+
+  fn done(&mut self) -> Result<()>;
+}
+
+/// Hits and tries done by a worker in all of its chunks, kept by its caller
+/// so they are not lost if the worker panics.
+#[derive(Default)]
+struct Tally {
+  hits: Vec<Hit>,
+  current: usize,
+  /// Tries of the chunks taken so far
+  taken: usize,
+}
+
+/// Generate and check the chunks of tries taken by the worker `id`, the
+/// loop of every worker, counting its hits and tries in `tally`.
+fn work(
+  context: &Context,
+  id: usize,
+  sink: &mut impl Sink,
+  tally: &mut Tally,
+) -> Result<()> {
+  let resumed = context.resume.map(|resume| &resume.workers[id]);
+  let offset = resumed.map_or(0, |worker| worker.done);
+
+  let Tally {
+    hits,
+    current,
+    taken,
+  } = tally;
+  let mut patterns = ActivePatterns::new(context.patterns);
+  let mut pruned_hits = vec![0; context.matchers.len()];
+  // tries which hit since the last report to the confidence target
+  let mut confident_hits = 0;
+  // hits not recorded to the checkpoint yet, if the research is checkpointed
+  let mut unrecorded = Vec::new();
+
+  'chunks: for chunk in context.work.chunks(id) {
+    // seeded by chunk, so the tries do not depend on the worker taking them
+    match resumed {
+      Some(worker) if chunk.continued => fastrand::seed(worker.rng_state),
+      _ => {
+        if let Some(seed) = context.seed {
+          fastrand::seed(worker_seed(seed, chunk.index));
+        }
+      }
+    }
+
+    sink.total(context.work.estimate(id, *current, &chunk.tries));
+    *taken += chunk.tries.len();
+
+    let (mut generator, times): (Box<dyn WakuchinGenerator + '_>, _) =
+      match context.source {
+        Source::Random { times } => (
+          new_generator(context.generator, context.symbols, context.rng),
+          times,
+        ),
+        Source::List(candidates) => (
+          Box::new(ListGenerator::new(&candidates[chunk.tries.clone()])),
+          0,
+        ),
+      };
+
+    for i in chunk.tries {
+      // checked before generating, so the generator is checkpointed as of
+      // the tries done
+      if context.is_stopped.load(Ordering::Relaxed) {
+        context.checkpoint(id, offset + *current, &unrecorded)?;
+
+        return Err(WakuchinError::Cancelled);
+      }
+
+      if context.early_stop.is_set() {
+        break 'chunks;
+      }
+
+      if context.pause.is_paused() {
+        sink.pause(context.pause)?;
+      }
+
+      let wakuchin = generator.next(times);
+
+      let mut candidate = None;
+
+      for pattern in patterns.matches(&wakuchin) {
+        let chars = candidate
+          .get_or_insert_with(|| Candidate::from_utf8_lossy(&wakuchin))
+          .clone();
+        let mut hit = Hit::new(i, chars).with_pattern(pattern);
+
+        hit.distance = context.patterns.distance(&wakuchin);
+
+        if let Some(locator) = context.locator {
+          if context.capture_matches {
+            (hit.span, hit.groups) = locator.locate(pattern, &wakuchin);
+          }
+
+          if context.match_mode == MatchMode::CountAll {
+            hit.matches = locator.count(pattern, &wakuchin);
+          }
+        }
+
+        if context.is_notable(&wakuchin) {
+          sink.notable_hit(NotableHit {
+            hit: hit.clone(),
+            found_at: SystemTime::now(),
+            seed: context.seed,
+            rarity: context.rarity,
+          })?;
+        }
+
+        sink.hit(&hit)?;
+
+        for callback in context.on_hit {
+          callback(&hit);
+        }
+
+        context
+          .targets
+          .add(pattern, hit.matches, context.early_stop);
+        pruned_hits[pattern] += hit.matches;
+
+        if context.checkpointer.is_some() {
+          unrecorded.push(hit.clone());
+        }
+
+        // the worker takes its tries in order, so these are its first hits
+        if hits.len() < context.max_hit_details {
+          hits.push(hit);
+        }
+      }
+
+      if let Some(confidence) = context.confidence {
+        confident_hits += usize::from(candidate.is_some());
+
+        if (*current + 1) % REPORT_EVERY == 0 {
+          confidence.report(
+            REPORT_EVERY,
+            &mut confident_hits,
+            context.early_stop,
+          );
+        }
+      }
+
+      if let Some(pruner) = context.pruner {
+        if (*current + 1) % REPORT_EVERY == 0 {
+          pruner.report(REPORT_EVERY, &mut pruned_hits);
+          patterns.refresh(context, pruner)?;
+        }
+      }
+
+      if (*current + 1) % UPDATE_EVERY == 0 {
+        context.checkpoint(id, offset + *current + 1, &unrecorded)?;
+        unrecorded.clear();
+      }
+
+      if sink.progress(&wakuchin, *current)? == ControlFlow::Stop {
+        // let the other workers stop too
+        context.is_stopped.store(true, Ordering::SeqCst);
+        context.checkpoint(id, offset + *current + 1, &unrecorded)?;
+
+        return Err(WakuchinError::Cancelled);
+      }
+
+      *current += 1;
+    }
+  }
+
+  context.checkpoint(id, offset + *current, &unrecorded)?;
+  sink.total(*current);
+  sink.done()?;
+
+  Ok(())
+}
+
+/// Patterns matched by a worker, rebuilt without the ones retired by the
+/// pruner.
+struct ActivePatterns<'a> {
+  all: &'a PatternSet,
+  /// Patterns left and their indices, once any was retired
+  pruned: Option<(PatternSet, Vec<usize>)>,
+  retirements: usize,
+}
+
+impl<'a> ActivePatterns<'a> {
+  fn new(all: &'a PatternSet) -> Self {
+    Self {
+      all,
+      pruned: None,
+      retirements: 0,
+    }
+  }
+
+  /// Rebuild the patterns if more of them were retired.
+  fn refresh(&mut self, context: &Context, pruner: &Pruner) -> Result<()> {
+    if pruner.retirements() == self.retirements {
+      return Ok(());
+    }
+
+    let retired_at = pruner.retired_at();
+    let indices = (0..retired_at.len())
+      .filter(|pattern| retired_at[*pattern].is_none())
+      .collect::<Vec<_>>();
+
+    self.retirements = retired_at.len() - indices.len();
+    self.pruned = Some((
+      PatternSet::new(
+        &context.matchers.select(&indices),
+        context.symbols,
+        context.regex_engine,
+      )?,
+      indices,
+    ));
+
+    Ok(())
+  }
+
+  /// Return the indices of the patterns matching `chars` among all of them.
+  #[inline]
+  fn matches<'b>(&'b self, chars: &[u8]) -> impl Iterator<Item = usize> + 'b {
+    match &self.pruned {
+      None => Either::Left(self.all.matches(chars)),
+      Some((patterns, indices)) => {
+        Either::Right(patterns.matches(chars).map(|index| indices[index]))
+      }
+    }
+  }
+}
+
+impl Context<'_> {
+  /// Return the info of the research passed to its handler as it starts.
+  fn run_info(&self) -> RunInfo {
+    RunInfo {
+      tries: self.tries,
+      times: match self.source {
+        Source::Random { times } => times,
+        Source::List(_) => 0,
+      },
+      patterns: self.matchers.patterns().map(Into::into).collect(),
+      workers: self.work.workers(),
+      seed: self.seed,
+      started_at: SystemTime::now(),
+    }
+  }
+
+  /// Record that the worker `id` did `done` tries of its range, with `hits`
+  /// since its previous record, if the research is checkpointed.
+  #[inline]
+  fn checkpoint(&self, id: usize, done: usize, hits: &[Hit]) -> Result<()> {
+    match self.checkpointer {
+      Some(checkpointer) => {
+        checkpointer.update(id, done, fastrand::get_seed(), hits)
+      }
+      None => Ok(()),
+    }
+  }
+
+  /// Return whether the hits of `wakuchin` are notable.
+  #[inline]
+  fn is_notable(&self, wakuchin: &[u8]) -> bool {
+    self.rarity.is_some_and(|rarity| rarity.notable)
+      || self.notable_patterns.is_some_and(|notable_patterns| {
+        notable_patterns.matches(wakuchin).next().is_some()
+      })
+  }
+}
+
+/// Derive the seed of a worker from the seed of the research, the first
+/// worker using the seed itself.
+#[inline]
+fn worker_seed(seed: u64, id: usize) -> u64 {
+  seed ^ (id as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+/// Reports to the render and hit counter threads through channels.
+struct ThreadSink {
+  reporter: ProgressReporter,
+  hit_tx: Sender<Hit>,
+  notable_tx: Sender<NotableHit>,
+}
+
+impl Sink for ThreadSink {
+  #[inline]
+  fn progress(
+    &mut self,
+    wakuchin: &[u8],
+    current: usize,
+  ) -> Result<ControlFlow> {
+    // the render thread stops the workers
+    self.reporter.report(wakuchin, current);
+
+    Ok(ControlFlow::Continue)
+  }
+
+  #[inline]
+  fn hit(&mut self, hit: &Hit) -> Result<()> {
+    self
+      .hit_tx
+      .send(hit.clone())
+      .expect("hit channel is unavailable");
+
+    Ok(())
+  }
+
+  #[inline]
+  fn total(&mut self, total: usize) {
+    self.reporter.set_total(total);
+  }
+
+  #[inline]
+  fn notable_hit(&mut self, hit: NotableHit) -> Result<()> {
+    // the render thread may be gone after a stop, the hit is counted anyway
+    let _ = self.notable_tx.send(hit);
+
+    Ok(())
+  }
+
+  #[inline]
+  fn pause(&mut self, pause: &PauseState) -> Result<()> {
+    // the render thread reports the pause
+    pause.wait_while_paused();
+
+    Ok(())
+  }
+
+  #[inline]
+  fn done(&mut self) -> Result<()> {
+    self.reporter.done();
+
+    Ok(())
+  }
+}
+
+/// Reports to a render on the same thread.
+struct InlineSink<'a> {
+  render: &'a mut Render,
+  interval: ProgressIntervalPolicy,
+  total: usize,
+  yield_every: Option<(usize, &'a YieldCallback)>,
+}
+
+impl Sink for InlineSink<'_> {
+  fn progress(
+    &mut self,
+    wakuchin: &[u8],
+    current: usize,
+  ) -> Result<ControlFlow> {
+    let flow = self.render.render_progress(self.interval, || {
+      Progress(ProgressKind::Processing(ProcessingDetail::new(
+        0,
+        String::from_utf8_lossy(wakuchin).into_owned(),
+        current,
+        self.total,
+      )))
+    })?;
+
+    if let Some((every, callback)) = self.yield_every {
+      if (current + 1).is_multiple_of(every) {
+        callback();
+      }
+    }
+
+    Ok(flow)
+  }
+
+  #[inline]
+  fn hit(&mut self, hit: &Hit) -> Result<()> {
+    self.render.handle_hit(hit)?;
+
+    Ok(())
+  }
+
+  #[inline]
+  fn total(&mut self, _total: usize) {
+    // the only worker does all of the tries
+  }
+
+  #[inline]
+  fn notable_hit(&mut self, hit: NotableHit) -> Result<()> {
+    self.render.handle_notable_hit(&hit)?;
+
+    Ok(())
+  }
+
+  #[inline]
+  fn pause(&mut self, pause: &PauseState) -> Result<()> {
+    self.render.pause(pause)?;
+
+    Ok(())
+  }
+
+  #[inline]
+  fn done(&mut self) -> Result<()> {
+    // the final progress is rendered at shutdown
+    Ok(())
+  }
+}
+
+/// Spawn `workers` on the pool of `ps`, collecting their outcomes with their
+/// index in `outcomes`.
+fn spawn_pooled<'scope, W, O>(
+  ps: &rayon_core::Scope<'scope>,
+  workers: Vec<W>,
+  outcomes: &'scope Mutex<Vec<(usize, O)>>,
+) where
+  W: FnMut() -> O + Send + 'scope,
+  O: Send + 'scope,
+{
+  for (id, mut worker) in workers.into_iter().enumerate() {
+    ps.spawn(move |_| outcomes.lock().unwrap().push((id, worker())));
+  }
+}
+
+/// Join the threads of `handles` within `grace`, failing with the names of
+/// the threads still running after it, which are left detached.
+fn join_within<T>(
+  handles: Vec<(&'static str, threads::Handle<T>)>,
+  grace: Duration,
+) -> Result<Vec<T>> {
+  let deadline = Instant::now() + grace;
+
+  while Instant::now() < deadline
+    && !handles.iter().all(|(_, handle)| handle.is_finished())
+  {
+    thread::sleep(Duration::from_millis(10));
+  }
+
+  let stuck = handles
+    .iter()
+    .filter(|(_, handle)| !handle.is_finished())
+    .map(|(name, _)| *name)
+    .collect::<Vec<_>>();
+
+  if !stuck.is_empty() {
+    return Err(WakuchinError::ShutdownTimeout(stuck));
+  }
+
+  Ok(
+    handles
+      .into_iter()
+      .map(|(_, handle)| handle.join().unwrap_or_else(|e| resume_unwind(e)))
+      .collect(),
+  )
+}
+
+/// Signal hooks of a research, stopping it on Ctrl-C and termination and
+/// pausing it on SIGTSTP.
+struct Signals {
+  #[cfg(not(target_arch = "wasm32"))]
+  stop: (Vec<signal_hook_registry::SigId>, flume::Receiver<()>),
+  #[cfg(windows)]
+  close: crate::console::CloseHandler,
+  #[cfg(unix)]
+  pause: (signal_hook_registry::SigId, flume::Receiver<()>),
+  /// Wakes the signal thread up to check the research again
+  #[cfg(not(target_arch = "wasm32"))]
+  wake: (flume::Sender<()>, flume::Receiver<()>),
+}
+
+/// What woke the signal thread up.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(PartialEq)]
+enum Wakeup {
+  Stop,
+  #[cfg(unix)]
+  Pause,
+  /// The token, the end of the research or its time limit
+  Other,
+}
+
+impl Signals {
+  fn register() -> Result<Self> {
+    // used internally to prevent 'static lifetime issues
+    #[cfg(not(target_arch = "wasm32"))]
+    let (tx, rx) = flume::bounded(1);
+
+    // all stop the research the same way as Ctrl-C, so it is checkpointed
+    #[cfg(unix)]
+    let stop_signals = {
+      use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+      [SIGINT, SIGTERM, SIGHUP]
+    };
+    #[cfg(all(not(unix), not(target_arch = "wasm32")))]
+    let stop_signals = {
+      use signal_hook::consts::{SIGBREAK, SIGINT, SIGTERM};
+
+      [SIGINT, SIGTERM, SIGBREAK]
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let stop = {
+      let ids = stop_signals
+        .into_iter()
+        .map(|signal| {
+          let tx = tx.clone();
+
+          unsafe {
+            signal_hook_registry::register(signal, move || {
+              let _ = tx.try_send(());
+            })
+          }
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+      (ids, rx)
+    };
+
+    // closing the console is not a signal on Windows
+    #[cfg(windows)]
+    let close = crate::console::CloseHandler::register(tx)?;
+
+    // pause on job control, e.g. Ctrl-Z
+    #[cfg(unix)]
+    let pause = {
+      use signal_hook::consts::SIGTSTP;
+
+      let (tx, rx) = flume::bounded(1);
+
+      let id = unsafe {
+        signal_hook_registry::register(SIGTSTP, move || {
+          let _ = tx.try_send(());
+        })
+      }?;
+
+      (id, rx)
+    };
+
+    Ok(Self {
+      #[cfg(not(target_arch = "wasm32"))]
+      stop,
+      #[cfg(windows)]
+      close,
+      #[cfg(unix)]
+      pause,
+      #[cfg(not(target_arch = "wasm32"))]
+      wake: flume::bounded(1),
+    })
+  }
+
+  /// Handle the signals, `cancel` and `time_limit` until the research is
+  /// stopped, sleeping in between.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn watch(
+    &self,
+    is_stopped: &AtomicBool,
+    pause: &PauseState,
+    cancel: &CancellationToken,
+    early_stop: &EarlyStop,
+    time_limit: Option<Duration>,
+  ) {
+    let started = Instant::now();
+    let mut wakeup = Wakeup::Other;
+
+    let watcher = cancel.watch(self.wake.0.clone());
+
+    loop {
+      if is_stopped.load(Ordering::SeqCst) {
+        break;
+      }
+
+      if !early_stop.is_set() {
+        let reason = match time_limit {
+          _ if cancel.is_stopped() => Some(StopReason::Requested),
+          Some(limit) if started.elapsed() >= limit => {
+            Some(StopReason::TimeLimit { limit })
+          }
+          _ => None,
+        };
+
+        if let Some(reason) = reason {
+          early_stop.stop(reason);
+
+          // paused workers have to go on to stop
+          pause.cancel();
+        }
+      }
+
+      if wakeup == Wakeup::Stop || cancel.is_cancelled() {
+        is_stopped.store(true, Ordering::SeqCst);
+
+        // wake up paused threads so they can stop
+        pause.cancel();
+
+        break;
+      }
+
+      #[cfg(unix)]
+      if wakeup == Wakeup::Pause {
+        pause.suspend();
+      }
+
+      wakeup = self.wait(
+        time_limit
+          .filter(|_| !early_stop.is_set())
+          .map(|limit| started + limit),
+      );
+    }
+
+    cancel.unwatch(watcher);
+  }
+
+  /// Block until a signal, [`wake`](Self::wake) or `deadline`.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn wait(&self, deadline: Option<Instant>) -> Wakeup {
+    let selector = flume::Selector::new()
+      .recv(&self.stop.1, |_| Wakeup::Stop)
+      .recv(&self.wake.1, |_| Wakeup::Other);
+    #[cfg(unix)]
+    let selector = selector.recv(&self.pause.1, |_| Wakeup::Pause);
+
+    match deadline {
+      Some(deadline) => {
+        selector.wait_deadline(deadline).unwrap_or(Wakeup::Other)
+      }
+      None => selector.wait(),
+    }
+  }
+
+  /// Wake the signal thread up, e.g. once the research is stopped.
+  #[cfg(not(target_arch = "wasm32"))]
+  fn wake(&self) {
+    let _ = self.wake.0.try_send(());
+  }
+
+  fn unregister(&self) {
+    #[cfg(not(target_arch = "wasm32"))]
+    for id in &self.stop.0 {
+      signal_hook_registry::unregister(*id);
+    }
+
+    #[cfg(windows)]
+    self.close.unregister();
+
+    #[cfg(unix)]
+    signal_hook_registry::unregister(self.pause.0);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::borrow::Cow;
+  use std::sync::{Arc, Mutex};
+  use std::thread;
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use instant::SystemTime;
+  use regex::Regex;
+
+  use crate::error::WakuchinError;
+  use crate::generator::{ShuffleGenerator, WakuchinGenerator};
+  use crate::handlers::{ControlFlow, ProgressHandler, RunInfo};
+  use crate::pattern::Matchers;
+  use crate::progress::{Progress, ProgressIntervalPolicy, ProgressKind};
+  use crate::result::HitCount;
+  use crate::worker::RunOptions;
+
+  use super::{run, Executor, Research, Source};
+
+  /// Invocation of a progress handler, without the worker ids since they
+  /// differ between executors.
+  #[derive(Clone, Debug, PartialEq)]
+  enum Call {
+    /// Info of the run, started at the epoch so executors are compared
+    BeforeStart(RunInfo),
+    Handle {
+      /// Kind and processed tries of each progress
+      progresses: Vec<(&'static str, usize)>,
+      hit_counts: Vec<HitCount>,
+      interval: Duration,
+      current_diff: usize,
+      all_done: bool,
+    },
+    AfterFinish,
+  }
+
+  #[derive(Clone, Default)]
+  struct RecordingHandler(Arc<Mutex<Vec<Call>>>);
+
+  impl ProgressHandler for RecordingHandler {
+    fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+      self.0.lock().unwrap().push(Call::BeforeStart(RunInfo {
+        started_at: SystemTime::UNIX_EPOCH,
+        ..info.clone()
+      }));
+
+      Ok(())
+    }
+
+    fn handle(
+      &mut self,
+      progresses: &[Progress],
+      hit_counts: &[HitCount],
+      interval: Duration,
+      current_diff: usize,
+      all_done: bool,
+    ) -> Result<ControlFlow> {
+      let progresses = progresses
+        .iter()
+        .map(|progress| {
+          let kind = match progress {
+            Progress(ProgressKind::Idle(_)) => "idle",
+            Progress(ProgressKind::Processing(_)) => "processing",
+            Progress(ProgressKind::Done(_)) => "done",
+            Progress(ProgressKind::Stalled(_)) => "stalled",
+          };
+
+          (kind, progress.current())
+        })
+        .collect();
+
+      let mut hit_counts = hit_counts.to_vec();
+
+      hit_counts.sort_by(|a, b| a.chars.cmp(&b.chars));
+
+      self.0.lock().unwrap().push(Call::Handle {
+        progresses,
+        hit_counts,
+        interval,
+        current_diff,
+        all_done,
+      });
+
+      Ok(ControlFlow::Continue)
+    }
+
+    fn after_finish(&mut self) -> Result<()> {
+      self.0.lock().unwrap().push(Call::AfterFinish);
+
+      Ok(())
+    }
+  }
+
+  /// Slows down the default generator, so progresses are rendered.
+  struct SlowGenerator(ShuffleGenerator);
+
+  impl WakuchinGenerator for SlowGenerator {
+    fn next(&mut self, times: usize) -> Cow<'_, [u8]> {
+      thread::sleep(Duration::from_millis(2));
+
+      self.0.next(times)
+    }
+  }
+
+  /// Slowly generates a hit of `^WK` every other try.
+  #[derive(Default)]
+  struct AlternatingGenerator(bool);
+
+  impl WakuchinGenerator for AlternatingGenerator {
+    fn next(&mut self, _times: usize) -> Cow<'_, [u8]> {
+      thread::sleep(Duration::from_millis(2));
+      self.0 = !self.0;
+
+      Cow::Borrowed(if self.0 { b"WKCN" } else { b"CNWK" })
+    }
+  }
+
+  /// Run a research with `executor`, returning the calls to its handler.
+  fn record(
+    executor: Executor,
+    interval: impl Into<ProgressIntervalPolicy>,
+    options: RunOptions,
+  ) -> Result<Vec<Call>, WakuchinError> {
+    let handler = RecordingHandler::default();
+
+    run(
+      Research {
+        tries: 50,
+        source: Source::Random { times: 1 },
+        patterns: &Matchers::Regex(vec![Regex::new(r"^WK").unwrap()]),
+        progress_handler: Box::new(handler.clone()),
+        progress_interval: interval.into(),
+        options,
+        compiled: None,
+      },
+      executor,
+    )?;
+
+    let calls = handler.0.lock().unwrap().clone();
+
+    Ok(calls)
+  }
+
+  #[test]
+  fn test_same_calls() -> Result<(), WakuchinError> {
+    let options = RunOptions {
+      seed: Some(42),
+      ..RunOptions::default()
+    };
+
+    // never rendered periodically, so only the final rendering is left
+    let interval = Duration::from_secs(3600);
+    let seq = record(Executor::Inline, interval, options.clone())?;
+    let par = record(Executor::Threads(1), interval, options)?;
+
+    assert_eq!(seq, par);
+    assert!(matches!(
+      &seq[..],
+      [
+        Call::BeforeStart(info),
+        Call::Handle {
+          progresses,
+          interval: handled_interval,
+          current_diff: 0,
+          all_done: true,
+          ..
+        },
+        Call::AfterFinish,
+      ] if progresses == &[("done", 50)] && *handled_interval == interval
+        && *info == RunInfo {
+          tries: 50,
+          times: 1,
+          patterns: vec!["^WK".into()],
+          seed: Some(42),
+          started_at: SystemTime::UNIX_EPOCH,
+          ..RunInfo::new(1)
+        }
+    ));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_progress_diffs() -> Result<(), WakuchinError> {
+    let options = RunOptions {
+      generator: Some(Arc::new(|| {
+        Box::new(SlowGenerator(ShuffleGenerator::default()))
+      })),
+      ..RunOptions::default()
+    };
+
+    for executor in [Executor::Inline, Executor::Threads(1)] {
+      let interval = Duration::from_millis(10);
+      let calls = record(executor, interval, options.clone())?;
+      let mut previous = 0;
+      let mut rendered = 0;
+
+      for call in &calls {
+        if let Call::Handle {
+          progresses,
+          interval: handled_interval,
+          current_diff,
+          all_done,
+          ..
+        } = call
+        {
+          let current = progresses.iter().map(|(_, current)| current).sum();
+
+          assert_eq!(*handled_interval, interval, "{executor:?}");
+
+          if *all_done {
+            assert_eq!(current, 50, "{executor:?}");
+            assert_eq!(*current_diff, 0, "{executor:?}");
+          } else {
+            assert_eq!(*current_diff, current - previous, "{executor:?}");
+
+            previous = current;
+            rendered += 1;
+          }
+        }
+      }
+
+      assert!(rendered > 0, "{executor:?}");
+      assert_eq!(calls.last(), Some(&Call::AfterFinish), "{executor:?}");
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_flush_on_hit() -> Result<(), WakuchinError> {
+    let options = RunOptions {
+      generator: Some(Arc::new(|| Box::<AlternatingGenerator>::default())),
+      ..RunOptions::default()
+    };
+    // never rendered periodically, so only hits are rendered
+    let policy = ProgressIntervalPolicy::Adaptive {
+      min: Duration::from_secs(3600),
+      max: Duration::from_secs(3600),
+      flush_on_hit: true,
+    };
+
+    for executor in [Executor::Inline, Executor::Threads(1)] {
+      let calls = record(executor, policy, options.clone())?;
+      let rendered = calls
+        .iter()
+        .filter_map(|call| match call {
+          Call::Handle {
+            hit_counts,
+            interval,
+            all_done: false,
+            ..
+          } => {
+            assert_eq!(*interval, Duration::from_secs(3600), "{executor:?}");
+
+            Some(hit_counts)
+          }
+          _ => None,
+        })
+        .collect::<Vec<_>>();
+
+      assert!(!rendered.is_empty(), "{executor:?}");
+      assert!(
+        rendered.iter().all(|hit_counts| !hit_counts.is_empty()),
+        "{executor:?}"
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_shared_work() -> Result<(), WakuchinError> {
+    let patterns = Matchers::Regex(vec![Regex::new(r"^WK").unwrap()]);
+    let research = |executor, handler: &RecordingHandler| {
+      run(
+        Research {
+          tries: 10_000,
+          source: Source::Random { times: 2 },
+          patterns: &patterns,
+          progress_handler: Box::new(handler.clone()),
+          progress_interval: Duration::from_secs(3600).into(),
+          options: RunOptions {
+            seed: Some(42),
+            ..RunOptions::default()
+          },
+          compiled: None,
+        },
+        executor,
+      )
+    };
+
+    let seq = research(Executor::Inline, &RecordingHandler::default())?;
+
+    assert!(seq.hits_total > 0);
+
+    for workers in [1, 3, 8] {
+      let handler = RecordingHandler::default();
+      let par = research(Executor::Threads(workers), &handler)?;
+
+      // the same strings are tried whichever worker takes them
+      assert_eq!(par.hits_detail, seq.hits_detail, "{workers} workers");
+
+      let calls = handler.0.lock().unwrap();
+      let Some(Call::Handle { progresses, .. }) = calls.iter().rev().nth(1)
+      else {
+        panic!("no final rendering with {workers} workers");
+      };
+
+      assert_eq!(progresses.len(), workers);
+      assert!(progresses.iter().all(|(kind, _)| *kind == "done"));
+      assert_eq!(
+        progresses.iter().map(|(_, done)| done).sum::<usize>(),
+        10_000
+      );
+    }
+
+    Ok(())
+  }
+
+  /// Asks to stop on the first rendering.
+  #[derive(Clone, Default)]
+  struct StoppingHandler(Arc<Mutex<Vec<&'static str>>>);
+
+  impl ProgressHandler for StoppingHandler {
+    fn handle(
+      &mut self,
+      _: &[Progress],
+      _: &[HitCount],
+      _: Duration,
+      _: usize,
+      _: bool,
+    ) -> Result<ControlFlow> {
+      self.0.lock().unwrap().push("handle");
+
+      Ok(ControlFlow::Stop)
+    }
+
+    fn on_accidential_stop(&mut self) -> Result<()> {
+      self.0.lock().unwrap().push("stop");
+
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn test_handler_stop() {
+    let patterns = Matchers::Regex(vec![Regex::new(r"^WK").unwrap()]);
+
+    for executor in [Executor::Inline, Executor::Threads(2)] {
+      let handler = StoppingHandler::default();
+
+      let result = run(
+        Research {
+          tries: usize::MAX,
+          source: Source::Random { times: 2 },
+          patterns: &patterns,
+          progress_handler: Box::new(handler.clone()),
+          progress_interval: Duration::ZERO.into(),
+          options: RunOptions::default(),
+          compiled: None,
+        },
+        executor,
+      );
+
+      assert!(
+        matches!(result, Err(WakuchinError::Cancelled)),
+        "{executor:?}"
+      );
+      assert_eq!(
+        *handler.0.lock().unwrap(),
+        ["handle", "stop"],
+        "{executor:?}"
+      );
+    }
+  }
+}
@@ -10,8 +10,31 @@ pub enum WakuchinError {
   /// You may specified bad number of times.
   #[error("times cannot be zero")]
   TimesIsZero,
+  /// A research needs at least one pattern.
+  #[error("no patterns to detect hits of")]
+  NoPatterns,
+  /// Threads did not stop in time after all workers finished.
+  #[error("stuck at shutdown, still running: {}", .0.join(", "))]
+  ShutdownTimeout(Vec<&'static str>),
+  /// A worker panicked, the research went on without it.
+  #[error("worker {id} panicked: {message}")]
+  WorkerError { id: usize, message: String },
+  #[error("invalid symbol set: {0}")]
+  InvalidSymbolSet(String),
+  /// A pattern needs chars which are never generated, so it never hits.
+  #[error("'{pattern}' can never match: {reason}")]
+  ImpossiblePattern { pattern: String, reason: String },
+  /// The checkpoint to resume is of a research with other options.
+  #[error("the checkpoint is of another research")]
+  CheckpointMismatch,
+  #[error("failed to access the checkpoint: {0}")]
+  CheckpointIoError(#[source] io::Error),
   #[error("'{0}': unknown result output format")]
   UnknownResultOutputFormat(String),
+  #[error("'{0}': unknown locale")]
+  UnknownLocale(String),
+  #[error("'{0}': unknown protocol version")]
+  UnknownProtocolVersion(u8),
   #[error("error while serializing result: {0}")]
   SerializeError(#[from] io::Error),
   #[error(transparent)]
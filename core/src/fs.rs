@@ -0,0 +1,222 @@
+//! Filesystem helpers for persisting research artifacts
+
+use std::fs::{remove_file, rename, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of the next temporary file of the process.
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Step of [`atomic_write`], used to inject failures in tests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+  Create,
+  Write,
+  Sync,
+  Rename,
+}
+
+/// Return a temporary path next to `path`, unique to the process and the
+/// call.
+///
+/// It never draws from the generator of `fastrand`, which workers restore to
+/// keep seeded researches reproducible.
+fn temp_path(path: &Path) -> PathBuf {
+  let file_name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .unwrap_or_default();
+
+  path.with_file_name(format!(
+    ".{file_name}.{}.{}.tmp",
+    std::process::id(),
+    TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+  ))
+}
+
+/// Create the temporary file `path`, with the permissions `mode` on Unix.
+fn create_temp(path: &Path, mode: Option<u32>) -> io::Result<File> {
+  let mut options = OpenOptions::new();
+
+  options.write(true).create_new(true);
+
+  #[cfg(unix)]
+  if let Some(mode) = mode {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    options.mode(mode);
+  }
+
+  #[cfg(not(unix))]
+  let _ = mode;
+
+  options.open(path)
+}
+
+fn atomic_write_with(
+  path: &Path,
+  contents: &[u8],
+  mode: Option<u32>,
+  inject: impl Fn(Step) -> io::Result<()>,
+) -> io::Result<()> {
+  let temp = temp_path(path);
+
+  let write = || {
+    inject(Step::Create)?;
+
+    let mut file = create_temp(&temp, mode)?;
+
+    inject(Step::Write)?;
+    file.write_all(contents)?;
+
+    inject(Step::Sync)?;
+    file.sync_all()?;
+
+    inject(Step::Rename)?;
+    rename(&temp, path)
+  };
+
+  if let Err(err) = write() {
+    // best effort, the temp file may not exist yet
+    let _ = remove_file(&temp);
+
+    return Err(err);
+  }
+
+  // persist the rename itself, not supported on every platform
+  #[cfg(unix)]
+  if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+    File::open(parent)?.sync_all()?;
+  }
+
+  Ok(())
+}
+
+/// Write `contents` to `path` atomically.
+///
+/// The contents are written to a temporary file next to `path`, flushed to
+/// disk and then renamed over `path`, so readers see either the old or the
+/// new contents but never a half-written file, even if the process crashes.
+///
+/// # Arguments
+///
+/// * `path` - file to write
+/// * `contents` - bytes to write
+///
+/// # Errors
+///
+/// Returns any I/O error raised while writing. `path` is left untouched and
+/// the temporary file is removed in that case.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::fs::read_to_string;
+///
+/// use wakuchin::fs::atomic_write;
+///
+/// let path = std::env::temp_dir().join("wakuchin-atomic-write-example.json");
+///
+/// atomic_write(&path, r#"{"tries":10}"#)?;
+///
+/// assert_eq!(read_to_string(&path)?, r#"{"tries":10}"#);
+/// #
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[inline]
+pub fn atomic_write(
+  path: impl AsRef<Path>,
+  contents: impl AsRef<[u8]>,
+) -> io::Result<()> {
+  atomic_write_with(path.as_ref(), contents.as_ref(), None, |_| Ok(()))
+}
+
+/// Write `contents` to `path` atomically like [`atomic_write`], the file
+/// being created with the permissions `mode`, e.g. `0o600` for secrets.
+///
+/// The permissions are set when the temporary file is created, before any
+/// of `contents` is written, so the contents are never readable by others,
+/// the umask of the process applying as well. `mode` is ignored on other
+/// platforms than Unix.
+///
+/// # Errors
+///
+/// Same as [`atomic_write`].
+#[inline]
+pub fn atomic_write_with_mode(
+  path: impl AsRef<Path>,
+  contents: impl AsRef<[u8]>,
+  mode: u32,
+) -> io::Result<()> {
+  atomic_write_with(path.as_ref(), contents.as_ref(), Some(mode), |_| Ok(()))
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+  use std::env::temp_dir;
+  use std::fs::{create_dir_all, read_dir, read_to_string, remove_dir_all};
+  use std::io;
+
+  use super::{atomic_write, atomic_write_with, atomic_write_with_mode, Step};
+
+  #[test]
+  fn test_atomic_write() -> io::Result<()> {
+    let dir = temp_dir().join(format!("wakuchin-fs-{}", fastrand::u64(..)));
+
+    create_dir_all(&dir)?;
+
+    let path = dir.join("result.json");
+
+    atomic_write(&path, "old")?;
+
+    assert_eq!(read_to_string(&path)?, "old");
+
+    for step in [Step::Create, Step::Write, Step::Sync, Step::Rename] {
+      let result = atomic_write_with(&path, b"new", None, |current| {
+        if current == step {
+          Err(io::Error::other("injected"))
+        } else {
+          Ok(())
+        }
+      });
+
+      assert!(result.is_err(), "{step:?} should fail");
+      assert_eq!(read_to_string(&path)?, "old", "{step:?} broke the file");
+      assert_eq!(read_dir(&dir)?.count(), 1, "{step:?} left a temp file");
+    }
+
+    // seeded researches write checkpoints without changing their tries
+    fastrand::seed(42);
+
+    let next = fastrand::u64(..);
+
+    fastrand::seed(42);
+    atomic_write(&path, "new")?;
+
+    assert_eq!(fastrand::u64(..), next);
+    assert_eq!(read_to_string(&path)?, "new");
+    assert_eq!(read_dir(&dir)?.count(), 1);
+
+    remove_dir_all(dir)?;
+
+    Ok(())
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_atomic_write_with_mode() -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = temp_dir().join(format!("wakuchin-mode-{}", fastrand::u64(..)));
+
+    atomic_write_with_mode(&path, "secret", 0o600)?;
+
+    assert_eq!(read_to_string(&path)?, "secret");
+    assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+
+    std::fs::remove_file(path)?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,101 @@
+//! Awaiting researches from async applications
+//!
+//! [`ResearchBuilder::run_par_async`](crate::builder::ResearchBuilder::run_par_async)
+//! runs a research on a thread of its own, whose workers are spawned as with
+//! [`run_par`](crate::builder::ResearchBuilder::run_par), so the executor of
+//! the application is never blocked. It returns a [`ResearchFuture`] of the
+//! result and a [`HitStream`] of the hits as they are found.
+
+use std::future::{poll_fn, Future};
+use std::panic::resume_unwind;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use flume::r#async::RecvStream;
+use futures_core::Stream;
+use tokio::sync::oneshot;
+
+use crate::error::WakuchinError;
+use crate::result::{Hit, WakuchinResult};
+use crate::threads::Handle;
+
+type Result<T> = std::result::Result<T, WakuchinError>;
+
+/// Result of a research running on a thread of its own, see the
+/// [module](self).
+///
+/// Dropping it does not stop the research, use a
+/// [`CancellationToken`](crate::cancel::CancellationToken) for that.
+pub struct ResearchFuture {
+  result: oneshot::Receiver<Result<WakuchinResult>>,
+  thread: Option<Handle<()>>,
+}
+
+impl ResearchFuture {
+  pub(crate) fn new(
+    result: oneshot::Receiver<Result<WakuchinResult>>,
+    thread: Handle<()>,
+  ) -> Self {
+    Self {
+      result,
+      thread: Some(thread),
+    }
+  }
+}
+
+impl Future for ResearchFuture {
+  type Output = Result<WakuchinResult>;
+
+  fn poll(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Self::Output> {
+    let result = ready!(Pin::new(&mut self.result).poll(cx));
+    let thread = self
+      .thread
+      .take()
+      .expect("research future polled after completion");
+
+    match result {
+      Ok(result) => Poll::Ready(result),
+      // the result is only dropped by a panic of the research
+      Err(_) => match thread.join() {
+        Err(e) => resume_unwind(e),
+        Ok(()) => unreachable!("research ended without a result"),
+      },
+    }
+  }
+}
+
+/// Hits of a research as they are found by its workers, ending with the
+/// research, see the [module](self).
+///
+/// Hits are buffered until they are read, so a stream which is not read
+/// should be dropped.
+pub struct HitStream {
+  hits: RecvStream<'static, Hit>,
+}
+
+impl HitStream {
+  pub(crate) fn new(hits: flume::Receiver<Hit>) -> Self {
+    Self {
+      hits: hits.into_stream(),
+    }
+  }
+
+  /// Wait for the next hit, `None` once the research ended.
+  pub async fn next(&mut self) -> Option<Hit> {
+    poll_fn(|cx| Pin::new(&mut self.hits).poll_next(cx)).await
+  }
+}
+
+impl Stream for HitStream {
+  type Item = Hit;
+
+  fn poll_next(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    Pin::new(&mut self.hits).poll_next(cx)
+  }
+}
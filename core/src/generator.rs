@@ -0,0 +1,137 @@
+//! Pluggable generators of the strings tried by researches
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use crate::gen_bytes_into_rng;
+use crate::rng::{Rng, RngBackend};
+use crate::symbol::SymbolSet;
+
+/// Generator of the strings tried by a research, see
+/// [`ResearchBuilder::generator`](crate::builder::ResearchBuilder::generator).
+///
+/// Strings are generated as UTF-8 bytes, which are matched without being
+/// turned into a `String` until they hit. Each worker owns its own generator,
+/// so it can keep state between strings.
+/// Random numbers should come from [`fastrand`](https://docs.rs/fastrand)'s
+/// thread-local generator to be reproducible with
+/// [`ResearchBuilder::seed`](crate::builder::ResearchBuilder::seed).
+///
+/// # Examples
+///
+/// ```rust
+/// use std::borrow::Cow;
+///
+/// use wakuchin::generator::WakuchinGenerator;
+///
+/// /// Biased towards repeating the previous symbol.
+/// #[derive(Clone)]
+/// struct Sticky {
+///   buf: Vec<u8>,
+/// }
+///
+/// impl WakuchinGenerator for Sticky {
+///   fn next(&mut self, times: usize) -> Cow<'_, [u8]> {
+///     let mut previous = b'W';
+///
+///     self.buf.clear();
+///
+///     for _ in 0..times * 4 {
+///       if fastrand::u8(..4) != 0 {
+///         previous = b"WKCN"[fastrand::usize(..4)];
+///       }
+///
+///       self.buf.push(previous);
+///     }
+///
+///     Cow::Borrowed(&self.buf)
+///   }
+/// }
+///
+/// let mut sticky = Sticky { buf: Vec::new() };
+///
+/// assert_eq!(sticky.next(2).len(), 8);
+/// ```
+pub trait WakuchinGenerator: Send {
+  /// Generate the UTF-8 bytes of the next string of `times`.
+  fn next(&mut self, times: usize) -> Cow<'_, [u8]>;
+}
+
+/// Default generator, shuffling `times` of each symbol like
+/// [`gen_with`](crate::gen_with) without allocating.
+#[derive(Clone, Debug, Default)]
+pub struct ShuffleGenerator {
+  symbols: SymbolSet,
+  buf: Vec<u8>,
+  rng: Rng,
+}
+
+impl ShuffleGenerator {
+  #[must_use]
+  pub fn new(symbols: SymbolSet) -> Self {
+    Self {
+      symbols,
+      buf: Vec::new(),
+      rng: Rng::default(),
+    }
+  }
+
+  /// Shuffle with a generator of `backend`, seeded from the thread-local
+  /// one when called.
+  ///
+  /// Clones of a generator of its own, unlike the thread-local one, go on
+  /// from the same state, so they generate the same strings.
+  #[must_use]
+  pub fn with_rng(mut self, backend: RngBackend) -> Self {
+    self.rng = Rng::new(backend);
+    self
+  }
+}
+
+impl WakuchinGenerator for ShuffleGenerator {
+  #[inline]
+  fn next(&mut self, times: usize) -> Cow<'_, [u8]> {
+    gen_bytes_into_rng(times, &self.symbols, &mut self.buf, &mut self.rng);
+
+    Cow::Borrowed(&self.buf)
+  }
+}
+
+/// Generator of the strings of a list in order, ignoring `times`.
+pub(crate) struct ListGenerator<'a> {
+  candidates: std::slice::Iter<'a, String>,
+}
+
+impl<'a> ListGenerator<'a> {
+  pub fn new(candidates: &'a [String]) -> Self {
+    Self {
+      candidates: candidates.iter(),
+    }
+  }
+}
+
+impl WakuchinGenerator for ListGenerator<'_> {
+  #[inline]
+  fn next(&mut self, _: usize) -> Cow<'_, [u8]> {
+    let candidate = self.candidates.next().map_or("", String::as_str);
+
+    Cow::Borrowed(candidate.as_bytes())
+  }
+}
+
+/// Creates a generator for each chunk of tries.
+pub(crate) type GeneratorFactory =
+  Arc<dyn Fn() -> Box<dyn WakuchinGenerator> + Send + Sync>;
+
+/// Create the generator of a chunk, the default one shuffling with `rng`
+/// if no `factory` is set.
+pub(crate) fn new_generator(
+  factory: Option<&GeneratorFactory>,
+  symbols: &SymbolSet,
+  rng: RngBackend,
+) -> Box<dyn WakuchinGenerator> {
+  match factory {
+    Some(factory) => factory(),
+    None => Box::new(ShuffleGenerator::new(symbols.clone()).with_rng(rng)),
+  }
+}
@@ -1,18 +1,87 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
+use instant::SystemTime;
 
-use crate::{progress::Progress, result::HitCount};
+use crate::progress::Progress;
+use crate::result::{Hit, HitCount, NotableHit};
 
+use self::deadline::DeadlineOverrun;
+
+#[cfg(feature = "async")]
+pub mod async_writer;
+pub mod counting;
+pub mod csv;
+pub mod deadline;
 pub mod empty;
+pub mod file;
 pub mod msgpack;
+#[cfg(feature = "notify")]
+pub mod notify;
+pub mod snapshot;
+pub mod socket;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "sse")]
+pub mod sse;
+pub mod throttle;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+/// Whether a research goes on, returned by [`ProgressHandler::handle`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlFlow {
+  /// Keep researching
+  Continue,
+  /// Stop the research like SIGINT does, so it fails with
+  /// [`WakuchinError::Cancelled`](crate::error::WakuchinError::Cancelled)
+  /// after [`ProgressHandler::on_accidential_stop`] is called
+  Stop,
+}
+
+/// What a research is about, passed to [`ProgressHandler::before_start`] so
+/// handlers need not be given the options of the research themselves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunInfo {
+  /// Tries of the whole research, including those of a resumed checkpoint
+  pub tries: usize,
+  /// Times of the tried strings, 0 for a list of candidates
+  pub times: usize,
+  /// Sources of the patterns, see
+  /// [`Matchers::patterns`](crate::pattern::Matchers::patterns)
+  pub patterns: Vec<String>,
+  pub workers: usize,
+  pub seed: Option<u64>,
+  pub started_at: SystemTime,
+}
+
+impl RunInfo {
+  /// Create the info of a research of `workers` starting now, everything
+  /// else left empty.
+  pub fn new(workers: usize) -> Self {
+    Self {
+      tries: 0,
+      times: 0,
+      patterns: Vec::new(),
+      workers,
+      seed: None,
+      started_at: SystemTime::now(),
+    }
+  }
+}
 
 pub trait ProgressHandler: Send {
   #[inline]
-  fn before_start(&mut self, _total_workers: usize) -> Result<()> {
+  fn before_start(&mut self, _info: &RunInfo) -> Result<()> {
     Ok(())
   }
 
+  /// Render the progress of the workers, returning whether the research
+  /// goes on.
+  ///
+  /// Stopping is cooperative, so workers may still process a few tries,
+  /// and it has no effect once `all_done` is set.
   fn handle(
     &mut self,
     progresses: &[Progress],
@@ -20,15 +89,391 @@ pub trait ProgressHandler: Send {
     elapsed_time: Duration,
     current_diff: usize,
     all_done: bool,
-  ) -> Result<()>;
+  ) -> Result<ControlFlow>;
 
   #[inline]
   fn after_finish(&mut self) -> Result<()> {
     Ok(())
   }
 
+  /// Called when the research is paused by SIGTSTP, before the process is
+  /// stopped.
+  #[inline]
+  fn on_pause(&mut self) -> Result<()> {
+    Ok(())
+  }
+
+  /// Called when the paused research is continued by SIGCONT.
+  #[inline]
+  fn on_resume(&mut self) -> Result<()> {
+    Ok(())
+  }
+
+  /// Called for each hit once it is counted, before the progress counting
+  /// it is rendered, so handlers can react to hits as they are found rather
+  /// than to their counts.
+  ///
+  /// Hits of parallel researches are passed in the order they are counted,
+  /// not in the order of their tries.
+  #[inline]
+  fn on_hit(&mut self, _hit: &Hit) -> Result<()> {
+    Ok(())
+  }
+
+  /// Called as soon as a notable hit is found, before the next progress is
+  /// rendered, e.g. to announce it.
+  #[inline]
+  fn on_notable_hit(&mut self, _hit: &NotableHit) -> Result<()> {
+    Ok(())
+  }
+
+  /// Called once the research looks like taking longer than expected, see
+  /// [`DeadlineProgressHandler`](deadline::DeadlineProgressHandler).
+  #[inline]
+  fn on_deadline_overrun(&mut self, _overrun: &DeadlineOverrun) -> Result<()> {
+    Ok(())
+  }
+
   #[inline]
   fn on_accidential_stop(&mut self) -> Result<()> {
     self.after_finish()
   }
 }
+
+/// Implement the listed methods of [`ProgressHandler`] by calling those of
+/// the `inner` field, for handlers wrapping another one which only act on
+/// some of the callbacks themselves.
+///
+/// Invoke it in the `impl ProgressHandler` block, next to the methods
+/// implemented by hand.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use anyhow::Result;
+///
+/// use wakuchin::forward_to_inner;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::handlers::{ControlFlow, ProgressHandler};
+/// use wakuchin::progress::Progress;
+/// use wakuchin::result::{Hit, HitCount};
+///
+/// /// Counts the hits passed to the handler it wraps
+/// struct HitCounting {
+///   inner: Box<dyn ProgressHandler>,
+///   hits: usize,
+/// }
+///
+/// impl ProgressHandler for HitCounting {
+///   fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+///     self.hits += 1;
+///     self.inner.on_hit(hit)
+///   }
+///
+///   forward_to_inner!(
+///     before_start,
+///     handle,
+///     after_finish,
+///     on_pause,
+///     on_resume,
+///     on_notable_hit,
+///     on_deadline_overrun,
+///     on_accidential_stop,
+///   );
+/// }
+///
+/// let mut handler = HitCounting {
+///   inner: Box::new(EmptyProgressHandler::new()),
+///   hits: 0,
+/// };
+///
+/// handler.on_hit(&Hit::new(0, "WKCN"))?;
+///
+/// assert_eq!(handler.hits, 1);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[macro_export]
+macro_rules! forward_to_inner {
+  (@method before_start) => {
+    #[inline]
+    fn before_start(
+      &mut self,
+      info: &$crate::handlers::RunInfo,
+    ) -> ::anyhow::Result<()> {
+      self.inner.before_start(info)
+    }
+  };
+  (@method handle) => {
+    #[inline]
+    fn handle(
+      &mut self,
+      progresses: &[$crate::progress::Progress],
+      hit_counts: &[$crate::result::HitCount],
+      elapsed_time: ::std::time::Duration,
+      current_diff: usize,
+      all_done: bool,
+    ) -> ::anyhow::Result<$crate::handlers::ControlFlow> {
+      self.inner.handle(
+        progresses,
+        hit_counts,
+        elapsed_time,
+        current_diff,
+        all_done,
+      )
+    }
+  };
+  (@method after_finish) => {
+    #[inline]
+    fn after_finish(&mut self) -> ::anyhow::Result<()> {
+      self.inner.after_finish()
+    }
+  };
+  (@method on_pause) => {
+    #[inline]
+    fn on_pause(&mut self) -> ::anyhow::Result<()> {
+      self.inner.on_pause()
+    }
+  };
+  (@method on_resume) => {
+    #[inline]
+    fn on_resume(&mut self) -> ::anyhow::Result<()> {
+      self.inner.on_resume()
+    }
+  };
+  (@method on_hit) => {
+    #[inline]
+    fn on_hit(&mut self, hit: &$crate::result::Hit) -> ::anyhow::Result<()> {
+      self.inner.on_hit(hit)
+    }
+  };
+  (@method on_notable_hit) => {
+    #[inline]
+    fn on_notable_hit(
+      &mut self,
+      hit: &$crate::result::NotableHit,
+    ) -> ::anyhow::Result<()> {
+      self.inner.on_notable_hit(hit)
+    }
+  };
+  (@method on_deadline_overrun) => {
+    #[inline]
+    fn on_deadline_overrun(
+      &mut self,
+      overrun: &$crate::handlers::deadline::DeadlineOverrun,
+    ) -> ::anyhow::Result<()> {
+      self.inner.on_deadline_overrun(overrun)
+    }
+  };
+  (@method on_accidential_stop) => {
+    #[inline]
+    fn on_accidential_stop(&mut self) -> ::anyhow::Result<()> {
+      self.inner.on_accidential_stop()
+    }
+  };
+  ($($method:ident),+ $(,)?) => {
+    $($crate::forward_to_inner!(@method $method);)+
+  };
+}
+
+/// Boxed handlers are handlers as well, so wrappers generic over their
+/// handler like [`Throttled`](throttle::Throttled) take them.
+impl<H: ProgressHandler + ?Sized> ProgressHandler for Box<H> {
+  #[inline]
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    (**self).before_start(info)
+  }
+
+  #[inline]
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    (**self).handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  #[inline]
+  fn after_finish(&mut self) -> Result<()> {
+    (**self).after_finish()
+  }
+
+  #[inline]
+  fn on_pause(&mut self) -> Result<()> {
+    (**self).on_pause()
+  }
+
+  #[inline]
+  fn on_resume(&mut self) -> Result<()> {
+    (**self).on_resume()
+  }
+
+  #[inline]
+  fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+    (**self).on_hit(hit)
+  }
+
+  #[inline]
+  fn on_notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+    (**self).on_notable_hit(hit)
+  }
+
+  #[inline]
+  fn on_deadline_overrun(&mut self, overrun: &DeadlineOverrun) -> Result<()> {
+    (**self).on_deadline_overrun(overrun)
+  }
+
+  #[inline]
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    (**self).on_accidential_stop()
+  }
+}
+
+/// What a research does once its progress handler fails, see
+/// [`ResearchBuilder::handler_error_policy`](crate::builder::ResearchBuilder::handler_error_policy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HandlerErrorPolicy {
+  /// Fail the research with the error of the handler
+  #[default]
+  Abort,
+  /// Warn about the error and keep calling the handler, an error repeated on
+  /// the following calls being only warned about once
+  LogAndContinue,
+  /// Warn about the error and stop calling the handler, the research going
+  /// on without rendering its progress
+  DisableHandler,
+}
+
+/// Callback warned about failures a research goes on despite, as wakuchin
+/// never prints on its own, see
+/// [`ResearchBuilder::on_warning`](crate::builder::ResearchBuilder::on_warning).
+pub(crate) type WarningCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Handler applying a [`HandlerErrorPolicy`] to the errors of the handler it
+/// wraps.
+pub(crate) struct GuardedProgressHandler {
+  /// The wrapped handler, `None` once disabled
+  inner: Option<Box<dyn ProgressHandler>>,
+  policy: HandlerErrorPolicy,
+  /// Message of the error of the previous call, if it failed
+  last_error: Option<String>,
+  on_warning: Option<WarningCallback>,
+}
+
+impl GuardedProgressHandler {
+  pub fn new(
+    inner: Box<dyn ProgressHandler>,
+    policy: HandlerErrorPolicy,
+    on_warning: Option<WarningCallback>,
+  ) -> Self {
+    Self {
+      inner: Some(inner),
+      policy,
+      last_error: None,
+      on_warning,
+    }
+  }
+
+  fn warn(&self, message: &str) {
+    if let Some(on_warning) = &self.on_warning {
+      on_warning(message);
+    }
+  }
+
+  /// Call `f` with the wrapped handler, returning `default` instead of its
+  /// error unless the policy aborts, or without a handler.
+  fn call<T>(
+    &mut self,
+    default: T,
+    f: impl FnOnce(&mut dyn ProgressHandler) -> Result<T>,
+  ) -> Result<T> {
+    let Some(inner) = &mut self.inner else {
+      return Ok(default);
+    };
+
+    let error = match f(inner.as_mut()) {
+      Ok(value) => {
+        self.last_error = None;
+
+        return Ok(value);
+      }
+      Err(error) => error,
+    };
+
+    match self.policy {
+      HandlerErrorPolicy::Abort => return Err(error),
+      HandlerErrorPolicy::LogAndContinue => {
+        let message = format!("{error:#}");
+
+        if self.last_error.as_ref() != Some(&message) {
+          self.warn(&format!("progress handler failed: {message}"));
+          self.last_error = Some(message);
+        }
+      }
+      HandlerErrorPolicy::DisableHandler => {
+        self.warn(&format!("progress handler failed, disabled: {error:#}"));
+        self.inner = None;
+      }
+    }
+
+    Ok(default)
+  }
+}
+
+impl ProgressHandler for GuardedProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.call((), |inner| inner.before_start(info))
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    self.call(ControlFlow::Continue, |inner| {
+      inner.handle(progresses, hit_counts, elapsed_time, current_diff, all_done)
+    })
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.call((), |inner| inner.after_finish())
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    self.call((), |inner| inner.on_pause())
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    self.call((), |inner| inner.on_resume())
+  }
+
+  fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+    self.call((), |inner| inner.on_hit(hit))
+  }
+
+  fn on_notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+    self.call((), |inner| inner.on_notable_hit(hit))
+  }
+
+  fn on_deadline_overrun(&mut self, overrun: &DeadlineOverrun) -> Result<()> {
+    self.call((), |inner| inner.on_deadline_overrun(overrun))
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.call((), |inner| inner.on_accidential_stop())
+  }
+}
@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::io::{self, ErrorKind, Write};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Future writing what an [`AsyncWriter`] is given, to be spawned or awaited
+/// on the runtime of the application.
+///
+/// It completes once every handle of the writer is dropped and what they
+/// were given is written and flushed, or fails with the first error of the
+/// writer.
+pub type WriteTask = Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+/// Blocking [`Write`] handing what it is given over to a [`WriteTask`]
+/// writing it to an [`AsyncWrite`], so the handlers taking a [`Write`] never
+/// block on a slow consumer, nor the runtime of the application.
+///
+/// Writes only fail once the task ended, with [`ErrorKind::BrokenPipe`].
+/// Nothing bounds what is queued while the consumer lags behind, so
+/// consider the [`Acks`](super::msgpack::Acks) of the msgpack handlers for
+/// consumers slower than the research.
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::msgpack::MsgpackProgressHandler;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let (handler, task) =
+///   MsgpackProgressHandler::new_async(10, tokio::io::sink());
+/// let task = tokio::spawn(task);
+///
+/// ResearchBuilder::new()
+///   .tries(10)
+///   .times(1)
+///   .regex(Regex::new(r"WKCN")?)
+///   .progress_handler(Box::new(handler))
+///   .run_par_async()
+///   .0
+///   .await?;
+///
+/// // the handler was dropped along with the research
+/// task.await??;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncWriter {
+  chunks: flume::Sender<Vec<u8>>,
+}
+
+impl AsyncWriter {
+  /// Create a writer to `writer`, and the task writing to it.
+  pub fn new<W>(writer: W) -> (Arc<Mutex<Self>>, WriteTask)
+  where
+    W: AsyncWrite + Unpin + Send + 'static,
+  {
+    let (tx, rx) = flume::unbounded::<Vec<u8>>();
+    let task = async move {
+      let mut writer = writer;
+
+      while let Ok(chunk) = rx.recv_async().await {
+        writer.write_all(&chunk).await?;
+
+        // flush once caught up rather than after every chunk
+        if rx.is_empty() {
+          writer.flush().await?;
+        }
+      }
+
+      writer.flush().await
+    };
+
+    (Arc::new(Mutex::new(Self { chunks: tx })), Box::pin(task))
+  }
+}
+
+impl Write for AsyncWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self
+      .chunks
+      .send(buf.to_vec())
+      .map_err(|_| io::Error::new(ErrorKind::BrokenPipe, "write task ended"))?;
+
+    Ok(buf.len())
+  }
+
+  /// Do nothing, the task flushes once it wrote everything queued.
+  #[inline]
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::{ErrorKind, Write};
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use tokio::io::AsyncReadExt;
+
+  use crate::handlers::csv::CsvProgressHandler;
+  use crate::handlers::{ProgressHandler, RunInfo};
+
+  use super::AsyncWriter;
+
+  #[tokio::test(flavor = "current_thread")]
+  async fn test_async_writer() -> Result<()> {
+    let (writer, mut reader) = tokio::io::duplex(1024);
+    let (mut handler, task) = CsvProgressHandler::new_async(writer);
+
+    handler.before_start(&RunInfo::new(1))?;
+    handler.handle(&[], &[], Duration::from_secs(1), 0, true)?;
+    handler.after_finish()?;
+    drop(handler);
+
+    task.await?;
+
+    let mut output = String::new();
+
+    reader.read_to_string(&mut output).await?;
+
+    assert!(output.starts_with("timestamp,current,rate,eta,hits\n"));
+    assert_eq!(output.lines().count(), 2);
+
+    let (writer, task) = AsyncWriter::new(tokio::io::sink());
+
+    drop(task);
+
+    // the consumer is gone
+    assert_eq!(
+      writer.lock().unwrap().write(b"frame").unwrap_err().kind(),
+      ErrorKind::BrokenPipe
+    );
+
+    Ok(())
+  }
+}
@@ -0,0 +1,311 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+
+use crate::progress::Progress;
+use crate::result::{Hit, HitCount, NotableHit};
+
+use super::deadline::DeadlineOverrun;
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// How often a callback of a handler was called, and how long it took.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CallStats {
+  pub calls: usize,
+  /// Longest time a single call took
+  pub max_latency: Duration,
+  /// Time all the calls took
+  pub total_latency: Duration,
+}
+
+impl CallStats {
+  /// Return the mean time a call took, zero if it was never called.
+  pub fn mean_latency(&self) -> Duration {
+    match u32::try_from(self.calls) {
+      Ok(0) => Duration::ZERO,
+      Ok(calls) => self.total_latency / calls,
+      Err(_) => self.total_latency.div_f64(self.calls as f64),
+    }
+  }
+
+  fn record(&mut self, latency: Duration) {
+    self.calls += 1;
+    self.max_latency = self.max_latency.max(latency);
+    self.total_latency += latency;
+  }
+}
+
+/// What a [`CountingProgressHandler`] recorded so far.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HandlerCounts {
+  pub before_start: CallStats,
+  pub handle: CallStats,
+  pub after_finish: CallStats,
+  pub on_pause: CallStats,
+  pub on_resume: CallStats,
+  pub on_hit: CallStats,
+  pub on_notable_hit: CallStats,
+  pub on_deadline_overrun: CallStats,
+  pub on_accidential_stop: CallStats,
+  /// Bytes written through the writers of [`Counts::writer`]
+  pub bytes_written: u64,
+}
+
+/// Shared handle to the [`HandlerCounts`] of a [`CountingProgressHandler`],
+/// still readable once the handler is moved into a research.
+#[derive(Clone, Debug, Default)]
+pub struct Counts(Arc<Mutex<HandlerCounts>>);
+
+impl Counts {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Return what was recorded so far.
+  pub fn get(&self) -> HandlerCounts {
+    self.0.lock().unwrap().clone()
+  }
+
+  /// Wrap `writer` so the bytes written to it are counted, to be given to
+  /// the handler being measured.
+  pub fn writer(
+    &self,
+    writer: Arc<Mutex<dyn Write + Send>>,
+  ) -> Arc<Mutex<CountingWriter>> {
+    Arc::new(Mutex::new(CountingWriter {
+      inner: writer,
+      counts: self.clone(),
+    }))
+  }
+
+  fn record(
+    &self,
+    stats: impl FnOnce(&mut HandlerCounts) -> &mut CallStats,
+    latency: Duration,
+  ) {
+    stats(&mut self.0.lock().unwrap()).record(latency);
+  }
+}
+
+/// Writer counting the bytes written to the writer it wraps, created by
+/// [`Counts::writer`].
+pub struct CountingWriter {
+  inner: Arc<Mutex<dyn Write + Send>>,
+  counts: Counts,
+}
+
+impl Write for CountingWriter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let n = self.inner.lock().unwrap().write(buf)?;
+
+    self.counts.0.lock().unwrap().bytes_written += n as u64;
+
+    Ok(n)
+  }
+
+  #[inline]
+  fn flush(&mut self) -> io::Result<()> {
+    self.inner.lock().unwrap().flush()
+  }
+}
+
+/// Handler recording how many times each callback of the handler it wraps
+/// is called, how long the calls take, and how many bytes it writes, to
+/// measure the overhead of handlers e.g. in benchmarks.
+///
+/// The latencies are those of the wrapped handler alone. Bytes are only
+/// counted if the wrapped handler writes through [`Counts::writer`].
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use std::sync::{Arc, Mutex};
+///
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::counting::{CountingProgressHandler, Counts};
+/// use wakuchin::handlers::msgpack::MsgpackProgressHandler;
+///
+/// let counts = Counts::new();
+/// let writer = counts.writer(Arc::new(Mutex::new(Cursor::new(Vec::new()))));
+/// let handler =
+///   CountingProgressHandler::new(MsgpackProgressHandler::new(0, writer))
+///     .with_counts(counts.clone());
+///
+/// ResearchBuilder::new()
+///   .tries(10)
+///   .times(1)
+///   .regex(Regex::new(r"WKCN")?)
+///   .progress_handler(Box::new(handler))
+///   .run_seq()?;
+///
+/// let counts = counts.get();
+///
+/// assert_eq!(counts.before_start.calls, 1);
+/// assert!(counts.handle.calls > 0);
+/// assert!(counts.bytes_written > 0);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct CountingProgressHandler<H> {
+  inner: H,
+  counts: Counts,
+}
+
+impl<H: ProgressHandler> CountingProgressHandler<H> {
+  pub fn new(inner: H) -> Self {
+    Self {
+      inner,
+      counts: Counts::new(),
+    }
+  }
+
+  /// Record to `counts`, e.g. those whose writer is given to the wrapped
+  /// handler.
+  #[must_use]
+  pub fn with_counts(mut self, counts: Counts) -> Self {
+    self.counts = counts;
+    self
+  }
+
+  /// Return the handle to what is recorded.
+  #[inline]
+  pub fn counts(&self) -> Counts {
+    self.counts.clone()
+  }
+
+  /// Return the wrapped handler.
+  #[inline]
+  pub fn into_inner(self) -> H {
+    self.inner
+  }
+
+  /// Call `f` with the wrapped handler, recording its latency to `stats`.
+  fn measure<T>(
+    &mut self,
+    stats: impl FnOnce(&mut HandlerCounts) -> &mut CallStats,
+    f: impl FnOnce(&mut H) -> T,
+  ) -> T {
+    let start = Instant::now();
+    let result = f(&mut self.inner);
+
+    // recorded once the call returns, as it may write through the counts
+    self.counts.record(stats, start.elapsed());
+
+    result
+  }
+}
+
+impl<H: ProgressHandler> ProgressHandler for CountingProgressHandler<H> {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.measure(|c| &mut c.before_start, |inner| inner.before_start(info))
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    self.measure(
+      |c| &mut c.handle,
+      |inner| {
+        inner.handle(
+          progresses,
+          hit_counts,
+          elapsed_time,
+          current_diff,
+          all_done,
+        )
+      },
+    )
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.measure(|c| &mut c.after_finish, |inner| inner.after_finish())
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    self.measure(|c| &mut c.on_pause, |inner| inner.on_pause())
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    self.measure(|c| &mut c.on_resume, |inner| inner.on_resume())
+  }
+
+  fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+    self.measure(|c| &mut c.on_hit, |inner| inner.on_hit(hit))
+  }
+
+  fn on_notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+    self.measure(|c| &mut c.on_notable_hit, |inner| inner.on_notable_hit(hit))
+  }
+
+  fn on_deadline_overrun(&mut self, overrun: &DeadlineOverrun) -> Result<()> {
+    self.measure(
+      |c| &mut c.on_deadline_overrun,
+      |inner| inner.on_deadline_overrun(overrun),
+    )
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.measure(
+      |c| &mut c.on_accidential_stop,
+      |inner| inner.on_accidential_stop(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::Cursor;
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::csv::CsvProgressHandler;
+  use crate::handlers::{ProgressHandler, RunInfo};
+
+  use super::{CallStats, CountingProgressHandler, Counts};
+
+  #[test]
+  fn test_counting() -> Result<()> {
+    let output = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+    let counts = Counts::new();
+    let mut handler = CountingProgressHandler::new(CsvProgressHandler::new(
+      counts.writer(output.clone()),
+    ))
+    .with_counts(counts.clone());
+
+    handler.before_start(&RunInfo::new(1))?;
+
+    for _ in 0..3 {
+      handler.handle(&[], &[], Duration::from_secs(1), 0, false)?;
+    }
+
+    handler.after_finish()?;
+
+    let counts = counts.get();
+
+    assert_eq!(counts.before_start.calls, 1);
+    assert_eq!(counts.handle.calls, 3);
+    assert_eq!(counts.after_finish.calls, 1);
+    assert_eq!(counts.on_hit, CallStats::default());
+    assert!(counts.handle.max_latency <= counts.handle.total_latency);
+    assert_eq!(
+      counts.bytes_written,
+      output.lock().unwrap().get_ref().len() as u64
+    );
+    assert_eq!(handler.counts().get(), counts);
+
+    Ok(())
+  }
+}
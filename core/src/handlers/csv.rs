@@ -0,0 +1,304 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::SystemTime;
+
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::HitCount;
+
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Columns of every row, followed by one column per hit chars.
+const COLUMNS: [&str; 5] = ["timestamp", "current", "rate", "eta", "hits"];
+
+/// Handler appending one CSV row per progress to a writer, so researches
+/// can be charted in a spreadsheet once done.
+///
+/// Each row has the Unix timestamp in seconds, the tries done so far, the
+/// tries per second, the estimated remaining seconds and the hits of all
+/// chars, followed by the hits of each chars given to
+/// [`with_chars`](Self::with_chars). The rate and the ETA are left empty
+/// until they can be estimated.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use std::sync::{Arc, Mutex};
+///
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::csv::CsvProgressHandler;
+///
+/// let csv = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+///
+/// ResearchBuilder::new()
+///   .tries(10)
+///   .times(1)
+///   .regex(Regex::new(r"WKCN|WCKN")?)
+///   .progress_handler(Box::new(
+///     CsvProgressHandler::new(csv.clone()).with_chars(["WKCN", "WCKN"]),
+///   ))
+///   .run_seq()?;
+///
+/// let csv = String::from_utf8(csv.lock().unwrap().get_ref().clone())?;
+///
+/// assert!(csv.starts_with("timestamp,current,rate,eta,hits,WKCN,WCKN\n"));
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct CsvProgressHandler {
+  tries: usize,
+  writer: Arc<Mutex<dyn Write + Send>>,
+  chars: Vec<String>,
+  header_written: bool,
+}
+
+impl CsvProgressHandler {
+  /// Create a handler writing rows to `writer`, the tries of the research
+  /// being taken from the [`RunInfo`] once it starts.
+  pub fn new(writer: Arc<Mutex<dyn Write + Send>>) -> Self {
+    Self {
+      tries: 0,
+      writer,
+      chars: Vec::new(),
+      header_written: false,
+    }
+  }
+
+  /// Create a handler writing rows to the async `writer`, and the task
+  /// writing them, see [`AsyncWriter`](super::async_writer::AsyncWriter).
+  #[cfg(feature = "async")]
+  pub fn new_async<W>(writer: W) -> (Self, super::async_writer::WriteTask)
+  where
+    W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+  {
+    let (writer, task) = super::async_writer::AsyncWriter::new(writer);
+
+    (Self::new(writer), task)
+  }
+
+  /// Add a column of the hits of each of `chars`, in internal wakuchin
+  /// chars like patterns, as columns cannot be added once rows are written.
+  #[must_use]
+  pub fn with_chars<S: Into<String>>(
+    mut self,
+    chars: impl IntoIterator<Item = S>,
+  ) -> Self {
+    self.chars = chars.into_iter().map(Into::into).collect();
+    self
+  }
+
+  fn write_header(&mut self) -> Result<()> {
+    let mut header = COLUMNS.join(",");
+
+    for chars in &self.chars {
+      header.push(',');
+      push_field(&mut header, chars);
+    }
+
+    header.push('\n');
+    self.writer.lock().unwrap().write_all(header.as_bytes())?;
+    self.header_written = true;
+
+    Ok(())
+  }
+}
+
+/// Push `field` to `row`, quoted if it contains a delimiter.
+fn push_field(row: &mut String, field: &str) {
+  if !field.contains([',', '"', '\n', '\r']) {
+    row.push_str(field);
+    return;
+  }
+
+  row.push('"');
+  row.push_str(&field.replace('"', "\"\""));
+  row.push('"');
+}
+
+/// Push `value` to `row` with 2 decimals, nothing if it is not finite.
+fn push_float(row: &mut String, value: f64) {
+  if value.is_finite() {
+    let _ = write!(row, "{value:.2}");
+  }
+}
+
+impl ProgressHandler for CsvProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.tries = info.tries;
+
+    if self.header_written {
+      return Ok(());
+    }
+
+    self.write_header()
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    if !self.header_written {
+      self.write_header()?;
+    }
+
+    let current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>();
+    let current = if self.tries == 0 {
+      current
+    } else {
+      current.min(self.tries)
+    };
+
+    let rate = current_diff as f64 / elapsed_time.as_secs_f64();
+    let eta = if all_done {
+      0.0
+    } else if self.tries == 0 {
+      f64::NAN
+    } else {
+      (self.tries - current) as f64 / rate
+    };
+
+    let timestamp = SystemTime::now()
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .unwrap_or_default();
+    let mut row = format!("{:.3},{current},", timestamp.as_secs_f64());
+
+    push_float(&mut row, rate);
+    row.push(',');
+    push_float(&mut row, eta);
+
+    let hits = hit_counts.iter().map(|count| count.hits).sum::<usize>();
+    let _ = write!(row, ",{hits}");
+
+    for chars in &self.chars {
+      let hits = hit_counts
+        .iter()
+        .filter(|count| count.chars == chars.as_str())
+        .map(|count| count.hits)
+        .sum::<usize>();
+
+      let _ = write!(row, ",{hits}");
+    }
+
+    row.push('\n');
+    self.writer.lock().unwrap().write_all(row.as_bytes())?;
+
+    Ok(ControlFlow::Continue)
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.writer.lock().unwrap().flush()?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::Cursor;
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::progress::{DoneDetail, ProcessingDetail, Progress, ProgressKind};
+  use crate::result::HitCount;
+
+  use super::CsvProgressHandler;
+
+  #[test]
+  fn test_rows() -> Result<()> {
+    let cursor = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+    let mut handler =
+      CsvProgressHandler::new(cursor.clone()).with_chars(["WKCN", "a,\"b\""]);
+
+    handler.before_start(&RunInfo {
+      tries: 100,
+      ..RunInfo::new(2)
+    })?;
+
+    let progresses = [
+      Progress(ProgressKind::Processing(ProcessingDetail {
+        id: 1,
+        wakuchin: "WKCN".into(),
+        current: 20,
+        total: 50,
+      })),
+      Progress(ProgressKind::Processing(ProcessingDetail {
+        id: 2,
+        wakuchin: "WCKN".into(),
+        current: 30,
+        total: 50,
+      })),
+    ];
+    let hit_counts = [
+      HitCount::new("WKCN", 2),
+      HitCount {
+        pattern: 1,
+        ..HitCount::new("WKCN", 1)
+      },
+      HitCount::new("WCKN", 4),
+    ];
+
+    handler.handle(
+      &progresses,
+      &hit_counts,
+      Duration::from_secs(2),
+      50,
+      false,
+    )?;
+    handler.handle(&progresses, &[], Duration::ZERO, 0, false)?;
+
+    let done = [Progress(ProgressKind::Done(DoneDetail {
+      id: 0,
+      total: 100,
+    }))];
+
+    handler.handle(&done, &hit_counts, Duration::from_secs(1), 50, true)?;
+    handler.after_finish()?;
+
+    let csv = String::from_utf8(cursor.lock().unwrap().get_ref().clone())?;
+    let rows = csv
+      .lines()
+      .map(|row| row.split_once(',').map_or(row, |(_, rest)| rest))
+      .collect::<Vec<_>>();
+
+    assert!(csv.starts_with("timestamp,"));
+    assert_eq!(
+      rows,
+      [
+        "current,rate,eta,hits,WKCN,\"a,\"\"b\"\"\"",
+        "50,25.00,2.00,7,3,0",
+        "50,,,0,0,0",
+        "100,50.00,0.00,7,3,0",
+      ]
+    );
+
+    Ok(())
+  }
+}
@@ -0,0 +1,238 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::HitCount;
+
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Share of the expected duration a research may take longer before it is
+/// warned about, unless configured otherwise.
+pub const DEFAULT_MARGIN: f64 = 0.1;
+
+/// Share of the tries done before the ETA is trusted, as the first tries
+/// are slowed down by spawning workers.
+const TRUSTED_SHARE: f64 = 0.01;
+
+/// ETA of a research exceeding its expected duration, passed to
+/// [`ProgressHandler::on_deadline_overrun`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeadlineOverrun {
+  pub expected: Duration,
+  /// Time elapsed since the research started
+  pub elapsed: Duration,
+  /// Estimated duration of the whole research
+  pub estimated: Duration,
+}
+
+/// Handler which warns the wrapped handler once the ETA of the research
+/// exceeds its expected duration by a margin, so miscalibrated researches
+/// are noticed long before they are due.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::deadline::DeadlineProgressHandler;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+///
+/// let handler = DeadlineProgressHandler::new(
+///   10,
+///   Duration::from_secs(3600),
+///   Box::new(EmptyProgressHandler::new()),
+/// );
+/// let warning = handler.warning();
+///
+/// ResearchBuilder::new()
+///   .tries(10)
+///   .times(1)
+///   .regex(Regex::new(r"WKCN")?)
+///   .progress_handler(Box::new(handler))
+///   .run_seq()?;
+///
+/// assert!(warning.lock().unwrap().is_none());
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct DeadlineProgressHandler {
+  tries: usize,
+  expected: Duration,
+  margin: f64,
+  inner: Box<dyn ProgressHandler>,
+  started: Instant,
+  warning: Arc<Mutex<Option<DeadlineOverrun>>>,
+}
+
+impl DeadlineProgressHandler {
+  /// Warn once the `tries` look like taking longer than `expected`.
+  pub fn new(
+    tries: usize,
+    expected: Duration,
+    inner: Box<dyn ProgressHandler>,
+  ) -> Self {
+    Self {
+      tries,
+      expected,
+      margin: DEFAULT_MARGIN,
+      inner,
+      started: Instant::now(),
+      warning: Arc::default(),
+    }
+  }
+
+  /// Warn once the ETA exceeds the expected duration by `margin` of it,
+  /// defaults to [`DEFAULT_MARGIN`].
+  pub fn with_margin(mut self, margin: f64) -> Self {
+    self.margin = margin;
+    self
+  }
+
+  /// Return a handle to the warning, set once it was given.
+  #[inline]
+  pub fn warning(&self) -> Arc<Mutex<Option<DeadlineOverrun>>> {
+    self.warning.clone()
+  }
+
+  /// Return the overrun of the research at `current` tries, if any.
+  fn overrun(&self, current: usize) -> Option<DeadlineOverrun> {
+    let elapsed = self.started.elapsed();
+    let limit = self.expected.mul_f64(1.0 + self.margin);
+    let done = current as f64 / self.tries as f64;
+
+    let estimated = if done >= TRUSTED_SHARE {
+      elapsed.mul_f64(1.0 / done)
+    } else if elapsed > limit {
+      // late anyway, however few tries were done
+      elapsed
+    } else {
+      return None;
+    };
+
+    (estimated > limit).then_some(DeadlineOverrun {
+      expected: self.expected,
+      elapsed,
+      estimated,
+    })
+  }
+}
+
+impl ProgressHandler for DeadlineProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.started = Instant::now();
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    let current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>()
+      .min(self.tries);
+
+    if !all_done && self.warning.lock().unwrap().is_none() {
+      if let Some(overrun) = self.overrun(current) {
+        self.inner.on_deadline_overrun(&overrun)?;
+        *self.warning.lock().unwrap() = Some(overrun);
+      }
+    }
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  crate::forward_to_inner!(
+    after_finish,
+    on_pause,
+    on_resume,
+    on_hit,
+    on_notable_hit,
+    on_deadline_overrun,
+    on_accidential_stop
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use std::thread;
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::progress::{ProcessingDetail, Progress, ProgressKind};
+
+  use super::DeadlineProgressHandler;
+
+  fn processing(current: usize) -> [Progress; 1] {
+    [Progress(ProgressKind::Processing(ProcessingDetail {
+      id: 1,
+      wakuchin: "WKCN".into(),
+      current,
+      total: 1000,
+    }))]
+  }
+
+  #[test]
+  fn test_overrun() -> Result<()> {
+    let mut handler = DeadlineProgressHandler::new(
+      1000,
+      Duration::from_secs(2),
+      Box::new(EmptyProgressHandler::new()),
+    );
+    let warning = handler.warning();
+
+    handler.before_start(&RunInfo::new(1))?;
+    thread::sleep(Duration::from_millis(20));
+
+    // too few tries to trust the ETA
+    handler.handle(&processing(5), &[], Duration::ZERO, 0, false)?;
+    assert!(warning.lock().unwrap().is_none());
+
+    // on track
+    handler.handle(&processing(500), &[], Duration::ZERO, 0, false)?;
+    assert!(warning.lock().unwrap().is_none());
+
+    thread::sleep(Duration::from_millis(150));
+    handler.handle(&processing(50), &[], Duration::ZERO, 0, false)?;
+
+    let overrun = warning.lock().unwrap().clone().unwrap();
+
+    assert_eq!(overrun.expected, Duration::from_secs(2));
+    assert!(overrun.estimated > Duration::from_secs(2));
+
+    Ok(())
+  }
+}
@@ -4,7 +4,7 @@ use anyhow::Result;
 
 use crate::{progress::Progress, result::HitCount};
 
-use super::ProgressHandler;
+use super::{ControlFlow, ProgressHandler};
 
 pub struct EmptyProgressHandler {}
 
@@ -29,7 +29,7 @@ impl ProgressHandler for EmptyProgressHandler {
     _: Duration,
     _: usize,
     _: bool,
-  ) -> Result<()> {
-    Ok(())
+  ) -> Result<ControlFlow> {
+    Ok(ControlFlow::Continue)
   }
 }
@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::SystemTime;
+use serde_json::{json, Value};
+
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::{HitCount, NotableHit};
+
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Size a log file may reach before it is rotated, unless configured
+/// otherwise.
+pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Rotated log files kept besides the current one, unless configured
+/// otherwise.
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// Handler logging the progress and the hits of a research to a file, one
+/// JSON object per line, for unattended researches running for days.
+///
+/// Each line has an `event` and the Unix timestamp `at` in seconds:
+///
+///  - `start`: the [`RunInfo`] of the research
+///  - `progress`: the tries done so far, the tries per second, the
+///    estimated remaining seconds and the hits of all chars
+///  - `hits`: the hits of chars found since the previous progress
+///  - `notable_hit`: a notable hit, see
+///    [`ProgressHandler::on_notable_hit`]
+///  - `pause`, `resume`, `finish` and `stop`
+///
+/// Once the file would grow larger than
+/// [`with_max_size`](Self::with_max_size), it is renamed to `<path>.1`,
+/// `<path>.1` to `<path>.2` and so on, dropping the oldest, and a new file
+/// is started. Rotated files are compressed to `<path>.1.gz` with
+/// [`with_gzip`](Self::with_gzip), which requires the `gzip` feature.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::file::FileProgressHandler;
+///
+/// let handler = FileProgressHandler::new("research.log")?
+///   .with_max_size(16 * 1024 * 1024)
+///   .with_max_files(10);
+///
+/// ResearchBuilder::new()
+///   .tries(100_000_000)
+///   .times(2)
+///   .regex(Regex::new(r"WKNCWKNC")?)
+///   .progress_handler(Box::new(handler))
+///   .run_par()?;
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct FileProgressHandler {
+  path: PathBuf,
+  file: File,
+  /// Bytes written to the current file
+  size: u64,
+  max_size: u64,
+  max_files: usize,
+  gzip: bool,
+  tries: usize,
+  /// Hits of each chars and pattern as of the previous progress
+  hits: HashMap<(usize, String), usize>,
+}
+
+impl FileProgressHandler {
+  /// Create a handler appending to the file at `path`, created if missing.
+  pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+    let path = path.into();
+    let file = open(&path)?;
+    let size = file.metadata()?.len();
+
+    Ok(Self {
+      path,
+      file,
+      size,
+      max_size: DEFAULT_MAX_SIZE,
+      max_files: DEFAULT_MAX_FILES,
+      gzip: false,
+      tries: 0,
+      hits: HashMap::new(),
+    })
+  }
+
+  /// Rotate the file once it would grow larger than `max_size` bytes,
+  /// defaults to [`DEFAULT_MAX_SIZE`].
+  #[must_use]
+  pub fn with_max_size(mut self, max_size: u64) -> Self {
+    self.max_size = max_size;
+    self
+  }
+
+  /// Keep `max_files` rotated files, defaults to [`DEFAULT_MAX_FILES`].
+  #[must_use]
+  pub fn with_max_files(mut self, max_files: usize) -> Self {
+    self.max_files = max_files;
+    self
+  }
+
+  /// Compress rotated files with gzip.
+  #[cfg(feature = "gzip")]
+  #[must_use]
+  pub fn with_gzip(mut self, gzip: bool) -> Self {
+    self.gzip = gzip;
+    self
+  }
+
+  /// Path of the file rotated `n` times.
+  fn rotated_path(&self, n: usize) -> PathBuf {
+    let mut path = self.path.clone().into_os_string();
+
+    path.push(format!(".{n}"));
+
+    if self.gzip {
+      path.push(".gz");
+    }
+
+    path.into()
+  }
+
+  /// Rename the file and the rotated files, then start a new file.
+  fn rotate(&mut self) -> io::Result<()> {
+    self.file.flush()?;
+
+    if self.max_files == 0 {
+      self.file = File::create(&self.path)?;
+      self.size = 0;
+
+      return Ok(());
+    }
+
+    match fs::remove_file(self.rotated_path(self.max_files)) {
+      Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+      _ => {}
+    }
+
+    for n in (1..self.max_files).rev() {
+      match fs::rename(self.rotated_path(n), self.rotated_path(n + 1)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+      }
+    }
+
+    if self.gzip {
+      compress(&self.path, &self.rotated_path(1))?;
+      fs::remove_file(&self.path)?;
+    } else {
+      fs::rename(&self.path, self.rotated_path(1))?;
+    }
+
+    self.file = open(&self.path)?;
+    self.size = 0;
+
+    Ok(())
+  }
+
+  /// Append the `event` with its `fields` as a line.
+  fn log(&mut self, event: &str, fields: Value) -> Result<()> {
+    let at = SystemTime::now()
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .unwrap_or_default();
+    let mut line = json!({ "event": event, "at": at.as_secs_f64() });
+
+    if let (Value::Object(line), Value::Object(fields)) = (&mut line, fields) {
+      line.extend(fields);
+    }
+
+    let mut line = serde_json::to_vec(&line)?;
+
+    line.push(b'\n');
+
+    // a line larger than the limit is written to a file of its own
+    if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+      self.rotate()?;
+    }
+
+    self.file.write_all(&line)?;
+    self.size += line.len() as u64;
+
+    Ok(())
+  }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+  OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Compress the file at `from` to a gzip file at `to`.
+#[cfg(feature = "gzip")]
+fn compress(from: &Path, to: &Path) -> io::Result<()> {
+  use flate2::write::GzEncoder;
+
+  let mut encoder =
+    GzEncoder::new(File::create(to)?, flate2::Compression::default());
+
+  io::copy(&mut File::open(from)?, &mut encoder)?;
+  encoder.finish()?.sync_all()
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(_from: &Path, _to: &Path) -> io::Result<()> {
+  unreachable!("gzip is only enabled with the \"gzip\" feature")
+}
+
+impl ProgressHandler for FileProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.tries = info.tries;
+    self.hits.clear();
+
+    let started_at = info
+      .started_at
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .unwrap_or_default();
+
+    self.log(
+      "start",
+      json!({
+        "tries": info.tries,
+        "times": info.times,
+        "patterns": info.patterns,
+        "workers": info.workers,
+        "seed": info.seed,
+        "started_at": started_at.as_secs_f64(),
+      }),
+    )
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    for count in hit_counts {
+      let key = (count.pattern, count.chars.to_string());
+      let previous = self.hits.insert(key, count.hits).unwrap_or(0);
+
+      if count.hits > previous {
+        self.log(
+          "hits",
+          json!({
+            "chars": count.chars,
+            "pattern": count.pattern,
+            "new": count.hits - previous,
+            "hits": count.hits,
+          }),
+        )?;
+      }
+    }
+
+    let current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>()
+      .min(self.tries);
+
+    let rate = current_diff as f64 / elapsed_time.as_secs_f64();
+    let eta = if all_done {
+      0.0
+    } else {
+      (self.tries - current) as f64 / rate
+    };
+
+    // non-finite rates and ETAs are written as null
+    self.log(
+      "progress",
+      json!({
+        "current": current,
+        "tries": self.tries,
+        "rate": rate,
+        "eta": eta,
+        "hits": hit_counts.iter().map(|count| count.hits).sum::<usize>(),
+        "all_done": all_done,
+      }),
+    )?;
+
+    Ok(ControlFlow::Continue)
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.log("finish", json!({}))?;
+    self.file.sync_all()?;
+
+    Ok(())
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    self.log("pause", json!({}))
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    self.log("resume", json!({}))
+  }
+
+  fn on_notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+    self.log(
+      "notable_hit",
+      json!({
+        "chars": hit.hit.chars.as_str(),
+        "pattern": hit.hit.pattern,
+        "hit_on": hit.hit.hit_on,
+        "one_in": hit.rarity.map(|rarity| rarity.one_in.to_string()),
+      }),
+    )
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.log("stop", json!({}))?;
+    self.file.sync_all()?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::env::temp_dir;
+  use std::fs;
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use serde_json::Value;
+
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::progress::{ProcessingDetail, Progress, ProgressKind};
+  use crate::result::HitCount;
+
+  use super::FileProgressHandler;
+
+  #[test]
+  fn test_rotation() -> Result<()> {
+    let dir = temp_dir().join(format!("wakuchin-file-{}", fastrand::u64(..)));
+
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join("research.log");
+    let mut handler = FileProgressHandler::new(&path)?
+      .with_max_size(512)
+      .with_max_files(2);
+
+    handler.before_start(&RunInfo {
+      tries: 100,
+      ..RunInfo::new(1)
+    })?;
+
+    for current in 0..20 {
+      let progresses = [Progress(ProgressKind::Processing(ProcessingDetail {
+        id: 0,
+        wakuchin: "WKCN".into(),
+        current,
+        total: 100,
+      }))];
+      let hit_counts = [HitCount::new("WKCN", current / 5)];
+
+      handler.handle(
+        &progresses,
+        &hit_counts,
+        Duration::from_secs(1),
+        1,
+        false,
+      )?;
+    }
+
+    handler.after_finish()?;
+
+    let read = |path| -> Result<Vec<Value>> {
+      fs::read_to_string(path)?
+        .lines()
+        .map(|line| serde_json::from_str(line).map_err(Into::into))
+        .collect()
+    };
+    let current = read(&path)?;
+    let rotated = read(&dir.join("research.log.1"))?;
+
+    assert!(fs::metadata(&path)?.len() <= 512);
+    assert!(fs::metadata(dir.join("research.log.2")).is_ok());
+    assert!(fs::metadata(dir.join("research.log.3")).is_err());
+
+    assert_eq!(current.last().unwrap()["event"], "finish");
+    assert!(rotated.iter().all(|line| line["at"].is_f64()));
+
+    let hits = current
+      .iter()
+      .chain(&rotated)
+      .filter(|line| line["event"] == "hits")
+      .collect::<Vec<_>>();
+
+    assert!(hits.iter().all(|line| line["new"] == 1));
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn test_gzip() -> Result<()> {
+    use std::fs::File;
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    let dir = temp_dir().join(format!("wakuchin-file-{}", fastrand::u64(..)));
+
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join("research.log");
+    let mut handler = FileProgressHandler::new(&path)?
+      .with_max_size(1)
+      .with_gzip(true);
+
+    handler.before_start(&RunInfo::new(1))?;
+    handler.after_finish()?;
+
+    let mut rotated = String::new();
+
+    GzDecoder::new(File::open(dir.join("research.log.1.gz"))?)
+      .read_to_string(&mut rotated)?;
+
+    let line: Value = serde_json::from_str(rotated.trim_end())?;
+
+    assert_eq!(line["event"], "start");
+    assert!(fs::metadata(dir.join("research.log.1")).is_err());
+
+    fs::remove_dir_all(&dir)?;
+
+    Ok(())
+  }
+}
@@ -1,37 +1,288 @@
-use std::io::Write;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use base64::{engine::general_purpose, Engine as _};
-use serde::Serialize;
 
-use crate::progress::{DoneDetail, ProcessingDetail, Progress, ProgressKind};
+use crate::progress::Progress;
+use crate::protocol::{self, ProgressReport, ProtocolVersion};
 use crate::result::HitCount;
 
-use super::ProgressHandler;
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// How consecutive frames are delimited in the output stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Framing {
+  /// Frames are written back-to-back
+  #[default]
+  None,
+  /// Each frame is preceded by its length as a big-endian `u32`
+  LengthPrefixed,
+  /// Each frame is followed by `\n`, only meaningful for base64 frames
+  Newline,
+}
 
-#[derive(Clone, Debug, Serialize)]
-struct MsgpackProgress<'a> {
-  progresses: &'a [Progress],
-  hit_counts: &'a [HitCount],
-  current_rate: f64,
-  remaining_time: f64,
-  tries: usize,
-  all_done: bool,
+/// Compression of each frame, before it is encoded with base64 if so.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+  #[default]
+  None,
+  /// Each frame is a zstd frame of the msgpack frame, compressed at the
+  /// level, told apart from msgpack frames by its magic number `28 b5 2f fd`
+  #[cfg(feature = "zstd")]
+  Zstd(i32),
+}
+
+impl Compression {
+  /// Compress `frame`, as is without compression.
+  pub fn compress(self, frame: Vec<u8>) -> io::Result<Vec<u8>> {
+    match self {
+      Self::None => Ok(frame),
+      #[cfg(feature = "zstd")]
+      Self::Zstd(level) => zstd::bulk::compress(&frame, level),
+    }
+  }
+
+  /// Decompress `frame` as written by a handler of any compression, telling
+  /// zstd frames apart by their magic number.
+  pub fn decompress(frame: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !frame.starts_with(&ZSTD_MAGIC) {
+      return Ok(frame);
+    }
+
+    #[cfg(feature = "zstd")]
+    return zstd::decode_all(&frame[..]);
+
+    #[cfg(not(feature = "zstd"))]
+    return Err(io::Error::new(
+      io::ErrorKind::Unsupported,
+      "zstd frame, which requires the \"zstd\" feature",
+    ));
+  }
+}
+
+/// First bytes of zstd frames, which msgpack frames never start with.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Frames a consumer may lag behind by default, see [`Acks::new`].
+pub const DEFAULT_ACK_WINDOW: u64 = 8;
+
+/// Acknowledgements of the frames read by the consumer of the progress, so
+/// frames are skipped rather than piling up in the pipe or the socket when
+/// it cannot keep up.
+///
+/// A frame is written only while fewer than `window` written frames are
+/// unacknowledged, the last frame of the research always being written.
+/// Skipped frames are counted in the `skipped` field of the frames since
+/// [`V2`](crate::protocol::ProtocolVersion::V2).
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+///
+/// use wakuchin::handlers::msgpack::Acks;
+///
+/// let acks = Acks::new(2);
+///
+/// assert!(acks.allows(1));
+/// assert!(!acks.allows(2));
+///
+/// // the consumer read the frames 0 and 1
+/// acks.read_lines(Cursor::new("0\n1\n"))?;
+///
+/// assert!(acks.allows(3));
+/// #
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Acks {
+  /// Number of the first unacknowledged frame
+  acked: Arc<AtomicU64>,
+  window: u64,
+}
+
+impl Acks {
+  pub fn new(window: u64) -> Self {
+    Self {
+      acked: Arc::default(),
+      window: window.max(1),
+    }
+  }
+
+  /// Acknowledge the frames up to `seq`.
+  #[inline]
+  pub fn ack(&self, seq: u64) {
+    self.acked.fetch_max(seq + 1, Ordering::SeqCst);
+  }
+
+  /// Acknowledge the frames whose `seq` numbers are read from `reader`, one
+  /// decimal number per line, until it is closed.
+  pub fn read_lines(&self, reader: impl BufRead) -> io::Result<()> {
+    for line in reader.lines() {
+      let line = line?;
+      let line = line.trim();
+
+      if line.is_empty() {
+        continue;
+      }
+
+      let seq = line.parse().map_err(|_| {
+        io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("'{line}' is not the seq number of a frame"),
+        )
+      })?;
+
+      self.ack(seq);
+    }
+
+    Ok(())
+  }
+
+  /// Return whether the frame of `seq` can be written.
+  #[inline]
+  pub fn allows(&self, seq: u64) -> bool {
+    seq < self.acked.load(Ordering::SeqCst) + self.window
+  }
+}
+
+/// Frames written and skipped by a handler, waiting for the acknowledgements
+/// of its consumer if any.
+#[derive(Default)]
+struct FlowControl {
+  acks: Option<Acks>,
+  seq: u64,
+  skipped: u64,
+}
+
+impl FlowControl {
+  /// Return the report numbered as the next frame, or `None` if the frame
+  /// has to be skipped.
+  fn next<'a>(
+    &mut self,
+    report: ProgressReport<'a>,
+  ) -> Option<ProgressReport<'a>> {
+    let Some(acks) = &self.acks else {
+      return Some(report.with_seq(self.seq));
+    };
+
+    if !report.all_done && !acks.allows(self.seq) {
+      self.skipped += 1;
+
+      return None;
+    }
+
+    Some(report.with_seq(self.seq).with_skipped(self.skipped))
+  }
+
+  /// Count the frame returned by `next` as written.
+  fn written(&mut self) {
+    self.seq += 1;
+  }
+}
+
+pub(super) fn write_frame(
+  writer: &mut (dyn Write + Send),
+  framing: Framing,
+  frame: &[u8],
+) -> std::io::Result<()> {
+  match framing {
+    Framing::None => writer.write_all(frame),
+    Framing::LengthPrefixed => {
+      let len = u32::try_from(frame.len()).map_err(|_| {
+        std::io::Error::new(
+          std::io::ErrorKind::InvalidInput,
+          "frame is larger than 4 GiB",
+        )
+      })?;
+
+      writer.write_all(&len.to_be_bytes())?;
+      writer.write_all(frame)
+    }
+    Framing::Newline => {
+      writer.write_all(frame)?;
+      writer.write_all(b"\n")
+    }
+  }
 }
 
 pub struct MsgpackBase64ProgressHandler {
   tries: usize,
   writer: Arc<Mutex<dyn Write + Send>>,
+  framing: Framing,
+  protocol_version: ProtocolVersion,
+  compression: Compression,
+  flow: FlowControl,
+  /// Research being reported, once started
+  info: Option<RunInfo>,
 }
 
 impl MsgpackBase64ProgressHandler {
+  /// Create a handler writing to `writer` the progress of `tries`, replaced
+  /// by the tries of the [`RunInfo`] once the research starts.
   pub fn new(tries: usize, writer: Arc<Mutex<dyn Write + Send>>) -> Self {
-    Self { tries, writer }
+    Self {
+      tries,
+      writer,
+      framing: Framing::None,
+      protocol_version: ProtocolVersion::default(),
+      compression: Compression::None,
+      flow: FlowControl::default(),
+      info: None,
+    }
+  }
+
+  /// Create a handler writing to the async `writer` the progress of
+  /// `tries`, and the task writing it, see
+  /// [`AsyncWriter`](super::async_writer::AsyncWriter).
+  #[cfg(feature = "async")]
+  pub fn new_async<W>(
+    tries: usize,
+    writer: W,
+  ) -> (Self, super::async_writer::WriteTask)
+  where
+    W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+  {
+    let (writer, task) = super::async_writer::AsyncWriter::new(writer);
+
+    (Self::new(tries, writer), task)
+  }
+
+  /// Delimit frames with `framing`, defaults to [`Framing::None`].
+  pub fn with_framing(mut self, framing: Framing) -> Self {
+    self.framing = framing;
+    self
+  }
+
+  /// Lay out frames as `version`, defaults to [`ProtocolVersion::LATEST`].
+  pub fn with_protocol_version(mut self, version: ProtocolVersion) -> Self {
+    self.protocol_version = version;
+    self
+  }
+
+  /// Compress frames with `compression`, defaults to [`Compression::None`].
+  pub fn with_compression(mut self, compression: Compression) -> Self {
+    self.compression = compression;
+    self
+  }
+
+  /// Skip frames while the consumer lags behind `acks`.
+  pub fn with_acks(mut self, acks: Acks) -> Self {
+    self.flow.acks = Some(acks);
+    self
   }
 }
 
 impl ProgressHandler for MsgpackBase64ProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> anyhow::Result<()> {
+    self.tries = info.tries;
+    self.info = Some(info.clone());
+
+    Ok(())
+  }
+
   fn handle(
     &mut self,
     progresses: &[Progress],
@@ -39,70 +290,115 @@ impl ProgressHandler for MsgpackBase64ProgressHandler {
     elapsed_time: Duration,
     current_diff: usize,
     all_done: bool,
-  ) -> anyhow::Result<()> {
-    let mut buf = Vec::new();
-    let mut serializer = rmp_serde::Serializer::new(&mut buf);
-
-    let mut current_total = 0;
-
-    for progress in progresses {
-      match progress {
-        Progress(ProgressKind::Processing(ProcessingDetail {
-          current,
-          ..
-        })) => {
-          current_total += current;
-        }
-        Progress(ProgressKind::Done(DoneDetail { total, .. })) => {
-          current_total += total;
-        }
-        _ => {}
-      }
-    }
-
-    if current_total > self.tries {
-      current_total = self.tries;
-    }
-
-    let elapsed_time = elapsed_time.as_secs_f64();
-    let current_rate = current_diff as f64 / elapsed_time;
-    let remaining_time = (self.tries - current_total) as f64 / current_rate;
-
-    let progress = MsgpackProgress {
+  ) -> anyhow::Result<ControlFlow> {
+    let report = ProgressReport::new(
       progresses,
       hit_counts,
-      current_rate,
-      remaining_time,
-      tries: self.tries,
+      self.tries,
+      elapsed_time,
+      current_diff,
       all_done,
+    );
+    let report = match &self.info {
+      Some(info) => report.with_info(info),
+      None => report,
+    };
+    let Some(report) = self.flow.next(report) else {
+      return Ok(ControlFlow::Continue);
     };
 
-    progress.serialize(&mut serializer)?;
-
-    let encoded = general_purpose::STANDARD.encode(&mut buf);
+    let buf = self
+      .compression
+      .compress(protocol::encode(self.protocol_version, &report)?)?;
+    let encoded = general_purpose::STANDARD.encode(buf);
 
     let mut writer = self.writer.lock().unwrap();
-    writer.write_all(encoded.as_bytes())?;
+    write_frame(&mut *writer, self.framing, encoded.as_bytes())?;
 
-    Ok(())
+    self.flow.written();
+
+    Ok(ControlFlow::Continue)
   }
 }
 
 pub struct MsgpackProgressHandler {
   tries: usize,
   writer: Arc<Mutex<dyn Write + Send>>,
+  framing: Framing,
+  protocol_version: ProtocolVersion,
+  compression: Compression,
+  flow: FlowControl,
+  /// Research being reported, once started
+  info: Option<RunInfo>,
 }
 
 impl MsgpackProgressHandler {
+  /// Create a handler writing to `writer` the progress of `tries`, replaced
+  /// by the tries of the [`RunInfo`] once the research starts.
   pub fn new(
     tries: usize,
     writer: Arc<Mutex<dyn Write + Send + 'static>>,
   ) -> Self {
-    Self { tries, writer }
+    Self {
+      tries,
+      writer,
+      framing: Framing::None,
+      protocol_version: ProtocolVersion::default(),
+      compression: Compression::None,
+      flow: FlowControl::default(),
+      info: None,
+    }
+  }
+
+  /// Create a handler writing to the async `writer` the progress of
+  /// `tries`, and the task writing it, see
+  /// [`AsyncWriter`](super::async_writer::AsyncWriter).
+  #[cfg(feature = "async")]
+  pub fn new_async<W>(
+    tries: usize,
+    writer: W,
+  ) -> (Self, super::async_writer::WriteTask)
+  where
+    W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+  {
+    let (writer, task) = super::async_writer::AsyncWriter::new(writer);
+
+    (Self::new(tries, writer), task)
+  }
+
+  /// Delimit frames with `framing`, defaults to [`Framing::None`].
+  pub fn with_framing(mut self, framing: Framing) -> Self {
+    self.framing = framing;
+    self
+  }
+
+  /// Lay out frames as `version`, defaults to [`ProtocolVersion::LATEST`].
+  pub fn with_protocol_version(mut self, version: ProtocolVersion) -> Self {
+    self.protocol_version = version;
+    self
+  }
+
+  /// Compress frames with `compression`, defaults to [`Compression::None`].
+  pub fn with_compression(mut self, compression: Compression) -> Self {
+    self.compression = compression;
+    self
+  }
+
+  /// Skip frames while the consumer lags behind `acks`.
+  pub fn with_acks(mut self, acks: Acks) -> Self {
+    self.flow.acks = Some(acks);
+    self
   }
 }
 
 impl ProgressHandler for MsgpackProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> anyhow::Result<()> {
+    self.tries = info.tries;
+    self.info = Some(info.clone());
+
+    Ok(())
+  }
+
   fn handle(
     &mut self,
     progresses: &[Progress],
@@ -110,49 +406,32 @@ impl ProgressHandler for MsgpackProgressHandler {
     elapsed_time: Duration,
     current_diff: usize,
     all_done: bool,
-  ) -> anyhow::Result<()> {
-    let mut buf = Vec::new();
-    let mut serializer = rmp_serde::Serializer::new(&mut buf);
-
-    let mut current_total = 0;
-
-    for progress in progresses {
-      match progress {
-        Progress(ProgressKind::Processing(ProcessingDetail {
-          current,
-          ..
-        })) => {
-          current_total += current;
-        }
-        Progress(ProgressKind::Done(DoneDetail { total, .. })) => {
-          current_total += total;
-        }
-        _ => {}
-      }
-    }
-
-    if current_total > self.tries {
-      current_total = self.tries;
-    }
-
-    let elapsed_time = elapsed_time.as_secs_f64();
-    let current_rate = current_diff as f64 / elapsed_time;
-    let remaining_time = (self.tries - current_total) as f64 / current_rate;
-
-    let progress = MsgpackProgress {
+  ) -> anyhow::Result<ControlFlow> {
+    let report = ProgressReport::new(
       progresses,
       hit_counts,
-      current_rate,
-      remaining_time,
-      tries: self.tries,
+      self.tries,
+      elapsed_time,
+      current_diff,
       all_done,
+    );
+    let report = match &self.info {
+      Some(info) => report.with_info(info),
+      None => report,
+    };
+    let Some(report) = self.flow.next(report) else {
+      return Ok(ControlFlow::Continue);
     };
 
-    progress.serialize(&mut serializer)?;
+    let buf = self
+      .compression
+      .compress(protocol::encode(self.protocol_version, &report)?)?;
 
-    self.writer.lock().unwrap().write_all(&buf)?;
+    write_frame(&mut *self.writer.lock().unwrap(), self.framing, &buf)?;
 
-    Ok(())
+    self.flow.written();
+
+    Ok(ControlFlow::Continue)
   }
 }
 
@@ -168,16 +447,21 @@ mod test {
 
   use crate::handlers::ProgressHandler;
   use crate::progress::{ProcessingDetail, Progress, ProgressKind};
+  use crate::protocol::ProtocolVersion;
   use crate::result::HitCount;
 
+  #[cfg(feature = "zstd")]
+  use super::Compression;
   use super::MsgpackBase64ProgressHandler;
   use super::MsgpackProgressHandler;
+  use super::{write_frame, Acks, Framing};
 
   #[test]
   fn test_msgpack_base64_progress() -> Result<()> {
     let tries = 100;
     let cursor = Arc::new(Mutex::new(Cursor::new(Vec::new())));
-    let mut handler = MsgpackBase64ProgressHandler::new(tries, cursor.clone());
+    let mut handler = MsgpackBase64ProgressHandler::new(tries, cursor.clone())
+      .with_protocol_version(ProtocolVersion::V1);
 
     let progresses =
       vec![Progress(ProgressKind::Processing(ProcessingDetail {
@@ -187,10 +471,7 @@ mod test {
         wakuchin: "WKNCWKNC".into(),
       }))];
 
-    let hit_counts = vec![HitCount {
-      chars: "あ".into(),
-      hits: 0,
-    }];
+    let hit_counts = vec![HitCount::new("あ", 0)];
 
     let elapsed_time = Duration::from_secs(1);
     let current_diff = 1;
@@ -227,7 +508,8 @@ mod test {
   fn test_msgpack_progress() -> Result<()> {
     let tries = 100;
     let cursor = Arc::new(Mutex::new(Cursor::new(Vec::new())));
-    let mut handler = MsgpackProgressHandler::new(tries, cursor.clone());
+    let mut handler = MsgpackProgressHandler::new(tries, cursor.clone())
+      .with_protocol_version(ProtocolVersion::V1);
 
     let progresses =
       vec![Progress(ProgressKind::Processing(ProcessingDetail {
@@ -237,10 +519,7 @@ mod test {
         wakuchin: "WKNCWKNC".into(),
       }))];
 
-    let hit_counts = vec![HitCount {
-      chars: "あ".into(),
-      hits: 0,
-    }];
+    let hit_counts = vec![HitCount::new("あ", 0)];
 
     let elapsed_time = Duration::from_secs(1);
     let current_diff = 1;
@@ -272,4 +551,114 @@ mod test {
 
     Ok(())
   }
+
+  #[test]
+  fn test_framing() -> Result<()> {
+    let mut buf = Vec::new();
+
+    write_frame(&mut buf, Framing::None, b"ab")?;
+    write_frame(&mut buf, Framing::LengthPrefixed, b"cde")?;
+    write_frame(&mut buf, Framing::Newline, b"fg")?;
+
+    assert_eq!(buf, b"ab\x00\x00\x00\x03cdefg\n");
+
+    let cursor = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+    let mut handler = MsgpackProgressHandler::new(100, cursor.clone())
+      .with_framing(Framing::LengthPrefixed)
+      .with_protocol_version(ProtocolVersion::V1);
+
+    let progresses =
+      vec![Progress(ProgressKind::Processing(ProcessingDetail {
+        current: 0,
+        total: 100,
+        id: 0,
+        wakuchin: "WKNCWKNC".into(),
+      }))];
+
+    for _ in 0..2 {
+      handler.handle(&progresses, &[], Duration::from_secs(1), 1, false)?;
+    }
+
+    let buf = cursor.lock().unwrap().get_ref().clone();
+    let len = u32::from_be_bytes(buf[..4].try_into()?) as usize;
+
+    assert_eq!(buf.len(), (4 + len) * 2);
+    assert_eq!(buf[..4 + len], buf[4 + len..]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_acks() -> Result<()> {
+    let cursor = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+    let acks = Acks::new(1);
+    let mut handler = MsgpackProgressHandler::new(100, cursor.clone())
+      .with_framing(Framing::LengthPrefixed)
+      .with_acks(acks.clone());
+
+    let frames = || -> Result<Vec<serde_json::Value>> {
+      let buf = {
+        let mut cursor = cursor.lock().unwrap();
+
+        cursor.set_position(0);
+        std::mem::take(cursor.get_mut())
+      };
+      let mut frames = Vec::new();
+      let mut rest = &buf[..];
+
+      while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into()?) as usize;
+
+        frames.push(rmp_serde::from_slice(&rest[4..4 + len])?);
+        rest = &rest[4 + len..];
+      }
+
+      Ok(frames)
+    };
+
+    for _ in 0..3 {
+      handler.handle(&[], &[], Duration::from_secs(1), 1, false)?;
+    }
+
+    let written = frames()?;
+
+    assert_eq!(written.len(), 1);
+    assert_eq!(written[0]["skipped"], 0);
+
+    acks.ack(0);
+    handler.handle(&[], &[], Duration::from_secs(1), 1, false)?;
+    // the last frame is written anyway
+    handler.handle(&[], &[], Duration::from_secs(1), 1, true)?;
+
+    let written = frames()?;
+
+    assert_eq!(written.len(), 2);
+    assert_eq!(written[0]["seq"], 1);
+    assert_eq!(written[0]["skipped"], 2);
+    assert_eq!(written[1]["seq"], 2);
+
+    Ok(())
+  }
+
+  #[cfg(feature = "zstd")]
+  #[test]
+  fn test_zstd() -> Result<()> {
+    let cursor = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+    let mut handler = MsgpackProgressHandler::new(100, cursor.clone())
+      .with_compression(Compression::Zstd(3));
+
+    handler.handle(&[], &[], Duration::from_secs(1), 1, true)?;
+
+    let buf = cursor.lock().unwrap().get_ref().clone();
+
+    assert_eq!(buf[..4], [0x28, 0xb5, 0x2f, 0xfd]);
+
+    let frame: serde_json::Value =
+      rmp_serde::from_slice(&Compression::decompress(buf)?)?;
+
+    assert_eq!(frame["all_done"], true);
+    assert_eq!(Compression::decompress(b"ab".to_vec())?, b"ab");
+
+    Ok(())
+  }
 }
@@ -0,0 +1,317 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+use notify_rust::Notification;
+use regex::Regex;
+
+use crate::progress::Progress;
+use crate::result::{Hit, HitCount};
+
+use super::{ControlFlow, ProgressHandler, RunInfo, WarningCallback};
+
+/// Time between notifications of hits, so frequent hits do not flood the
+/// desktop.
+pub const HIT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Application name of the notifications.
+const APP_NAME: &str = "wakuchin";
+
+/// Show a notification with `summary` and `body`.
+type Show = Box<dyn FnMut(&str, &str) -> Result<()> + Send>;
+
+fn show(summary: &str, body: &str) -> Result<()> {
+  Notification::new()
+    .appname(APP_NAME)
+    .summary(summary)
+    .body(body)
+    .show()?;
+
+  Ok(())
+}
+
+/// Handler showing desktop notifications once the research finishes, and
+/// on the hits matching a pattern, besides the handler it wraps.
+///
+/// Hits are notified at most once per [`HIT_INTERVAL`], the hits in between
+/// being counted in the next notification. Notifications are best effort:
+/// if they cannot be shown, e.g. without a notification daemon, it is warned
+/// about once, see [`with_warning_callback`](Self::with_warning_callback),
+/// and the research goes on.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::handlers::notify::NotifyProgressHandler;
+///
+/// let handler =
+///   NotifyProgressHandler::new(Box::new(EmptyProgressHandler::new()))
+///     .with_hit_pattern(Regex::new(r"^WKNCWKNC$")?);
+///
+/// ResearchBuilder::new()
+///   .tries(1_000_000_000)
+///   .times(2)
+///   .regex(Regex::new(r"WKNC")?)
+///   .progress_handler(Box::new(handler))
+///   .run_par()?;
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct NotifyProgressHandler {
+  inner: Box<dyn ProgressHandler>,
+  hit_pattern: Option<Regex>,
+  show: Show,
+  started: Instant,
+  tries: usize,
+  /// Hit counts of the last progress
+  hit_counts: Vec<HitCount>,
+  /// When a hit was last notified
+  last_hit: Option<Instant>,
+  /// Matching hits not notified yet, and the chars of the last of them
+  pending: Option<(usize, String)>,
+  /// Whether a notification failed, so the failure is only warned once
+  failed: bool,
+  on_warning: Option<WarningCallback>,
+}
+
+impl NotifyProgressHandler {
+  /// Notify once the research of `inner` finishes.
+  pub fn new(inner: Box<dyn ProgressHandler>) -> Self {
+    Self {
+      inner,
+      hit_pattern: None,
+      show: Box::new(show),
+      started: Instant::now(),
+      tries: 0,
+      hit_counts: Vec::new(),
+      last_hit: None,
+      pending: None,
+      failed: false,
+      on_warning: None,
+    }
+  }
+
+  /// Notify the hits whose chars match `pattern` as well, in internal
+  /// wakuchin chars like the patterns of the research.
+  #[must_use]
+  pub fn with_hit_pattern(mut self, pattern: Regex) -> Self {
+    self.hit_pattern = Some(pattern);
+    self
+  }
+
+  /// Call `callback` with the first notification failing to be shown,
+  /// which is not reported otherwise.
+  #[must_use]
+  pub fn with_warning_callback<F>(mut self, callback: F) -> Self
+  where
+    F: Fn(&str) + Send + Sync + 'static,
+  {
+    self.on_warning = Some(Arc::new(callback));
+    self
+  }
+
+  /// Show a notification, warning about the first failure.
+  fn notify(&mut self, summary: &str, body: &str) {
+    if let Err(e) = (self.show)(summary, body) {
+      if !self.failed {
+        if let Some(on_warning) = &self.on_warning {
+          on_warning(&format!("desktop notification failed: {e}"));
+        }
+      }
+
+      self.failed = true;
+    }
+  }
+
+  /// Notify the pending hits if [`HIT_INTERVAL`] elapsed since the last
+  /// notification of hits, or `force`.
+  fn notify_hits(&mut self, force: bool) {
+    if !force
+      && self
+        .last_hit
+        .is_some_and(|last| last.elapsed() < HIT_INTERVAL)
+    {
+      return;
+    }
+
+    let Some((count, chars)) = self.pending.take() else {
+      return;
+    };
+    let body = match count {
+      1 => format!("Hit {chars}"),
+      _ => format!("{count} hits, the last of {chars}"),
+    };
+
+    self.last_hit = Some(Instant::now());
+    self.notify("Wakuchin hit", &body);
+  }
+
+  fn notify_end(&mut self, summary: &str) {
+    self.notify_hits(true);
+
+    let hits = self
+      .hit_counts
+      .iter()
+      .map(|count| count.hits)
+      .sum::<usize>();
+    let body = format!(
+      "{} tries, {hits} hits in {}s",
+      self.tries,
+      self.started.elapsed().as_secs()
+    );
+
+    self.notify(summary, &body);
+  }
+}
+
+impl ProgressHandler for NotifyProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.started = Instant::now();
+    self.tries = info.tries;
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    self.hit_counts = hit_counts.to_vec();
+    self.notify_hits(false);
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.notify_end("Wakuchin research finished");
+
+    self.inner.after_finish()
+  }
+
+  fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+    if self
+      .hit_pattern
+      .as_ref()
+      .is_some_and(|pattern| pattern.is_match(&hit.chars))
+    {
+      let count = self.pending.as_ref().map_or(0, |(count, _)| *count);
+
+      self.pending = Some((count + 1, hit.chars.to_string()));
+      self.notify_hits(false);
+    }
+
+    self.inner.on_hit(hit)
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.notify_end("Wakuchin research stopped");
+
+    self.inner.on_accidential_stop()
+  }
+
+  crate::forward_to_inner!(
+    on_pause,
+    on_resume,
+    on_notable_hit,
+    on_deadline_overrun
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use regex::Regex;
+
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::result::{Hit, HitCount};
+
+  use super::NotifyProgressHandler;
+
+  #[test]
+  fn test_notifications() -> Result<()> {
+    let shown = Arc::new(Mutex::new(Vec::new()));
+    let mut handler =
+      NotifyProgressHandler::new(Box::new(EmptyProgressHandler::new()))
+        .with_hit_pattern(Regex::new(r"^WK")?);
+
+    handler.show = Box::new({
+      let shown = shown.clone();
+
+      move |summary, body| {
+        shown.lock().unwrap().push(format!("{summary}: {body}"));
+
+        Ok(())
+      }
+    });
+
+    handler.before_start(&RunInfo {
+      tries: 100,
+      ..RunInfo::new(1)
+    })?;
+
+    for (i, chars) in ["WKCN", "NCWK", "WKNC", "WKCN"].into_iter().enumerate() {
+      handler.on_hit(&Hit::new(i, chars))?;
+    }
+
+    handler.handle(
+      &[],
+      &[HitCount::new("WKCN", 2), HitCount::new("WKNC", 1)],
+      Duration::ZERO,
+      0,
+      true,
+    )?;
+    handler.after_finish()?;
+
+    // the hits after the first are notified together at the end
+    assert_eq!(
+      *shown.lock().unwrap(),
+      [
+        "Wakuchin hit: Hit WKCN",
+        "Wakuchin hit: 2 hits, the last of WKCN",
+        "Wakuchin research finished: 100 tries, 3 hits in 0s",
+      ]
+    );
+
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let mut handler =
+      NotifyProgressHandler::new(Box::new(EmptyProgressHandler::new()))
+        .with_hit_pattern(Regex::new(r"^WK")?)
+        .with_warning_callback({
+          let warnings = warnings.clone();
+
+          move |message| warnings.lock().unwrap().push(message.to_string())
+        });
+
+    handler.show = Box::new(|_, _| anyhow::bail!("no notification daemon"));
+
+    handler.on_hit(&Hit::new(0, "WKCN"))?;
+    handler.after_finish()?;
+
+    // only the first failure is warned about
+    assert_eq!(
+      *warnings.lock().unwrap(),
+      ["desktop notification failed: no notification daemon"]
+    );
+
+    Ok(())
+  }
+}
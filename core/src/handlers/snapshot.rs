@@ -0,0 +1,352 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::fs::atomic_write;
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::{Hit, HitCount};
+
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Progress of a research at a point in time.
+///
+/// Snapshots written as checkpoints can be read back to resume the research
+/// from `current`, see [`SnapshotProgressHandler::with_baseline`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProgressSnapshot {
+  /// Total number of tries, including the baseline
+  pub tries: usize,
+  /// Number of tries done so far, including the baseline
+  pub current: usize,
+  /// Number of tries done by the runs this one resumes
+  #[serde(default)]
+  pub baseline: usize,
+  /// Tries per second since the previous progress
+  pub rate: f64,
+  /// Time elapsed since the research started
+  #[serde(
+    rename = "elapsed_secs",
+    serialize_with = "serialize_secs",
+    deserialize_with = "deserialize_secs"
+  )]
+  pub elapsed: Duration,
+  pub hits_total: usize,
+  pub hit_counts: Vec<HitCount>,
+  pub progresses: Vec<Progress>,
+  pub all_done: bool,
+  /// Whether the research is paused by job control
+  pub paused: bool,
+  /// Every hit so far, including those of the runs this one resumes, with
+  /// `hit_on` counted from the start of the first run
+  ///
+  /// Only checkpoints have them, so resuming them keeps the hits of the
+  /// tries before.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub hits: Vec<Hit>,
+}
+
+fn serialize_secs<S: serde::Serializer>(
+  duration: &Duration,
+  serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+  serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn deserialize_secs<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> std::result::Result<Duration, D::Error> {
+  Duration::try_from_secs_f64(f64::deserialize(deserializer)?)
+    .map_err(serde::de::Error::custom)
+}
+
+/// Cloneable handle to read the latest snapshot from any thread.
+#[derive(Clone, Default)]
+pub struct SnapshotReader {
+  latest: Arc<Mutex<Option<ProgressSnapshot>>>,
+}
+
+impl SnapshotReader {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Return the latest snapshot, `None` until the first progress is handled.
+  pub fn latest(&self) -> Option<ProgressSnapshot> {
+    self.latest.lock().unwrap().clone()
+  }
+}
+
+/// Handler which records a [`ProgressSnapshot`] of every progress before
+/// passing it on to the wrapped handler.
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::handlers::snapshot::SnapshotProgressHandler;
+///
+/// let handler =
+///   SnapshotProgressHandler::new(10, Box::new(EmptyProgressHandler::new()));
+/// let reader = handler.reader();
+///
+/// ResearchBuilder::new()
+///   .tries(10)
+///   .times(1)
+///   .regex(Regex::new(r"WKCN")?)
+///   .progress_handler(Box::new(handler))
+///   .run_seq()?;
+///
+/// let snapshot = reader.latest().unwrap();
+///
+/// assert!(snapshot.all_done);
+/// assert_eq!(snapshot.current, 10);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SnapshotProgressHandler {
+  tries: usize,
+  inner: Box<dyn ProgressHandler>,
+  reader: SnapshotReader,
+  started: Instant,
+  checkpoint: Option<PathBuf>,
+  baseline: usize,
+  /// Hits to write to the checkpoint
+  hits: Vec<Hit>,
+}
+
+impl SnapshotProgressHandler {
+  pub fn new(tries: usize, inner: Box<dyn ProgressHandler>) -> Self {
+    Self {
+      tries,
+      inner,
+      reader: SnapshotReader::default(),
+      started: Instant::now(),
+      checkpoint: None,
+      baseline: 0,
+      hits: Vec::new(),
+    }
+  }
+
+  /// Write the latest snapshot to `path` as JSON whenever the research is
+  /// paused.
+  pub fn with_checkpoint(mut self, path: impl Into<PathBuf>) -> Self {
+    self.checkpoint = Some(path.into());
+    self
+  }
+
+  /// Record the snapshots to `reader` instead of a reader of its own, e.g.
+  /// one created before the handler.
+  pub fn with_reader(mut self, reader: SnapshotReader) -> Self {
+    self.reader = reader;
+    self
+  }
+
+  /// Count `baseline` tries done by previous runs of a resumed research,
+  /// e.g. the `current` of its checkpoint, on top of the `tries` of this
+  /// run.
+  pub fn with_baseline(mut self, baseline: usize) -> Self {
+    self.baseline = baseline;
+    self
+  }
+
+  /// Start from the `hits` of the previous runs of a resumed research, e.g.
+  /// the `hits` of its checkpoint, so the checkpoints of this run have them
+  /// too.
+  pub fn with_baseline_hits(mut self, hits: Vec<Hit>) -> Self {
+    self.hits = hits;
+    self
+  }
+
+  fn set_paused(&self, paused: bool) -> Option<ProgressSnapshot> {
+    let mut latest = self.reader.latest.lock().unwrap();
+
+    latest.as_mut().map(|snapshot| {
+      snapshot.paused = paused;
+      snapshot.clone()
+    })
+  }
+
+  #[inline]
+  pub fn reader(&self) -> SnapshotReader {
+    self.reader.clone()
+  }
+}
+
+impl ProgressHandler for SnapshotProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.started = Instant::now();
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    let current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>()
+      .min(self.tries);
+
+    let rate = if elapsed_time.is_zero() {
+      0.0
+    } else {
+      current_diff as f64 / elapsed_time.as_secs_f64()
+    };
+
+    *self.reader.latest.lock().unwrap() = Some(ProgressSnapshot {
+      tries: self.baseline + self.tries,
+      current: self.baseline + current,
+      baseline: self.baseline,
+      rate,
+      elapsed: self.started.elapsed(),
+      hits_total: hit_counts.iter().map(|count| count.hits).sum(),
+      hit_counts: hit_counts.to_vec(),
+      progresses: progresses.to_vec(),
+      all_done,
+      paused: false,
+      hits: Vec::new(),
+    });
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    let snapshot = self.set_paused(true);
+
+    if let (Some(path), Some(mut snapshot)) = (&self.checkpoint, snapshot) {
+      snapshot.hits = self.hits.clone();
+
+      atomic_write(path, serde_json::to_vec_pretty(&snapshot)?)?;
+    }
+
+    self.inner.on_pause()
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    self.set_paused(false);
+
+    self.inner.on_resume()
+  }
+
+  fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+    if self.checkpoint.is_some() {
+      let mut hit = hit.clone();
+
+      hit.hit_on += self.baseline;
+      self.hits.push(hit);
+    }
+
+    self.inner.on_hit(hit)
+  }
+
+  crate::forward_to_inner!(
+    after_finish,
+    on_notable_hit,
+    on_deadline_overrun,
+    on_accidential_stop
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use std::env::temp_dir;
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::handlers::ProgressHandler;
+  use crate::progress::{ProcessingDetail, Progress, ProgressKind};
+  use crate::result::Hit;
+
+  use super::{ProgressSnapshot, SnapshotProgressHandler};
+
+  #[test]
+  fn test_baseline() -> Result<()> {
+    let mut handler =
+      SnapshotProgressHandler::new(60, Box::new(EmptyProgressHandler::new()))
+        .with_baseline(40);
+    let reader = handler.reader();
+
+    let progresses = [Progress(ProgressKind::Processing(ProcessingDetail {
+      id: 0,
+      current: 10,
+      total: 60,
+      wakuchin: "WKCN".into(),
+    }))];
+
+    handler.handle(&progresses, &[], Duration::from_secs(1), 10, false)?;
+
+    let snapshot = reader.latest().unwrap();
+
+    assert_eq!(snapshot.tries, 100);
+    assert_eq!(snapshot.current, 50);
+    assert_eq!(snapshot.baseline, 40);
+
+    // checkpoints are read back to resume from them
+    let json = serde_json::to_string(&snapshot)?;
+    let read = serde_json::from_str::<ProgressSnapshot>(&json)?;
+
+    assert_eq!(read.current, 50);
+    assert!(read.elapsed.abs_diff(snapshot.elapsed) < Duration::from_micros(1));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_checkpoint_hits() -> Result<()> {
+    let path =
+      temp_dir().join(format!("wakuchin-paused-{}.json", fastrand::u64(..)));
+    let mut handler =
+      SnapshotProgressHandler::new(60, Box::new(EmptyProgressHandler::new()))
+        .with_baseline(40)
+        .with_baseline_hits(vec![Hit::new(3, "WKCN")])
+        .with_checkpoint(&path);
+
+    handler.on_hit(&Hit::new(5, "WKCN"))?;
+    handler.handle(&[], &[], Duration::from_secs(1), 10, false)?;
+    handler.on_pause()?;
+
+    let checkpoint =
+      serde_json::from_slice::<ProgressSnapshot>(&std::fs::read(&path)?)?;
+
+    assert_eq!(checkpoint.hits, [Hit::new(3, "WKCN"), Hit::new(45, "WKCN")]);
+    // the latest snapshots are kept light
+    assert!(handler.reader().latest().unwrap().hits.is_empty());
+
+    std::fs::remove_file(path)?;
+
+    Ok(())
+  }
+}
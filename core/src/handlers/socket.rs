@@ -0,0 +1,393 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::protocol::{self, ProgressReport, ProtocolVersion};
+use crate::result::HitCount;
+
+use super::msgpack::{write_frame, Framing};
+use super::snapshot::ProgressSnapshot;
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Time between the attempts to connect to the socket.
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time a write may block before the consumer is considered gone.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What [`SocketProgressHandler`] streams.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SocketFormat {
+  /// Msgpack frames of the protocol version, each preceded by its length as
+  /// a big-endian `u32` like [`Framing::LengthPrefixed`]
+  #[default]
+  Msgpack,
+  /// A [`ProgressSnapshot`] as JSON per line
+  Json,
+}
+
+/// Connect to the Unix socket or the Windows named pipe at `path`.
+fn connect(path: &Path) -> io::Result<Box<dyn Write + Send>> {
+  #[cfg(unix)]
+  {
+    let stream = std::os::unix::net::UnixStream::connect(path)?;
+
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    Ok(Box::new(stream))
+  }
+
+  // the client end of a named pipe is opened like a file
+  #[cfg(windows)]
+  {
+    let _ = WRITE_TIMEOUT;
+
+    Ok(Box::new(
+      std::fs::OpenOptions::new().write(true).open(path)?,
+    ))
+  }
+
+  #[cfg(not(any(unix, windows)))]
+  {
+    let _ = (path, WRITE_TIMEOUT);
+
+    Err(io::Error::new(
+      io::ErrorKind::Unsupported,
+      "sockets are not supported",
+    ))
+  }
+}
+
+/// Handler streaming the progress to a Unix socket, or a Windows named pipe,
+/// besides the handler it wraps, so a sidecar UI can attach to a research
+/// without taking over its stdout.
+///
+/// The socket is connected to once the research starts, then at most once
+/// per [`RECONNECT_INTERVAL`] while nothing listens on it, so consumers can
+/// come and go during the research. The frames of each connection are
+/// numbered from 0, and the progress while disconnected is not sent. The
+/// research goes on whatever happens to the socket.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::handlers::socket::{SocketFormat, SocketProgressHandler};
+///
+/// let handler = SocketProgressHandler::new(
+///   "/tmp/wakuchin.sock",
+///   Box::new(EmptyProgressHandler::new()),
+/// )
+/// .with_format(SocketFormat::Json);
+///
+/// ResearchBuilder::new()
+///   .tries(1_000_000_000)
+///   .times(2)
+///   .regex(Regex::new(r"WKNC")?)
+///   .progress_handler(Box::new(handler))
+///   .run_par()?;
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SocketProgressHandler {
+  inner: Box<dyn ProgressHandler>,
+  path: PathBuf,
+  format: SocketFormat,
+  protocol_version: ProtocolVersion,
+  info: RunInfo,
+  stream: Option<Box<dyn Write + Send>>,
+  /// When the socket was last connected to, successfully or not
+  last_attempt: Option<Instant>,
+  /// Number of the next frame of the connection
+  seq: u64,
+  started: Instant,
+  paused: bool,
+}
+
+impl SocketProgressHandler {
+  /// Stream the progress to the socket at `path`.
+  pub fn new(
+    path: impl Into<PathBuf>,
+    inner: Box<dyn ProgressHandler>,
+  ) -> Self {
+    Self {
+      inner,
+      path: path.into(),
+      format: SocketFormat::default(),
+      protocol_version: ProtocolVersion::default(),
+      info: RunInfo::new(0),
+      stream: None,
+      last_attempt: None,
+      seq: 0,
+      started: Instant::now(),
+      paused: false,
+    }
+  }
+
+  /// Stream `format`, defaults to [`SocketFormat::Msgpack`].
+  #[must_use]
+  pub fn with_format(mut self, format: SocketFormat) -> Self {
+    self.format = format;
+    self
+  }
+
+  /// Lay out msgpack frames as `version`, defaults to
+  /// [`ProtocolVersion::LATEST`].
+  #[must_use]
+  pub fn with_protocol_version(mut self, version: ProtocolVersion) -> Self {
+    self.protocol_version = version;
+    self
+  }
+
+  /// Return the stream to the socket, connecting to it if due.
+  fn stream(&mut self) -> Option<&mut Box<dyn Write + Send>> {
+    if self.stream.is_none()
+      && self
+        .last_attempt
+        .is_none_or(|last| last.elapsed() >= RECONNECT_INTERVAL)
+    {
+      self.last_attempt = Some(Instant::now());
+      self.stream = connect(&self.path).ok();
+      self.seq = 0;
+    }
+
+    self.stream.as_mut()
+  }
+
+  fn encode(
+    &self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    match self.format {
+      SocketFormat::Msgpack => {
+        let report = ProgressReport::new(
+          progresses,
+          hit_counts,
+          self.info.tries,
+          elapsed_time,
+          current_diff,
+          all_done,
+        )
+        .with_info(&self.info)
+        .with_seq(self.seq);
+        let frame = protocol::encode(self.protocol_version, &report)?;
+
+        write_frame(&mut buf, Framing::LengthPrefixed, &frame)?;
+      }
+      SocketFormat::Json => {
+        let current = progresses
+          .iter()
+          .map(|progress| match progress {
+            Progress(ProgressKind::Processing(ProcessingDetail {
+              current,
+              ..
+            })) => *current,
+            Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+            Progress(ProgressKind::Stalled(StalledDetail {
+              current, ..
+            })) => *current,
+            _ => 0,
+          })
+          .sum::<usize>()
+          .min(self.info.tries);
+        let rate = current_diff as f64 / elapsed_time.as_secs_f64();
+        let snapshot = ProgressSnapshot {
+          tries: self.info.tries,
+          current,
+          baseline: 0,
+          rate: if rate.is_finite() { rate } else { 0.0 },
+          elapsed: self.started.elapsed(),
+          hits_total: hit_counts.iter().map(|count| count.hits).sum(),
+          hit_counts: hit_counts.to_vec(),
+          progresses: progresses.to_vec(),
+          all_done,
+          paused: self.paused,
+          hits: Vec::new(),
+        };
+
+        serde_json::to_writer(&mut buf, &snapshot)?;
+        buf.push(b'\n');
+      }
+    }
+
+    Ok(buf)
+  }
+}
+
+impl ProgressHandler for SocketProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.info = info.clone();
+    self.started = Instant::now();
+    self.paused = false;
+    self.last_attempt = None;
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    if self.stream().is_some() {
+      let buf = self.encode(
+        progresses,
+        hit_counts,
+        elapsed_time,
+        current_diff,
+        all_done,
+      )?;
+
+      // the consumer went away, or is too slow to be waited for
+      if self
+        .stream
+        .as_mut()
+        .is_some_and(|stream| stream.write_all(&buf).is_err())
+      {
+        self.stream = None;
+      }
+
+      self.seq += 1;
+    }
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    if let Some(mut stream) = self.stream.take() {
+      let _ = stream.flush();
+    }
+
+    self.inner.after_finish()
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    self.paused = true;
+
+    self.inner.on_pause()
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    self.paused = false;
+
+    self.inner.on_resume()
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.stream = None;
+
+    self.inner.on_accidential_stop()
+  }
+
+  crate::forward_to_inner!(on_hit, on_notable_hit, on_deadline_overrun);
+}
+
+#[cfg(all(test, unix))]
+mod test {
+  use std::env::temp_dir;
+  use std::io::{BufRead, BufReader};
+  use std::os::unix::net::UnixListener;
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::handlers::snapshot::ProgressSnapshot;
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::protocol::{decode, ProtocolVersion};
+  use crate::result::HitCount;
+
+  use super::{SocketFormat, SocketProgressHandler};
+
+  fn feed(handler: &mut SocketProgressHandler) -> Result<()> {
+    handler.before_start(&RunInfo {
+      tries: 10,
+      ..RunInfo::new(1)
+    })?;
+    handler.handle(
+      &[],
+      &[HitCount::new("WKNC", 1)],
+      Duration::from_secs(1),
+      5,
+      false,
+    )?;
+    handler.handle(&[], &[], Duration::ZERO, 0, true)?;
+    handler.after_finish()?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_socket() -> Result<()> {
+    let path =
+      temp_dir().join(format!("wakuchin-socket-{}.sock", fastrand::u64(..)));
+
+    // nothing listens yet, which the research does not mind
+    feed(&mut SocketProgressHandler::new(
+      &path,
+      Box::new(EmptyProgressHandler::new()),
+    ))?;
+
+    let listener = UnixListener::bind(&path)?;
+
+    feed(&mut SocketProgressHandler::new(
+      &path,
+      Box::new(EmptyProgressHandler::new()),
+    ))?;
+
+    let mut stream = listener.accept()?.0;
+    let mut len = [0; 4];
+
+    for seq in 0..2 {
+      std::io::Read::read_exact(&mut stream, &mut len)?;
+
+      let frame = decode(ProtocolVersion::LATEST, &mut stream)?;
+
+      assert_eq!(frame.seq, Some(seq));
+      assert_eq!(frame.tries, 10);
+    }
+
+    let mut handler =
+      SocketProgressHandler::new(&path, Box::new(EmptyProgressHandler::new()))
+        .with_format(SocketFormat::Json);
+
+    feed(&mut handler)?;
+
+    let lines = BufReader::new(listener.accept()?.0)
+      .lines()
+      .map(|line| Ok(serde_json::from_str::<ProgressSnapshot>(&line?)?))
+      .collect::<Result<Vec<_>>>()?;
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].hits_total, 1);
+    assert!(lines[1].all_done);
+
+    std::fs::remove_file(path)?;
+
+    Ok(())
+  }
+}
@@ -0,0 +1,430 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::{Instant, SystemTime};
+use rusqlite::{params, Connection};
+
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::{Hit, HitCount};
+
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Default time between the progress snapshots.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tables of the database, created unless they exist.
+///
+/// Times are seconds from the Unix epoch, and lists JSON arrays.
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+  id INTEGER PRIMARY KEY,
+  started_at REAL NOT NULL,
+  finished_at REAL,
+  -- 'finished' or 'stopped', NULL while researching or if it crashed
+  outcome TEXT,
+  tries INTEGER NOT NULL,
+  times INTEGER NOT NULL,
+  patterns TEXT NOT NULL,
+  workers INTEGER NOT NULL,
+  -- the bits of the u64 seed as a signed integer
+  seed INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS hits (
+  run_id INTEGER NOT NULL REFERENCES runs (id),
+  found_at REAL NOT NULL,
+  hit_on INTEGER NOT NULL,
+  chars TEXT NOT NULL,
+  pattern INTEGER NOT NULL,
+  matches INTEGER NOT NULL,
+  distance INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS snapshots (
+  run_id INTEGER NOT NULL REFERENCES runs (id),
+  timestamp REAL NOT NULL,
+  elapsed REAL NOT NULL,
+  current INTEGER NOT NULL,
+  -- tries per second since the previous snapshot
+  rate REAL NOT NULL,
+  hits_total INTEGER NOT NULL,
+  -- [{\"chars\": ..., \"hits\": ..., \"pattern\": ...}, ...]
+  hit_counts TEXT NOT NULL,
+  all_done INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS hits_run_id ON hits (run_id);
+CREATE INDEX IF NOT EXISTS snapshots_run_id ON snapshots (run_id);
+";
+
+/// Return the seconds from the Unix epoch to `time`.
+fn unix_secs(time: SystemTime) -> f64 {
+  time
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs_f64()
+}
+
+/// Handler inserting the hits and snapshots of the progress into an SQLite
+/// database besides the handler it wraps, so long studies accumulate a
+/// history to be queried with SQL.
+///
+/// Each research is a row of `runs`, its hits rows of `hits` and its
+/// progress rows of `snapshots`, one per interval and the final one, see
+/// [`SCHEMA`]. The chars of the hits are the internal ones, e.g. "WKNC".
+///
+/// Hits are inserted along with the next progress, in a transaction of
+/// their own, so a research finding many of them is not held back by the
+/// commits. Databases opened by [`open`](Self::open) are in WAL mode, so
+/// they can be read while researching.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::handlers::sqlite::SqliteProgressHandler;
+///
+/// let handler = SqliteProgressHandler::open(
+///   "wakuchin.sqlite",
+///   Box::new(EmptyProgressHandler::new()),
+/// )?;
+///
+/// ResearchBuilder::new()
+///   .tries(1_000_000_000)
+///   .times(2)
+///   .regex(Regex::new(r"WKNC")?)
+///   .progress_handler(Box::new(handler))
+///   .run_par()?;
+///
+/// // sqlite3 wakuchin.sqlite "SELECT chars, count(*) FROM hits GROUP BY 1"
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SqliteProgressHandler {
+  inner: Box<dyn ProgressHandler>,
+  connection: Connection,
+  interval: Duration,
+  /// Row of the research in `runs`
+  run_id: Option<i64>,
+  tries: usize,
+  started: Instant,
+  /// Hits not inserted yet, and when they were found
+  hits: Vec<(f64, Hit)>,
+  /// When the last snapshot was inserted, and the tries done then
+  last: Option<(Instant, usize)>,
+}
+
+impl SqliteProgressHandler {
+  /// Insert into the database at `path`, created unless it exists.
+  pub fn open(
+    path: impl AsRef<Path>,
+    inner: Box<dyn ProgressHandler>,
+  ) -> Result<Self> {
+    let connection = Connection::open(path)?;
+
+    connection
+      .pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+
+    Self::from_connection(connection, inner)
+  }
+
+  /// Insert into the database of `connection`.
+  pub fn from_connection(
+    connection: Connection,
+    inner: Box<dyn ProgressHandler>,
+  ) -> Result<Self> {
+    connection.execute_batch(SCHEMA)?;
+
+    Ok(Self {
+      inner,
+      connection,
+      interval: DEFAULT_INTERVAL,
+      run_id: None,
+      tries: 0,
+      started: Instant::now(),
+      hits: Vec::new(),
+      last: None,
+    })
+  }
+
+  /// Insert a snapshot of the progress every `interval`,
+  /// [`DEFAULT_INTERVAL`] by default.
+  #[must_use]
+  pub fn with_interval(mut self, interval: Duration) -> Self {
+    self.interval = interval;
+    self
+  }
+
+  /// Return the connection to the database, e.g. to query it.
+  #[inline]
+  pub fn connection(&self) -> &Connection {
+    &self.connection
+  }
+
+  /// Insert the pending hits.
+  fn insert_hits(&mut self) -> Result<()> {
+    let Some(run_id) = self.run_id else {
+      return Ok(());
+    };
+
+    if self.hits.is_empty() {
+      return Ok(());
+    }
+
+    let transaction = self.connection.transaction()?;
+
+    {
+      let mut statement = transaction.prepare_cached(
+        "INSERT INTO hits
+          (run_id, found_at, hit_on, chars, pattern, matches, distance)
+          VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+      )?;
+
+      for (found_at, hit) in &self.hits {
+        statement.execute(params![
+          run_id,
+          found_at,
+          hit.hit_on as i64,
+          hit.chars.as_str(),
+          hit.pattern as i64,
+          hit.matches as i64,
+          hit.distance.map(|distance| distance as i64),
+        ])?;
+      }
+    }
+
+    transaction.commit()?;
+    self.hits.clear();
+
+    Ok(())
+  }
+
+  /// Insert a snapshot of the research `current` tries in.
+  fn insert_snapshot(
+    &mut self,
+    current: usize,
+    hit_counts: &[HitCount],
+    all_done: bool,
+  ) -> Result<()> {
+    let Some(run_id) = self.run_id else {
+      return Ok(());
+    };
+
+    let (last, last_current) = self.last.unwrap_or((self.started, 0));
+    let rate = (current - last_current.min(current)) as f64
+      / last.elapsed().as_secs_f64();
+
+    self.connection.execute(
+      "INSERT INTO snapshots
+        (run_id, timestamp, elapsed, current, rate, hits_total, hit_counts,
+          all_done)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+      params![
+        run_id,
+        unix_secs(SystemTime::now()),
+        self.started.elapsed().as_secs_f64(),
+        current as i64,
+        if rate.is_finite() { rate } else { 0.0 },
+        hit_counts.iter().map(|count| count.hits).sum::<usize>() as i64,
+        serde_json::to_string(hit_counts)?,
+        all_done,
+      ],
+    )?;
+    self.last = Some((Instant::now(), current));
+
+    Ok(())
+  }
+
+  /// Record the end of the research as `outcome`.
+  fn finish(&mut self, outcome: &str) -> Result<()> {
+    self.insert_hits()?;
+
+    if let Some(run_id) = self.run_id.take() {
+      self.connection.execute(
+        "UPDATE runs SET finished_at = ?1, outcome = ?2 WHERE id = ?3",
+        params![unix_secs(SystemTime::now()), outcome, run_id],
+      )?;
+    }
+
+    Ok(())
+  }
+}
+
+impl ProgressHandler for SqliteProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.connection.execute(
+      "INSERT INTO runs (started_at, tries, times, patterns, workers, seed)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+      params![
+        unix_secs(info.started_at),
+        info.tries as i64,
+        info.times as i64,
+        serde_json::to_string(&info.patterns)?,
+        info.workers as i64,
+        info.seed.map(|seed| seed as i64),
+      ],
+    )?;
+
+    self.run_id = Some(self.connection.last_insert_rowid());
+    self.tries = info.tries;
+    self.started = Instant::now();
+    self.hits.clear();
+    self.last = None;
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    self.insert_hits()?;
+
+    if all_done
+      || self
+        .last
+        .is_none_or(|(last, _)| last.elapsed() >= self.interval)
+    {
+      let current = progresses
+        .iter()
+        .map(|progress| match progress {
+          Progress(ProgressKind::Processing(ProcessingDetail {
+            current,
+            ..
+          })) => *current,
+          Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+          Progress(ProgressKind::Stalled(StalledDetail {
+            current, ..
+          })) => *current,
+          _ => 0,
+        })
+        .sum::<usize>()
+        .min(self.tries);
+
+      self.insert_snapshot(current, hit_counts, all_done)?;
+    }
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.finish("finished")?;
+
+    self.inner.after_finish()
+  }
+
+  fn on_hit(&mut self, hit: &Hit) -> Result<()> {
+    self.hits.push((unix_secs(SystemTime::now()), hit.clone()));
+
+    self.inner.on_hit(hit)
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.finish("stopped")?;
+
+    self.inner.on_accidential_stop()
+  }
+
+  crate::forward_to_inner!(
+    on_pause,
+    on_resume,
+    on_notable_hit,
+    on_deadline_overrun
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use anyhow::Result;
+  use rusqlite::Connection;
+
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::result::{Hit, HitCount};
+
+  use super::SqliteProgressHandler;
+
+  #[test]
+  fn test_sqlite() -> Result<()> {
+    let mut handler = SqliteProgressHandler::from_connection(
+      Connection::open_in_memory()?,
+      Box::new(EmptyProgressHandler::new()),
+    )?
+    .with_interval(Duration::from_secs(3600));
+
+    handler.before_start(&RunInfo {
+      tries: 10,
+      patterns: vec!["WKNC".to_string()],
+      seed: Some(u64::MAX),
+      ..RunInfo::new(1)
+    })?;
+    handler.on_hit(&Hit::new(3, "WKNC"))?;
+    handler.handle(
+      &[],
+      &[HitCount::new("WKNC", 1)],
+      Duration::from_secs(1),
+      5,
+      false,
+    )?;
+    // within the interval, so only its hit is inserted
+    handler.on_hit(&Hit::new(7, "WKNC"))?;
+    handler.handle(
+      &[],
+      &[HitCount::new("WKNC", 2)],
+      Duration::from_secs(1),
+      5,
+      false,
+    )?;
+    handler.handle(
+      &[],
+      &[HitCount::new("WKNC", 2)],
+      Duration::ZERO,
+      0,
+      true,
+    )?;
+    handler.after_finish()?;
+
+    let connection = handler.connection();
+    let (outcome, seed): (String, i64) =
+      connection.query_row("SELECT outcome, seed FROM runs", [], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+      })?;
+
+    assert_eq!(outcome, "finished");
+    assert_eq!(seed as u64, u64::MAX);
+
+    let hits = connection
+      .prepare("SELECT hit_on, chars FROM hits ORDER BY hit_on")?
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<Vec<(i64, String)>, _>>()?;
+
+    assert_eq!(hits, [(3, "WKNC".to_string()), (7, "WKNC".to_string())]);
+
+    let snapshots = connection
+      .prepare("SELECT hits_total, all_done FROM snapshots")?
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<Vec<(i64, bool)>, _>>()?;
+
+    assert_eq!(snapshots, [(1, false), (2, true)]);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,438 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use flume::RecvTimeoutError;
+use instant::Instant;
+
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::HitCount;
+use crate::threads::{self, Handle};
+
+use super::snapshot::ProgressSnapshot;
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Time between the checks for new connections.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time a client may take to send its request, or block a write, before it
+/// is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Longest request accepted.
+const MAX_REQUEST: usize = 8 * 1024;
+
+/// Progress events waiting to be sent, beyond which new ones are skipped.
+const MAX_PENDING: usize = 16;
+
+/// Return the head of the responses, allowing pages of `allowed_origin` to
+/// read them if any.
+fn response_head(allowed_origin: Option<&str>) -> Vec<u8> {
+  let mut head = String::from(
+    "HTTP/1.1 200 OK\r\n\
+     Content-Type: text/event-stream\r\n\
+     Cache-Control: no-cache\r\n",
+  );
+
+  if let Some(origin) = allowed_origin {
+    head.push_str(&format!("Access-Control-Allow-Origin: {origin}\r\n"));
+  }
+
+  head.push_str("Connection: keep-alive\r\n\r\n");
+  head.into_bytes()
+}
+
+/// Read the request of `stream`, whatever it is as the progress is served
+/// anyway.
+fn read_request(stream: &mut TcpStream) -> io::Result<()> {
+  stream.set_nonblocking(false)?;
+  stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+  stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+  let mut request = Vec::new();
+  let mut buf = [0; 1024];
+
+  while !request.ends_with(b"\r\n\r\n") {
+    let n = stream.read(&mut buf)?;
+
+    if n == 0 || request.len() + n > MAX_REQUEST {
+      return Err(io::ErrorKind::InvalidData.into());
+    }
+
+    request.extend_from_slice(&buf[..n]);
+  }
+
+  Ok(())
+}
+
+/// Accept the clients of `listener` and send them `events`, until the
+/// handler hangs up.
+fn serve(
+  listener: TcpListener,
+  events: flume::Receiver<String>,
+  allowed_origin: Arc<Mutex<Option<String>>>,
+) {
+  let mut clients: Vec<TcpStream> = Vec::new();
+  // sent first to the clients connecting later
+  let mut last: Option<String> = None;
+
+  loop {
+    while let Ok((mut stream, _)) = listener.accept() {
+      if read_request(&mut stream).is_err() {
+        continue;
+      }
+
+      let mut head = response_head(allowed_origin.lock().unwrap().as_deref());
+
+      if let Some(event) = &last {
+        head.extend_from_slice(event.as_bytes());
+      }
+
+      if stream.write_all(&head).is_ok() {
+        clients.push(stream);
+      }
+    }
+
+    match events.recv_timeout(ACCEPT_INTERVAL) {
+      Ok(event) => {
+        // drop the clients failing to take it
+        clients.retain_mut(|stream| stream.write_all(event.as_bytes()).is_ok());
+        last = Some(event);
+      }
+      Err(RecvTimeoutError::Timeout) => {}
+      // the events left are received first, then the streams end
+      Err(RecvTimeoutError::Disconnected) => return,
+    }
+  }
+}
+
+/// Handler serving the progress as Server-Sent Events on a local port,
+/// besides the handler it wraps, for dashboards to read with an
+/// `EventSource`.
+///
+/// Any request is answered with an endless `text/event-stream`, starting with
+/// the last event sent so far. The events are:
+///
+/// - `progress`, whose data is a [`ProgressSnapshot`] as JSON
+/// - `finish` once the research finishes, and `stop` if it is stopped, with
+///   the last snapshot, after which the stream ends
+///
+/// The clients are served from a thread of their own, so slow ones do not
+/// hold the research back: `progress` events are skipped while they lag
+/// behind, and clients failing to take an event within a second are dropped.
+/// Only pages of the same origin may read the events, unless
+/// [`with_allowed_origin`](Self::with_allowed_origin) is set.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::handlers::sse::SseProgressHandler;
+///
+/// // new EventSource("http://127.0.0.1:8080/") in a browser
+/// let handler = SseProgressHandler::bind(
+///   "127.0.0.1:8080",
+///   Box::new(EmptyProgressHandler::new()),
+/// )?;
+///
+/// ResearchBuilder::new()
+///   .tries(1_000_000_000)
+///   .times(2)
+///   .regex(Regex::new(r"WKNC")?)
+///   .progress_handler(Box::new(handler))
+///   .run_par()?;
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SseProgressHandler {
+  inner: Box<dyn ProgressHandler>,
+  addr: SocketAddr,
+  /// Events to send, and the thread serving the clients, until the streams
+  /// end
+  events: Option<(flume::Sender<String>, Handle<()>)>,
+  allowed_origin: Arc<Mutex<Option<String>>>,
+  tries: usize,
+  started: Instant,
+  snapshot: Option<ProgressSnapshot>,
+}
+
+impl SseProgressHandler {
+  /// Serve the progress on `addr`, e.g. "127.0.0.1:8080", or
+  /// "127.0.0.1:0" for a free port told by
+  /// [`local_addr`](Self::local_addr).
+  pub fn bind(
+    addr: impl ToSocketAddrs,
+    inner: Box<dyn ProgressHandler>,
+  ) -> io::Result<Self> {
+    let listener = TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = flume::bounded(MAX_PENDING);
+    let allowed_origin = Arc::new(Mutex::new(None));
+
+    listener.set_nonblocking(true)?;
+
+    let handle = threads::spawn("sse", {
+      let allowed_origin = allowed_origin.clone();
+
+      move || serve(listener, rx, allowed_origin)
+    });
+
+    Ok(Self {
+      inner,
+      addr,
+      events: Some((tx, handle)),
+      allowed_origin,
+      tries: 0,
+      started: Instant::now(),
+      snapshot: None,
+    })
+  }
+
+  /// Let pages of `origin`, e.g. "http://localhost:3000", or of any origin
+  /// with "*", read the events.
+  #[must_use]
+  pub fn with_allowed_origin(self, origin: impl Into<String>) -> Self {
+    *self.allowed_origin.lock().unwrap() = Some(origin.into());
+    self
+  }
+
+  /// Return the address the progress is served on.
+  #[inline]
+  pub fn local_addr(&self) -> SocketAddr {
+    self.addr
+  }
+
+  /// Return the last snapshot as `event`, if any.
+  fn event(&self, event: &str) -> Result<Option<String>> {
+    let Some(snapshot) = &self.snapshot else {
+      return Ok(None);
+    };
+    let data = serde_json::to_string(snapshot)?;
+
+    Ok(Some(format!("event: {event}\ndata: {data}\n\n")))
+  }
+
+  /// Send the last snapshot as `event`, skipped if the clients lag behind.
+  fn send(&self, event: &str) -> Result<()> {
+    if let (Some(event), Some((tx, _))) = (self.event(event)?, &self.events) {
+      let _ = tx.try_send(event);
+    }
+
+    Ok(())
+  }
+
+  /// Send the last snapshot as `event` and end the streams, waiting for the
+  /// clients to take it.
+  fn close(&mut self, event: &str) -> Result<()> {
+    let event = self.event(event)?;
+
+    if let Some((tx, handle)) = self.events.take() {
+      if let Some(event) = event {
+        let _ = tx.send(event);
+      }
+
+      drop(tx);
+
+      // a panic of the thread only lost events
+      let _ = handle.join();
+    }
+
+    Ok(())
+  }
+}
+
+impl ProgressHandler for SseProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.tries = info.tries;
+    self.started = Instant::now();
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    let current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>()
+      .min(self.tries);
+
+    let rate = if elapsed_time.is_zero() {
+      0.0
+    } else {
+      current_diff as f64 / elapsed_time.as_secs_f64()
+    };
+
+    self.snapshot = Some(ProgressSnapshot {
+      tries: self.tries,
+      current,
+      baseline: 0,
+      rate,
+      elapsed: self.started.elapsed(),
+      hits_total: hit_counts.iter().map(|count| count.hits).sum(),
+      hit_counts: hit_counts.to_vec(),
+      progresses: progresses.to_vec(),
+      all_done,
+      paused: false,
+      hits: Vec::new(),
+    });
+    self.send("progress")?;
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.close("finish")?;
+
+    self.inner.after_finish()
+  }
+
+  fn on_pause(&mut self) -> Result<()> {
+    if let Some(snapshot) = &mut self.snapshot {
+      snapshot.paused = true;
+    }
+
+    self.send("progress")?;
+
+    self.inner.on_pause()
+  }
+
+  fn on_resume(&mut self) -> Result<()> {
+    if let Some(snapshot) = &mut self.snapshot {
+      snapshot.paused = false;
+    }
+
+    self.send("progress")?;
+
+    self.inner.on_resume()
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.close("stop")?;
+
+    self.inner.on_accidential_stop()
+  }
+
+  crate::forward_to_inner!(on_hit, on_notable_hit, on_deadline_overrun);
+}
+
+#[cfg(test)]
+mod test {
+  use std::io::{BufRead, BufReader, Read, Write};
+  use std::net::TcpStream;
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::result::HitCount;
+
+  use super::SseProgressHandler;
+
+  /// Connect to `handler`, returning the stream and the lines of the head of
+  /// its response.
+  fn connect(
+    handler: &SseProgressHandler,
+  ) -> Result<(BufReader<TcpStream>, Vec<String>)> {
+    let mut stream = TcpStream::connect(handler.local_addr())?;
+
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+
+    let mut reader = BufReader::new(stream);
+    let mut head = Vec::new();
+
+    // the client is served once its request is answered
+    while head.last().map(String::as_str) != Some("") {
+      let mut line = String::new();
+
+      reader.read_line(&mut line)?;
+      head.push(line.trim_end().to_string());
+    }
+
+    Ok((reader, head))
+  }
+
+  #[test]
+  fn test_events() -> Result<()> {
+    let mut handler = SseProgressHandler::bind(
+      "127.0.0.1:0",
+      Box::new(EmptyProgressHandler::new()),
+    )?;
+    let (mut reader, head) = connect(&handler)?;
+
+    assert_eq!(head[0], "HTTP/1.1 200 OK");
+    assert!(head.contains(&"Content-Type: text/event-stream".to_string()));
+    assert!(!head.iter().any(|line| line.starts_with("Access-Control")));
+
+    handler.before_start(&RunInfo {
+      tries: 10,
+      ..RunInfo::new(1)
+    })?;
+    handler.handle(
+      &[],
+      &[HitCount::new("WKNC", 1)],
+      Duration::from_secs(1),
+      5,
+      false,
+    )?;
+    handler.after_finish()?;
+
+    let mut events = String::new();
+
+    reader.read_to_string(&mut events)?;
+
+    let events = events.split("\n\n").collect::<Vec<_>>();
+
+    assert_eq!(events.len(), 3);
+    assert!(events[0].starts_with("event: progress\ndata: {"));
+    assert!(events[0].contains(r#""hits_total":1"#));
+    assert!(events[1].starts_with("event: finish\n"));
+
+    Ok(())
+  }
+  #[test]
+  fn test_allowed_origin() -> Result<()> {
+    let handler = SseProgressHandler::bind(
+      "127.0.0.1:0",
+      Box::new(EmptyProgressHandler::new()),
+    )?
+    .with_allowed_origin("http://localhost:3000");
+    let (_, head) = connect(&handler)?;
+
+    assert!(head.contains(
+      &"Access-Control-Allow-Origin: http://localhost:3000".to_string()
+    ));
+
+    Ok(())
+  }
+}
@@ -0,0 +1,243 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+
+use crate::progress::Progress;
+use crate::result::HitCount;
+
+use super::{ControlFlow, ProgressHandler, RunInfo};
+
+/// Which progresses [`Throttled`] forwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Limit {
+  /// At most one per duration
+  Interval(Duration),
+  /// One of every number of progresses
+  Every(usize),
+}
+
+/// Handler forwarding only some of the progresses to the handler it wraps,
+/// for consumers slower than the research renders its progress.
+///
+/// The first and the final progress are always forwarded, the tries and the
+/// time of the skipped progresses being added to the next forwarded one so
+/// its rate stays right. The other events are all forwarded.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+///
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::msgpack::MsgpackProgressHandler;
+/// use wakuchin::handlers::throttle::Throttled;
+///
+/// let output = Arc::new(Mutex::new(Cursor::new(Vec::new())));
+///
+/// // at most 1 frame per second
+/// let handler = Throttled::new(
+///   MsgpackProgressHandler::new(0, output.clone()),
+///   Duration::from_secs(1),
+/// );
+///
+/// ResearchBuilder::new()
+///   .tries(10)
+///   .times(1)
+///   .regex(Regex::new(r"WKCN")?)
+///   .progress_handler(Box::new(handler))
+///   .run_seq()?;
+///
+/// assert!(!output.lock().unwrap().get_ref().is_empty());
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Throttled<H> {
+  inner: H,
+  limit: Limit,
+  /// When the last progress was forwarded
+  last: Option<Instant>,
+  /// Progresses handled so far
+  calls: usize,
+  /// Tries done during the progresses skipped since the last forwarded one
+  skipped_diff: usize,
+  /// Time elapsed during the progresses skipped since the last forwarded one
+  skipped_elapsed: Duration,
+}
+
+impl<H: ProgressHandler> Throttled<H> {
+  /// Forward at most one progress per `interval` to `inner`.
+  pub fn new(inner: H, interval: Duration) -> Self {
+    Self::with_limit(inner, Limit::Interval(interval))
+  }
+
+  /// Forward one of every `n` progresses to `inner`, every one if `n` is 0.
+  pub fn every(inner: H, n: usize) -> Self {
+    Self::with_limit(inner, Limit::Every(n.max(1)))
+  }
+
+  fn with_limit(inner: H, limit: Limit) -> Self {
+    Self {
+      inner,
+      limit,
+      last: None,
+      calls: 0,
+      skipped_diff: 0,
+      skipped_elapsed: Duration::ZERO,
+    }
+  }
+
+  /// Return the wrapped handler.
+  #[inline]
+  pub fn into_inner(self) -> H {
+    self.inner
+  }
+
+  /// Return whether the current progress is forwarded, unless it is the
+  /// final one.
+  fn is_due(&self) -> bool {
+    match (self.limit, self.last) {
+      (_, None) => true,
+      (Limit::Interval(interval), Some(last)) => last.elapsed() >= interval,
+      (Limit::Every(n), Some(_)) => self.calls.is_multiple_of(n),
+    }
+  }
+}
+
+impl<H: ProgressHandler> ProgressHandler for Throttled<H> {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.last = None;
+    self.calls = 0;
+    self.skipped_diff = 0;
+    self.skipped_elapsed = Duration::ZERO;
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    let due = all_done || self.is_due();
+
+    self.calls += 1;
+
+    if !due {
+      self.skipped_diff += current_diff;
+      self.skipped_elapsed += elapsed_time;
+
+      return Ok(ControlFlow::Continue);
+    }
+
+    self.last = Some(Instant::now());
+
+    let flow = self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time + self.skipped_elapsed,
+      current_diff + self.skipped_diff,
+      all_done,
+    )?;
+
+    self.skipped_diff = 0;
+    self.skipped_elapsed = Duration::ZERO;
+
+    Ok(flow)
+  }
+
+  crate::forward_to_inner!(
+    after_finish,
+    on_pause,
+    on_resume,
+    on_hit,
+    on_notable_hit,
+    on_deadline_overrun,
+    on_accidential_stop
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::{ControlFlow, ProgressHandler, RunInfo};
+  use crate::progress::Progress;
+  use crate::result::HitCount;
+
+  use super::Throttled;
+
+  /// Handler recording the elapsed time, the diff and `all_done` of each
+  /// progress.
+  #[derive(Default)]
+  struct RecordingHandler(Vec<(Duration, usize, bool)>);
+
+  impl ProgressHandler for RecordingHandler {
+    fn handle(
+      &mut self,
+      _: &[Progress],
+      _: &[HitCount],
+      elapsed_time: Duration,
+      current_diff: usize,
+      all_done: bool,
+    ) -> Result<ControlFlow> {
+      self.0.push((elapsed_time, current_diff, all_done));
+
+      Ok(ControlFlow::Continue)
+    }
+  }
+
+  fn feed<H: ProgressHandler>(handler: &mut H, calls: usize) -> Result<()> {
+    let second = Duration::from_secs(1);
+
+    handler.before_start(&RunInfo::new(1))?;
+
+    for _ in 0..calls {
+      handler.handle(&[], &[], second, 10, false)?;
+    }
+
+    handler.handle(&[], &[], second, 0, true)?;
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_throttled() -> Result<()> {
+    let second = Duration::from_secs(1);
+
+    let mut every = Throttled::every(RecordingHandler::default(), 3);
+
+    feed(&mut every, 7)?;
+
+    assert_eq!(
+      every.into_inner().0,
+      [
+        (second, 10, false),
+        (second * 3, 30, false),
+        (second * 3, 30, false),
+        (second, 0, true),
+      ]
+    );
+
+    let mut interval =
+      Throttled::new(RecordingHandler::default(), Duration::from_secs(3600));
+
+    feed(&mut interval, 5)?;
+
+    assert_eq!(
+      interval.into_inner().0,
+      [(second, 10, false), (second * 5, 40, true)]
+    );
+
+    Ok(())
+  }
+}
@@ -0,0 +1,463 @@
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use instant::Instant;
+use serde_json::{json, Value};
+
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::HitCount;
+use crate::threads::{self, Handle};
+
+use super::{ControlFlow, ProgressHandler, RunInfo, WarningCallback};
+
+/// Default time between the progress summaries.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Default number of retries of a failed request.
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Time before the first retry, doubled before each of the next ones.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Time limit of a request.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shape of the JSON bodies [`WebhookProgressHandler`] posts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WebhookFormat {
+  /// The event, the tries, the hit counts and a text summary of them
+  #[default]
+  Json,
+  /// `{"content": <summary>}` for Discord webhooks
+  Discord,
+  /// `{"text": <summary>}` for Slack incoming webhooks
+  Slack,
+}
+
+impl WebhookFormat {
+  /// Return the format the service of `url` expects, [`WebhookFormat::Json`]
+  /// if it is not known.
+  pub fn detect(url: &str) -> Self {
+    let host = url
+      .split_once("://")
+      .map_or(url, |(_, rest)| rest)
+      .split(['/', ':', '?'])
+      .next()
+      .unwrap_or_default();
+
+    match host {
+      "discord.com" | "discordapp.com" => WebhookFormat::Discord,
+      "hooks.slack.com" => WebhookFormat::Slack,
+      _ => WebhookFormat::Json,
+    }
+  }
+}
+
+/// Outcome of a request.
+enum Delivery {
+  Sent,
+  /// Failed, but may succeed if retried
+  Retry(String),
+  Failed(String),
+}
+
+/// Post a body to a URL.
+type Post = Arc<dyn Fn(&str, &Value) -> Delivery + Send + Sync>;
+
+fn post(agent: &ureq::Agent, url: &str, body: &Value) -> Delivery {
+  match agent.post(url).send_json(body) {
+    Ok(_) => Delivery::Sent,
+    Err(ureq::Error::Status(code, _)) if code == 429 || code >= 500 => {
+      Delivery::Retry(format!("status {code}"))
+    }
+    Err(ureq::Error::Status(code, _)) => {
+      Delivery::Failed(format!("status {code}"))
+    }
+    Err(e) => Delivery::Retry(e.to_string()),
+  }
+}
+
+/// Post each body of `bodies` in order, retrying with exponential backoff.
+fn deliver(
+  post: Post,
+  url: String,
+  bodies: flume::Receiver<Value>,
+  retries: u32,
+  backoff: Duration,
+  on_warning: Option<WarningCallback>,
+) {
+  for body in bodies {
+    let mut wait = backoff;
+
+    for attempt in 0..=retries {
+      match post(&url, &body) {
+        Delivery::Sent => break,
+        Delivery::Retry(_) if attempt < retries => {
+          thread::sleep(wait);
+          wait *= 2;
+        }
+        Delivery::Retry(e) | Delivery::Failed(e) => {
+          if let Some(on_warning) = &on_warning {
+            on_warning(&format!("webhook request failed: {e}"));
+          }
+
+          break;
+        }
+      }
+    }
+  }
+}
+
+/// Handler posting summaries of the progress to a webhook at a cadence, and
+/// the result once the research finishes, besides the handler it wraps.
+///
+/// The requests are sent from a thread of their own so a slow webhook does
+/// not hold the research back, failed ones being retried with exponential
+/// backoff. Webhooks are best effort: a request still failing after the
+/// retries is warned about, see [`with_warning_callback`](Self::with_warning_callback),
+/// and the research goes on. The final request is waited for before the
+/// research returns.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::handlers::webhook::WebhookProgressHandler;
+///
+/// let handler = WebhookProgressHandler::new(
+///   "https://hooks.slack.com/services/T000/B000/XXXX",
+///   Box::new(EmptyProgressHandler::new()),
+/// )
+/// .with_interval(Duration::from_secs(3600));
+///
+/// ResearchBuilder::new()
+///   .tries(1_000_000_000)
+///   .times(2)
+///   .regex(Regex::new(r"WKNC")?)
+///   .progress_handler(Box::new(handler))
+///   .run_par()?;
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct WebhookProgressHandler {
+  inner: Box<dyn ProgressHandler>,
+  url: String,
+  format: WebhookFormat,
+  interval: Duration,
+  retries: u32,
+  backoff: Duration,
+  post: Post,
+  on_warning: Option<WarningCallback>,
+  started: Instant,
+  tries: usize,
+  current: usize,
+  hit_counts: Vec<HitCount>,
+  /// When the last summary was posted, and the tries done then
+  last: Option<(Instant, usize)>,
+  /// Bodies to post, and the thread posting them
+  sender: Option<(flume::Sender<Value>, Handle<()>)>,
+}
+
+impl WebhookProgressHandler {
+  /// Post to `url` in the format [`WebhookFormat::detect`] returns for it.
+  pub fn new(url: impl Into<String>, inner: Box<dyn ProgressHandler>) -> Self {
+    let url = url.into();
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+
+    Self {
+      inner,
+      format: WebhookFormat::detect(&url),
+      url,
+      interval: DEFAULT_INTERVAL,
+      retries: DEFAULT_RETRIES,
+      backoff: INITIAL_BACKOFF,
+      post: Arc::new(move |url, body| post(&agent, url, body)),
+      on_warning: None,
+      started: Instant::now(),
+      tries: 0,
+      current: 0,
+      hit_counts: Vec::new(),
+      last: None,
+      sender: None,
+    }
+  }
+
+  /// Post in `format` instead of the detected one.
+  #[must_use]
+  pub fn with_format(mut self, format: WebhookFormat) -> Self {
+    self.format = format;
+    self
+  }
+
+  /// Post a summary of the progress every `interval`, [`DEFAULT_INTERVAL`]
+  /// by default.
+  #[must_use]
+  pub fn with_interval(mut self, interval: Duration) -> Self {
+    self.interval = interval;
+    self
+  }
+
+  /// Retry a failed request up to `retries` times, [`DEFAULT_RETRIES`] by
+  /// default.
+  #[must_use]
+  pub fn with_retries(mut self, retries: u32) -> Self {
+    self.retries = retries;
+    self
+  }
+
+  /// Call `callback` with the requests still failing after the retries,
+  /// which are not reported otherwise.
+  #[must_use]
+  pub fn with_warning_callback<F>(mut self, callback: F) -> Self
+  where
+    F: Fn(&str) + Send + Sync + 'static,
+  {
+    self.on_warning = Some(Arc::new(callback));
+    self
+  }
+
+  fn hits(&self) -> usize {
+    self.hit_counts.iter().map(|count| count.hits).sum()
+  }
+
+  /// Queue a request of `event`, summarized by `text`.
+  fn send(&mut self, event: &str, text: String) {
+    let body = match self.format {
+      WebhookFormat::Json => json!({
+        "event": event,
+        "text": text,
+        "tries": self.tries,
+        "current": self.current,
+        "elapsed": self.started.elapsed().as_secs_f64(),
+        "hits": self
+          .hit_counts
+          .iter()
+          .map(|count| json!({ "chars": count.chars, "hits": count.hits }))
+          .collect::<Vec<_>>(),
+      }),
+      WebhookFormat::Discord => json!({ "content": text }),
+      WebhookFormat::Slack => json!({ "text": text }),
+    };
+
+    let (tx, _) = self.sender.get_or_insert_with(|| {
+      let (tx, rx) = flume::unbounded();
+      let post = self.post.clone();
+      let url = self.url.clone();
+      let (retries, backoff) = (self.retries, self.backoff);
+      let on_warning = self.on_warning.clone();
+
+      (
+        tx,
+        threads::spawn("webhook", move || {
+          deliver(post, url, rx, retries, backoff, on_warning)
+        }),
+      )
+    });
+
+    // the thread only ends once the sender is dropped
+    let _ = tx.send(body);
+  }
+
+  /// Post the result as `event`, waiting for the pending requests.
+  fn send_end(&mut self, event: &str, summary: &str) {
+    let mut text = format!(
+      "{summary}: {} tries, {} hits in {}s",
+      self.current,
+      self.hits(),
+      self.started.elapsed().as_secs()
+    );
+
+    for count in &self.hit_counts {
+      text.push_str(&format!("\n{}: {}", count.chars, count.hits));
+    }
+
+    self.send(event, text);
+
+    if let Some((tx, handle)) = self.sender.take() {
+      drop(tx);
+
+      // a panic of the thread only lost requests
+      let _ = handle.join();
+    }
+  }
+}
+
+impl ProgressHandler for WebhookProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.started = Instant::now();
+    self.tries = info.tries;
+    self.current = 0;
+    self.last = Some((self.started, 0));
+
+    self.send(
+      "start",
+      format!("Wakuchin research started: {} tries", info.tries),
+    );
+
+    self.inner.before_start(info)
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Result<ControlFlow> {
+    self.current = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>()
+      .min(self.tries);
+    self.hit_counts = hit_counts.to_vec();
+
+    let (last, last_current) = *self.last.get_or_insert((self.started, 0));
+
+    if !all_done && last.elapsed() >= self.interval {
+      let rate = (self.current - last_current.min(self.current)) as f64
+        / last.elapsed().as_secs_f64();
+      let text = format!(
+        "Wakuchin research: {} of {} tries ({:.1}%), {} hits, {:.0} tries/s",
+        self.current,
+        self.tries,
+        self.current as f64 * 100.0 / self.tries.max(1) as f64,
+        self.hits(),
+        if rate.is_finite() { rate } else { 0.0 },
+      );
+
+      self.last = Some((Instant::now(), self.current));
+      self.send("progress", text);
+    }
+
+    self.inner.handle(
+      progresses,
+      hit_counts,
+      elapsed_time,
+      current_diff,
+      all_done,
+    )
+  }
+
+  fn after_finish(&mut self) -> Result<()> {
+    self.send_end("finish", "Wakuchin research finished");
+
+    self.inner.after_finish()
+  }
+
+  fn on_accidential_stop(&mut self) -> Result<()> {
+    self.send_end("stop", "Wakuchin research stopped");
+
+    self.inner.on_accidential_stop()
+  }
+
+  crate::forward_to_inner!(
+    on_pause,
+    on_resume,
+    on_hit,
+    on_notable_hit,
+    on_deadline_overrun
+  );
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::{Arc, Mutex};
+  use std::time::Duration;
+
+  use anyhow::Result;
+
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::handlers::{ProgressHandler, RunInfo};
+  use crate::progress::{DoneDetail, Progress, ProgressKind};
+  use crate::result::HitCount;
+
+  use super::{Delivery, WebhookFormat, WebhookProgressHandler};
+
+  #[test]
+  fn test_detect() {
+    for (url, format) in [
+      (
+        "https://discord.com/api/webhooks/1/x",
+        WebhookFormat::Discord,
+      ),
+      (
+        "https://hooks.slack.com/services/T/B/x",
+        WebhookFormat::Slack,
+      ),
+      ("http://localhost:8080/hooks", WebhookFormat::Json),
+    ] {
+      assert_eq!(WebhookFormat::detect(url), format);
+    }
+  }
+
+  #[test]
+  fn test_posts() -> Result<()> {
+    let posted = Arc::new(Mutex::new(Vec::new()));
+    let mut handler = WebhookProgressHandler::new(
+      "http://localhost/hooks",
+      Box::new(EmptyProgressHandler::new()),
+    )
+    .with_interval(Duration::ZERO)
+    .with_retries(1);
+
+    handler.backoff = Duration::ZERO;
+    handler.post = Arc::new({
+      let posted = posted.clone();
+
+      move |_, body| {
+        let mut posted = posted.lock().unwrap();
+
+        posted.push(body["event"].as_str().unwrap().to_string());
+
+        // fail every first attempt
+        match posted.len() % 2 {
+          1 => Delivery::Retry("status 503".to_string()),
+          _ => Delivery::Sent,
+        }
+      }
+    });
+
+    handler.before_start(&RunInfo {
+      tries: 10,
+      ..RunInfo::new(1)
+    })?;
+    handler.handle(
+      &[Progress(ProgressKind::Done(DoneDetail {
+        id: 0,
+        total: 10,
+      }))],
+      &[HitCount::new("WKNC", 2)],
+      Duration::ZERO,
+      10,
+      false,
+    )?;
+    handler.after_finish()?;
+
+    assert_eq!(
+      *posted.lock().unwrap(),
+      ["start", "start", "progress", "progress", "finish", "finish"]
+    );
+
+    Ok(())
+  }
+}
@@ -1,2 +1,6 @@
-pub mod counter;
+//! Counting of hits, usable without running a research
+
+pub(crate) mod counter;
 mod store;
+
+pub use counter::HitCounter;
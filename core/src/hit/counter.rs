@@ -1,20 +1,22 @@
 use std::borrow::Cow;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use flume::Receiver;
+use serde::{Deserialize, Serialize};
 
+use crate::channel::{Receiver, Sender};
 use crate::result::{Hit, HitCount};
+use crate::sync::{AtomicBool, AtomicUsize, Ordering};
 
-use super::store::{AtomicHitStore, HitStore};
+use super::store::HitStore;
 
-pub struct HitCounterEntry {
-  entry: Vec<(Cow<'static, str>, usize)>,
+pub(crate) struct HitCounterEntry {
+  entry: Vec<(usize, Cow<'static, str>, usize)>,
 }
 
 impl HitCounterEntry {
   #[inline]
-  pub fn new(entry: Vec<(Cow<'static, str>, usize)>) -> Self {
+  pub fn new(entry: Vec<(usize, Cow<'static, str>, usize)>) -> Self {
     Self { entry }
   }
 
@@ -23,30 +25,63 @@ impl HitCounterEntry {
     self
       .entry
       .into_iter()
-      .map(|(chars, hits)| HitCount { chars, hits })
+      .map(|(pattern, chars, hits)| HitCount {
+        chars,
+        hits,
+        pattern,
+      })
       .collect()
   }
 }
 
 #[derive(Clone)]
-pub struct ThreadHitCounter {
+pub(crate) struct ThreadHitCounter {
   pub count_stopped: Arc<AtomicBool>,
-  store: AtomicHitStore,
+  counter: Arc<HitCounter>,
+  /// Number of hits counted so far
+  received: Arc<AtomicUsize>,
   hit_rx: Receiver<Hit>,
+  /// Where the counted hits are passed on, if anywhere
+  counted_tx: Option<Sender<Hit>>,
 }
 
 impl ThreadHitCounter {
   pub fn new(hit_rx: Receiver<Hit>) -> Self {
     Self {
       count_stopped: Arc::new(AtomicBool::new(false)),
-      store: AtomicHitStore::new(),
+      counter: Arc::default(),
+      received: Arc::new(AtomicUsize::new(0)),
       hit_rx,
+      counted_tx: None,
     }
   }
 
+  /// Pass the hits on to `counted_tx` once counted.
+  pub fn with_counted(mut self, counted_tx: Sender<Hit>) -> Self {
+    self.counted_tx = Some(counted_tx);
+    self
+  }
+
   pub fn run(&self) {
     for hit in &self.hit_rx {
-      self.store.add(hit.chars);
+      match &self.counted_tx {
+        Some(counted_tx) => {
+          self.counter.add_count_for(
+            hit.pattern,
+            hit.chars.clone(),
+            hit.matches,
+          );
+          // the render may be gone after a stop
+          let _ = counted_tx.send(hit);
+        }
+        None => {
+          self
+            .counter
+            .add_count_for(hit.pattern, hit.chars, hit.matches);
+        }
+      }
+
+      self.received.fetch_add(1, Ordering::Release);
     }
 
     self.count_stopped.store(true, Ordering::Release);
@@ -54,29 +89,317 @@ impl ThreadHitCounter {
 
   #[inline]
   pub fn get_all(&self) -> HitCounterEntry {
-    HitCounterEntry::new(self.store.get_all())
+    self.counter.get_all()
+  }
+
+  /// Return the number of hits counted so far, cheaper than summing the
+  /// counter.
+  #[inline]
+  pub fn received(&self) -> usize {
+    self.received.load(Ordering::Acquire)
+  }
+
+  /// Counter the received hits are added to.
+  #[inline]
+  pub fn hit_counter(&self) -> Arc<HitCounter> {
+    self.counter.clone()
   }
 }
 
+/// Counter of hits by their chars and pattern.
+///
+/// Researches count their hits with it, and it can be used on its own to
+/// aggregate hits of another generation loop. Counting only needs a shared
+/// reference, and counters of several loops can be merged. Methods without
+/// a pattern count hits of the first one.
+///
+/// It is serialized as a map of chars to their count, sorted by chars, or
+/// to their count of each pattern if other patterns were hit.
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+///
+/// use wakuchin::hit::HitCounter;
+/// use wakuchin::{check, gen};
+///
+/// let regex = Regex::new(r"^WK")?;
+/// let counter = HitCounter::new();
+/// let other = HitCounter::new();
+///
+/// for _ in 0..1000 {
+///   let wakuchin = gen(1);
+///
+///   if check(&wakuchin, &regex) {
+///     counter.add(wakuchin);
+///   }
+/// }
+///
+/// other.add("WKNC");
+/// counter.merge(&other);
+///
+/// assert!(counter.get("WKNC") > 0);
+/// assert_eq!(counter.get("NCWK"), 0);
+///
+/// for hit_count in counter.snapshot() {
+///   assert!(hit_count.chars.starts_with("WK"));
+/// }
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(
+  from = "BTreeMap<Cow<'static, str>, PatternCounts>",
+  into = "BTreeMap<Cow<'static, str>, PatternCounts>"
+)]
 pub struct HitCounter {
   store: HitStore,
 }
 
 impl HitCounter {
   #[inline]
+  #[must_use]
   pub fn new() -> Self {
     Self {
       store: HitStore::new(),
     }
   }
 
+  /// Count a hit of `chars`.
   #[inline]
-  pub fn add(&self, chars: impl Into<Cow<'static, str>>) {
-    self.store.add(chars);
+  pub fn add(&self, chars: impl AsRef<str> + Into<Cow<'static, str>>) {
+    self.store.add(0, chars);
   }
 
+  /// Count a hit of `chars` on the pattern at `pattern`.
   #[inline]
-  pub fn get_all(&self) -> HitCounterEntry {
+  pub fn add_for(
+    &self,
+    pattern: usize,
+    chars: impl AsRef<str> + Into<Cow<'static, str>>,
+  ) {
+    self.store.add(pattern, chars);
+  }
+
+  /// Count `count` hits of `chars` at once.
+  #[inline]
+  pub fn add_count(&self, chars: impl Into<Cow<'static, str>>, count: usize) {
+    self.store.add_count(0, chars, count);
+  }
+
+  /// Count `count` hits of `chars` on the pattern at `pattern` at once.
+  #[inline]
+  pub fn add_count_for(
+    &self,
+    pattern: usize,
+    chars: impl Into<Cow<'static, str>>,
+    count: usize,
+  ) {
+    self.store.add_count(pattern, chars, count);
+  }
+
+  /// Return the number of hits of `chars` on all patterns.
+  #[inline]
+  pub fn get(&self, chars: &str) -> usize {
+    self.store.get_total(chars)
+  }
+
+  /// Return the number of hits of `chars` on the pattern at `pattern`.
+  #[inline]
+  pub fn get_for(&self, pattern: usize, chars: &str) -> usize {
+    self.store.get(pattern, chars)
+  }
+
+  /// Return the number of hits of all chars.
+  pub fn total(&self) -> usize {
+    self.store.get_all().iter().map(|(_, _, hits)| hits).sum()
+  }
+
+  /// Return the number of distinct chars which hit.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.store.len()
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Add the hits of `other` to this counter.
+  pub fn merge(&self, other: &HitCounter) {
+    for (pattern, chars, hits) in other.store.get_all() {
+      self.store.add_count(pattern, chars, hits);
+    }
+  }
+
+  /// Return the current counts, sorted by chars and pattern.
+  pub fn snapshot(&self) -> Vec<HitCount> {
+    let mut hit_counts = self.get_all().into_hit_counts();
+
+    hit_counts.sort_unstable_by(|a, b| {
+      (&a.chars, a.pattern).cmp(&(&b.chars, b.pattern))
+    });
+
+    hit_counts
+  }
+
+  #[inline]
+  pub(crate) fn get_all(&self) -> HitCounterEntry {
     HitCounterEntry::new(self.store.get_all())
   }
 }
+
+/// Serialized hits of chars, a single count for the first pattern only.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PatternCounts {
+  First(usize),
+  ByPattern(Vec<usize>),
+}
+
+impl From<BTreeMap<Cow<'static, str>, PatternCounts>> for HitCounter {
+  fn from(hits: BTreeMap<Cow<'static, str>, PatternCounts>) -> Self {
+    let counter = Self::new();
+
+    for (chars, counts) in hits {
+      match counts {
+        PatternCounts::First(count) => counter.add_count(chars, count),
+        PatternCounts::ByPattern(counts) => {
+          for (pattern, count) in counts.into_iter().enumerate() {
+            counter.add_count_for(pattern, chars.clone(), count);
+          }
+        }
+      }
+    }
+
+    counter
+  }
+}
+
+impl From<HitCounter> for BTreeMap<Cow<'static, str>, PatternCounts> {
+  fn from(counter: HitCounter) -> Self {
+    let mut hits = BTreeMap::<_, PatternCounts>::new();
+
+    for (pattern, chars, count) in counter.store.get_all() {
+      let counts = hits.entry(chars).or_insert(PatternCounts::First(0));
+
+      match counts {
+        PatternCounts::First(first) if pattern == 0 => *first = count,
+        PatternCounts::First(first) => {
+          let mut by_pattern = vec![0; pattern + 1];
+
+          by_pattern[0] = *first;
+          by_pattern[pattern] = count;
+          *counts = PatternCounts::ByPattern(by_pattern);
+        }
+        PatternCounts::ByPattern(by_pattern) => {
+          if by_pattern.len() <= pattern {
+            by_pattern.resize(pattern + 1, 0);
+          }
+
+          by_pattern[pattern] = count;
+        }
+      }
+    }
+
+    hits
+  }
+}
+
+impl FromIterator<HitCount> for HitCounter {
+  fn from_iter<I: IntoIterator<Item = HitCount>>(iter: I) -> Self {
+    let counter = Self::new();
+
+    for hit_count in iter {
+      counter.add_count_for(hit_count.pattern, hit_count.chars, hit_count.hits);
+    }
+
+    counter
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::error::Error;
+
+  use crate::result::HitCount;
+
+  use super::HitCounter;
+
+  #[test]
+  fn test_merge() {
+    let counter = HitCounter::new();
+    let other = HitCounter::new();
+
+    counter.add("WKCN");
+    counter.add("WKNC");
+    other.add("WKCN");
+    other.add_count("NCWK", 3);
+
+    counter.merge(&other);
+
+    assert_eq!(counter.get("WKCN"), 2);
+    assert_eq!(counter.get("NCWK"), 3);
+    assert_eq!(counter.total(), 6);
+    assert_eq!(counter.len(), 3);
+    assert_eq!(
+      counter.snapshot(),
+      [
+        HitCount::new("NCWK", 3),
+        HitCount::new("WKCN", 2),
+        HitCount::new("WKNC", 1),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_serde() -> Result<(), Box<dyn Error>> {
+    let counter = [HitCount::new("WKNC", 1), HitCount::new("WKCN", 2)]
+      .into_iter()
+      .collect::<HitCounter>();
+
+    let json = serde_json::to_string(&counter)?;
+
+    assert_eq!(json, r#"{"WKCN":2,"WKNC":1}"#);
+    assert_eq!(
+      serde_json::from_str::<HitCounter>(&json)?.snapshot(),
+      counter.snapshot()
+    );
+
+    counter.add_for(2, "WKCN");
+
+    let json = serde_json::to_string(&counter)?;
+
+    assert_eq!(json, r#"{"WKCN":[2,0,1],"WKNC":1}"#);
+    assert_eq!(
+      serde_json::from_str::<HitCounter>(&json)?.snapshot(),
+      counter.snapshot()
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_patterns() {
+    let counter = HitCounter::new();
+
+    counter.add("WKCN");
+    counter.add_for(1, "WKCN");
+    counter.add_count_for(3, "NCWK", 2);
+
+    assert_eq!(counter.get("WKCN"), 2);
+    assert_eq!(counter.get_for(1, "WKCN"), 1);
+    assert_eq!(counter.get_for(2, "WKCN"), 0);
+    assert_eq!(counter.total(), 4);
+    assert_eq!(
+      counter.snapshot(),
+      [
+        HitCount::new("NCWK", 2).with_pattern(3),
+        HitCount::new("WKCN", 1),
+        HitCount::new("WKCN", 1).with_pattern(1),
+      ]
+    );
+  }
+}
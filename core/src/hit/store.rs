@@ -1,44 +1,56 @@
 use std::borrow::Cow;
-use std::sync::atomic::AtomicUsize;
-use std::sync::atomic::Ordering;
-use std::sync::Arc;
 
 use dashmap::DashMap;
 
-#[derive(Clone)]
-pub struct AtomicHitStore {
-  map: Arc<DashMap<Cow<'static, str>, AtomicUsize>>,
+/// Hits of chars by pattern, only allocating for the patterns after the
+/// first one.
+#[derive(Clone, Debug, Default)]
+struct Counts {
+  first: usize,
+  rest: Vec<usize>,
 }
 
-impl AtomicHitStore {
+impl Counts {
   #[inline]
-  pub fn new() -> Self {
-    Self {
-      map: Arc::new(DashMap::new()),
+  fn get(&self, pattern: usize) -> usize {
+    match pattern {
+      0 => self.first,
+      _ => self.rest.get(pattern - 1).copied().unwrap_or(0),
     }
   }
 
   #[inline]
-  pub fn add(&self, chars: impl Into<Cow<'static, str>>) {
-    self
-      .map
-      .entry(chars.into())
-      .or_insert_with(|| AtomicUsize::new(0))
-      .fetch_add(1, Ordering::Relaxed);
+  fn get_mut(&mut self, pattern: usize) -> &mut usize {
+    if pattern == 0 {
+      return &mut self.first;
+    }
+
+    if self.rest.len() < pattern {
+      self.rest.resize(pattern, 0);
+    }
+
+    &mut self.rest[pattern - 1]
   }
 
-  #[inline]
-  pub fn get_all(&self) -> Vec<(Cow<'static, str>, usize)> {
-    self
-      .map
-      .iter()
-      .map(|entry| (entry.key().clone(), entry.value().load(Ordering::Relaxed)))
-      .collect()
+  /// Return the hits of each pattern, skipping patterns without hits after
+  /// the first one.
+  fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let first = (self.first != 0 || self.rest.is_empty()).then_some(self.first);
+
+    first.map(|hits| (0, hits)).into_iter().chain(
+      self
+        .rest
+        .iter()
+        .enumerate()
+        .filter(|(_, hits)| **hits != 0)
+        .map(|(pattern, hits)| (pattern + 1, *hits)),
+    )
   }
 }
 
+#[derive(Clone, Debug, Default)]
 pub struct HitStore {
-  map: DashMap<Cow<'static, str>, usize>,
+  map: DashMap<Cow<'static, str>, Counts>,
 }
 
 impl HitStore {
@@ -50,20 +62,61 @@ impl HitStore {
   }
 
   #[inline]
-  pub fn add(&self, chars: impl Into<Cow<'static, str>>) {
-    self
-      .map
-      .entry(chars.into())
-      .and_modify(|hits| *hits += 1)
-      .or_insert(1);
+  pub fn add(
+    &self,
+    pattern: usize,
+    chars: impl AsRef<str> + Into<Cow<'static, str>>,
+  ) {
+    // only allocate the key of new chars
+    if let Some(mut counts) = self.map.get_mut(chars.as_ref()) {
+      *counts.get_mut(pattern) += 1;
+
+      return;
+    }
+
+    *self.map.entry(chars.into()).or_default().get_mut(pattern) += 1;
+  }
+
+  #[inline]
+  pub fn add_count(
+    &self,
+    pattern: usize,
+    chars: impl Into<Cow<'static, str>>,
+    count: usize,
+  ) {
+    *self.map.entry(chars.into()).or_default().get_mut(pattern) += count;
   }
 
   #[inline]
-  pub fn get_all(&self) -> Vec<(Cow<'static, str>, usize)> {
+  pub fn get(&self, pattern: usize, chars: &str) -> usize {
+    self.map.get(chars).map_or(0, |counts| counts.get(pattern))
+  }
+
+  /// Return the hits of `chars` of all patterns.
+  #[inline]
+  pub fn get_total(&self, chars: &str) -> usize {
     self
       .map
-      .iter()
-      .map(|entry| (entry.key().clone(), *entry.value()))
-      .collect()
+      .get(chars)
+      .map_or(0, |counts| counts.iter().map(|(_, hits)| hits).sum())
+  }
+
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.map.len()
+  }
+
+  /// Return the hits of all chars as `(pattern, chars, hits)`.
+  #[inline]
+  pub fn get_all(&self) -> Vec<(usize, Cow<'static, str>, usize)> {
+    let mut all = Vec::with_capacity(self.map.len());
+
+    for entry in self.map.iter() {
+      for (pattern, hits) in entry.value().iter() {
+        all.push((pattern, entry.key().clone(), hits));
+      }
+    }
+
+    all
   }
 }
@@ -0,0 +1,115 @@
+//! Iterator over randomized wakuchin strings
+
+use std::borrow::Cow;
+use std::iter::FusedIterator;
+
+use crate::gen_with;
+use crate::symbol::{SymbolSet, WAKUCHIN_SYMBOLS};
+
+/// Iterator yielding randomized wakuchin strings, for driving your own loops
+/// without a whole research.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::iter::WakuchinIter;
+/// use wakuchin::validate;
+///
+/// // endless, bounded by `take`
+/// for wakuchin in WakuchinIter::new(2).take(3) {
+///   assert_eq!(wakuchin.len(), 8);
+///   assert!(validate(&wakuchin));
+/// }
+///
+/// // bounded, with an exact size hint
+/// let wakuchins = WakuchinIter::bounded(2, 5);
+///
+/// assert_eq!(wakuchins.size_hint(), (5, Some(5)));
+/// assert_eq!(wakuchins.count(), 5);
+/// ```
+#[derive(Clone, Debug)]
+pub struct WakuchinIter {
+  times: usize,
+  symbols: SymbolSet,
+  remaining: Option<usize>,
+}
+
+impl WakuchinIter {
+  /// Create an endless iterator of wakuchin strings of `times`.
+  #[must_use]
+  pub fn new(times: usize) -> Self {
+    Self {
+      times,
+      symbols: WAKUCHIN_SYMBOLS,
+      remaining: None,
+    }
+  }
+
+  /// Create an iterator of `len` wakuchin strings of `times`.
+  #[must_use]
+  pub fn bounded(times: usize, len: usize) -> Self {
+    Self {
+      remaining: Some(len),
+      ..Self::new(times)
+    }
+  }
+
+  /// Generate strings of `symbols` instead of "わくちん".
+  #[must_use]
+  pub fn with_symbols(mut self, symbols: SymbolSet) -> Self {
+    self.symbols = symbols;
+    self
+  }
+}
+
+impl Iterator for WakuchinIter {
+  type Item = Cow<'static, str>;
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(remaining) = &mut self.remaining {
+      *remaining = remaining.checked_sub(1)?;
+    }
+
+    Some(gen_with(self.times, &self.symbols))
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    match self.remaining {
+      Some(remaining) => (remaining, Some(remaining)),
+      None => (usize::MAX, None),
+    }
+  }
+}
+
+impl FusedIterator for WakuchinIter {}
+
+#[cfg(test)]
+mod test {
+  use crate::symbol::SymbolSet;
+
+  use super::WakuchinIter;
+
+  #[test]
+  fn test_wakuchin_iter() -> Result<(), Box<dyn std::error::Error>> {
+    let mut bounded = WakuchinIter::bounded(1, 2);
+
+    assert_eq!(bounded.size_hint(), (2, Some(2)));
+    assert!(bounded.next().is_some());
+    assert!(bounded.next().is_some());
+    assert!(bounded.next().is_none());
+    assert!(bounded.next().is_none());
+
+    assert_eq!(WakuchinIter::new(1).size_hint(), (usize::MAX, None));
+    assert_eq!(WakuchinIter::new(1).take(1000).count(), 1000);
+
+    let symbols = SymbolSet::new("ABCDE", "あいうえお")?;
+
+    assert!(WakuchinIter::bounded(2, 10)
+      .with_symbols(symbols.clone())
+      .all(|chars| crate::validate_with(&chars, &symbols)));
+
+    Ok(())
+  }
+}
@@ -1,22 +1,52 @@
 //! Core functions of wakuchin tools
 
+pub mod aggregator;
 pub mod builder;
+pub mod cancel;
+pub mod candidate;
+pub mod checkpoint;
 pub mod convert;
 pub mod error;
+pub mod fs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod future;
+pub mod generator;
 pub mod handlers;
+pub mod hit;
+pub mod iter;
+pub mod locale;
+pub mod math;
+pub mod pattern;
+pub mod pause;
 pub mod progress;
+pub mod protocol;
+pub mod prune;
 pub mod result;
+pub mod rng;
+pub mod session;
 pub mod symbol;
+pub mod threads;
 pub mod worker;
 
 mod channel;
-mod hit;
+#[cfg(windows)]
+mod console;
+mod engine;
 mod render;
+mod shutdown;
+mod sync;
 mod utils;
 
 use std::borrow::Cow;
+use std::panic::resume_unwind;
+use std::thread;
 
-use regex::Regex;
+use divide_range::RangeDivisions;
+
+use crate::error::WakuchinError;
+use crate::pattern::Matcher;
+use crate::rng::Rng;
+use crate::symbol::SymbolSet;
 
 /// Generate a randomized wakuchin string.
 ///
@@ -60,11 +90,217 @@ use regex::Regex;
 /// ```
 #[inline]
 pub fn gen(times: usize) -> Cow<'static, str> {
-  let mut wakuchin = symbol::WAKUCHIN.repeat(times);
+  gen_with(times, &symbol::WAKUCHIN_SYMBOLS)
+}
+
+/// Generate a randomized string of the internal symbols of `symbols`.
+///
+/// # Arguments
+///
+/// * `times` - times n, each symbol appears n times
+/// * `symbols` - symbol set to use
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::gen_with;
+/// use wakuchin::symbol::SymbolSet;
+///
+/// let symbols = SymbolSet::new("ABCDE", "あいうえお")?;
+/// let chars = gen_with(2, &symbols);
+///
+/// assert_eq!(chars.len(), 10);
+/// assert_eq!(chars.matches('E').count(), 2);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[inline]
+pub fn gen_with(times: usize, symbols: &SymbolSet) -> Cow<'static, str> {
+  let mut chars = symbols.internal().repeat(times);
+
+  fastrand::shuffle(&mut chars);
+
+  chars.iter().collect::<String>().into()
+}
+
+/// Generate a randomized wakuchin string into `buf`, reusing its allocation.
+///
+/// This is the allocation-free version of [`gen`] for hot loops, the
+/// previous contents of `buf` are replaced.
+///
+/// # Arguments
+///
+/// * `times` - wakuchin times n
+/// * `buf` - buffer to write the wakuchin string into
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::{gen_into, validate};
+///
+/// let mut buf = String::new();
+///
+/// for _ in 0..3 {
+///   gen_into(3, &mut buf);
+///
+///   assert_eq!(buf.len(), 12);
+///   assert!(validate(&buf));
+/// }
+/// ```
+#[inline]
+pub fn gen_into(times: usize, buf: &mut String) {
+  gen_into_with(times, &symbol::WAKUCHIN_SYMBOLS, buf);
+}
+
+/// Generate a randomized string of the internal symbols of `symbols` into
+/// `buf`, reusing its allocation.
+///
+/// Symbol sets with non-ASCII internal symbols need a temporary buffer to
+/// shuffle chars, so they still allocate.
+///
+/// The output is the same as [`gen_with`] for the same random state.
+pub fn gen_into_with(times: usize, symbols: &SymbolSet, buf: &mut String) {
+  let mut bytes = std::mem::take(buf).into_bytes();
+
+  gen_bytes_into_with(times, symbols, &mut bytes);
+
+  *buf = String::from_utf8(bytes).expect("whole chars are shuffled");
+}
+
+/// Generate a randomized wakuchin string as UTF-8 bytes into `buf`, reusing
+/// its allocation.
+///
+/// This is what researches generate, the bytes being checked with
+/// [`check_bytes`] and only turned into a `String` on hits.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::gen_bytes_into;
+///
+/// let mut buf = Vec::new();
+///
+/// gen_bytes_into(2, &mut buf);
+///
+/// assert_eq!(buf.len(), 8);
+/// assert!(buf.iter().all(|b| b"WKCN".contains(b)));
+/// ```
+#[inline]
+pub fn gen_bytes_into(times: usize, buf: &mut Vec<u8>) {
+  gen_bytes_into_with(times, &symbol::WAKUCHIN_SYMBOLS, buf);
+}
+
+/// Generate a randomized string of the internal symbols of `symbols` as
+/// UTF-8 bytes into `buf`, see [`gen_bytes_into`].
+///
+/// Symbol sets with non-ASCII internal symbols need a temporary buffer to
+/// shuffle chars, so they still allocate.
+///
+/// The output is the bytes of [`gen_with`] for the same random state.
+#[inline]
+pub fn gen_bytes_into_with(
+  times: usize,
+  symbols: &SymbolSet,
+  buf: &mut Vec<u8>,
+) {
+  gen_bytes_into_rng(times, symbols, buf, &mut Rng::ThreadLocal);
+}
+
+/// Same as [`gen_bytes_into_with`], shuffling with `rng`.
+pub(crate) fn gen_bytes_into_rng(
+  times: usize,
+  symbols: &SymbolSet,
+  buf: &mut Vec<u8>,
+  rng: &mut Rng,
+) {
+  buf.clear();
+
+  if symbols.internal().iter().all(char::is_ascii) {
+    for _ in 0..times {
+      buf.extend(symbols.internal().iter().map(|&c| c as u8));
+    }
+
+    // every char is a single byte, so shuffling bytes keeps the string valid
+    rng.shuffle(buf);
 
-  fastrand::shuffle(&mut wakuchin);
+    return;
+  }
+
+  let mut chars = symbols.internal().repeat(times);
+  let mut encoded = [0; 4];
+
+  rng.shuffle(&mut chars);
+
+  for c in chars {
+    buf.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+  }
+}
 
-  wakuchin.iter().collect::<String>().into()
+/// Generate `count` randomized wakuchin strings into `batch`.
+///
+/// A single random number generator, seeded from the thread-local one, is
+/// used for the whole batch and the symbols are laid out only once, so
+/// workers can pull strings in chunks instead of one by one. Each string is
+/// still allocated, so [`gen_into`] is faster when the strings are consumed
+/// one at a time.
+///
+/// # Arguments
+///
+/// * `count` - number of strings to generate
+/// * `times` - wakuchin times n
+/// * `batch` - collection to extend with the strings
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::{gen_batch, validate};
+///
+/// let mut batch = Vec::with_capacity(1024);
+///
+/// gen_batch(1024, 2, &mut batch);
+///
+/// assert_eq!(batch.len(), 1024);
+/// assert!(batch.iter().all(|wakuchin| validate(wakuchin)));
+/// ```
+#[inline]
+pub fn gen_batch(count: usize, times: usize, batch: &mut impl Extend<String>) {
+  gen_batch_with(count, times, &symbol::WAKUCHIN_SYMBOLS, batch);
+}
+
+/// Generate `count` randomized strings of the internal symbols of `symbols`
+/// into `batch`, see [`gen_batch`].
+pub fn gen_batch_with(
+  count: usize,
+  times: usize,
+  symbols: &SymbolSet,
+  batch: &mut impl Extend<String>,
+) {
+  // a shuffle of any permutation is uniform, so the previous one is reused
+  let rng = fastrand::Rng::with_seed(fastrand::u64(..));
+
+  if symbols.internal().iter().all(char::is_ascii) {
+    let mut bytes = symbols
+      .internal()
+      .iter()
+      .map(|&c| c as u8)
+      .collect::<Vec<_>>()
+      .repeat(times);
+
+    batch.extend((0..count).map(|_| {
+      // every char is a single byte, so shuffling bytes keeps it valid
+      rng.shuffle(&mut bytes);
+
+      String::from_utf8(bytes.clone()).expect("ASCII is valid UTF-8")
+    }));
+  } else {
+    let mut chars = symbols.internal().repeat(times);
+
+    batch.extend((0..count).map(|_| {
+      rng.shuffle(&mut chars);
+
+      chars.iter().collect()
+    }));
+  }
 }
 
 /// Generate a vector of randomized wakuchin string.
@@ -115,7 +351,58 @@ pub fn gen(times: usize) -> Cow<'static, str> {
 /// ```
 #[inline]
 pub fn gen_vec(len: usize, times: usize) -> Vec<Cow<'static, str>> {
-  (0..len).map(|_| gen(times)).collect()
+  iter::WakuchinIter::bounded(times, len).collect()
+}
+
+/// Generate a vector of `len` randomized wakuchin strings on `workers`
+/// threads, like [`gen_vec`] but faster for millions of strings.
+///
+/// The vector is split into even ranges, each generated by its own thread.
+///
+/// # Arguments
+///
+/// * `len` - length of vector to generate
+/// * `times` - wakuchin times n
+/// * `workers` - number of threads, 0 means the number of logical CPUs
+///
+/// # Errors
+///
+/// Returns an I/O error if `workers` is zero and the number of logical CPUs
+/// cannot be queried, like [`run_par`](crate::worker::run_par).
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::{gen_vec_par, validate};
+///
+/// let wakuchin_vec = gen_vec_par(10_000, 2, 4)?;
+///
+/// assert_eq!(wakuchin_vec.len(), 10_000);
+/// assert!(wakuchin_vec.iter().all(|wakuchin| validate(wakuchin)));
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn gen_vec_par(
+  len: usize,
+  times: usize,
+  workers: usize,
+) -> Result<Vec<Cow<'static, str>>, WakuchinError> {
+  let workers = worker::get_total_workers(workers)?.min(len.max(1));
+
+  Ok(thread::scope(|s| {
+    let handles = (0..len)
+      .divide_evenly_into(workers)
+      .map(|range| s.spawn(move || gen_vec(range.len(), times)))
+      .collect::<Vec<_>>();
+
+    let mut wakuchins = Vec::with_capacity(len);
+
+    for handle in handles {
+      wakuchins.extend(handle.join().unwrap_or_else(|e| resume_unwind(e)));
+    }
+
+    wakuchins
+  }))
 }
 
 /// Check if a string is a internally used wakuchin string.
@@ -138,7 +425,26 @@ pub fn gen_vec(len: usize, times: usize) -> Vec<Cow<'static, str>> {
 /// assert!(!validate("WKCNX"));
 /// ```
 pub fn validate(wakuchin: &str) -> bool {
-  wakuchin.chars().all(|c| symbol::WAKUCHIN.contains(&c))
+  validate_with(wakuchin, &symbol::WAKUCHIN_SYMBOLS)
+}
+
+/// Check if a string is made of the internal symbols of `symbols`.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::validate_with;
+/// use wakuchin::symbol::SymbolSet;
+///
+/// let symbols = SymbolSet::new("ABCDE", "あいうえお")?;
+///
+/// assert!(validate_with("EDCBA", &symbols));
+/// assert!(!validate_with("WKCN", &symbols));
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn validate_with(chars: &str, symbols: &SymbolSet) -> bool {
+  chars.chars().all(|c| symbols.internal().contains(&c))
 }
 
 /// Check whether the string is a valid wakuchin characters.
@@ -162,18 +468,21 @@ pub fn validate(wakuchin: &str) -> bool {
 /// assert!(!validate_external("WKCNX"));
 /// ```
 pub fn validate_external(wakuchin: &str) -> bool {
-  wakuchin
-    .chars()
-    .all(|c| symbol::WAKUCHIN_EXTERNAL.contains(&c))
+  validate_external_with(wakuchin, &symbol::WAKUCHIN_SYMBOLS)
+}
+
+/// Check if a string is made of the external symbols of `symbols`.
+pub fn validate_external_with(chars: &str, symbols: &SymbolSet) -> bool {
+  chars.chars().all(|c| symbols.external().contains(&c))
 }
 
-/// Check wakuchin string with specified regular expression.
-/// This function is a wrapper of `Regex::is_match`.
+/// Check wakuchin string with specified matcher, e.g. a regular expression.
+/// This function is a wrapper of [`Matcher::is_match`].
 ///
 /// # Arguments
 ///
 /// * `chars` - wakuchin string to check
-/// * `regex` - regular expression to use
+/// * `matcher` - regular expression or other [`Matcher`] to use
 ///
 /// # Returns
 ///
@@ -189,9 +498,28 @@ pub fn validate_external(wakuchin: &str) -> bool {
 /// assert!(check("WKCN", &Regex::new(r"^[WKCN]+$").unwrap()));
 /// assert!(!check("わくちん", &Regex::new(r"^[WKCN]+$").unwrap()));
 /// assert!(!check("WKCNX", &Regex::new(r"^[WKCN]+$").unwrap()));
+/// assert!(check("WKKW", &|s: &str| s.chars().eq(s.chars().rev())));
+/// ```
+#[inline(always)]
+pub fn check(chars: &str, matcher: &(impl Matcher + ?Sized)) -> bool {
+  matcher.is_match(chars)
+}
+
+/// Check wakuchin bytes with specified regular expression, the byte version
+/// of [`check`].
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::bytes::Regex;
+///
+/// use wakuchin::check_bytes;
+///
+/// assert!(check_bytes(b"WKCN", &Regex::new(r"^[WKCN]+$").unwrap()));
+/// assert!(!check_bytes(b"WKCNX", &Regex::new(r"^[WKCN]+$").unwrap()));
 /// ```
 #[inline(always)]
-pub fn check(chars: &str, regex: &Regex) -> bool {
+pub fn check_bytes(chars: &[u8], regex: &regex::bytes::Regex) -> bool {
   regex.is_match(chars)
 }
 
@@ -201,7 +529,12 @@ mod test {
 
   use regex::Regex;
 
-  use crate::{check, gen, gen_vec, symbol, validate, validate_external};
+  use crate::symbol::SymbolSet;
+  use crate::{
+    check, check_bytes, gen, gen_batch, gen_batch_with, gen_bytes_into,
+    gen_bytes_into_with, gen_into, gen_into_with, gen_vec, gen_vec_par,
+    gen_with, symbol, validate, validate_external, validate_with,
+  };
 
   #[test]
   fn test_gen() {
@@ -261,6 +594,104 @@ mod test {
     assert_eq!(wakuchin_n_count, 9);
   }
 
+  #[test]
+  fn test_gen_vec_par() -> Result<(), Box<dyn Error>> {
+    let wakuchin_vec = gen_vec_par(1001, 2, 4)?;
+
+    assert_eq!(wakuchin_vec.len(), 1001);
+    assert!(wakuchin_vec
+      .iter()
+      .all(|wakuchin| wakuchin.len() == 8 && validate(wakuchin)));
+
+    // more workers than strings
+    assert_eq!(gen_vec_par(3, 1, 8)?.len(), 3);
+    assert!(gen_vec_par(0, 1, 8)?.is_empty());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_gen_into() -> Result<(), Box<dyn Error>> {
+    let mut buf = String::from("leftover");
+
+    gen_into(2, &mut buf);
+
+    assert_eq!(buf.len(), 8);
+    assert!(validate(&buf));
+
+    for symbols in [
+      SymbolSet::new("ABCDE", "あいうえお")?,
+      SymbolSet::new("あいうえお", "ABCDE")?,
+    ] {
+      fastrand::seed(42);
+      gen_into_with(3, &symbols, &mut buf);
+
+      fastrand::seed(42);
+      assert_eq!(buf, gen_with(3, &symbols));
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_gen_bytes_into() -> Result<(), Box<dyn Error>> {
+    let mut buf = b"leftover".repeat(2);
+
+    gen_bytes_into(2, &mut buf);
+
+    assert_eq!(buf.len(), 8);
+    assert!(validate(std::str::from_utf8(&buf)?));
+
+    for symbols in [
+      SymbolSet::new("ABCDE", "あいうえお")?,
+      SymbolSet::new("あいうえお", "ABCDE")?,
+    ] {
+      fastrand::seed(42);
+      gen_bytes_into_with(3, &symbols, &mut buf);
+
+      fastrand::seed(42);
+      assert_eq!(buf, gen_with(3, &symbols).as_bytes());
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_gen_batch() -> Result<(), Box<dyn Error>> {
+    let mut batch = vec![String::from("kept")];
+
+    gen_batch(3, 2, &mut batch);
+
+    assert_eq!(batch.len(), 4);
+    assert!(batch[1..].iter().all(|wakuchin| validate(wakuchin)));
+
+    for symbols in [
+      SymbolSet::new("ABCDE", "あいうえお")?,
+      SymbolSet::new("あいうえお", "ABCDE")?,
+    ] {
+      let mut batch = Vec::new();
+
+      gen_batch_with(100, 2, &symbols, &mut batch);
+
+      assert_eq!(batch.len(), 100);
+      assert!(batch.iter().all(
+        |chars| chars.chars().count() == 10 && validate_with(chars, &symbols)
+      ));
+    }
+
+    fastrand::seed(42);
+    let mut first = Vec::new();
+    gen_batch(10, 2, &mut first);
+
+    fastrand::seed(42);
+    let mut second = Vec::new();
+    gen_batch(10, 2, &mut second);
+
+    assert_eq!(first, second);
+
+    Ok(())
+  }
+
   #[test]
   fn test_validate() {
     assert!(validate("WKCN"));
@@ -282,6 +713,11 @@ mod test {
     assert!(!check("わくちん", &Regex::new(r"^[WKCN]+$")?));
     assert!(!check("WKCNX", &Regex::new(r"^[WKCN]+$")?));
 
+    let regex = regex::bytes::Regex::new(r"^[わくちん]+$")?;
+
+    assert!(check_bytes("わくちん".as_bytes(), &regex));
+    assert!(!check_bytes(b"WKCN", &regex));
+
     Ok(())
   }
 }
@@ -0,0 +1,179 @@
+//! Formatting of the numbers of human-facing output
+
+#[cfg(feature = "i18n")]
+use crate::error::WakuchinError;
+
+/// How the digits of a number are grouped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Grouping {
+  /// Not grouped, e.g. "300000000"
+  #[default]
+  None,
+  /// By thousands, e.g. "300,000,000"
+  Thousands,
+  /// By thousands then by hundreds, e.g. "30,00,00,000"
+  Indian,
+  /// By ten thousands like 万 and 億, e.g. "3,0000,0000"
+  Man,
+}
+
+impl Grouping {
+  /// Return the size of the group at `index`, from the rightmost one.
+  #[inline]
+  fn size(self, index: usize) -> Option<usize> {
+    match (self, index) {
+      (Self::None, _) => None,
+      (Self::Thousands, _) | (Self::Indian, 0) => Some(3),
+      (Self::Indian, _) => Some(2),
+      (Self::Man, _) => Some(4),
+    }
+  }
+}
+
+/// Format of numbers, plain digits by default.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::locale::{Grouping, NumberFormat};
+///
+/// let format = NumberFormat::default().with_grouping(Grouping::Man);
+///
+/// assert_eq!(format.integer(300_000_000), "3,0000,0000");
+/// assert_eq!(format.number("12345.67"), "1,2345.67");
+/// assert_eq!(NumberFormat::default().integer(300_000_000), "300000000");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NumberFormat {
+  pub grouping: Grouping,
+  /// Separator of groups of digits
+  pub separator: String,
+  /// Separator of the fractional part
+  pub decimal: String,
+}
+
+impl Default for NumberFormat {
+  fn default() -> Self {
+    Self {
+      grouping: Grouping::None,
+      separator: ",".into(),
+      decimal: ".".into(),
+    }
+  }
+}
+
+impl NumberFormat {
+  /// Format of the locale `name`, e.g. "ja", "de-CH" or "ja_JP.UTF-8".
+  ///
+  /// Locales unknown with their region fall back to their language.
+  #[cfg(feature = "i18n")]
+  pub fn from_locale(name: &str) -> Result<Self, WakuchinError> {
+    use num_format::Locale;
+
+    // "ja_JP.UTF-8" is "ja-JP" with an encoding
+    let tag = name.split(['.', '@']).next().unwrap_or_default();
+    let tag = tag.replace('_', "-");
+    let language = tag.split('-').next().unwrap_or_default();
+
+    let locale = Locale::from_name(&tag)
+      .or_else(|_| Locale::from_name(language))
+      .map_err(|_| WakuchinError::UnknownLocale(name.into()))?;
+
+    Ok(Self {
+      grouping: match locale.grouping() {
+        num_format::Grouping::Standard => Grouping::Thousands,
+        num_format::Grouping::Indian => Grouping::Indian,
+        num_format::Grouping::Posix => Grouping::None,
+      },
+      separator: locale.separator().into(),
+      decimal: locale.decimal().into(),
+    })
+  }
+
+  /// Group the digits by `grouping` instead.
+  pub fn with_grouping(mut self, grouping: Grouping) -> Self {
+    self.grouping = grouping;
+    self
+  }
+
+  /// Format `n`.
+  #[inline]
+  pub fn integer(&self, n: usize) -> String {
+    self.number(itoa::Buffer::new().format(n))
+  }
+
+  /// Format `number`, digits with an optional fractional part separated by
+  /// a dot, e.g. "1234.5".
+  pub fn number(&self, number: &str) -> String {
+    let (integer, fraction) = match number.split_once('.') {
+      Some((integer, fraction)) => (integer, Some(fraction)),
+      None => (number, None),
+    };
+    let mut formatted = String::with_capacity(number.len() * 2);
+    let mut groups = Vec::new();
+    let mut rest = integer;
+
+    for index in 0.. {
+      match self.grouping.size(index) {
+        Some(size) if rest.len() > size => {
+          let (head, group) = rest.split_at(rest.len() - size);
+
+          groups.push(group);
+          rest = head;
+        }
+        _ => break,
+      }
+    }
+
+    formatted.push_str(rest);
+
+    for group in groups.into_iter().rev() {
+      formatted.push_str(&self.separator);
+      formatted.push_str(group);
+    }
+
+    if let Some(fraction) = fraction {
+      formatted.push_str(&self.decimal);
+      formatted.push_str(fraction);
+    }
+
+    formatted
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Grouping, NumberFormat};
+
+  #[test]
+  fn test_number() {
+    let format = |grouping| NumberFormat::default().with_grouping(grouping);
+
+    assert_eq!(format(Grouping::None).integer(1234567), "1234567");
+    assert_eq!(format(Grouping::Thousands).integer(1234567), "1,234,567");
+    assert_eq!(format(Grouping::Thousands).integer(123), "123");
+    assert_eq!(
+      format(Grouping::Indian).integer(300_000_000),
+      "30,00,00,000"
+    );
+    assert_eq!(format(Grouping::Man).integer(300_000_000), "3,0000,0000");
+    assert_eq!(format(Grouping::Man).integer(10000), "1,0000");
+    assert_eq!(format(Grouping::Man).integer(0), "0");
+    assert_eq!(
+      format(Grouping::Thousands).number("1234.5678"),
+      "1,234.5678"
+    );
+  }
+
+  #[cfg(feature = "i18n")]
+  #[test]
+  fn test_from_locale() {
+    let format = |name| NumberFormat::from_locale(name).unwrap();
+
+    assert_eq!(format("ja").integer(300_000_000), "300,000,000");
+    assert_eq!(format("ja_JP.UTF-8").integer(300_000_000), "300,000,000");
+    assert_eq!(format("de").number("1234.5"), "1.234,5");
+    assert_eq!(format("en-IN").integer(300_000_000), "30,00,00,000");
+    assert!(NumberFormat::from_locale("xx").is_err());
+  }
+}
@@ -0,0 +1,452 @@
+//! Combinatorics of wakuchin strings, to sanity-check results and size
+//! researches before running them
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::symbol::{self, SymbolSet};
+use crate::{check, gen_into_with};
+
+/// Number of random strings checked when estimating a probability, strings
+/// are enumerated instead when there are fewer of them.
+pub const DEFAULT_SAMPLES: usize = 100_000;
+
+/// Hits rarer than 1 in as many tries are notable unless configured
+/// otherwise, see [`Rarity::notable`].
+pub const DEFAULT_NOTABLE_RARITY: u128 = 1_000_000;
+
+/// Probability of a pattern to hit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Probability {
+  /// Counted over every string
+  Exact(f64),
+  /// Estimated by checking `samples` random strings
+  Estimated { probability: f64, samples: usize },
+}
+
+impl Probability {
+  /// Return the probability, whether it is exact or estimated.
+  #[inline]
+  pub fn value(&self) -> f64 {
+    match self {
+      Probability::Exact(probability)
+      | Probability::Estimated { probability, .. } => *probability,
+    }
+  }
+
+  /// Return the number of hits to expect in `tries`.
+  #[inline]
+  pub fn expected_hits(&self, tries: usize) -> f64 {
+    self.value() * tries as f64
+  }
+}
+
+/// Theoretical rarity of the hits of a research.
+///
+/// Every distinct string is as likely to be tried, so any hit is 1 in as
+/// many tries as there are strings.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::math::Rarity;
+/// use wakuchin::symbol::SymbolSet;
+///
+/// let rarity = Rarity::of(2, &SymbolSet::default(), 1000).unwrap();
+///
+/// assert_eq!(rarity.one_in, 2520);
+/// assert!(rarity.notable);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rarity {
+  /// Number of distinct strings, so a hit is 1 in as many tries
+  pub one_in: u128,
+  /// Whether hits are rarer than the threshold of the research
+  pub notable: bool,
+}
+
+impl Rarity {
+  /// Return the rarity of the hits of strings of `times` of the symbols of
+  /// `symbols`, notable if rarer than 1 in `threshold`, or `None` if the
+  /// number of strings does not fit in `u128`.
+  pub fn of(
+    times: usize,
+    symbols: &SymbolSet,
+    threshold: u128,
+  ) -> Option<Self> {
+    total_permutations_with(times, symbols).map(|one_in| Self {
+      one_in,
+      notable: one_in > threshold,
+    })
+  }
+}
+
+/// Return the number of distinct wakuchin strings of `times`, or `None` if
+/// it does not fit in `u128`.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::math::total_permutations;
+///
+/// assert_eq!(total_permutations(1), Some(24));
+/// assert_eq!(total_permutations(2), Some(2520));
+/// assert_eq!(total_permutations(100), None);
+/// ```
+#[inline]
+pub fn total_permutations(times: usize) -> Option<u128> {
+  total_permutations_with(times, &symbol::WAKUCHIN_SYMBOLS)
+}
+
+/// Return the number of distinct strings of `times` of the symbols of
+/// `symbols`, or `None` if it does not fit in `u128`.
+pub fn total_permutations_with(
+  times: usize,
+  symbols: &SymbolSet,
+) -> Option<u128> {
+  let times = times as u128;
+  let mut total = 1_u128;
+
+  // place each symbol in the positions left by the previous ones
+  for placed in 1..=symbols.len() as u128 {
+    total = total.checked_mul(binomial(placed * times, times)?)?;
+  }
+
+  Some(total)
+}
+
+/// Return the z-score of a two-sided interval of `confidence`, e.g. 1.96 for
+/// 0.95.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::math::z_score;
+///
+/// assert!((z_score(0.95) - 1.959964).abs() < 1e-6);
+/// assert!((z_score(0.99) - 2.575829).abs() < 1e-6);
+/// ```
+pub fn z_score(confidence: f64) -> f64 {
+  normal_quantile(1.0 - (1.0 - confidence) / 2.0)
+}
+
+/// Return the quantile of the standard normal distribution at `p`, with
+/// the rational approximation of Peter Acklam.
+#[allow(clippy::excessive_precision)]
+fn normal_quantile(p: f64) -> f64 {
+  const A: [f64; 6] = [
+    -3.969683028665376e1,
+    2.209460984245205e2,
+    -2.759285104469687e2,
+    1.383577518672690e2,
+    -3.066479806614716e1,
+    2.506628277459239,
+  ];
+  const B: [f64; 5] = [
+    -5.447609879822406e1,
+    1.615858368580409e2,
+    -1.556989798598866e2,
+    6.680131188771972e1,
+    -1.328068155288572e1,
+  ];
+  const C: [f64; 6] = [
+    -7.784894002430293e-3,
+    -3.223964580411365e-1,
+    -2.400758277161838,
+    -2.549732539343734,
+    4.374664141464968,
+    2.938163982698783,
+  ];
+  const D: [f64; 4] = [
+    7.784695709041462e-3,
+    3.224671290700398e-1,
+    2.445134137142996,
+    3.754408661907416,
+  ];
+  const P_LOW: f64 = 0.02425;
+
+  let poly = |coefficients: &[f64], x: f64| {
+    coefficients.iter().fold(0.0, |sum, c| sum * x + c)
+  };
+  let tail = |q: f64| poly(&C, q) / (poly(&D, q) * q + 1.0);
+
+  if p < P_LOW {
+    tail((-2.0 * p.ln()).sqrt())
+  } else if p <= 1.0 - P_LOW {
+    let q = p - 0.5;
+    let r = q * q;
+
+    poly(&A, r) * q / (poly(&B, r) * r + 1.0)
+  } else {
+    -tail((-2.0 * (1.0 - p).ln()).sqrt())
+  }
+}
+
+/// Return the Wilson score interval of the hit rate of `hits` in `tries`
+/// for the z-score `z`, see [`z_score`].
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::math::wilson_interval;
+///
+/// let (low, high) = wilson_interval(50, 1000, 1.96);
+///
+/// assert!(low < 0.05 && 0.05 < high);
+/// assert!((low - 0.0381).abs() < 1e-4 && (high - 0.0653).abs() < 1e-4);
+/// assert_eq!(wilson_interval(0, 0, 1.96), (0.0, 1.0));
+/// ```
+pub fn wilson_interval(hits: usize, tries: usize, z: f64) -> (f64, f64) {
+  if tries == 0 {
+    return (0.0, 1.0);
+  }
+
+  let n = tries as f64;
+  let p = hits as f64 / n;
+  let z2 = z * z;
+  let denominator = 1.0 + z2 / n;
+  let center = (p + z2 / (2.0 * n)) / denominator;
+  let half = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+
+  ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+/// Return the Levenshtein distance between `a` and `b` in chars, or `None`
+/// if it is more than `max`.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::math::edit_distance;
+///
+/// assert_eq!(edit_distance("WKCN", "WKCN", 0), Some(0));
+/// assert_eq!(edit_distance("WKCN", "WKNC", 2), Some(2));
+/// assert_eq!(edit_distance("WKCN", "WCN", 2), Some(1));
+/// assert_eq!(edit_distance("WKCN", "NCKW", 2), None);
+/// ```
+pub fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+  let a = a.chars().collect::<Vec<_>>();
+  let mut previous = (0..=a.len()).collect::<Vec<_>>();
+  let mut current = vec![0; a.len() + 1];
+
+  for (j, b) in b.chars().enumerate() {
+    current[0] = j + 1;
+
+    for (i, a) in a.iter().enumerate() {
+      current[i + 1] = (previous[i] + usize::from(*a != b))
+        .min(previous[i + 1] + 1)
+        .min(current[i] + 1);
+    }
+
+    // distances never decrease from a row to the next
+    if current.iter().all(|distance| *distance > max) {
+      return None;
+    }
+
+    std::mem::swap(&mut previous, &mut current);
+  }
+
+  Some(previous[a.len()]).filter(|distance| *distance <= max)
+}
+
+/// Return `n` choose `k`, or `None` on overflow.
+fn binomial(n: u128, k: u128) -> Option<u128> {
+  let k = k.min(n - k);
+  let mut result = 1_u128;
+
+  for i in 1..=k {
+    // the product of `i` consecutive integers is divisible by `i!`
+    result = result.checked_mul(n - k + i)? / i;
+  }
+
+  Some(result)
+}
+
+/// Return the probability of a wakuchin string of `times` to match `regex`.
+///
+/// It is exact if there are at most [`DEFAULT_SAMPLES`] strings, and
+/// estimated from as many random strings otherwise.
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+///
+/// use wakuchin::math::{pattern_probability, Probability};
+///
+/// let probability = pattern_probability(&Regex::new(r"^WKCNWKCN$")?, 2);
+///
+/// assert_eq!(probability, Probability::Exact(1.0 / 2520.0));
+/// assert_eq!(probability.expected_hits(2520 * 100), 100.0);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[inline]
+pub fn pattern_probability(regex: &Regex, times: usize) -> Probability {
+  pattern_probability_with(
+    regex,
+    times,
+    &symbol::WAKUCHIN_SYMBOLS,
+    DEFAULT_SAMPLES,
+  )
+}
+
+/// Return the probability of a string of `times` of the symbols of
+/// `symbols` to match `regex`.
+///
+/// It is exact if there are at most `samples` strings, and estimated from
+/// `samples` random strings otherwise, which are drawn from
+/// [`fastrand`](https://docs.rs/fastrand)'s thread-local generator.
+pub fn pattern_probability_with(
+  regex: &Regex,
+  times: usize,
+  symbols: &SymbolSet,
+  samples: usize,
+) -> Probability {
+  match total_permutations_with(times, symbols) {
+    Some(total) if total <= samples as u128 => Probability::Exact(
+      count_permutations(regex, times, symbols) / total as f64,
+    ),
+    _ => {
+      let mut buf = String::new();
+      let hits = (0..samples)
+        .filter(|_| {
+          gen_into_with(times, symbols, &mut buf);
+
+          check(&buf, regex)
+        })
+        .count();
+
+      Probability::Estimated {
+        probability: hits as f64 / samples as f64,
+        samples,
+      }
+    }
+  }
+}
+
+/// Count the distinct strings matching `regex`.
+fn count_permutations(regex: &Regex, times: usize, symbols: &SymbolSet) -> f64 {
+  let mut chars = symbols.internal().repeat(times);
+  let mut buf = String::new();
+  let mut hits = 0_u64;
+
+  chars.sort_unstable();
+
+  loop {
+    buf.clear();
+    buf.extend(&chars);
+
+    if check(&buf, regex) {
+      hits += 1;
+    }
+
+    if !next_permutation(&mut chars) {
+      return hits as f64;
+    }
+  }
+}
+
+/// Rearrange `items` into the next permutation in lexicographic order,
+/// returning `false` once they are sorted in descending order.
+fn next_permutation<T: Ord>(items: &mut [T]) -> bool {
+  let Some(i) = items.windows(2).rposition(|pair| pair[0] < pair[1]) else {
+    return false;
+  };
+
+  let j = items
+    .iter()
+    .rposition(|item| items[i] < *item)
+    .expect("the item after `i` is greater");
+
+  items.swap(i, j);
+  items[i + 1..].reverse();
+
+  true
+}
+
+#[cfg(test)]
+mod test {
+  use std::error::Error;
+
+  use regex::Regex;
+
+  use crate::symbol::SymbolSet;
+
+  use super::{
+    next_permutation, pattern_probability, pattern_probability_with,
+    total_permutations, total_permutations_with, Probability, Rarity,
+  };
+
+  #[test]
+  fn test_total_permutations() -> Result<(), Box<dyn Error>> {
+    assert_eq!(total_permutations(0), Some(1));
+    assert_eq!(total_permutations(3), Some(369_600));
+    assert_eq!(total_permutations(10), Some(4_705_360_871_073_570_227_520));
+    assert_eq!(total_permutations(100), None);
+
+    let symbols = SymbolSet::new("AB", "あい")?;
+
+    assert_eq!(total_permutations_with(3, &symbols), Some(20));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_rarity() {
+    let symbols = SymbolSet::default();
+
+    assert_eq!(
+      Rarity::of(3, &symbols, 1_000_000),
+      Some(Rarity {
+        one_in: 369_600,
+        notable: false,
+      })
+    );
+    assert!(Rarity::of(4, &symbols, 1_000_000).unwrap().notable);
+    assert!(!Rarity::of(1, &symbols, 24).unwrap().notable);
+    assert_eq!(Rarity::of(100, &symbols, 1_000_000), None);
+  }
+
+  #[test]
+  fn test_next_permutation() {
+    let mut items = ['A', 'A', 'B'];
+    let mut permutations = vec![items.iter().collect::<String>()];
+
+    while next_permutation(&mut items) {
+      permutations.push(items.iter().collect());
+    }
+
+    assert_eq!(permutations, ["AAB", "ABA", "BAA"]);
+  }
+
+  #[test]
+  fn test_pattern_probability() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+      pattern_probability(&Regex::new(r"^WKCN$")?, 1),
+      Probability::Exact(1.0 / 24.0)
+    );
+    assert_eq!(
+      pattern_probability(&Regex::new(r"^W")?, 2),
+      Probability::Exact(0.25)
+    );
+
+    fastrand::seed(42);
+
+    let symbols = SymbolSet::default();
+    let estimated =
+      pattern_probability_with(&Regex::new(r"^W")?, 3, &symbols, 10_000);
+
+    assert!(matches!(
+      estimated,
+      Probability::Estimated {
+        samples: 10_000,
+        ..
+      }
+    ));
+    assert!((estimated.value() - 0.25).abs() < 0.02);
+    assert!((estimated.expected_hits(1000) - 250.0).abs() < 20.0);
+
+    Ok(())
+  }
+}
@@ -0,0 +1,1171 @@
+//! Patterns researches detect hits of
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+use aho_corasick::AhoCorasick;
+use regex::bytes::{Regex as BytesRegex, RegexSet, SetMatchesIntoIter};
+use regex::Regex;
+use regex_syntax::hir::{Class, Hir, HirKind, Literal};
+
+use crate::convert::pattern_to_internal;
+use crate::error::WakuchinError;
+use crate::math::edit_distance;
+use crate::symbol::SymbolSet;
+
+/// Detector of hits in the tried strings.
+///
+/// It is implemented for [`Regex`], [`regex::RegexSet`] hitting on any of
+/// its patterns, [`AhoCorasick`] and closures, so custom automata can be
+/// plugged into researches with
+/// [`ResearchBuilder::matcher`](crate::builder::ResearchBuilder::matcher).
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::pattern::Matcher;
+///
+/// let palindrome = |s: &str| s.chars().eq(s.chars().rev());
+///
+/// assert!(palindrome.is_match("WKKW"));
+/// assert_eq!(palindrome.find("WKKW"), Some(0..4));
+/// assert!(!palindrome.is_match("WKCN"));
+/// ```
+pub trait Matcher: Send + Sync {
+  fn is_match(&self, s: &str) -> bool;
+
+  /// Return the byte range of the first match in `s`, the whole string by
+  /// default.
+  fn find(&self, s: &str) -> Option<Range<usize>> {
+    self.is_match(s).then_some(0..s.len())
+  }
+
+  /// Return the number of non-overlapping matches in `s`, one at most by
+  /// default.
+  fn count(&self, s: &str) -> usize {
+    usize::from(self.is_match(s))
+  }
+
+  /// Check that strings of `symbols` can match, failing with
+  /// [`WakuchinError::ImpossiblePattern`] otherwise. Nothing is checked by
+  /// default.
+  fn validate_alphabet(
+    &self,
+    _symbols: &SymbolSet,
+  ) -> Result<(), WakuchinError> {
+    Ok(())
+  }
+}
+
+impl Matcher for Regex {
+  #[inline]
+  fn is_match(&self, s: &str) -> bool {
+    Regex::is_match(self, s)
+  }
+
+  #[inline]
+  fn find(&self, s: &str) -> Option<Range<usize>> {
+    Regex::find(self, s).map(|found| found.range())
+  }
+
+  #[inline]
+  fn count(&self, s: &str) -> usize {
+    Regex::find_iter(self, s).count()
+  }
+
+  fn validate_alphabet(
+    &self,
+    symbols: &SymbolSet,
+  ) -> Result<(), WakuchinError> {
+    // compiled regexes always parse, but do not expose their syntax
+    let Ok(hir) =
+      regex_syntax::parse(&pattern_to_internal(self.as_str(), symbols))
+    else {
+      return Ok(());
+    };
+
+    hir_alphabet(&hir, symbols)
+      .map_err(|reason| impossible(self.as_str(), reason))
+  }
+}
+
+fn impossible(pattern: &str, reason: String) -> WakuchinError {
+  WakuchinError::ImpossiblePattern {
+    pattern: pattern.to_owned(),
+    reason,
+  }
+}
+
+/// Return why `c` is not a symbol of `symbols`, if it is not.
+fn not_a_symbol(c: char, symbols: &SymbolSet) -> Option<String> {
+  (!symbols.internal().contains(&c)).then(|| {
+    format!(
+      "'{c}' is not a symbol of \"{}\" or \"{}\"",
+      symbols.internal().iter().collect::<String>(),
+      symbols
+        .internal()
+        .iter()
+        .filter_map(|c| symbols.to_external(*c))
+        .collect::<String>()
+    )
+  })
+}
+
+/// Check that `hir` matches some string of the internal symbols of
+/// `symbols`, returning why it cannot otherwise.
+fn hir_alphabet(hir: &Hir, symbols: &SymbolSet) -> Result<(), String> {
+  match hir.kind() {
+    HirKind::Empty | HirKind::Look(_) => Ok(()),
+    HirKind::Literal(Literal(bytes)) => String::from_utf8_lossy(bytes)
+      .chars()
+      .find_map(|c| not_a_symbol(c, symbols))
+      .map_or(Ok(()), Err),
+    HirKind::Class(class) => {
+      let possible = symbols.internal().iter().any(|c| match class {
+        Class::Unicode(class) => class
+          .ranges()
+          .iter()
+          .any(|range| (range.start()..=range.end()).contains(c)),
+        Class::Bytes(class) => class.ranges().iter().any(|range| {
+          c.encode_utf8(&mut [0; 4])
+            .bytes()
+            .any(|byte| (range.start()..=range.end()).contains(&byte))
+        }),
+      });
+
+      if possible {
+        Ok(())
+      } else {
+        Err(format!("{hir} has none of the symbols"))
+      }
+    }
+    HirKind::Repetition(repetition) if repetition.min == 0 => Ok(()),
+    HirKind::Repetition(repetition) => hir_alphabet(&repetition.sub, symbols),
+    HirKind::Capture(capture) => hir_alphabet(&capture.sub, symbols),
+    HirKind::Concat(hirs) => {
+      hirs.iter().try_for_each(|hir| hir_alphabet(hir, symbols))
+    }
+    // possible if any alternative is
+    HirKind::Alternation(hirs) => {
+      let mut first_reason = None;
+
+      for hir in hirs {
+        match hir_alphabet(hir, symbols) {
+          Ok(()) => return Ok(()),
+          Err(reason) => {
+            first_reason.get_or_insert(reason);
+          }
+        }
+      }
+
+      first_reason.map_or(Ok(()), Err)
+    }
+  }
+}
+
+impl Matcher for regex::RegexSet {
+  #[inline]
+  fn is_match(&self, s: &str) -> bool {
+    regex::RegexSet::is_match(self, s)
+  }
+}
+
+impl Matcher for AhoCorasick {
+  #[inline]
+  fn is_match(&self, s: &str) -> bool {
+    AhoCorasick::is_match(self, s)
+  }
+
+  #[inline]
+  fn find(&self, s: &str) -> Option<Range<usize>> {
+    AhoCorasick::find(self, s).map(|found| found.range())
+  }
+
+  #[inline]
+  fn count(&self, s: &str) -> usize {
+    AhoCorasick::find_iter(self, s).count()
+  }
+}
+
+impl<F> Matcher for F
+where
+  F: Fn(&str) -> bool + Send + Sync,
+{
+  #[inline]
+  fn is_match(&self, s: &str) -> bool {
+    self(s)
+  }
+}
+
+/// Engine matching the regexes of a research, see
+/// [`ResearchBuilder::regex_engine`](crate::builder::ResearchBuilder::regex_engine).
+///
+/// Literals and custom matchers are not affected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RegexEngine {
+  /// Regexes of the `regex` crate
+  #[default]
+  Regex,
+  /// A dense DFA of `regex-automata` matching all patterns at once, built
+  /// once before the research and shared by the workers
+  ///
+  /// It is faster once generating strings is cheap, as tries are short and
+  /// of a small alphabet, but it takes longer to build for large patterns
+  /// and does not support Unicode word boundaries, use `(?-u:\b)`.
+  #[cfg(feature = "dfa")]
+  Dfa,
+}
+
+/// What the hits of a research count.
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+///
+/// use wakuchin::builder::ResearchBuilder;
+/// use wakuchin::pattern::MatchMode;
+///
+/// let result = ResearchBuilder::new()
+///   .tries(10)
+///   .times(8)
+///   .regex(Regex::new(r"WK")?)
+///   .match_mode(MatchMode::CountAll)
+///   .run_seq()?;
+///
+/// let matches = result.hits_detail.iter().map(|hit| hit.matches).sum::<usize>();
+///
+/// assert_eq!(result.hits_total, matches);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchMode {
+  /// Each try matching a pattern is a hit of it
+  #[default]
+  Any,
+  /// Each non-overlapping match of a pattern in a try is counted, e.g.
+  /// twice for "WKCN" in "WKCNWKCN", see
+  /// [`Hit::matches`](crate::result::Hit::matches)
+  CountAll,
+}
+
+/// How a research detects its hits.
+///
+/// Hits of each pattern are counted separately, identified by the index of
+/// the pattern, see [`Hit::pattern`](crate::result::Hit::pattern).
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::pattern::Matchers;
+///
+/// let matchers = Matchers::Literals(vec!["WKNC".into(), "WCKN".into()]);
+///
+/// assert_eq!(matchers.len(), 2);
+/// assert_eq!(matchers.pattern(1), Some("WCKN"));
+/// ```
+#[derive(Clone)]
+pub enum Matchers {
+  /// Regular expressions, recompiled from their pattern to match bytes, so
+  /// use inline flags like `(?i)` instead of `RegexBuilder` options, or
+  /// [`Matchers::Bytes`]
+  Regex(Vec<Regex>),
+  /// Byte regular expressions, matched as compiled so their
+  /// `RegexBuilder` options are kept
+  ///
+  /// External symbols in them are not translated and their alphabet is not
+  /// validated, so write them with the internal symbols.
+  ///
+  /// ```rust
+  /// use regex::bytes::RegexBuilder;
+  ///
+  /// use wakuchin::pattern::Matchers;
+  ///
+  /// let regex = RegexBuilder::new(r"^wkcn").case_insensitive(true).build()?;
+  /// let matchers = Matchers::Bytes(vec![regex]);
+  ///
+  /// assert_eq!(matchers.pattern(0), Some("^wkcn"));
+  /// #
+  /// # Ok::<(), Box<dyn std::error::Error>>(())
+  /// ```
+  Bytes(Vec<BytesRegex>),
+  /// Literal strings, hit anywhere in a try like regexes without
+  /// metacharacters, but matched much faster with an Aho-Corasick automaton
+  Literals(Vec<String>),
+  /// A single [`Matcher`] of its own, slower than the others as tries are
+  /// matched as `&str`
+  Custom(Arc<dyn Matcher>),
+  /// Tries within a Levenshtein distance of `max_distance` of `pattern`, a
+  /// whole string, see [`Hit::distance`](crate::result::Hit::distance)
+  Fuzzy {
+    pattern: String,
+    max_distance: usize,
+  },
+  /// The symbol each position of a try must be, `None` for free positions
+  /// and those past the end, checked with a mask instead of a regex
+  ///
+  /// ```rust
+  /// use wakuchin::pattern::Matchers;
+  ///
+  /// // like "^WKCN" or "^わくちん"
+  /// let prefix = Matchers::Positional("WKCN".chars().map(Some).collect());
+  /// // like "^.W..N"
+  /// let sparse = Matchers::Positional(vec![None, Some('W'), None, None, Some('N')]);
+  /// ```
+  Positional(Vec<Option<char>>),
+}
+
+impl Matchers {
+  /// Detect hits with `matcher`.
+  #[inline]
+  pub fn custom(matcher: impl Matcher + 'static) -> Self {
+    Self::Custom(Arc::new(matcher))
+  }
+
+  /// Detect hits within `max_distance` edits of `pattern`.
+  #[inline]
+  pub fn fuzzy(pattern: impl Into<String>, max_distance: usize) -> Self {
+    Self::Fuzzy {
+      pattern: pattern.into(),
+      max_distance,
+    }
+  }
+
+  /// Return the number of patterns.
+  #[inline]
+  pub fn len(&self) -> usize {
+    match self {
+      Self::Regex(regexes) => regexes.len(),
+      Self::Bytes(regexes) => regexes.len(),
+      Self::Literals(literals) => literals.len(),
+      Self::Custom(_) | Self::Fuzzy { .. } | Self::Positional(_) => 1,
+    }
+  }
+
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Return the source of the pattern at `index`, custom matchers having
+  /// none.
+  pub fn pattern(&self, index: usize) -> Option<&str> {
+    match self {
+      Self::Regex(regexes) => regexes.get(index).map(Regex::as_str),
+      Self::Bytes(regexes) => regexes.get(index).map(BytesRegex::as_str),
+      Self::Literals(literals) => literals.get(index).map(String::as_str),
+      Self::Custom(_) | Self::Positional(_) => None,
+      Self::Fuzzy { pattern, .. } => (index == 0).then_some(pattern.as_str()),
+    }
+  }
+
+  /// Return the sources of all patterns, in order.
+  pub fn patterns(&self) -> impl Iterator<Item = &str> + '_ {
+    (0..self.len()).filter_map(|index| self.pattern(index))
+  }
+
+  /// Return the patterns at `indices`, in their order. Custom, fuzzy and
+  /// positional matchers of a single pattern are returned as is.
+  pub(crate) fn select(&self, indices: &[usize]) -> Self {
+    match self {
+      Self::Regex(regexes) => {
+        Self::Regex(indices.iter().map(|i| regexes[*i].clone()).collect())
+      }
+      Self::Bytes(regexes) => {
+        Self::Bytes(indices.iter().map(|i| regexes[*i].clone()).collect())
+      }
+      Self::Literals(literals) => {
+        Self::Literals(indices.iter().map(|i| literals[*i].clone()).collect())
+      }
+      _ => self.clone(),
+    }
+  }
+
+  /// Check that every pattern can match strings of `symbols`, so patterns
+  /// which would never hit fail before the research, see
+  /// [`Matcher::validate_alphabet`].
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use regex::Regex;
+  ///
+  /// use wakuchin::pattern::Matchers;
+  /// use wakuchin::symbol::SymbolSet;
+  ///
+  /// let symbols = SymbolSet::default();
+  /// let matchers = |regex| Matchers::Regex(vec![Regex::new(regex).unwrap()]);
+  ///
+  /// assert!(matchers("^(わくちん|WKNC)+$").validate_alphabet(&symbols).is_ok());
+  /// assert!(matchers("^(X|WK)").validate_alphabet(&symbols).is_ok());
+  /// assert!(matchers("^wkcn").validate_alphabet(&symbols).is_err());
+  /// assert!(matchers("(?i)^wkcn").validate_alphabet(&symbols).is_ok());
+  /// ```
+  pub fn validate_alphabet(
+    &self,
+    symbols: &SymbolSet,
+  ) -> Result<(), WakuchinError> {
+    let literal_alphabet = |pattern: &str| {
+      pattern_to_internal(pattern, symbols)
+        .chars()
+        .find_map(|c| not_a_symbol(c, symbols))
+        .map_or(Ok(()), |reason| Err(impossible(pattern, reason)))
+    };
+
+    match self {
+      Self::Regex(regexes) => regexes
+        .iter()
+        .try_for_each(|regex| regex.validate_alphabet(symbols)),
+      // their options may change what they match, e.g. their case
+      Self::Bytes(_) => Ok(()),
+      Self::Literals(literals) => literals
+        .iter()
+        .try_for_each(|literal| literal_alphabet(literal)),
+      Self::Custom(matcher) => matcher.validate_alphabet(symbols),
+      Self::Fuzzy {
+        pattern,
+        max_distance,
+      } => {
+        // each char which is not a symbol takes an edit
+        let unknown = pattern_to_internal(pattern, symbols)
+          .chars()
+          .filter(|c| !symbols.internal().contains(c))
+          .count();
+
+        if unknown > *max_distance {
+          return Err(impossible(
+            pattern,
+            format!(
+              "{unknown} chars are not symbols, more than {max_distance} edits"
+            ),
+          ));
+        }
+
+        Ok(())
+      }
+      Self::Positional(positions) => {
+        literal_alphabet(&positions.iter().flatten().collect::<String>())
+      }
+    }
+  }
+}
+
+impl fmt::Debug for Matchers {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Regex(regexes) => f.debug_tuple("Regex").field(regexes).finish(),
+      Self::Bytes(regexes) => f.debug_tuple("Bytes").field(regexes).finish(),
+      Self::Literals(literals) => {
+        f.debug_tuple("Literals").field(literals).finish()
+      }
+      Self::Custom(_) => f.debug_tuple("Custom").finish_non_exhaustive(),
+      Self::Fuzzy {
+        pattern,
+        max_distance,
+      } => f
+        .debug_struct("Fuzzy")
+        .field("pattern", pattern)
+        .field("max_distance", max_distance)
+        .finish(),
+      Self::Positional(positions) => {
+        f.debug_tuple("Positional").field(positions).finish()
+      }
+    }
+  }
+}
+
+/// Patterns of a research, a single [`Regex`] or [`regex::bytes::Regex`],
+/// several of them or [`Matchers`].
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+///
+/// use wakuchin::pattern::Patterns;
+///
+/// let single = Regex::new(r"^WKCN")?;
+/// let several = [Regex::new(r"^WKCN")?, Regex::new(r"^NCWK")?];
+///
+/// assert_eq!(single.to_matchers().len(), 1);
+/// assert_eq!(several.to_matchers().len(), 2);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait Patterns {
+  fn to_matchers(&self) -> Cow<'_, Matchers>;
+}
+
+impl Patterns for Matchers {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    Cow::Borrowed(self)
+  }
+}
+
+impl Patterns for Regex {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    Cow::Owned(Matchers::Regex(vec![self.clone()]))
+  }
+}
+
+impl Patterns for [Regex] {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    Cow::Owned(Matchers::Regex(self.to_vec()))
+  }
+}
+
+impl<const N: usize> Patterns for [Regex; N] {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    self.as_slice().to_matchers()
+  }
+}
+
+impl Patterns for Vec<Regex> {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    self.as_slice().to_matchers()
+  }
+}
+
+impl Patterns for BytesRegex {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    Cow::Owned(Matchers::Bytes(vec![self.clone()]))
+  }
+}
+
+impl Patterns for [BytesRegex] {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    Cow::Owned(Matchers::Bytes(self.to_vec()))
+  }
+}
+
+impl<const N: usize> Patterns for [BytesRegex; N] {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    self.as_slice().to_matchers()
+  }
+}
+
+impl Patterns for Vec<BytesRegex> {
+  #[inline]
+  fn to_matchers(&self) -> Cow<'_, Matchers> {
+    self.as_slice().to_matchers()
+  }
+}
+
+/// Matcher of the tries within a Levenshtein distance of a string.
+#[derive(Clone, Debug)]
+pub(crate) struct FuzzyMatcher {
+  pattern: String,
+  max_distance: usize,
+}
+
+impl FuzzyMatcher {
+  /// Return the distance of `chars` to the pattern, if it is close enough.
+  #[inline]
+  pub fn distance(&self, chars: &[u8]) -> Option<usize> {
+    edit_distance(
+      &self.pattern,
+      &String::from_utf8_lossy(chars),
+      self.max_distance,
+    )
+  }
+}
+
+impl Matcher for FuzzyMatcher {
+  #[inline]
+  fn is_match(&self, s: &str) -> bool {
+    edit_distance(&self.pattern, s, self.max_distance).is_some()
+  }
+}
+
+/// Matcher of the tries with given symbols at given positions.
+#[derive(Clone, Debug)]
+pub(crate) struct PositionalMatcher {
+  /// Internal symbols of the constrained positions
+  positions: Vec<Option<char>>,
+  /// Mask and expected bytes of each position, if tries are ASCII
+  mask: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl PositionalMatcher {
+  fn new(positions: &[Option<char>], symbols: &SymbolSet) -> Self {
+    let positions = positions
+      .iter()
+      .map(|position| {
+        position.map(|c| {
+          pattern_to_internal(c.encode_utf8(&mut [0; 4]), symbols)
+            .chars()
+            .next()
+            .unwrap_or(c)
+        })
+      })
+      .collect::<Vec<_>>();
+
+    // a position is a byte of ASCII tries
+    let mask = (symbols.internal().iter().all(char::is_ascii)
+      && positions.iter().flatten().all(char::is_ascii))
+    .then(|| {
+      positions
+        .iter()
+        .map(|position| position.map_or((0, 0), |c| (u8::MAX, c as u8)))
+        .unzip()
+    });
+
+    Self { positions, mask }
+  }
+
+  #[inline]
+  pub fn is_match_bytes(&self, chars: &[u8]) -> bool {
+    match &self.mask {
+      Some((mask, expected)) => {
+        chars.len() >= mask.len()
+          && chars
+            .iter()
+            .zip(mask)
+            .zip(expected)
+            .all(|((c, mask), expected)| c & mask == *expected)
+      }
+      None => self.is_match(&String::from_utf8_lossy(chars)),
+    }
+  }
+}
+
+impl Matcher for PositionalMatcher {
+  fn is_match(&self, s: &str) -> bool {
+    let mut chars = s.chars();
+
+    self
+      .positions
+      .iter()
+      .all(|position| chars.next().is_some_and(|c| position.unwrap_or(c) == c))
+  }
+}
+
+/// Patterns compiled to match bytes, so strings are matched as generated
+/// without going through `String`.
+pub(crate) enum PatternSet {
+  /// A single pattern, faster than a set of one
+  Single(BytesRegex),
+  Set(RegexSet),
+  /// Compiled regexes which cannot be put into a set, tried one by one
+  Compiled(Vec<BytesRegex>),
+  Literals(AhoCorasick),
+  Custom(Arc<dyn Matcher>),
+  Fuzzy(FuzzyMatcher),
+  Positional(PositionalMatcher),
+  /// All patterns compiled into a single DFA reporting every pattern
+  #[cfg(feature = "dfa")]
+  Dfa(Box<regex_automata::dfa::dense::DFA<Vec<u32>>>),
+}
+
+impl PatternSet {
+  /// Compile the patterns of `matchers` from their source, so use inline
+  /// flags like `(?i)` instead of `RegexBuilder` options. Those of
+  /// [`Matchers::Bytes`] are matched as compiled instead.
+  ///
+  /// External symbols of `symbols` in the patterns are matched as their
+  /// internal symbols, see [`pattern_to_internal`]. Regexes are matched by
+  /// `engine`.
+  pub fn new(
+    matchers: &Matchers,
+    symbols: &SymbolSet,
+    // only the regex engine without the "dfa" feature
+    #[cfg_attr(not(feature = "dfa"), allow(unused_variables))]
+    engine: RegexEngine,
+  ) -> Result<Self, WakuchinError> {
+    if matchers.is_empty() {
+      return Err(WakuchinError::NoPatterns);
+    }
+
+    let internal = |pattern| pattern_to_internal(pattern, symbols);
+
+    match matchers {
+      #[cfg(feature = "dfa")]
+      Matchers::Regex(regexes) if engine == RegexEngine::Dfa => {
+        use regex_automata::dfa::dense;
+        use regex_automata::MatchKind;
+
+        let patterns = regexes
+          .iter()
+          .map(|regex| internal(regex.as_str()))
+          .collect::<Vec<_>>();
+
+        Ok(Self::Dfa(Box::new(
+          dense::Builder::new()
+            .configure(dense::Config::new().match_kind(MatchKind::All))
+            .build_many(&patterns)
+            .map_err(anyhow::Error::from)?,
+        )))
+      }
+      Matchers::Regex(regexes) => match regexes.as_slice() {
+        [regex] => Ok(Self::Single(
+          BytesRegex::new(&internal(regex.as_str()))
+            .map_err(anyhow::Error::from)?,
+        )),
+        regexes => Ok(Self::Set(
+          RegexSet::new(regexes.iter().map(|regex| internal(regex.as_str())))
+            .map_err(anyhow::Error::from)?,
+        )),
+      },
+      Matchers::Bytes(regexes) => match regexes.as_slice() {
+        [regex] => Ok(Self::Single(regex.clone())),
+        regexes => Ok(Self::Compiled(regexes.to_vec())),
+      },
+      Matchers::Literals(literals) => Ok(Self::Literals(
+        AhoCorasick::new(
+          literals
+            .iter()
+            .map(|literal| internal(literal).into_owned()),
+        )
+        .map_err(anyhow::Error::from)?,
+      )),
+      Matchers::Custom(matcher) => Ok(Self::Custom(matcher.clone())),
+      Matchers::Fuzzy {
+        pattern,
+        max_distance,
+      } => Ok(Self::Fuzzy(FuzzyMatcher {
+        pattern: internal(pattern).into_owned(),
+        max_distance: *max_distance,
+      })),
+      Matchers::Positional(positions) => {
+        Ok(Self::Positional(PositionalMatcher::new(positions, symbols)))
+      }
+    }
+  }
+
+  /// Return the edit distance of `chars` to the pattern if it is fuzzy.
+  #[inline]
+  pub fn distance(&self, chars: &[u8]) -> Option<usize> {
+    match self {
+      Self::Fuzzy(matcher) => matcher.distance(chars),
+      _ => None,
+    }
+  }
+
+  /// Return the indices of the patterns matching `chars`, in order.
+  #[inline]
+  pub fn matches(&self, chars: &[u8]) -> Matches {
+    match self {
+      Self::Single(regex) => {
+        Matches::Single(regex.is_match(chars).then_some(0).into_iter())
+      }
+      Self::Set(set) => Matches::Set(set.matches(chars).into_iter()),
+      Self::Compiled(regexes) => Matches::Listed(
+        regexes
+          .iter()
+          .enumerate()
+          .filter(|(_, regex)| regex.is_match(chars))
+          .map(|(index, _)| index)
+          .collect::<Vec<_>>()
+          .into_iter(),
+      ),
+      Self::Literals(automaton) => {
+        // most tries hit nothing, so only collect the patterns of hits
+        if !automaton.is_match(chars) {
+          return Matches::Listed(Vec::new().into_iter());
+        }
+
+        let mut patterns = automaton
+          .find_overlapping_iter(chars)
+          .map(|found| found.pattern().as_usize())
+          .collect::<Vec<_>>();
+
+        patterns.sort_unstable();
+        patterns.dedup();
+
+        Matches::Listed(patterns.into_iter())
+      }
+      Self::Custom(matcher) => Matches::Single(
+        matcher
+          .is_match(&String::from_utf8_lossy(chars))
+          .then_some(0)
+          .into_iter(),
+      ),
+      Self::Fuzzy(matcher) => {
+        Matches::Single(matcher.distance(chars).map(|_| 0).into_iter())
+      }
+      Self::Positional(matcher) => {
+        Matches::Single(matcher.is_match_bytes(chars).then_some(0).into_iter())
+      }
+      #[cfg(feature = "dfa")]
+      Self::Dfa(dfa) => {
+        use regex_automata::dfa::{Automaton, OverlappingState};
+        use regex_automata::Input;
+
+        let input = Input::new(chars);
+        let mut state = OverlappingState::start();
+        let mut patterns = Vec::new();
+
+        loop {
+          // searches only fail with quit bytes, which are never configured
+          dfa
+            .try_search_overlapping_fwd(&input, &mut state)
+            .expect("DFA search failed");
+
+          match state.get_match() {
+            Some(found) => patterns.push(found.pattern().as_usize()),
+            None => break,
+          }
+        }
+
+        patterns.sort_unstable();
+        patterns.dedup();
+
+        Matches::Listed(patterns.into_iter())
+      }
+    }
+  }
+}
+
+/// Locator of the matches of each pattern, for the spans, capture groups and
+/// match counts of hits.
+pub(crate) enum Locator {
+  /// A regex of each pattern, literals being escaped
+  Regex(Vec<BytesRegex>),
+  Custom(Arc<dyn Matcher>),
+}
+
+impl Locator {
+  /// Compile the patterns of `matchers` like [`PatternSet::new`].
+  pub fn new(
+    matchers: &Matchers,
+    symbols: &SymbolSet,
+  ) -> Result<Self, WakuchinError> {
+    let internal = |pattern| pattern_to_internal(pattern, symbols);
+    let regexes = match matchers {
+      Matchers::Regex(regexes) => regexes
+        .iter()
+        .map(|regex| BytesRegex::new(&internal(regex.as_str())))
+        .collect::<Result<_, _>>(),
+      Matchers::Bytes(regexes) => Ok(regexes.clone()),
+      Matchers::Literals(literals) => literals
+        .iter()
+        .map(|literal| BytesRegex::new(&regex::escape(&internal(literal))))
+        .collect::<Result<_, _>>(),
+      Matchers::Custom(matcher) => return Ok(Self::Custom(matcher.clone())),
+      // matches the whole try
+      Matchers::Fuzzy {
+        pattern,
+        max_distance,
+      } => {
+        return Ok(Self::Custom(Arc::new(FuzzyMatcher {
+          pattern: internal(pattern).into_owned(),
+          max_distance: *max_distance,
+        })))
+      }
+      Matchers::Positional(positions) => {
+        return Ok(Self::Custom(Arc::new(PositionalMatcher::new(
+          positions, symbols,
+        ))))
+      }
+    };
+
+    Ok(Self::Regex(regexes.map_err(anyhow::Error::from)?))
+  }
+
+  /// Return the byte range of the first match of `pattern` in `chars` and
+  /// its named capture groups which participated in the match.
+  pub fn locate(
+    &self,
+    pattern: usize,
+    chars: &[u8],
+  ) -> (Option<Range<usize>>, BTreeMap<String, String>) {
+    match self {
+      Self::Regex(regexes) => {
+        let regex = &regexes[pattern];
+        let Some(captures) = regex.captures(chars) else {
+          return (None, BTreeMap::new());
+        };
+        let groups = regex
+          .capture_names()
+          .enumerate()
+          .filter_map(|(index, name)| {
+            let group = captures.get(index)?;
+
+            Some((
+              name?.to_owned(),
+              String::from_utf8_lossy(group.as_bytes()).into_owned(),
+            ))
+          })
+          .collect();
+
+        (captures.get(0).map(|found| found.range()), groups)
+      }
+      Self::Custom(matcher) => (
+        matcher.find(&String::from_utf8_lossy(chars)),
+        BTreeMap::new(),
+      ),
+    }
+  }
+
+  /// Return the number of non-overlapping matches of `pattern` in `chars`.
+  pub fn count(&self, pattern: usize, chars: &[u8]) -> usize {
+    match self {
+      Self::Regex(regexes) => regexes[pattern].find_iter(chars).count(),
+      Self::Custom(matcher) => matcher.count(&String::from_utf8_lossy(chars)),
+    }
+  }
+}
+
+/// Iterator over the indices of the patterns matching a string.
+pub(crate) enum Matches {
+  Single(std::option::IntoIter<usize>),
+  Set(SetMatchesIntoIter),
+  Listed(std::vec::IntoIter<usize>),
+}
+
+impl Iterator for Matches {
+  type Item = usize;
+
+  #[inline]
+  fn next(&mut self) -> Option<usize> {
+    match self {
+      Self::Single(matches) => matches.next(),
+      Self::Set(matches) => matches.next(),
+      Self::Listed(matches) => matches.next(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use regex::bytes::{Regex as BytesRegex, RegexBuilder};
+  use regex::Regex;
+
+  use crate::error::WakuchinError;
+  use crate::symbol::SymbolSet;
+
+  use super::{Locator, Matchers, PatternSet, RegexEngine};
+
+  #[test]
+  fn test_matches() -> Result<(), WakuchinError> {
+    let symbols = SymbolSet::default();
+    let single = PatternSet::new(
+      &Matchers::Regex(vec![Regex::new(r"^WK").unwrap()]),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+    let set = PatternSet::new(
+      &Matchers::Regex(vec![
+        Regex::new(r"^WK").unwrap(),
+        Regex::new(r"CN$").unwrap(),
+        Regex::new(r"^NC").unwrap(),
+      ]),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+    let literals = PatternSet::new(
+      &Matchers::Literals(vec!["KC".into(), "WK".into(), "WKC".into()]),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+
+    assert_eq!(single.matches(b"WKCN").collect::<Vec<_>>(), [0]);
+    assert_eq!(single.matches(b"NCWK").count(), 0);
+    assert_eq!(set.matches(b"WKCN").collect::<Vec<_>>(), [0, 1]);
+    assert_eq!(set.matches(b"NCWK").collect::<Vec<_>>(), [2]);
+    assert_eq!(literals.matches(b"WKCN").collect::<Vec<_>>(), [0, 1, 2]);
+    assert_eq!(literals.matches(b"NCWKWK").collect::<Vec<_>>(), [1]);
+    assert_eq!(literals.matches(b"CNKW").count(), 0);
+
+    let hiragana = PatternSet::new(
+      &Matchers::Regex(vec![Regex::new(r"^わく").unwrap()]),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+    let hiragana_literals = PatternSet::new(
+      &Matchers::Literals(vec!["ちん".into(), "WK".into()]),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+
+    assert_eq!(hiragana.matches(b"WKCN").collect::<Vec<_>>(), [0]);
+    assert_eq!(
+      hiragana_literals.matches(b"WKCN").collect::<Vec<_>>(),
+      [0, 1]
+    );
+
+    let custom = PatternSet::new(
+      &Matchers::custom(|s: &str| s.len() == 4),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+
+    assert_eq!(custom.matches(b"NCWK").collect::<Vec<_>>(), [0]);
+    assert_eq!(custom.matches(b"NCWKNC").count(), 0);
+
+    let fuzzy = PatternSet::new(
+      &Matchers::fuzzy("WKCN", 1),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+
+    assert_eq!(fuzzy.matches(b"WKCN").collect::<Vec<_>>(), [0]);
+    assert_eq!(fuzzy.distance(b"WKCN"), Some(0));
+    assert_eq!(fuzzy.distance(b"WKCW"), Some(1));
+    assert_eq!(fuzzy.distance(b"WKC"), Some(1));
+    assert_eq!(fuzzy.matches(b"WKNC").count(), 0);
+    assert_eq!(custom.distance(b"WKCN"), None);
+
+    let positional = PatternSet::new(
+      &Matchers::Positional(vec![Some('わ'), None, Some('N')]),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+
+    let bytes = PatternSet::new(
+      &Matchers::Bytes(vec![
+        RegexBuilder::new(r"^wk")
+          .case_insensitive(true)
+          .build()
+          .unwrap(),
+        BytesRegex::new(r"CN$").unwrap(),
+      ]),
+      &symbols,
+      RegexEngine::Regex,
+    )?;
+
+    assert_eq!(bytes.matches(b"WKCN").collect::<Vec<_>>(), [0, 1]);
+    assert_eq!(bytes.matches(b"NCWK").count(), 0);
+
+    assert_eq!(positional.matches(b"WKNC").collect::<Vec<_>>(), [0]);
+    assert_eq!(positional.matches(b"WCNK").collect::<Vec<_>>(), [0]);
+    assert_eq!(positional.matches(b"KWNC").count(), 0);
+    assert_eq!(positional.matches(b"WK").count(), 0);
+
+    let hiragana = SymbolSet::new("わくちん", "WKCN")?;
+    let positional = PatternSet::new(
+      &Matchers::Positional(vec![Some('W'), None, Some('ん')]),
+      &hiragana,
+      RegexEngine::Regex,
+    )?;
+
+    assert_eq!(
+      positional
+        .matches("わくんち".as_bytes())
+        .collect::<Vec<_>>(),
+      [0]
+    );
+    assert_eq!(positional.matches("わくちん".as_bytes()).count(), 0);
+    assert!(matches!(
+      PatternSet::new(
+        &Matchers::Literals(Vec::new()),
+        &symbols,
+        RegexEngine::Regex
+      ),
+      Err(WakuchinError::NoPatterns)
+    ));
+
+    Ok(())
+  }
+
+  #[cfg(feature = "dfa")]
+  #[test]
+  fn test_dfa() -> Result<(), WakuchinError> {
+    let symbols = SymbolSet::default();
+    let dfa = PatternSet::new(
+      &Matchers::Regex(vec![
+        Regex::new(r"^WK").unwrap(),
+        Regex::new(r"CN$").unwrap(),
+        Regex::new(r"^NC").unwrap(),
+        Regex::new(r"(?:WKCN){2}").unwrap(),
+      ]),
+      &symbols,
+      RegexEngine::Dfa,
+    )?;
+
+    assert_eq!(dfa.matches(b"WKCN").collect::<Vec<_>>(), [0, 1]);
+    assert_eq!(dfa.matches(b"NCWK").collect::<Vec<_>>(), [2]);
+    assert_eq!(dfa.matches(b"KNWKCNWKCNCW").collect::<Vec<_>>(), [3]);
+    assert_eq!(dfa.matches(b"KWNC").count(), 0);
+    assert!(PatternSet::new(
+      &Matchers::Regex(vec![Regex::new(r"\bWK").unwrap()]),
+      &symbols,
+      RegexEngine::Dfa,
+    )
+    .is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_validate_alphabet() {
+    let symbols = SymbolSet::default();
+    let reason = |matchers: Matchers| match matchers.validate_alphabet(&symbols)
+    {
+      Err(WakuchinError::ImpossiblePattern { reason, .. }) => Some(reason),
+      _ => None,
+    };
+    let regex = |regex| Matchers::Regex(vec![Regex::new(regex).unwrap()]);
+
+    assert_eq!(reason(regex(r"^[WK]{2}(CN|NC)?.*$")), None);
+    assert_eq!(reason(regex(r"^WKCNx?$")), None);
+    assert_eq!(reason(regex(r"\bWK")), None);
+    assert_eq!(
+      reason(regex(r"^WK(XW|YK)")),
+      Some(r#"'X' is not a symbol of "WKCN" or "わくちん""#.into())
+    );
+    assert_eq!(
+      reason(regex(r"[a-z]+")),
+      Some("[a-z] has none of the symbols".into())
+    );
+    assert_eq!(reason(regex(r"[^WK]{2}")), None);
+    assert!(reason(regex(r"[^WKCN]")).is_some());
+    assert!(
+      reason(Matchers::Literals(vec!["WK".into(), "ぬ".into()])).is_some()
+    );
+    assert_eq!(reason(Matchers::Literals(vec!["わく".into()])), None);
+    assert_eq!(reason(Matchers::fuzzy("WKCX", 1)), None);
+    assert!(reason(Matchers::fuzzy("WKXX", 1)).is_some());
+    assert!(reason(Matchers::Positional(vec![None, Some('x')])).is_some());
+    assert_eq!(reason(Matchers::custom(|_: &str| false)), None);
+  }
+
+  #[test]
+  fn test_locate() -> Result<(), WakuchinError> {
+    let symbols = SymbolSet::default();
+    let regexes = Locator::new(
+      &Matchers::Regex(vec![
+        Regex::new(r"^WK").unwrap(),
+        Regex::new(r"(?P<head>C|N)(?P<tail>N)?(?P<rest>W)?$").unwrap(),
+      ]),
+      &symbols,
+    )?;
+    let literals = Locator::new(
+      &Matchers::Literals(vec!["KC".into(), "K+".into()]),
+      &symbols,
+    )?;
+    let custom =
+      Locator::new(&Matchers::custom(|s: &str| s.len() == 4), &symbols)?;
+
+    assert_eq!(regexes.locate(0, b"WKCN"), (Some(0..2), Default::default()));
+    assert_eq!(
+      regexes.locate(1, b"WKCN"),
+      (
+        Some(2..4),
+        [("head".into(), "C".into()), ("tail".into(), "N".into())].into()
+      )
+    );
+    assert_eq!(regexes.locate(0, b"NCWK"), (None, Default::default()));
+    assert_eq!(literals.locate(0, b"WKCN").0, Some(1..3));
+    assert_eq!(literals.locate(1, b"WK+N").0, Some(1..3));
+    assert_eq!(custom.locate(0, b"NCWK").0, Some(0..4));
+    assert_eq!(regexes.count(0, b"WKWKWK"), 1);
+    assert_eq!(literals.count(0, b"WKCNKCKC"), 3);
+    assert_eq!(literals.count(1, b"WKCN"), 0);
+    assert_eq!(custom.count(0, b"NCWK"), 1);
+
+    Ok(())
+  }
+}
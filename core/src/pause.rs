@@ -0,0 +1,293 @@
+//! Pausing and resuming researches
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::sync::{AtomicBool, Condvar, Mutex, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+  Running,
+  /// Pause is requested, but not reported to the handler yet
+  Pausing,
+  Paused,
+  /// The research is cancelled, so it cannot be paused anymore
+  Cancelled,
+}
+
+/// Shared state to pause workers and report it to the handler.
+pub(crate) struct PauseState {
+  is_paused: AtomicBool,
+  state: Mutex<State>,
+  changed: Condvar,
+}
+
+impl Default for PauseState {
+  fn default() -> Self {
+    Self {
+      is_paused: AtomicBool::new(false),
+      state: Mutex::new(State::Running),
+      changed: Condvar::new(),
+    }
+  }
+}
+
+impl PauseState {
+  /// Cheap check to call on every iteration.
+  #[inline]
+  pub fn is_paused(&self) -> bool {
+    self.is_paused.load(Ordering::Relaxed)
+  }
+
+  /// Pause if running, returning whether this call paused it.
+  pub fn pause(&self) -> bool {
+    let mut state = self.state.lock().unwrap();
+
+    if *state != State::Running {
+      return false;
+    }
+
+    *state = State::Pausing;
+    self.is_paused.store(true, Ordering::SeqCst);
+
+    true
+  }
+
+  /// Mark the pause as reported to the handler.
+  pub fn report(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    if *state == State::Pausing {
+      *state = State::Paused;
+      self.changed.notify_all();
+    }
+  }
+
+  /// Wait until the pause is reported, or `timeout` elapsed.
+  pub fn wait_reported(&self, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    let mut state = self.state.lock().unwrap();
+
+    while *state == State::Pausing {
+      let Some(timeout) = deadline.checked_duration_since(Instant::now())
+      else {
+        return;
+      };
+
+      state = self.changed.wait_timeout(state, timeout).unwrap().0;
+    }
+  }
+
+  pub fn resume(&self) {
+    let mut state = self.state.lock().unwrap();
+
+    if *state != State::Cancelled {
+      *state = State::Running;
+    }
+
+    self.is_paused.store(false, Ordering::SeqCst);
+    self.changed.notify_all();
+  }
+
+  /// Wake up paused threads so they can stop, ignoring later pauses which
+  /// would block them forever.
+  pub fn cancel(&self) {
+    *self.state.lock().unwrap() = State::Cancelled;
+    self.is_paused.store(false, Ordering::SeqCst);
+    self.changed.notify_all();
+  }
+
+  /// Block the calling thread while paused.
+  #[inline]
+  pub fn wait_while_paused(&self) {
+    if !self.is_paused() {
+      return;
+    }
+
+    let mut state = self.state.lock().unwrap();
+
+    while matches!(*state, State::Pausing | State::Paused) {
+      state = self.changed.wait(state).unwrap();
+    }
+  }
+
+  /// Pause, wait for the handler to report it, then stop the process the
+  /// way SIGTSTP would, resuming once the process is continued.
+  ///
+  /// Researches paused before are left paused, so whoever paused them still
+  /// decides when they are resumed.
+  #[cfg(unix)]
+  pub fn suspend(&self) {
+    use signal_hook::consts::SIGTSTP;
+    use signal_hook::low_level::emulate_default_handler;
+
+    let paused = self.pause();
+
+    self.wait_reported(Duration::from_secs(1));
+
+    // returns after SIGCONT
+    let _ = emulate_default_handler(SIGTSTP);
+
+    if paused {
+      self.resume();
+    }
+  }
+}
+
+/// Handle to pause and resume a research from another thread, see
+/// [`ResearchBuilder::pause_handle`](crate::builder::ResearchBuilder::pause_handle).
+///
+/// Workers block until resumed, and the progress handler is notified with
+/// [`on_pause`](crate::handlers::ProgressHandler::on_pause) and
+/// [`on_resume`](crate::handlers::ProgressHandler::on_resume).
+#[derive(Clone, Default)]
+pub struct PauseHandle {
+  state: Arc<PauseState>,
+}
+
+impl PauseHandle {
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[inline]
+  pub fn pause(&self) {
+    self.state.pause();
+  }
+
+  #[inline]
+  pub fn resume(&self) {
+    self.state.resume();
+  }
+
+  #[inline]
+  pub fn is_paused(&self) -> bool {
+    self.state.is_paused()
+  }
+
+  #[inline]
+  pub(crate) fn into_state(self) -> Arc<PauseState> {
+    self.state
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+  use std::thread;
+  use std::time::Duration;
+
+  use super::PauseState;
+
+  #[test]
+  fn test_pause_state() {
+    let pause = Arc::new(PauseState::default());
+
+    pause.wait_while_paused();
+
+    assert!(pause.pause());
+    assert!(pause.is_paused());
+    // paused already, so resuming is left to the first pause
+    assert!(!pause.pause());
+
+    let worker = thread::spawn({
+      let pause = pause.clone();
+
+      move || {
+        pause.report();
+        pause.wait_while_paused();
+
+        assert!(!pause.is_paused());
+      }
+    });
+
+    pause.wait_reported(Duration::from_secs(10));
+    pause.resume();
+
+    worker.join().unwrap();
+  }
+}
+
+#[cfg(all(test, wakuchin_loom))]
+mod loom_test {
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  use loom::thread;
+
+  use crate::sync::{AtomicBool, Ordering};
+
+  use super::PauseState;
+
+  #[test]
+  fn test_loom_pause_reported() {
+    loom::model(|| {
+      let pause = Arc::new(PauseState::default());
+
+      pause.pause();
+
+      let worker = thread::spawn({
+        let pause = pause.clone();
+
+        move || pause.wait_while_paused()
+      });
+
+      let render = thread::spawn({
+        let pause = pause.clone();
+
+        move || {
+          if pause.is_paused() {
+            pause.report();
+            pause.wait_while_paused();
+          }
+        }
+      });
+
+      pause.wait_reported(Duration::from_secs(1));
+      pause.resume();
+
+      worker.join().unwrap();
+      render.join().unwrap();
+    });
+  }
+
+  #[test]
+  fn test_loom_cancel_while_paused() {
+    loom::model(|| {
+      let pause = Arc::new(PauseState::default());
+      let is_stopped = Arc::new(AtomicBool::new(false));
+
+      // same ordering as the loop of the workers
+      let worker = thread::spawn({
+        let pause = pause.clone();
+        let is_stopped = is_stopped.clone();
+
+        move || {
+          for _ in 0..2 {
+            if is_stopped.load(Ordering::Relaxed) {
+              return;
+            }
+
+            pause.wait_while_paused();
+          }
+        }
+      });
+
+      // e.g. --only-when-idle pausing around the cancellation
+      let pauser = thread::spawn({
+        let pause = pause.clone();
+
+        move || pause.pause()
+      });
+
+      is_stopped.store(true, Ordering::SeqCst);
+      pause.cancel();
+
+      pauser.join().unwrap();
+      worker.join().unwrap();
+
+      assert!(!pause.is_paused());
+    });
+  }
+}
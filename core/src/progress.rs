@@ -1,7 +1,9 @@
-use std::borrow::Cow;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
+use crate::candidate::Candidate;
+
 /// Kind of progress data.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ProgressKind {
@@ -13,6 +15,10 @@ pub enum ProgressKind {
 
   /// Worker finished all tasks.
   Done(DoneDetail),
+
+  /// Worker made no progress for longer than the stall timeout, see
+  /// [`ResearchBuilder::stall_timeout`](crate::builder::ResearchBuilder::stall_timeout).
+  Stalled(StalledDetail),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -28,7 +34,7 @@ pub struct ProcessingDetail {
   pub id: usize,
 
   /// Current processing wakuchin chars.
-  pub wakuchin: Cow<'static, str>,
+  pub wakuchin: Candidate,
 
   /// Current processing index.
   pub current: usize,
@@ -40,7 +46,7 @@ pub struct ProcessingDetail {
 impl ProcessingDetail {
   pub(crate) fn new(
     id: usize,
-    wakuchin: impl Into<Cow<'static, str>>,
+    wakuchin: impl Into<Candidate>,
     current: usize,
     total: usize,
   ) -> Self {
@@ -63,6 +69,191 @@ pub struct DoneDetail {
   pub total: usize,
 }
 
+/// Detail of stalled progress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StalledDetail {
+  /// Worker id. 1-indexed.
+  pub id: usize,
+
+  /// Index the worker is stuck at.
+  pub current: usize,
+
+  /// Time since the worker last made progress.
+  pub stalled_for: Duration,
+}
+
 /// Progress data you will use in `progress_handler`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Progress(pub ProgressKind);
+
+impl Progress {
+  /// Number of tries processed by the worker.
+  #[inline]
+  pub(crate) fn current(&self) -> usize {
+    match self {
+      Progress(ProgressKind::Idle(_)) => 0,
+      Progress(ProgressKind::Processing(ProcessingDetail {
+        current, ..
+      })) => *current,
+      Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+      Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+        *current
+      }
+    }
+  }
+}
+
+/// Share of the time elapsed since the start an adaptive interval grows to.
+const ADAPTIVE_DIVISOR: u32 = 20;
+
+/// How often the progress is rendered, see
+/// [`ResearchBuilder::progress_interval_policy`](crate::builder::ResearchBuilder::progress_interval_policy).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressIntervalPolicy {
+  /// Render at the same interval from start to end.
+  Fixed(Duration),
+
+  /// Render every `min` at the start, then less often as the research goes
+  /// on, every twentieth of the time elapsed, up to every `max`.
+  Adaptive {
+    min: Duration,
+    max: Duration,
+    /// Render as soon as a hit is found, instead of at the next interval
+    flush_on_hit: bool,
+  },
+}
+
+impl ProgressIntervalPolicy {
+  /// Adaptive policy from 100 milliseconds to 5 seconds, rendering hits
+  /// as soon as they are found.
+  #[must_use]
+  pub const fn adaptive() -> Self {
+    Self::Adaptive {
+      min: Duration::from_millis(100),
+      max: Duration::from_secs(5),
+      flush_on_hit: true,
+    }
+  }
+
+  /// Return the interval between renderings once `elapsed` passed since
+  /// the start.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use std::time::Duration;
+  ///
+  /// use wakuchin::progress::ProgressIntervalPolicy;
+  ///
+  /// let policy = ProgressIntervalPolicy::adaptive();
+  ///
+  /// assert_eq!(policy.interval(Duration::ZERO), Duration::from_millis(100));
+  /// assert_eq!(policy.interval(Duration::from_secs(20)), Duration::from_secs(1));
+  /// assert_eq!(policy.interval(Duration::from_secs(3600)), Duration::from_secs(5));
+  /// ```
+  pub fn interval(&self, elapsed: Duration) -> Duration {
+    match *self {
+      Self::Fixed(interval) => interval,
+      // not `clamp`, which panics if `min` exceeds `max`
+      Self::Adaptive { min, max, .. } => {
+        (elapsed / ADAPTIVE_DIVISOR).min(max).max(min)
+      }
+    }
+  }
+
+  /// Return whether hits are rendered as soon as they are found.
+  #[inline]
+  pub fn flushes_on_hit(&self) -> bool {
+    matches!(
+      self,
+      Self::Adaptive {
+        flush_on_hit: true,
+        ..
+      }
+    )
+  }
+}
+
+impl From<Duration> for ProgressIntervalPolicy {
+  #[inline]
+  fn from(interval: Duration) -> Self {
+    Self::Fixed(interval)
+  }
+}
+
+/// Progress of a worker as sent to the render, the current chars are only
+/// turned into a string when rendered.
+#[derive(Clone, Debug)]
+pub(crate) enum WorkerProgress {
+  Idle {
+    id: usize,
+  },
+  Processing {
+    id: usize,
+    chars: Vec<u8>,
+    current: usize,
+    total: usize,
+  },
+  Done {
+    id: usize,
+    total: usize,
+  },
+}
+
+impl WorkerProgress {
+  /// Number of tries processed by the worker.
+  #[inline]
+  pub fn current(&self) -> usize {
+    match self {
+      WorkerProgress::Idle { .. } => 0,
+      WorkerProgress::Processing { current, .. } => *current,
+      WorkerProgress::Done { total, .. } => *total,
+    }
+  }
+
+  /// Whether the worker finished all of its tries.
+  #[inline]
+  pub fn is_done(&self) -> bool {
+    matches!(self, WorkerProgress::Done { .. })
+  }
+
+  /// Progress of the worker stuck for `stalled_for`.
+  pub fn to_stalled(&self, stalled_for: Duration) -> Progress {
+    let id = match self {
+      WorkerProgress::Idle { id }
+      | WorkerProgress::Processing { id, .. }
+      | WorkerProgress::Done { id, .. } => *id,
+    };
+
+    Progress(ProgressKind::Stalled(StalledDetail {
+      id,
+      current: self.current(),
+      stalled_for,
+    }))
+  }
+
+  pub fn to_progress(&self) -> Progress {
+    match self {
+      WorkerProgress::Idle { id } => {
+        Progress(ProgressKind::Idle(IdleDetail { id: *id }))
+      }
+      WorkerProgress::Processing {
+        id,
+        chars,
+        current,
+        total,
+      } => Progress(ProgressKind::Processing(ProcessingDetail::new(
+        *id,
+        Candidate::from_utf8_lossy(chars),
+        *current,
+        *total,
+      ))),
+      WorkerProgress::Done { id, total } => {
+        Progress(ProgressKind::Done(DoneDetail {
+          id: *id,
+          total: *total,
+        }))
+      }
+    }
+  }
+}
@@ -0,0 +1,632 @@
+//! Versioned layouts of the progress frames written by the msgpack handlers,
+//! so existing consumers can keep reading the layout they were written for
+
+use std::io::Read;
+use std::time::Duration;
+
+use instant::SystemTime;
+use serde::{Deserialize, Serialize};
+
+use crate::error::WakuchinError;
+use crate::handlers::RunInfo;
+use crate::progress::{
+  DoneDetail, ProcessingDetail, Progress, ProgressKind, StalledDetail,
+};
+use crate::result::HitCount;
+
+/// Layout of the progress frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+  /// Array of progresses, hit counts, current rate, remaining time, tries
+  /// and whether all done, in this order
+  V1,
+  /// Map of the fields of [`V1`](ProtocolVersion::V1) by name, with the
+  /// `version` and the `seq` number of the frame, and the number of frames
+  /// `skipped` with [`Acks`](crate::handlers::msgpack::Acks)
+  V2,
+  /// Map of the fields of [`V2`](ProtocolVersion::V2), with the wall-clock
+  /// `timestamp` of the frame, the `elapsed` seconds of the research, its
+  /// `seed` and its number of `workers`
+  V3,
+}
+
+impl ProtocolVersion {
+  /// Latest version, written by default.
+  pub const LATEST: Self = Self::V3;
+
+  /// Return the number of this version.
+  #[inline]
+  pub const fn number(self) -> u8 {
+    match self {
+      Self::V1 => 1,
+      Self::V2 => 2,
+      Self::V3 => 3,
+    }
+  }
+}
+
+impl Default for ProtocolVersion {
+  fn default() -> Self {
+    Self::LATEST
+  }
+}
+
+impl TryFrom<u8> for ProtocolVersion {
+  type Error = WakuchinError;
+
+  fn try_from(number: u8) -> Result<Self, Self::Error> {
+    match number {
+      1 => Ok(Self::V1),
+      2 => Ok(Self::V2),
+      3 => Ok(Self::V3),
+      _ => Err(WakuchinError::UnknownProtocolVersion(number)),
+    }
+  }
+}
+
+/// Progress of a research as written in a frame, whatever its layout.
+#[derive(Clone, Debug)]
+pub struct ProgressReport<'a> {
+  pub progresses: &'a [Progress],
+  pub hit_counts: &'a [HitCount],
+  /// Tries per second, 0 if unknown
+  pub current_rate: f64,
+  /// Estimated remaining seconds, 0 if unknown
+  pub remaining_time: f64,
+  pub tries: usize,
+  pub all_done: bool,
+  /// Number of the frame in the stream, from 0
+  pub seq: u64,
+  /// Frames skipped so far as the consumer lagged behind, if it
+  /// acknowledges them
+  pub skipped: Option<u64>,
+  /// Seconds since the Unix epoch when the report was created
+  pub timestamp: f64,
+  /// Seconds since the research started, 0 if unknown
+  pub elapsed: f64,
+  pub seed: Option<u64>,
+  /// Workers of the research, 0 if unknown
+  pub workers: usize,
+}
+
+impl<'a> ProgressReport<'a> {
+  /// Create the report of a call to
+  /// [`ProgressHandler::handle`](crate::handlers::ProgressHandler::handle)
+  /// in a research of `tries`.
+  pub fn new(
+    progresses: &'a [Progress],
+    hit_counts: &'a [HitCount],
+    tries: usize,
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> Self {
+    let current_total = progresses
+      .iter()
+      .map(|progress| match progress {
+        Progress(ProgressKind::Processing(ProcessingDetail {
+          current,
+          ..
+        })) => *current,
+        Progress(ProgressKind::Done(DoneDetail { total, .. })) => *total,
+        Progress(ProgressKind::Stalled(StalledDetail { current, .. })) => {
+          *current
+        }
+        _ => 0,
+      })
+      .sum::<usize>()
+      .min(tries);
+
+    // the first progress may come before any time elapsed
+    let finite_or_zero = |n: f64| if n.is_finite() { n } else { 0.0 };
+    let current_rate =
+      finite_or_zero(current_diff as f64 / elapsed_time.as_secs_f64());
+    let remaining_time =
+      finite_or_zero((tries - current_total) as f64 / current_rate);
+
+    Self {
+      progresses,
+      hit_counts,
+      current_rate,
+      remaining_time,
+      tries,
+      all_done,
+      seq: 0,
+      skipped: None,
+      timestamp: unix_secs(SystemTime::now()),
+      elapsed: 0.0,
+      seed: None,
+      workers: 0,
+    }
+  }
+
+  /// Describe the research of `info`, only written since
+  /// [`V3`](ProtocolVersion::V3).
+  pub fn with_info(mut self, info: &RunInfo) -> Self {
+    self.elapsed = (self.timestamp - unix_secs(info.started_at)).max(0.0);
+    self.seed = info.seed;
+    self.workers = info.workers;
+    self
+  }
+
+  /// Number the frame with `seq`, only written since
+  /// [`V2`](ProtocolVersion::V2).
+  pub fn with_seq(mut self, seq: u64) -> Self {
+    self.seq = seq;
+    self
+  }
+
+  /// Count the `skipped` frames, only written since
+  /// [`V2`](ProtocolVersion::V2).
+  pub fn with_skipped(mut self, skipped: u64) -> Self {
+    self.skipped = Some(skipped);
+    self
+  }
+}
+
+/// Return the seconds from the Unix epoch to `time`.
+fn unix_secs(time: SystemTime) -> f64 {
+  time
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs_f64()
+}
+
+/// Progress frame as read by a consumer, whatever its layout.
+#[derive(Clone, Debug)]
+pub struct Frame {
+  pub version: ProtocolVersion,
+  pub progresses: Vec<Progress>,
+  pub hit_counts: Vec<HitCount>,
+  pub current_rate: f64,
+  pub remaining_time: f64,
+  pub tries: usize,
+  pub all_done: bool,
+  /// Number of the frame, only since [`V2`](ProtocolVersion::V2)
+  pub seq: Option<u64>,
+  /// Frames skipped so far, only with acknowledgements
+  pub skipped: Option<u64>,
+  /// Seconds since the Unix epoch, only since [`V3`](ProtocolVersion::V3)
+  pub timestamp: Option<f64>,
+  /// Seconds since the research started, only since
+  /// [`V3`](ProtocolVersion::V3)
+  pub elapsed: Option<f64>,
+  /// Seed of the research, only since [`V3`](ProtocolVersion::V3) and if
+  /// seeded
+  pub seed: Option<u64>,
+  /// Workers of the research, only since [`V3`](ProtocolVersion::V3)
+  pub workers: Option<usize>,
+}
+
+mod v1 {
+  use serde::{Deserialize, Serialize};
+
+  use crate::progress::Progress;
+  use crate::result::HitCount;
+
+  use super::ProgressReport;
+
+  #[derive(Serialize)]
+  pub(super) struct ProgressFrame<'a> {
+    progresses: &'a [Progress],
+    hit_counts: &'a [HitCount],
+    current_rate: f64,
+    remaining_time: f64,
+    tries: usize,
+    all_done: bool,
+  }
+
+  /// Fields of a frame read back, in the order of [`ProgressFrame`].
+  #[derive(Deserialize)]
+  pub(super) struct OwnedFrame(
+    pub Vec<Progress>,
+    pub Vec<HitCount>,
+    pub f64,
+    pub f64,
+    pub usize,
+    pub bool,
+  );
+
+  impl<'a> From<&ProgressReport<'a>> for ProgressFrame<'a> {
+    fn from(report: &ProgressReport<'a>) -> Self {
+      Self {
+        progresses: report.progresses,
+        hit_counts: report.hit_counts,
+        current_rate: report.current_rate,
+        remaining_time: report.remaining_time,
+        tries: report.tries,
+        all_done: report.all_done,
+      }
+    }
+  }
+}
+
+mod v2 {
+  use serde::{Deserialize, Serialize};
+
+  use crate::progress::Progress;
+  use crate::result::HitCount;
+
+  use super::{ProgressReport, ProtocolVersion};
+
+  #[derive(Serialize)]
+  pub(super) struct ProgressFrame<'a> {
+    version: u8,
+    seq: u64,
+    progresses: &'a [Progress],
+    hit_counts: &'a [HitCount],
+    current_rate: f64,
+    remaining_time: f64,
+    tries: usize,
+    all_done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<u64>,
+  }
+
+  /// Frame read back, rejecting fields of no version.
+  #[derive(Deserialize)]
+  #[serde(deny_unknown_fields)]
+  pub(super) struct OwnedFrame {
+    pub version: u8,
+    pub seq: u64,
+    pub progresses: Vec<Progress>,
+    pub hit_counts: Vec<HitCount>,
+    pub current_rate: f64,
+    pub remaining_time: f64,
+    pub tries: usize,
+    pub all_done: bool,
+    #[serde(default)]
+    pub skipped: Option<u64>,
+  }
+
+  impl<'a> From<&ProgressReport<'a>> for ProgressFrame<'a> {
+    fn from(report: &ProgressReport<'a>) -> Self {
+      Self {
+        version: ProtocolVersion::V2.number(),
+        seq: report.seq,
+        progresses: report.progresses,
+        hit_counts: report.hit_counts,
+        current_rate: report.current_rate,
+        remaining_time: report.remaining_time,
+        tries: report.tries,
+        all_done: report.all_done,
+        skipped: report.skipped,
+      }
+    }
+  }
+}
+
+mod v3 {
+  use serde::{Deserialize, Serialize};
+
+  use crate::progress::Progress;
+  use crate::result::HitCount;
+
+  use super::{ProgressReport, ProtocolVersion};
+
+  #[derive(Serialize)]
+  pub(super) struct ProgressFrame<'a> {
+    version: u8,
+    seq: u64,
+    timestamp: f64,
+    elapsed: f64,
+    progresses: &'a [Progress],
+    hit_counts: &'a [HitCount],
+    current_rate: f64,
+    remaining_time: f64,
+    tries: usize,
+    all_done: bool,
+    workers: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<u64>,
+  }
+
+  /// Frame read back, rejecting fields of no version.
+  #[derive(Deserialize)]
+  #[serde(deny_unknown_fields)]
+  pub(super) struct OwnedFrame {
+    pub version: u8,
+    pub seq: u64,
+    pub timestamp: f64,
+    pub elapsed: f64,
+    pub progresses: Vec<Progress>,
+    pub hit_counts: Vec<HitCount>,
+    pub current_rate: f64,
+    pub remaining_time: f64,
+    pub tries: usize,
+    pub all_done: bool,
+    pub workers: usize,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub skipped: Option<u64>,
+  }
+
+  impl<'a> From<&ProgressReport<'a>> for ProgressFrame<'a> {
+    fn from(report: &ProgressReport<'a>) -> Self {
+      Self {
+        version: ProtocolVersion::V3.number(),
+        seq: report.seq,
+        timestamp: report.timestamp,
+        elapsed: report.elapsed,
+        progresses: report.progresses,
+        hit_counts: report.hit_counts,
+        current_rate: report.current_rate,
+        remaining_time: report.remaining_time,
+        tries: report.tries,
+        all_done: report.all_done,
+        workers: report.workers,
+        seed: report.seed,
+        skipped: report.skipped,
+      }
+    }
+  }
+}
+
+/// Serialize `report` to msgpack in the layout of `version`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use wakuchin::protocol::{encode, ProgressReport, ProtocolVersion};
+///
+/// let report =
+///   ProgressReport::new(&[], &[], 100, Duration::from_secs(1), 10, false);
+///
+/// // v1 frames are arrays of 6 elements
+/// assert_eq!(encode(ProtocolVersion::V1, &report)?[0], 0x96);
+/// // v2 frames are maps of 8 entries
+/// assert_eq!(encode(ProtocolVersion::V2, &report)?[0], 0x88);
+/// // v3 frames are maps of 11 entries without a seed
+/// assert_eq!(encode(ProtocolVersion::V3, &report)?[0], 0x8b);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn encode(
+  version: ProtocolVersion,
+  report: &ProgressReport,
+) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+  let mut buf = Vec::new();
+
+  match version {
+    ProtocolVersion::V1 => v1::ProgressFrame::from(report)
+      .serialize(&mut rmp_serde::Serializer::new(&mut buf))?,
+    ProtocolVersion::V2 => v2::ProgressFrame::from(report)
+      .serialize(&mut rmp_serde::Serializer::new(&mut buf).with_struct_map())?,
+    ProtocolVersion::V3 => v3::ProgressFrame::from(report)
+      .serialize(&mut rmp_serde::Serializer::new(&mut buf).with_struct_map())?,
+  }
+
+  Ok(buf)
+}
+
+/// Deserialize the next frame of `version` from `reader`, the inverse of
+/// [`encode`].
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use wakuchin::protocol::{decode, encode, ProgressReport, ProtocolVersion};
+///
+/// let report =
+///   ProgressReport::new(&[], &[], 100, Duration::from_secs(1), 10, false)
+///     .with_seq(7);
+/// let frame = encode(ProtocolVersion::V2, &report)?;
+///
+/// assert_eq!(decode(ProtocolVersion::V2, &frame[..])?.seq, Some(7));
+/// assert!(decode(ProtocolVersion::V1, &frame[..]).is_err());
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn decode(
+  version: ProtocolVersion,
+  reader: impl Read,
+) -> Result<Frame, rmp_serde::decode::Error> {
+  let mut deserializer = rmp_serde::Deserializer::new(reader);
+
+  match version {
+    ProtocolVersion::V1 => {
+      let v1::OwnedFrame(
+        progresses,
+        hit_counts,
+        current_rate,
+        remaining_time,
+        tries,
+        all_done,
+      ) = Deserialize::deserialize(&mut deserializer)?;
+
+      Ok(Frame {
+        version,
+        progresses,
+        hit_counts,
+        current_rate,
+        remaining_time,
+        tries,
+        all_done,
+        seq: None,
+        skipped: None,
+        timestamp: None,
+        elapsed: None,
+        seed: None,
+        workers: None,
+      })
+    }
+    ProtocolVersion::V2 => {
+      let frame = v2::OwnedFrame::deserialize(&mut deserializer)?;
+
+      check_version(version, frame.version)?;
+
+      Ok(Frame {
+        version,
+        progresses: frame.progresses,
+        hit_counts: frame.hit_counts,
+        current_rate: frame.current_rate,
+        remaining_time: frame.remaining_time,
+        tries: frame.tries,
+        all_done: frame.all_done,
+        seq: Some(frame.seq),
+        skipped: frame.skipped,
+        timestamp: None,
+        elapsed: None,
+        seed: None,
+        workers: None,
+      })
+    }
+    ProtocolVersion::V3 => {
+      let frame = v3::OwnedFrame::deserialize(&mut deserializer)?;
+
+      check_version(version, frame.version)?;
+
+      Ok(Frame {
+        version,
+        progresses: frame.progresses,
+        hit_counts: frame.hit_counts,
+        current_rate: frame.current_rate,
+        remaining_time: frame.remaining_time,
+        tries: frame.tries,
+        all_done: frame.all_done,
+        seq: Some(frame.seq),
+        skipped: frame.skipped,
+        timestamp: Some(frame.timestamp),
+        elapsed: Some(frame.elapsed),
+        seed: frame.seed,
+        workers: Some(frame.workers),
+      })
+    }
+  }
+}
+
+/// Fail unless the `number` a frame was written with is of `version`.
+fn check_version(
+  version: ProtocolVersion,
+  number: u8,
+) -> Result<(), rmp_serde::decode::Error> {
+  if number != version.number() {
+    return Err(serde::de::Error::custom(format!(
+      "frame of version {number}, not {}",
+      version.number()
+    )));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod test {
+  use std::error::Error;
+  use std::time::Duration;
+
+  use serde_json::{json, Value};
+
+  use crate::handlers::RunInfo;
+  use crate::progress::{DoneDetail, Progress, ProgressKind};
+  use crate::result::HitCount;
+
+  use super::{decode, encode, ProgressReport, ProtocolVersion};
+
+  #[test]
+  fn test_encode() -> Result<(), Box<dyn Error>> {
+    let progresses =
+      [Progress(ProgressKind::Done(DoneDetail { id: 1, total: 4 }))];
+    let hit_counts = [HitCount::new("WKCN", 1)];
+    let report = ProgressReport::new(
+      &progresses,
+      &hit_counts,
+      8,
+      Duration::from_secs(2),
+      4,
+      false,
+    )
+    .with_seq(3);
+
+    assert_eq!(report.current_rate, 2.0);
+    assert_eq!(report.remaining_time, 2.0);
+
+    let v1: Value =
+      rmp_serde::from_slice(&encode(ProtocolVersion::V1, &report)?)?;
+
+    assert_eq!(
+      v1,
+      json!([[{ "Done": [1, 4] }], [["WKCN", 1]], 2.0, 2.0, 8, false])
+    );
+
+    let v2: Value =
+      rmp_serde::from_slice(&encode(ProtocolVersion::V2, &report)?)?;
+
+    assert_eq!(v2["version"], 2);
+    assert_eq!(v2["seq"], 3);
+    assert_eq!(v2["tries"], 8);
+    assert_eq!(v2["all_done"], false);
+    assert_eq!(v2.get("skipped"), None);
+
+    for version in [
+      ProtocolVersion::V1,
+      ProtocolVersion::V2,
+      ProtocolVersion::V3,
+    ] {
+      let frame = decode(version, &encode(version, &report)?[..])?;
+
+      assert_eq!(frame.version, version);
+      assert_eq!(frame.tries, 8);
+      assert_eq!(frame.hit_counts, hit_counts);
+      assert_eq!(frame.current_rate, 2.0);
+    }
+
+    let v2: Value = rmp_serde::from_slice(&encode(
+      ProtocolVersion::V2,
+      &report.with_skipped(5),
+    )?)?;
+
+    assert_eq!(v2["skipped"], 5);
+
+    assert_eq!(ProtocolVersion::try_from(1)?, ProtocolVersion::V1);
+    assert!(ProtocolVersion::try_from(4).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_encode_v3() -> Result<(), Box<dyn Error>> {
+    let info = RunInfo {
+      seed: Some(42),
+      ..RunInfo::new(4)
+    };
+    let report = ProgressReport::new(&[], &[], 8, Duration::ZERO, 0, false)
+      .with_info(&info);
+
+    // no NaN before any time elapsed
+    assert_eq!(report.current_rate, 0.0);
+    assert_eq!(report.remaining_time, 0.0);
+
+    let v3: Value =
+      rmp_serde::from_slice(&encode(ProtocolVersion::V3, &report)?)?;
+
+    assert_eq!(v3["version"], 3);
+    assert_eq!(v3["seed"], 42);
+    assert_eq!(v3["workers"], 4);
+    assert!(v3["timestamp"].as_f64().is_some_and(|t| t > 0.0));
+    assert!(v3["elapsed"].as_f64().is_some_and(|e| e >= 0.0));
+
+    let frame = decode(
+      ProtocolVersion::V3,
+      &encode(ProtocolVersion::V3, &report)?[..],
+    )?;
+
+    assert_eq!(frame.seed, Some(42));
+    assert_eq!(frame.workers, Some(4));
+    assert_eq!(frame.timestamp, Some(report.timestamp));
+    assert!(decode(
+      ProtocolVersion::V2,
+      &encode(ProtocolVersion::V3, &report)?[..]
+    )
+    .is_err());
+
+    Ok(())
+  }
+}
@@ -0,0 +1,178 @@
+//! Retiring the patterns of multi-pattern researches once their hit rate is
+//! known well enough
+//!
+//! A pattern is retired once the interval of its hit rate is conclusive,
+//! entirely above or below the bound of the [`PruneRule`]. Workers stop
+//! matching it from then on, so the remaining patterns are matched faster,
+//! and [`PatternHits::retired_at`](crate::result::PatternHits::retired_at)
+//! records when it was retired.
+
+use crate::math::{wilson_interval, z_score};
+use crate::sync::{AtomicUsize, Mutex, Ordering};
+
+/// Tries after which workers report their tries and hits to the pruner.
+pub(crate) const REPORT_EVERY: usize = 4096;
+
+/// Tries before any pattern is retired unless configured otherwise, see
+/// [`PruneRule::min_tries`].
+pub const DEFAULT_MIN_TRIES: usize = 100_000;
+
+/// When to retire the patterns of a research, see
+/// [`ResearchBuilder::prune_patterns`](crate::builder::ResearchBuilder::prune_patterns).
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::prune::PruneRule;
+///
+/// // retire patterns once they surely hit more or less than 1 in 1000 tries
+/// let rule = PruneRule::new(0.001, 0.99).with_min_tries(10_000);
+///
+/// assert!(rule.is_conclusive(1000, 100_000));
+/// assert!(!rule.is_conclusive(100, 100_000));
+/// assert!(!rule.is_conclusive(1000, 5000));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PruneRule {
+  /// Hit rate the intervals are compared with
+  pub bound: f64,
+  /// Confidence of the intervals, e.g. 0.99
+  pub confidence: f64,
+  /// Tries before any pattern is retired, so early luck retires none
+  pub min_tries: usize,
+}
+
+impl PruneRule {
+  pub fn new(bound: f64, confidence: f64) -> Self {
+    Self {
+      bound,
+      confidence,
+      min_tries: DEFAULT_MIN_TRIES,
+    }
+  }
+
+  #[must_use]
+  pub fn with_min_tries(mut self, min_tries: usize) -> Self {
+    self.min_tries = min_tries;
+    self
+  }
+
+  /// Return whether a pattern of `hits` in `tries` can be retired.
+  pub fn is_conclusive(&self, hits: usize, tries: usize) -> bool {
+    tries >= self.min_tries
+      && self.is_conclusive_with(hits, tries, z_score(self.confidence))
+  }
+
+  #[inline]
+  fn is_conclusive_with(&self, hits: usize, tries: usize, z: f64) -> bool {
+    let (low, high) = wilson_interval(hits, tries, z);
+
+    low > self.bound || high < self.bound
+  }
+}
+
+/// Tries and hits of the patterns of a research, shared by its workers.
+pub(crate) struct Pruner {
+  rule: PruneRule,
+  z: f64,
+  /// Whether each pattern may be retired
+  eligible: Vec<bool>,
+  tries: AtomicUsize,
+  hits: Vec<AtomicUsize>,
+  /// Tries when each pattern was retired
+  retired_at: Mutex<Vec<Option<usize>>>,
+  retirements: AtomicUsize,
+}
+
+impl Pruner {
+  pub fn new(rule: PruneRule, eligible: Vec<bool>) -> Self {
+    Self {
+      rule,
+      z: z_score(rule.confidence),
+      hits: eligible.iter().map(|_| AtomicUsize::new(0)).collect(),
+      retired_at: Mutex::new(vec![None; eligible.len()]),
+      eligible,
+      tries: AtomicUsize::new(0),
+      retirements: AtomicUsize::new(0),
+    }
+  }
+
+  /// Add the `tries` and `hits` of a worker since its last report, retiring
+  /// the patterns which are conclusive by then.
+  ///
+  /// `hits` are reset, so the worker can count its next hits in place.
+  pub fn report(&self, tries: usize, hits: &mut [usize]) {
+    let total = self.tries.fetch_add(tries, Ordering::Relaxed) + tries;
+
+    for (counter, hits) in self.hits.iter().zip(hits) {
+      counter.fetch_add(std::mem::take(hits), Ordering::Relaxed);
+    }
+
+    if total < self.rule.min_tries {
+      return;
+    }
+
+    let mut retired_at = self.retired_at.lock().unwrap();
+    let mut active = retired_at.iter().filter(|at| at.is_none()).count();
+
+    for (pattern, at) in retired_at.iter_mut().enumerate() {
+      // the last pattern keeps being matched
+      if at.is_some() || !self.eligible[pattern] || active <= 1 {
+        continue;
+      }
+
+      let hits = self.hits[pattern].load(Ordering::Relaxed);
+
+      if self.rule.is_conclusive_with(hits, total, self.z) {
+        *at = Some(total);
+        active -= 1;
+        self.retirements.fetch_add(1, Ordering::SeqCst);
+      }
+    }
+  }
+
+  /// Return the number of patterns retired so far, which only grows.
+  #[inline]
+  pub fn retirements(&self) -> usize {
+    self.retirements.load(Ordering::Relaxed)
+  }
+
+  /// Return the tries when each pattern was retired.
+  pub fn retired_at(&self) -> Vec<Option<usize>> {
+    self.retired_at.lock().unwrap().clone()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{PruneRule, Pruner};
+
+  #[test]
+  fn test_report() {
+    let pruner = Pruner::new(
+      PruneRule::new(0.01, 0.99).with_min_tries(1000),
+      vec![true, true, true, false],
+    );
+
+    pruner.report(500, &mut [500, 5, 0, 0]);
+    assert_eq!(pruner.retirements(), 0);
+
+    let mut hits = [500, 5, 0, 0];
+
+    pruner.report(500, &mut hits);
+    assert_eq!(hits, [0; 4]);
+    assert_eq!(pruner.retired_at(), [Some(1000), None, Some(1000), None]);
+
+    pruner.report(100_000, &mut [0, 1000, 0, 0]);
+    assert_eq!(pruner.retirements(), 2);
+
+    // the last pattern is kept
+    let pruner = Pruner::new(
+      PruneRule::new(0.01, 0.99).with_min_tries(1000),
+      vec![true, true],
+    );
+
+    pruner.report(1000, &mut [500, 0]);
+    assert_eq!(pruner.retired_at(), [Some(1000), None]);
+  }
+}
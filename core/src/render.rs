@@ -1,138 +1,123 @@
-use std::borrow::Cow;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
 
 use anyhow::Result;
 use instant::Instant;
-use itertools::Itertools;
-use tokio::sync::watch;
 
-use crate::handlers::ProgressHandler;
+use crate::aggregator::ProgressAggregator;
+use crate::channel::Receiver;
+use crate::handlers::{ControlFlow, ProgressHandler, RunInfo};
 use crate::hit::counter::{HitCounter, ThreadHitCounter};
-use crate::progress::{DoneDetail, ProcessingDetail, Progress, ProgressKind};
-use crate::result::HitCount;
+use crate::pause::PauseState;
+use crate::progress::{Progress, ProgressIntervalPolicy};
+use crate::result::{Hit, HitCount, NotableHit};
+use crate::sync::{spin_loop, AtomicBool, Ordering};
 use crate::utils::DiffStore;
 
 pub struct ThreadRender {
   is_stopped_accidentially: Arc<AtomicBool>,
+  pause: Arc<PauseState>,
   counter: ThreadHitCounter,
-  progress_channels: Vec<watch::Receiver<Progress>>,
-  progress_handler: Box<dyn ProgressHandler>,
-  total: usize,
-  total_workers: usize,
+  aggregator: ProgressAggregator,
+  notable_hits: Option<Receiver<NotableHit>>,
+  /// Hits passed on by the counter once counted
+  hits: Option<Receiver<Hit>>,
+  /// Hits of the counter already passed to the aggregator
+  hits_seen: usize,
 }
 
 impl ThreadRender {
   pub fn new(
     is_stopped_accidentially: Arc<AtomicBool>,
+    pause: Arc<PauseState>,
     counter: ThreadHitCounter,
-    progress_channels: Vec<watch::Receiver<Progress>>,
-    progress_handler: Box<dyn ProgressHandler>,
-    total: usize,
-    total_workers: usize,
+    aggregator: ProgressAggregator,
   ) -> Self {
     Self {
       is_stopped_accidentially,
+      pause,
       counter,
-      progress_channels,
-      progress_handler,
-      total,
-      total_workers,
+      aggregator,
+      notable_hits: None,
+      hits: None,
+      hits_seen: 0,
     }
   }
 
-  #[inline]
-  fn hits(&self) -> Vec<HitCount> {
-    self.counter.get_all().into_hit_counts()
+  /// Pass the notable hits of `notable_hits` to the handler as they come.
+  pub fn with_notable_hits(
+    mut self,
+    notable_hits: Receiver<NotableHit>,
+  ) -> Self {
+    self.notable_hits = Some(notable_hits);
+    self
   }
 
-  #[inline]
-  pub fn invoke_before_start(&mut self) -> Result<()> {
-    self.progress_handler.before_start(self.total_workers)
+  /// Pass the hits of `hits` to the handler as they are counted.
+  pub fn with_hits(mut self, hits: Receiver<Hit>) -> Self {
+    self.hits = Some(hits);
+    self
   }
 
-  pub fn run(&mut self, interval: Duration) -> Result<()> {
-    self.invoke_before_start()?;
+  fn handle_hits(&mut self) -> Result<()> {
+    if let Some(hits) = &self.hits {
+      for hit in hits.try_iter() {
+        self.aggregator.handle_hit(&hit)?;
+      }
+    }
 
-    let mut start_time = Instant::now();
-    let mut current_diff = DiffStore::new(0_usize);
-    let mut current_ = 0;
+    if let Some(notable_hits) = &self.notable_hits {
+      for hit in notable_hits.try_iter() {
+        self.aggregator.notable_hit(&hit)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn run(&mut self) -> Result<()> {
+    self.aggregator.start()?;
 
     loop {
       if self.is_stopped_accidentially.load(Ordering::SeqCst) {
-        return self.invoke_on_accidential_stop();
+        return self.aggregator.stop();
       }
 
       if self.counter.count_stopped.load(Ordering::Acquire) {
-        self.progress_handler.handle(
-          &(0..self.progress_channels.len())
-            .map(|id| {
-              Progress(ProgressKind::Done(DoneDetail {
-                id,
-                total: self.total,
-              }))
-            })
-            .collect_vec(),
-          &self.hits(),
-          interval,
-          0,
-          true,
-        )?;
-
-        break;
-      }
+        // every worker sent its done progress and notable hits before the
+        // counter stopped, which passed on its hits before
+        self.handle_hits()?;
 
-      if start_time.elapsed() < interval {
-        continue;
+        return self.aggregator.finish();
       }
 
-      let progressses = &self
-        .progress_channels
-        .iter()
-        .map(|rx| {
-          let progress = rx.borrow();
-
-          match *progress {
-            Progress(ProgressKind::Processing(ProcessingDetail {
-              current,
-              ..
-            })) => {
-              current_ += current;
-            }
-            Progress(ProgressKind::Done(DoneDetail { total, .. })) => {
-              current_ += total;
-            }
-            _ => {}
-          }
+      self.handle_hits()?;
 
-          progress.clone()
-        })
-        .collect_vec();
+      let hits = self.counter.received();
 
-      self.progress_handler.handle(
-        progressses,
-        &self.hits(),
-        interval,
-        current_diff.update(current_),
-        false,
-      )?;
+      if hits != self.hits_seen {
+        self.hits_seen = hits;
+        self.aggregator.hit();
+      }
 
-      current_ = 0;
-      start_time = Instant::now();
-    }
+      if self.pause.is_paused() {
+        let pause = &self.pause;
 
-    self.invoke_after_finish()?;
+        self.aggregator.pause(|| {
+          pause.report();
+          pause.wait_while_paused();
+        })?;
+      }
 
-    Ok(())
-  }
+      if self.aggregator.poll()? == ControlFlow::Stop {
+        // stop like on SIGINT, the next iteration notifies the handler
+        self.is_stopped_accidentially.store(true, Ordering::SeqCst);
+        self.pause.cancel();
 
-  pub fn invoke_on_accidential_stop(&mut self) -> Result<()> {
-    self.progress_handler.on_accidential_stop()
-  }
+        continue;
+      }
 
-  pub fn invoke_after_finish(&mut self) -> Result<()> {
-    self.progress_handler.after_finish()
+      spin_loop();
+    }
   }
 }
 
@@ -140,7 +125,11 @@ pub struct Render {
   current_diff: DiffStore<usize>,
   counter: HitCounter,
   progress_handler: Box<dyn ProgressHandler>,
+  /// Time of the start, which adaptive intervals grow with
+  started: Instant,
   start_time: Instant,
+  /// Whether a hit was counted since the last rendering
+  hit: bool,
 }
 
 impl Render {
@@ -149,7 +138,9 @@ impl Render {
       current_diff: DiffStore::new(0),
       counter: HitCounter::new(),
       progress_handler,
+      started: Instant::now(),
       start_time: Instant::now(),
+      hit: false,
     }
   }
 
@@ -159,61 +150,86 @@ impl Render {
   }
 
   #[inline]
-  pub fn handle_hit(&self, chars: impl Into<Cow<'static, str>>) {
+  pub fn handle_hit(&mut self, hit: &Hit) -> Result<()> {
     // Insert hit to hit counter with specific char entry
-    self.counter.add(chars);
+    self
+      .counter
+      .add_count_for(hit.pattern, hit.chars.clone(), hit.matches);
+    self.hit = true;
+
+    self.progress_handler.on_hit(hit)
   }
 
   #[inline]
-  pub fn invoke_before_start(&mut self) -> Result<()> {
-    self.progress_handler.before_start(1)
+  pub fn invoke_before_start(&mut self, info: &RunInfo) -> Result<()> {
+    self.started = Instant::now();
+
+    self.progress_handler.before_start(info)
   }
 
+  /// Render the progress built by `progress` if the interval of `policy`
+  /// elapsed since the last rendering, or a hit is to be flushed, so nothing
+  /// is built otherwise.
   pub fn render_progress(
     &mut self,
-    interval: Duration,
-    progress: Progress,
-    all_done: bool,
-  ) -> Result<()> {
-    if interval.is_zero() {
-      self.progress_handler.handle(
-        &[progress],
-        &self.hits(),
-        interval,
-        0,
-        all_done,
-      )?;
-
-      return Ok(());
+    policy: ProgressIntervalPolicy,
+    progress: impl FnOnce() -> Progress,
+  ) -> Result<ControlFlow> {
+    let interval = policy.interval(self.started.elapsed());
+    let flush = self.hit && policy.flushes_on_hit();
+
+    if !flush && self.start_time.elapsed() < interval {
+      return Ok(ControlFlow::Continue);
     }
 
-    if self.start_time.elapsed() <= interval {
-      return Ok(());
-    }
+    let progress = progress();
+    let current_diff = self.current_diff.update(progress.current());
 
-    if matches!(progress, Progress(ProgressKind::Done(_))) {
-      return Ok(());
-    }
+    let flow = self.progress_handler.handle(
+      &[progress],
+      &self.hits(),
+      interval,
+      current_diff,
+      false,
+    )?;
 
-    let current_diff =
-      if let Progress(ProgressKind::Processing(ProcessingDetail {
-        current,
-        ..
-      })) = &progress
-      {
-        self.current_diff.update(*current)
-      } else {
-        0
-      };
+    self.start_time = Instant::now();
+    self.hit = false;
+
+    Ok(flow)
+  }
 
+  /// Render the final `progress` once the worker is done, like
+  /// [`ThreadRender`] does.
+  pub fn render_done(
+    &mut self,
+    policy: ProgressIntervalPolicy,
+    progress: Progress,
+  ) -> Result<()> {
+    // too late to stop
     self.progress_handler.handle(
       &[progress],
       &self.hits(),
-      interval,
-      current_diff,
-      all_done,
+      policy.interval(self.started.elapsed()),
+      0,
+      true,
     )?;
 
+    Ok(())
+  }
+
+  #[inline]
+  pub fn handle_notable_hit(&mut self, hit: &NotableHit) -> Result<()> {
+    self.progress_handler.on_notable_hit(hit)
+  }
+
+  /// Report the pause to the handler and block until resumed.
+  pub fn pause(&mut self, pause: &PauseState) -> Result<()> {
+    self.progress_handler.on_pause()?;
+    pause.report();
+    pause.wait_while_paused();
+    self.progress_handler.on_resume()?;
+
     self.start_time = Instant::now();
 
     Ok(())
@@ -229,3 +245,123 @@ impl Render {
     self.progress_handler.after_finish()
   }
 }
+
+#[cfg(all(test, wakuchin_loom))]
+mod loom_test {
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  use loom::thread;
+
+  use crate::aggregator::ProgressAggregator;
+  use crate::channel::channel;
+  use crate::handlers::empty::EmptyProgressHandler;
+  use crate::hit::counter::ThreadHitCounter;
+  use crate::pause::PauseState;
+  use crate::result::Hit;
+  use crate::sync::{AtomicBool, Ordering};
+
+  use super::ThreadRender;
+
+  /// Check `f` with bounded preemptions, since the spinning render makes the
+  /// whole state space too large to explore.
+  fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = loom::model::Builder::new();
+
+    builder.preemption_bound = Some(3);
+    builder.check(f);
+  }
+
+  /// Spawn the render of a research without progress channels, returning
+  /// whether it stopped without error.
+  fn spawn_render(
+    is_stopped: Arc<AtomicBool>,
+    pause: Arc<PauseState>,
+    counter: ThreadHitCounter,
+  ) -> thread::JoinHandle<bool> {
+    // never render periodically, so the render only watches the flags
+    let aggregator = ProgressAggregator::new(
+      Box::new(EmptyProgressHandler::new()),
+      Duration::MAX,
+    );
+    let mut render = ThreadRender::new(is_stopped, pause, counter, aggregator);
+
+    thread::spawn(move || render.run().is_ok())
+  }
+
+  #[test]
+  fn test_loom_counter_stop() {
+    model(|| {
+      let is_stopped = Arc::new(AtomicBool::new(false));
+      let pause = Arc::new(PauseState::default());
+      let (hit_tx, hit_rx) = channel();
+      let counter = ThreadHitCounter::new(hit_rx);
+
+      let render = spawn_render(is_stopped, pause, counter.clone());
+
+      let counter_handle = thread::spawn({
+        let counter = counter.clone();
+
+        move || counter.run()
+      });
+
+      let worker = thread::spawn(move || {
+        hit_tx.send(Hit::new(0, "WKCN")).unwrap();
+      });
+
+      worker.join().unwrap();
+      counter_handle.join().unwrap();
+
+      assert!(render.join().unwrap());
+      assert!(counter.count_stopped.load(Ordering::Acquire));
+      assert_eq!(counter.get_all().into_hit_counts()[0].hits, 1);
+    });
+  }
+
+  #[test]
+  fn test_loom_cancel_before_counter_stop() {
+    model(|| {
+      let is_stopped = Arc::new(AtomicBool::new(false));
+      let pause = Arc::new(PauseState::default());
+      let (hit_tx, hit_rx) = channel();
+      let counter = ThreadHitCounter::new(hit_rx);
+
+      let render =
+        spawn_render(is_stopped.clone(), pause.clone(), counter.clone());
+
+      let counter_handle = thread::spawn({
+        let counter = counter.clone();
+
+        move || counter.run()
+      });
+
+      // same ordering as the loop of the workers
+      let worker = thread::spawn({
+        let is_stopped = is_stopped.clone();
+        let pause = pause.clone();
+
+        move || {
+          for i in 0..2 {
+            if is_stopped.load(Ordering::Relaxed) {
+              break;
+            }
+
+            pause.wait_while_paused();
+            hit_tx.send(Hit::new(i, "WKCN")).unwrap();
+          }
+
+          drop(hit_tx);
+        }
+      });
+
+      // the signal thread on SIGINT
+      is_stopped.store(true, Ordering::SeqCst);
+      pause.cancel();
+
+      worker.join().unwrap();
+      counter_handle.join().unwrap();
+
+      assert!(render.join().unwrap());
+    });
+  }
+}
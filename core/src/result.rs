@@ -1,12 +1,20 @@
 //! Functions to manipulate the result of a research
 
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::Duration;
 use std::{borrow::Cow, str::FromStr};
 
+use instant::SystemTime;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use smooth::Smooth;
 
+use crate::candidate::Candidate;
 use crate::error::WakuchinError;
+use crate::hit::HitCounter;
+use crate::locale::NumberFormat;
+use crate::math::Rarity;
 
 /// The output format of the result
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
@@ -67,32 +75,109 @@ impl FromStr for ResultOutputFormat {
   }
 }
 
+#[inline]
+fn is_first_pattern(pattern: &usize) -> bool {
+  *pattern == 0
+}
+
+#[inline]
+fn one_match() -> usize {
+  1
+}
+
+#[inline]
+fn is_one_match(matches: &usize) -> bool {
+  *matches == 1
+}
+
 /// Used when the researcher detects a hit
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Hit {
   /// The index of the hit
   pub hit_on: usize,
 
   /// Wakuchin characters that were hit
-  pub chars: String,
+  pub chars: Candidate,
+
+  /// Index of the pattern that was hit, omitted when serialized if it is
+  /// the first one
+  ///
+  /// Chars matching several patterns are a hit of each of them.
+  #[serde(default, skip_serializing_if = "is_first_pattern")]
+  pub pattern: usize,
+
+  /// Byte range of the match in `chars`, only located with
+  /// [`ResearchBuilder::capture_matches`](crate::builder::ResearchBuilder::capture_matches)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub span: Option<Range<usize>>,
+
+  /// Named capture groups of the match by their name, see
+  /// [`span`](Self::span)
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub groups: BTreeMap<String, String>,
+
+  /// Number of non-overlapping matches of the pattern in `chars`, only
+  /// counted with [`MatchMode::CountAll`](crate::pattern::MatchMode::CountAll)
+  /// and omitted when serialized if it is one
+  #[serde(default = "one_match", skip_serializing_if = "is_one_match")]
+  pub matches: usize,
+
+  /// Levenshtein distance of `chars` to the pattern, only for
+  /// [`Matchers::Fuzzy`](crate::pattern::Matchers::Fuzzy)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub distance: Option<usize>,
 }
 
 impl Hit {
-  pub fn new(hit_on: usize, chars: impl Into<String>) -> Self {
+  pub fn new(hit_on: usize, chars: impl Into<Candidate>) -> Self {
     Self {
       hit_on,
       chars: chars.into(),
+      pattern: 0,
+      span: None,
+      groups: BTreeMap::new(),
+      matches: 1,
+      distance: None,
     }
   }
+
+  /// Set the index of the pattern that was hit.
+  #[must_use]
+  pub fn with_pattern(mut self, pattern: usize) -> Self {
+    self.pattern = pattern;
+    self
+  }
+}
+
+/// A notable hit, passed to
+/// [`ProgressHandler::on_notable_hit`](crate::handlers::ProgressHandler::on_notable_hit)
+/// as soon as it is found.
+///
+/// Hits are notable if they match one of the patterns of
+/// [`ResearchBuilder::notable_patterns`](crate::builder::ResearchBuilder::notable_patterns)
+/// or their [`Rarity`] is notable.
+#[derive(Clone, Debug)]
+pub struct NotableHit {
+  /// The hit, `hit_on` being its index in the whole research
+  pub hit: Hit,
+  /// When the hit was found
+  pub found_at: SystemTime,
+  /// Seed of the research, if it is seeded
+  pub seed: Option<u64>,
+  /// Theoretical rarity of the hit, see [`WakuchinResult::rarity`]
+  pub rarity: Option<Rarity>,
 }
 
 /// The count of hits you will use in `progress_handler`.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HitCount {
   /// Wakuchin chars that were hit.
   pub chars: Cow<'static, str>,
   /// The count of hits.
   pub hits: usize,
+  /// Index of the pattern that was hit, see [`Hit::pattern`].
+  #[serde(default, skip_serializing_if = "is_first_pattern")]
+  pub pattern: usize,
 }
 
 impl HitCount {
@@ -110,12 +195,92 @@ impl HitCount {
     Self {
       chars: chars.into(),
       hits,
+      pattern: 0,
+    }
+  }
+
+  /// Set the index of the pattern that was hit.
+  #[must_use]
+  pub fn with_pattern(mut self, pattern: usize) -> Self {
+    self.pattern = pattern;
+    self
+  }
+}
+
+/// Hits of a single pattern of a research.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatternHits {
+  /// Source of the pattern
+  pub pattern: String,
+  /// Total number of hits of the pattern
+  pub hits_total: usize,
+  /// Hits stopping the research, see
+  /// [`ResearchBuilder::stop_at_hits`](crate::builder::ResearchBuilder::stop_at_hits)
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub target: Option<usize>,
+  /// Tries after which the pattern was retired, see
+  /// [`ResearchBuilder::prune_patterns`](crate::builder::ResearchBuilder::prune_patterns)
+  ///
+  /// Workers stop matching it by their next report to the pruner, so its
+  /// hits are of a few thousand more tries per worker.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub retired_at: Option<usize>,
+}
+
+impl PatternHits {
+  /// Return whether the pattern has reached its target.
+  #[inline]
+  pub fn is_reached(&self) -> bool {
+    self.target.is_some_and(|target| self.hits_total >= target)
+  }
+}
+
+/// Why a research stopped before all of its tries.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StopReason {
+  /// The pattern at `pattern` reached its target of `hits` hits, see
+  /// [`ResearchBuilder::stop_at_hits`](crate::builder::ResearchBuilder::stop_at_hits)
+  PatternTarget { pattern: usize, hits: usize },
+  /// The research was stopped with
+  /// [`CancellationToken::stop`](crate::cancel::CancellationToken::stop)
+  Requested,
+  /// All patterns reached `hits` hits together, see
+  /// [`ResearchBuilder::stop_after_hits`](crate::builder::ResearchBuilder::stop_after_hits)
+  HitLimit { hits: usize },
+  /// The research ran for `limit`, see
+  /// [`ResearchBuilder::time_limit`](crate::builder::ResearchBuilder::time_limit)
+  TimeLimit { limit: Duration },
+  /// The interval of the hit probability at `confidence` got narrower than
+  /// `margin`, see
+  /// [`ResearchBuilder::stop_when_confident`](crate::builder::ResearchBuilder::stop_when_confident)
+  Confident { confidence: f64, margin: f64 },
+}
+
+/// A worker of a research which panicked, see [`WakuchinResult::failures`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkerFailure {
+  /// Worker id, from 1
+  pub id: usize,
+  /// Message of the panic
+  pub message: String,
+  /// Tries the worker left undone, which no other worker takes over: the
+  /// rest of its range, or of its last chunk if the workers shared chunks
+  #[serde(default)]
+  pub untried: usize,
+}
+
+impl From<WorkerFailure> for WakuchinError {
+  fn from(failure: WorkerFailure) -> Self {
+    WakuchinError::WorkerError {
+      id: failure.id,
+      message: failure.message,
     }
   }
 }
 
 /// The result of a research
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct WakuchinResult {
   /// The number of tries
   pub tries: usize,
@@ -126,11 +291,118 @@ pub struct WakuchinResult {
   /// The count of each hits
   pub hits: Vec<HitCount>,
 
-  /// A vector of `Hit`
+  /// Every hit in the order of the tries, but only the first ones if capped
+  /// with
+  /// [`ResearchBuilder::max_hit_details`](crate::builder::ResearchBuilder::max_hit_details),
+  /// `hits` and `hits_total` still counting all of them
   pub hits_detail: Vec<Hit>,
+
+  /// Hits of each pattern, in the order of the patterns
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub patterns: Vec<PatternHits>,
+
+  /// Theoretical rarity of each hit, if the number of strings of the
+  /// research is computable
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub rarity: Option<Rarity>,
+
+  /// Why the research stopped early, `tries` being the tries done by then
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub stop_reason: Option<StopReason>,
+
+  /// Workers which panicked, the others going on without them
+  ///
+  /// The result is then partial: `tries` and the hits are those done before
+  /// the panics, the rest of the tries of the failed workers being skipped.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub failures: Vec<WorkerFailure>,
 }
 
 impl WakuchinResult {
+  /// Merge the result of a research of the tries following those of this
+  /// one, e.g. of another machine researching with the same options.
+  ///
+  /// Tries and hits are summed, and the `hit_on` of the details of `other`
+  /// are offset by the tries of this one. Patterns are merged by their
+  /// index, patterns retired in either result being retired after the tries
+  /// they were matched in both. The stop reason and the rarity of this
+  /// result are kept, unless it has none.
+  ///
+  /// # Examples
+  ///
+  /// ```rust
+  /// use wakuchin::result::{Hit, HitCount, WakuchinResult};
+  ///
+  /// let first = WakuchinResult {
+  ///   tries: 10,
+  ///   hits_total: 1,
+  ///   hits: vec![HitCount::new("WKCN", 1)],
+  ///   hits_detail: vec![Hit::new(3, "WKCN")],
+  ///   ..WakuchinResult::default()
+  /// };
+  /// let second = WakuchinResult {
+  ///   tries: 20,
+  ///   hits_total: 2,
+  ///   hits: vec![HitCount::new("WKCN", 1), HitCount::new("WKNC", 1)],
+  ///   hits_detail: vec![Hit::new(0, "WKCN"), Hit::new(5, "WKNC")],
+  ///   ..WakuchinResult::default()
+  /// };
+  ///
+  /// let merged = first.merge(second);
+  ///
+  /// assert_eq!(merged.tries, 30);
+  /// assert_eq!(merged.hits_total, 3);
+  /// assert_eq!(
+  ///   merged.hits,
+  ///   [HitCount::new("WKCN", 2), HitCount::new("WKNC", 1)]
+  /// );
+  /// assert_eq!(
+  ///   merged.hits_detail,
+  ///   [Hit::new(3, "WKCN"), Hit::new(10, "WKCN"), Hit::new(15, "WKNC")]
+  /// );
+  /// ```
+  #[must_use]
+  pub fn merge(mut self, other: WakuchinResult) -> Self {
+    let offset = self.tries;
+    let hits = self.hits.into_iter().collect::<HitCounter>();
+
+    hits.merge(&other.hits.into_iter().collect());
+
+    self
+      .hits_detail
+      .extend(other.hits_detail.into_iter().map(|mut hit| {
+        hit.hit_on += offset;
+        hit
+      }));
+
+    let mut others = other.patterns.into_iter();
+
+    for (pattern, other_pattern) in self.patterns.iter_mut().zip(&mut others) {
+      pattern.retired_at = match (pattern.retired_at, other_pattern.retired_at)
+      {
+        (None, None) => None,
+        (retired_at, other_retired_at) => Some(
+          retired_at.unwrap_or(offset)
+            + other_retired_at.unwrap_or(other.tries),
+        ),
+      };
+      pattern.hits_total += other_pattern.hits_total;
+      pattern.target = pattern.target.or(other_pattern.target);
+    }
+
+    self.patterns.extend(others);
+    self.failures.extend(other.failures);
+
+    Self {
+      tries: self.tries + other.tries,
+      hits_total: self.hits_total + other.hits_total,
+      hits: hits.snapshot(),
+      rarity: self.rarity.or(other.rarity),
+      stop_reason: self.stop_reason.or(other.stop_reason),
+      ..self
+    }
+  }
+
   /// Return string of the result with specific output format.
   /// This function is a wrapper of `out`.
   #[inline]
@@ -140,6 +412,25 @@ impl WakuchinResult {
   ) -> Result<String, WakuchinError> {
     out(format, self)
   }
+
+  /// Return string of the result with specific output format, formatting
+  /// the numbers of the text output with `numbers`.
+  /// This function is a wrapper of `out_with`.
+  #[inline]
+  pub fn out_with(
+    &self,
+    format: ResultOutputFormat,
+    numbers: &NumberFormat,
+  ) -> Result<String, WakuchinError> {
+    out_with(format, self, numbers)
+  }
+}
+
+/// Merge results in order, see [`WakuchinResult::merge`].
+impl FromIterator<WakuchinResult> for WakuchinResult {
+  fn from_iter<I: IntoIterator<Item = WakuchinResult>>(iter: I) -> Self {
+    iter.into_iter().reduce(Self::merge).unwrap_or_default()
+  }
 }
 
 /// Return string of the result with specific output format.
@@ -166,19 +457,14 @@ impl WakuchinResult {
 ///     HitCount::new("WKNC", 1),
 ///   ],
 ///   hits_detail: vec![
-///     Hit {
-///       hit_on: 0,
-///       chars: "WKCN".to_string(),
-///     },
-///     Hit {
-///       hit_on: 1,
-///       chars: "WKNC".to_string(),
-///     },
-///     Hit {
-///       hit_on: 2,
-///       chars: "WKCN".to_string(),
-///     },
+///     Hit::new(0, "WKCN"),
+///     Hit::new(1, "WKNC"),
+///     Hit::new(2, "WKCN"),
 ///   ],
+///   patterns: Vec::new(),
+///   rarity: None,
+///   stop_reason: None,
+///   failures: Vec::new(),
 /// };
 ///
 /// assert_eq!(
@@ -197,32 +483,153 @@ impl WakuchinResult {
 /// #
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
+#[inline]
 pub fn out(
   format: ResultOutputFormat,
   result: &WakuchinResult,
 ) -> Result<String, WakuchinError> {
-  let mut itoa_buf = itoa::Buffer::new();
+  out_with(format, result, &NumberFormat::default())
+}
+
+/// Same as [`out`], but formatting the numbers of the text output with
+/// `numbers`, e.g. grouping their digits for the locale of the user.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::locale::{Grouping, NumberFormat};
+/// use wakuchin::result::{out_with, ResultOutputFormat, WakuchinResult};
+///
+/// let result = WakuchinResult {
+///   tries: 300_000_000,
+///   hits_total: 0,
+///   hits: Vec::new(),
+///   hits_detail: Vec::new(),
+///   patterns: Vec::new(),
+///   rarity: None,
+///   stop_reason: None,
+///   failures: Vec::new(),
+/// };
+/// let numbers = NumberFormat::default().with_grouping(Grouping::Man);
+///
+/// assert!(out_with(ResultOutputFormat::Text, &result, &numbers)?
+///   .contains("Tries: 3,0000,0000"));
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn out_with(
+  format: ResultOutputFormat,
+  result: &WakuchinResult,
+  numbers: &NumberFormat,
+) -> Result<String, WakuchinError> {
+  let percentage = |hits: usize| {
+    numbers.number(&(hits as f64 / result.tries as f64 * 100.0).smooth_str())
+  };
+  // only break the hits down by pattern if there are several
+  let patterns = match &result.patterns[..] {
+    [_] => &[],
+    patterns => patterns,
+  };
 
   match format {
-    ResultOutputFormat::Text => Ok(format!(
-      "--- Result ---
+    ResultOutputFormat::Text => Ok(
+      format!(
+        "--- Result ---
 Tries: {}
 {}
 Total hits: {} ({}%)",
-      result.tries,
-      result
-        .hits
+        numbers.integer(result.tries),
+        result
+          .hits
+          .iter()
+          .map(|h| {
+            let label = match patterns.get(h.pattern) {
+              Some(pattern) => {
+                format!("{} hits of {}", h.chars, pattern.pattern)
+              }
+              None => format!("{} hits", h.chars),
+            };
+
+            match result.rarity {
+              // observed and expected frequencies are compared the same way
+              Some(rarity) => format!(
+                "{label}: {} ({}%, 1 in {} tries, expected 1 in {}){}",
+                numbers.integer(h.hits),
+                percentage(h.hits),
+                numbers
+                  .number(&(result.tries as f64 / h.hits as f64).smooth_str()),
+                numbers.number(&rarity.one_in.to_string()),
+                if rarity.notable { " [notable]" } else { "" }
+              ),
+              None => format!(
+                "{label}: {} ({}%)",
+                numbers.integer(h.hits),
+                percentage(h.hits)
+              ),
+            }
+          })
+          .chain(patterns.iter().map(|pattern| {
+            let target = match pattern.target {
+              Some(target) => format!(
+                ", target {}{}",
+                numbers.integer(target),
+                if pattern.is_reached() {
+                  " [reached]"
+                } else {
+                  ""
+                }
+              ),
+              None => String::new(),
+            };
+            let retired = match pattern.retired_at {
+              Some(tries) => {
+                format!(", retired at {} tries", numbers.integer(tries))
+              }
+              None => String::new(),
+            };
+
+            format!(
+              "Hits of {}: {} ({}%){target}{retired}",
+              pattern.pattern,
+              numbers.integer(pattern.hits_total),
+              percentage(pattern.hits_total)
+            )
+          }))
+          .join("\n"),
+        numbers.integer(result.hits_total),
+        percentage(result.hits_total)
+      ) + &match &result.stop_reason {
+        Some(StopReason::PatternTarget { pattern, hits }) => format!(
+          "\nStopped early: pattern {pattern} reached {} hits",
+          numbers.integer(*hits)
+        ),
+        Some(StopReason::Requested) => "\nStopped early: requested".into(),
+        Some(StopReason::HitLimit { hits }) => {
+          format!("\nStopped early: {} hits found", numbers.integer(*hits))
+        }
+        Some(StopReason::TimeLimit { limit }) => {
+          format!("\nStopped early: time limit of {limit:?} reached")
+        }
+        Some(StopReason::Confident { confidence, margin }) => format!(
+          "\nStopped early: hit probability known within {}% at {}% \
+           confidence",
+          numbers.number(&(margin * 100.0).smooth_str()),
+          numbers.number(&(confidence * 100.0).smooth_str())
+        ),
+        None => String::new(),
+      } + &result
+        .failures
         .iter()
-        .map(|h| format!(
-          "{} hits: {} ({}%)",
-          h.chars,
-          itoa_buf.format(h.hits),
-          (h.hits as f64 / result.tries as f64 * 100.0).smooth_str()
-        ))
-        .join("\n"),
-      itoa_buf.format(result.hits_total),
-      (result.hits_total as f64 / result.tries as f64 * 100.0).smooth_str()
-    )),
+        .map(|failure| {
+          format!(
+            "\nWorker {} panicked, leaving {} tries untried: {}",
+            failure.id,
+            numbers.integer(failure.untried),
+            failure.message
+          )
+        })
+        .join(""),
+    ),
     ResultOutputFormat::Json => Ok(
       serde_json::to_string(result)
         .map_err(|e| WakuchinError::SerializeError(e.into()))?,
@@ -234,7 +641,11 @@ Total hits: {} ({}%)",
 mod test {
   use std::error::Error;
 
-  use crate::result::{out, Hit, HitCount, ResultOutputFormat, WakuchinResult};
+  use crate::math::Rarity;
+  use crate::result::{
+    out, Hit, HitCount, PatternHits, ResultOutputFormat, StopReason,
+    WakuchinResult,
+  };
 
   #[test]
   fn test_out() -> Result<(), Box<dyn Error>> {
@@ -246,20 +657,11 @@ mod test {
         HitCount::new("b".to_string(), 1),
         HitCount::new("c".to_string(), 1),
       ],
-      hits_detail: vec![
-        Hit {
-          hit_on: 0,
-          chars: "a".to_string(),
-        },
-        Hit {
-          hit_on: 1,
-          chars: "b".to_string(),
-        },
-        Hit {
-          hit_on: 2,
-          chars: "c".to_string(),
-        },
-      ],
+      hits_detail: vec![Hit::new(0, "a"), Hit::new(1, "b"), Hit::new(2, "c")],
+      patterns: Vec::new(),
+      rarity: None,
+      stop_reason: None,
+      failures: Vec::new(),
     };
 
     assert_eq!(
@@ -279,4 +681,178 @@ Total hits: 3 (30%)"
 
     Ok(())
   }
+
+  #[test]
+  fn test_out_patterns() -> Result<(), Box<dyn Error>> {
+    let mut result = WakuchinResult {
+      tries: 10,
+      hits_total: 3,
+      hits: vec![
+        HitCount::new("WKCN", 2),
+        HitCount::new("WKCN", 1).with_pattern(1),
+      ],
+      hits_detail: vec![
+        Hit::new(0, "WKCN"),
+        Hit::new(0, "WKCN").with_pattern(1),
+        Hit::new(5, "WKCN"),
+      ],
+      patterns: vec![
+        PatternHits {
+          pattern: "^WK".into(),
+          hits_total: 2,
+          target: None,
+          retired_at: None,
+        },
+        PatternHits {
+          pattern: "CN$".into(),
+          hits_total: 1,
+          target: None,
+          retired_at: None,
+        },
+      ],
+      rarity: None,
+      stop_reason: None,
+      failures: Vec::new(),
+    };
+
+    assert_eq!(
+      out(ResultOutputFormat::Text, &result)?,
+      "--- Result ---
+Tries: 10
+WKCN hits of ^WK: 2 (20%)
+WKCN hits of CN$: 1 (10%)
+Hits of ^WK: 2 (20%)
+Hits of CN$: 1 (10%)
+Total hits: 3 (30%)"
+    );
+
+    assert_eq!(
+      out(ResultOutputFormat::Json, &result)?,
+      r#"{"tries":10,"hits_total":3,"hits":[{"chars":"WKCN","hits":2},{"chars":"WKCN","hits":1,"pattern":1}],"hits_detail":[{"hit_on":0,"chars":"WKCN"},{"hit_on":0,"chars":"WKCN","pattern":1},{"hit_on":5,"chars":"WKCN"}],"patterns":[{"pattern":"^WK","hits_total":2},{"pattern":"CN$","hits_total":1}]}"#
+    );
+
+    result.patterns[0].target = Some(2);
+    result.patterns[1].target = Some(5);
+    result.patterns[1].retired_at = Some(8);
+    result.stop_reason = Some(StopReason::PatternTarget {
+      pattern: 0,
+      hits: 2,
+    });
+
+    assert!(out(ResultOutputFormat::Text, &result)?.ends_with(
+      "Hits of ^WK: 2 (20%), target 2 [reached]
+Hits of CN$: 1 (10%), target 5, retired at 8 tries
+Total hits: 3 (30%)
+Stopped early: pattern 0 reached 2 hits"
+    ));
+    assert!(out(ResultOutputFormat::Json, &result)?.ends_with(
+      r#"{"pattern":"CN$","hits_total":1,"target":5,"retired_at":8}],"stop_reason":{"kind":"pattern_target","pattern":0,"hits":2}}"#
+    ));
+
+    result.stop_reason = Some(StopReason::HitLimit { hits: 3 });
+
+    assert!(out(ResultOutputFormat::Text, &result)?
+      .ends_with("Stopped early: 3 hits found"));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_out_rarity() -> Result<(), Box<dyn Error>> {
+    let mut result = WakuchinResult {
+      tries: 3000,
+      hits_total: 3,
+      hits: vec![HitCount::new("WKCNWKCN", 2), HitCount::new("WKNCWKNC", 1)],
+      hits_detail: vec![
+        Hit::new(0, "WKCNWKCN"),
+        Hit::new(1000, "WKNCWKNC"),
+        Hit::new(2000, "WKCNWKCN"),
+      ],
+      patterns: Vec::new(),
+      rarity: Some(Rarity {
+        one_in: 2520,
+        notable: false,
+      }),
+      stop_reason: None,
+      failures: Vec::new(),
+    };
+
+    assert_eq!(
+      out(ResultOutputFormat::Text, &result)?,
+      "--- Result ---
+Tries: 3000
+WKCNWKCN hits: 2 (0.07%, 1 in 1500 tries, expected 1 in 2520)
+WKNCWKNC hits: 1 (0.03%, 1 in 3000 tries, expected 1 in 2520)
+Total hits: 3 (0.1%)"
+    );
+
+    result.rarity = Some(Rarity {
+      one_in: 2520,
+      notable: true,
+    });
+
+    assert!(out(ResultOutputFormat::Text, &result)?
+      .contains("expected 1 in 2520) [notable]"));
+    assert!(out(ResultOutputFormat::Json, &result)?
+      .ends_with(r#","rarity":{"one_in":2520,"notable":true}}"#));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_merge() {
+    let shard = |tries, hits: &[usize], retired_at| WakuchinResult {
+      tries,
+      hits_total: hits.iter().sum(),
+      hits: hits
+        .iter()
+        .enumerate()
+        .map(|(pattern, hits)| {
+          HitCount::new("WKCN", *hits).with_pattern(pattern)
+        })
+        .collect(),
+      hits_detail: vec![Hit::new(tries - 1, "WKCN")],
+      patterns: hits
+        .iter()
+        .map(|hits| PatternHits {
+          pattern: "WK".into(),
+          hits_total: *hits,
+          target: None,
+          retired_at,
+        })
+        .collect(),
+      ..WakuchinResult::default()
+    };
+
+    let merged = [
+      shard(100, &[10, 1], None),
+      shard(200, &[20, 2], Some(50)),
+      shard(300, &[30, 3], None),
+    ]
+    .into_iter()
+    .collect::<WakuchinResult>();
+
+    assert_eq!(merged.tries, 600);
+    assert_eq!(merged.hits_total, 66);
+    assert_eq!(
+      merged.hits,
+      [
+        HitCount::new("WKCN", 60),
+        HitCount::new("WKCN", 6).with_pattern(1)
+      ]
+    );
+    assert_eq!(
+      merged
+        .hits_detail
+        .iter()
+        .map(|hit| hit.hit_on)
+        .collect::<Vec<_>>(),
+      [99, 299, 599]
+    );
+    // matched in all tries of the first and the last shards
+    assert_eq!(merged.patterns[1].hits_total, 6);
+    assert_eq!(merged.patterns[1].retired_at, Some(450));
+
+    assert_eq!(Vec::new().into_iter().collect::<WakuchinResult>().tries, 0);
+  }
 }
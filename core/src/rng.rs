@@ -0,0 +1,120 @@
+//! Random number generators the default generator shuffles symbols with
+
+/// Random number generator of the default generator, see
+/// [`ResearchBuilder::rng`](crate::builder::ResearchBuilder::rng).
+///
+/// Every backend is seeded from [`fastrand`](https://docs.rs/fastrand)'s
+/// thread-local generator, so researches stay reproducible with
+/// [`ResearchBuilder::seed`](crate::builder::ResearchBuilder::seed).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RngBackend {
+  /// Thread-local generator of `fastrand`, the fastest one
+  #[default]
+  Fastrand,
+  /// ChaCha20 of `rand_chacha`, slower but of cryptographic quality, for
+  /// statistical studies
+  #[cfg(feature = "rng-chacha")]
+  ChaCha,
+}
+
+/// Generator instantiated from a [`RngBackend`].
+#[derive(Clone, Debug, Default)]
+pub(crate) enum Rng {
+  #[default]
+  ThreadLocal,
+  #[cfg(feature = "rng-chacha")]
+  ChaCha(Box<rand_chacha::ChaCha20Rng>),
+}
+
+impl Rng {
+  /// Create a generator of `backend`, seeded from the thread-local one.
+  pub fn new(backend: RngBackend) -> Self {
+    match backend {
+      RngBackend::Fastrand => Self::ThreadLocal,
+      #[cfg(feature = "rng-chacha")]
+      RngBackend::ChaCha => {
+        use rand_chacha::rand_core::SeedableRng;
+
+        Self::ChaCha(Box::new(rand_chacha::ChaCha20Rng::seed_from_u64(
+          fastrand::u64(..),
+        )))
+      }
+    }
+  }
+
+  /// Shuffle `items` uniformly.
+  #[inline]
+  pub fn shuffle<T>(&mut self, items: &mut [T]) {
+    match self {
+      Self::ThreadLocal => fastrand::shuffle(items),
+      #[cfg(feature = "rng-chacha")]
+      Self::ChaCha(rng) => chacha::shuffle(rng.as_mut(), items),
+    }
+  }
+}
+
+#[cfg(feature = "rng-chacha")]
+mod chacha {
+  use rand_chacha::rand_core::RngCore;
+
+  /// Fisher-Yates shuffle of `items`.
+  pub fn shuffle<T>(rng: &mut impl RngCore, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+      items.swap(i, below(rng, i as u64 + 1) as usize);
+    }
+  }
+
+  /// Return a uniform number in `0..n`, with Lemire's multiply and reject.
+  fn below(rng: &mut impl RngCore, n: u64) -> u64 {
+    let threshold = n.wrapping_neg() % n;
+
+    loop {
+      let product = u128::from(rng.next_u64()) * u128::from(n);
+
+      if product as u64 >= threshold {
+        return (product >> 64) as u64;
+      }
+    }
+  }
+}
+
+#[cfg(all(test, feature = "rng-chacha"))]
+mod test {
+  use super::{Rng, RngBackend};
+
+  #[test]
+  fn test_chacha() {
+    fastrand::seed(42);
+
+    let mut rng = Rng::new(RngBackend::ChaCha);
+    let mut counts = [[0_u32; 4]; 4];
+
+    for _ in 0..4000 {
+      let mut items = [0, 1, 2, 3];
+
+      rng.shuffle(&mut items);
+
+      for (position, item) in items.into_iter().enumerate() {
+        counts[position][item] += 1;
+      }
+    }
+
+    // each item lands about 1000 times at each position
+    assert!(counts
+      .iter()
+      .flatten()
+      .all(|&count| count.abs_diff(1000) < 150));
+
+    // reproducible from the thread-local seed
+    let shuffled = || {
+      let mut items = [0, 1, 2, 3, 4, 5, 6, 7];
+
+      fastrand::seed(7);
+      Rng::new(RngBackend::ChaCha).shuffle(&mut items);
+
+      items
+    };
+
+    assert_eq!(shuffled(), shuffled());
+  }
+}
@@ -0,0 +1,182 @@
+//! Running many researches with the same options
+//!
+//! Each call to [`run_par`](crate::builder::ResearchBuilder::run_par) compiles
+//! its patterns and spawns its workers again, which takes longer than short
+//! researches themselves. A [`ResearchSession`], created with
+//! [`ResearchBuilder::session`](crate::builder::ResearchBuilder::session),
+//! compiles them once and keeps its workers on a pool of threads between its
+//! runs.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use rayon_core::{ThreadPool, ThreadPoolBuilder};
+
+use crate::engine::{self, Compiled, Executor, Research, Source};
+use crate::error::WakuchinError;
+use crate::handlers::deadline::DeadlineOverrun;
+use crate::handlers::{ControlFlow, ProgressHandler, RunInfo};
+use crate::pattern::Matchers;
+use crate::progress::{Progress, ProgressIntervalPolicy};
+use crate::result::{Hit, HitCount, NotableHit, WakuchinResult};
+use crate::threads;
+use crate::worker::RunOptions;
+
+type Result<T> = std::result::Result<T, WakuchinError>;
+
+/// Researches of the same options and varying tries, run one after another
+/// on the same threads, see the [module](self).
+///
+/// The progress handler of the builder is kept as well, notified of the
+/// start and the end of each run. Seeded sessions try the same strings on
+/// each run.
+///
+/// # Examples
+///
+/// ```rust
+/// use regex::Regex;
+/// use wakuchin::builder::ResearchBuilder;
+///
+/// let mut session = ResearchBuilder::new()
+///   .times(1)
+///   .regex(Regex::new(r"^WK")?)
+///   .workers(2)
+///   .session()?;
+///
+/// for tries in [100, 1_000, 10_000] {
+///   let result = session.run(tries)?;
+///
+///   assert_eq!(result.tries, tries);
+/// }
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ResearchSession {
+  times: usize,
+  matchers: Matchers,
+  compiled: Compiled,
+  progress_handler: Arc<Mutex<Box<dyn ProgressHandler>>>,
+  progress_interval: ProgressIntervalPolicy,
+  options: RunOptions,
+  pool: ThreadPool,
+}
+
+impl ResearchSession {
+  pub(crate) fn new(
+    times: usize,
+    matchers: Matchers,
+    progress_handler: Box<dyn ProgressHandler>,
+    progress_interval: ProgressIntervalPolicy,
+    workers: usize,
+    options: RunOptions,
+  ) -> Result<Self> {
+    if times == 0 {
+      return Err(WakuchinError::TimesIsZero);
+    }
+
+    let compiled = Compiled::new(&matchers, &options)?;
+    let pool = ThreadPoolBuilder::new()
+      .num_threads(workers)
+      .thread_name(threads::worker_name)
+      .build()
+      .map_err(|e| anyhow!("cannot spawn the workers: {e}"))?;
+
+    Ok(Self {
+      times,
+      matchers,
+      compiled,
+      progress_handler: Arc::new(Mutex::new(progress_handler)),
+      progress_interval,
+      options,
+      pool,
+    })
+  }
+
+  /// Number of workers of each run.
+  #[inline]
+  pub fn workers(&self) -> usize {
+    self.pool.current_num_threads()
+  }
+
+  /// Research `tries` strings in parallel, the same way as
+  /// [`run_par`](crate::builder::ResearchBuilder::run_par).
+  pub fn run(&mut self, tries: usize) -> Result<WakuchinResult> {
+    engine::run(
+      Research {
+        tries,
+        source: Source::Random { times: self.times },
+        patterns: &self.matchers,
+        progress_handler: Box::new(SharedProgressHandler(
+          self.progress_handler.clone(),
+        )),
+        progress_interval: self.progress_interval,
+        options: self.options.clone(),
+        compiled: Some(&self.compiled),
+      },
+      Executor::Pool(&self.pool),
+    )
+  }
+}
+
+/// Progress handler of a session, lent to each of its runs.
+struct SharedProgressHandler(Arc<Mutex<Box<dyn ProgressHandler>>>);
+
+impl SharedProgressHandler {
+  fn call<T>(
+    &self,
+    f: impl FnOnce(&mut dyn ProgressHandler) -> AnyhowResult<T>,
+  ) -> AnyhowResult<T> {
+    f(self.0.lock().unwrap().as_mut())
+  }
+}
+
+impl ProgressHandler for SharedProgressHandler {
+  fn before_start(&mut self, info: &RunInfo) -> AnyhowResult<()> {
+    self.call(|inner| inner.before_start(info))
+  }
+
+  fn handle(
+    &mut self,
+    progresses: &[Progress],
+    hit_counts: &[HitCount],
+    elapsed_time: Duration,
+    current_diff: usize,
+    all_done: bool,
+  ) -> AnyhowResult<ControlFlow> {
+    self.call(|inner| {
+      inner.handle(progresses, hit_counts, elapsed_time, current_diff, all_done)
+    })
+  }
+
+  fn after_finish(&mut self) -> AnyhowResult<()> {
+    self.call(|inner| inner.after_finish())
+  }
+
+  fn on_pause(&mut self) -> AnyhowResult<()> {
+    self.call(|inner| inner.on_pause())
+  }
+
+  fn on_resume(&mut self) -> AnyhowResult<()> {
+    self.call(|inner| inner.on_resume())
+  }
+
+  fn on_hit(&mut self, hit: &Hit) -> AnyhowResult<()> {
+    self.call(|inner| inner.on_hit(hit))
+  }
+
+  fn on_notable_hit(&mut self, hit: &NotableHit) -> AnyhowResult<()> {
+    self.call(|inner| inner.on_notable_hit(hit))
+  }
+
+  fn on_deadline_overrun(
+    &mut self,
+    overrun: &DeadlineOverrun,
+  ) -> AnyhowResult<()> {
+    self.call(|inner| inner.on_deadline_overrun(overrun))
+  }
+
+  fn on_accidential_stop(&mut self) -> AnyhowResult<()> {
+    self.call(|inner| inner.on_accidential_stop())
+  }
+}
@@ -0,0 +1,218 @@
+//! End of a research, shared by `run_par` and `run_seq` so both clean up in
+//! the same order
+
+use crate::error::WakuchinError;
+
+type Result<T> = std::result::Result<T, WakuchinError>;
+
+/// Steps left after the workers finished, in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+  /// No more hits will be sent, so the hit counter can stop
+  CloseHits,
+  /// Every hit has to be counted before the final rendering
+  StopCounter,
+  /// The handler renders the final progress, or is notified of the stop
+  Render,
+  /// Signal hooks are unregistered, even after failures
+  Unregister,
+  Done,
+}
+
+/// Actions of each [`Step`].
+pub(crate) struct Steps<C, S, R, U> {
+  pub close_hits: C,
+  pub stop_counter: S,
+  /// Called with whether the workers stopped early
+  pub render: R,
+  pub unregister: U,
+}
+
+/// State machine running the [`Steps`] after the workers finished, keeping
+/// the first error.
+#[derive(Debug)]
+pub(crate) struct Shutdown {
+  step: Step,
+  error: Option<WakuchinError>,
+}
+
+impl Shutdown {
+  /// Start shutting down with the result of the workers.
+  pub fn after_workers(result: Result<()>) -> Self {
+    Self {
+      step: Step::CloseHits,
+      error: result.err(),
+    }
+  }
+
+  /// Record the result of the current step and move to the next one.
+  pub fn advance(&mut self, result: Result<()>) -> Step {
+    let failed = result.is_err();
+
+    if let Err(err) = result {
+      self.error.get_or_insert(err);
+    }
+
+    self.step = match self.step {
+      Step::CloseHits => Step::StopCounter,
+      // a stuck counter never lets the final progress render
+      Step::StopCounter if failed => Step::Unregister,
+      Step::StopCounter => Step::Render,
+      Step::Render => Step::Unregister,
+      Step::Unregister | Step::Done => Step::Done,
+    };
+
+    self.step
+  }
+
+  /// Run the remaining steps, failing with the first error.
+  pub fn run<C, S, R, U>(mut self, steps: Steps<C, S, R, U>) -> Result<()>
+  where
+    C: FnOnce(),
+    S: FnOnce() -> Result<()>,
+    R: FnOnce(bool) -> Result<()>,
+    U: FnOnce(),
+  {
+    let mut close_hits = Some(steps.close_hits);
+    let mut stop_counter = Some(steps.stop_counter);
+    let mut render = Some(steps.render);
+    let mut unregister = Some(steps.unregister);
+
+    loop {
+      let result = match self.step {
+        Step::CloseHits => {
+          if let Some(close_hits) = close_hits.take() {
+            close_hits();
+          }
+
+          Ok(())
+        }
+        Step::StopCounter => stop_counter.take().map_or(Ok(()), |f| f()),
+        Step::Render => {
+          let stopped = self.error.is_some();
+
+          render.take().map_or(Ok(()), |render| render(stopped))
+        }
+        Step::Unregister => {
+          if let Some(unregister) = unregister.take() {
+            unregister();
+          }
+
+          Ok(())
+        }
+        Step::Done => break,
+      };
+
+      self.advance(result);
+    }
+
+    self.error.map_or(Ok(()), Err)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::cell::RefCell;
+
+  use anyhow::anyhow;
+
+  use crate::error::WakuchinError;
+
+  use super::{Result, Shutdown, Step, Steps};
+
+  /// Run a shutdown, returning its result and the steps taken.
+  fn run(
+    workers: Result<()>,
+    stop_counter: Result<()>,
+    render: Result<()>,
+  ) -> (Result<()>, Vec<String>) {
+    let taken = RefCell::new(Vec::new());
+    let take = |step: &str| taken.borrow_mut().push(step.to_string());
+
+    let result = Shutdown::after_workers(workers).run(Steps {
+      close_hits: || take("close hits"),
+      stop_counter: || {
+        take("stop counter");
+        stop_counter
+      },
+      render: |stopped| {
+        take(if stopped { "notify stop" } else { "render" });
+        render
+      },
+      unregister: || take("unregister"),
+    });
+
+    (result, taken.into_inner())
+  }
+
+  #[test]
+  fn test_transitions() {
+    let mut shutdown = Shutdown::after_workers(Ok(()));
+
+    assert_eq!(shutdown.step, Step::CloseHits);
+    assert_eq!(shutdown.advance(Ok(())), Step::StopCounter);
+    assert_eq!(shutdown.advance(Ok(())), Step::Render);
+    assert_eq!(shutdown.advance(Ok(())), Step::Unregister);
+    assert_eq!(shutdown.advance(Ok(())), Step::Done);
+    assert_eq!(shutdown.advance(Ok(())), Step::Done);
+
+    let mut shutdown = Shutdown::after_workers(Ok(()));
+
+    shutdown.advance(Ok(()));
+
+    assert_eq!(
+      shutdown.advance(Err(WakuchinError::ShutdownTimeout(vec!["render"]))),
+      Step::Unregister
+    );
+  }
+
+  #[test]
+  fn test_finished() {
+    let (result, taken) = run(Ok(()), Ok(()), Ok(()));
+
+    assert!(result.is_ok());
+    assert_eq!(
+      taken,
+      ["close hits", "stop counter", "render", "unregister"]
+    );
+  }
+
+  #[test]
+  fn test_cancelled() {
+    let (result, taken) = run(
+      Err(WakuchinError::Cancelled),
+      Ok(()),
+      Err(anyhow!("oops").into()),
+    );
+
+    // the cancellation wins over the failure of the handler
+    assert!(matches!(result, Err(WakuchinError::Cancelled)));
+    assert_eq!(
+      taken,
+      ["close hits", "stop counter", "notify stop", "unregister"]
+    );
+  }
+
+  #[test]
+  fn test_handler_failed() {
+    let (result, taken) = run(Ok(()), Ok(()), Err(anyhow!("oops").into()));
+
+    assert!(matches!(result, Err(WakuchinError::Other(_))));
+    assert_eq!(
+      taken,
+      ["close hits", "stop counter", "render", "unregister"]
+    );
+  }
+
+  #[test]
+  fn test_counter_stuck() {
+    let (result, taken) = run(
+      Ok(()),
+      Err(WakuchinError::ShutdownTimeout(vec!["hit counter"])),
+      Ok(()),
+    );
+
+    assert!(matches!(result, Err(WakuchinError::ShutdownTimeout(_))));
+    assert_eq!(taken, ["close hits", "stop counter", "unregister"]);
+  }
+}
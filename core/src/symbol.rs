@@ -1,5 +1,12 @@
 //! Wakuchin symbol definitions
 
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::WakuchinError;
+
 /// Internally used wakuchin chars
 pub const WAKUCHIN: [char; 4] =
   [WAKUCHIN_W, WAKUCHIN_K, WAKUCHIN_C, WAKUCHIN_N];
@@ -35,3 +42,131 @@ pub const WAKUCHIN_EXTERNAL_C: char = 'ち';
 
 /// External wakuchin N
 pub const WAKUCHIN_EXTERNAL_N: char = 'ん';
+
+/// Default symbol set, "わくちん" written as "WKCN" internally
+pub const WAKUCHIN_SYMBOLS: SymbolSet = SymbolSet {
+  internal: Cow::Borrowed(&WAKUCHIN),
+  external: Cow::Borrowed(&WAKUCHIN_EXTERNAL),
+};
+
+/// Alphabet of a research.
+///
+/// Generated strings are made of the internal symbols, each of them being
+/// displayed as the external symbol at the same position.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::symbol::SymbolSet;
+///
+/// let symbols = SymbolSet::new("ABCDE", "あいうえお")?;
+///
+/// assert_eq!(symbols.len(), 5);
+/// assert_eq!(symbols.to_external('C'), Some('う'));
+/// assert_eq!(symbols.to_internal('お'), Some('E'));
+/// assert!(SymbolSet::new("AB", "あ").is_err());
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawSymbolSet", into = "RawSymbolSet")]
+pub struct SymbolSet {
+  internal: Cow<'static, [char]>,
+  external: Cow<'static, [char]>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSymbolSet {
+  internal: String,
+  external: String,
+}
+
+impl SymbolSet {
+  /// Create a symbol set from internal and external symbols, which need to
+  /// be unique and of the same count.
+  pub fn new(internal: &str, external: &str) -> Result<Self, WakuchinError> {
+    let internal = internal.chars().collect::<Vec<_>>();
+    let external = external.chars().collect::<Vec<_>>();
+
+    let invalid =
+      |reason: &str| Err(WakuchinError::InvalidSymbolSet(reason.to_string()));
+
+    if internal.is_empty() {
+      return invalid("no symbols");
+    }
+
+    if internal.len() != external.len() {
+      return invalid("internal and external symbols differ in count");
+    }
+
+    let has_duplicates = |symbols: &[char]| {
+      symbols.iter().collect::<HashSet<_>>().len() != symbols.len()
+    };
+
+    if has_duplicates(&internal) || has_duplicates(&external) {
+      return invalid("symbols are not unique");
+    }
+
+    Ok(Self {
+      internal: internal.into(),
+      external: external.into(),
+    })
+  }
+
+  #[inline]
+  pub fn internal(&self) -> &[char] {
+    &self.internal
+  }
+
+  #[inline]
+  pub fn external(&self) -> &[char] {
+    &self.external
+  }
+
+  /// Number of symbols, a string of times n has `len() * n` chars.
+  #[inline]
+  #[allow(clippy::len_without_is_empty)]
+  pub fn len(&self) -> usize {
+    self.internal.len()
+  }
+
+  /// Return the external symbol of `internal`.
+  #[inline]
+  pub fn to_external(&self, internal: char) -> Option<char> {
+    let index = self.internal.iter().position(|c| *c == internal)?;
+
+    Some(self.external[index])
+  }
+
+  /// Return the internal symbol of `external`.
+  #[inline]
+  pub fn to_internal(&self, external: char) -> Option<char> {
+    let index = self.external.iter().position(|c| *c == external)?;
+
+    Some(self.internal[index])
+  }
+}
+
+impl Default for SymbolSet {
+  fn default() -> Self {
+    WAKUCHIN_SYMBOLS
+  }
+}
+
+impl TryFrom<RawSymbolSet> for SymbolSet {
+  type Error = WakuchinError;
+
+  fn try_from(raw: RawSymbolSet) -> Result<Self, Self::Error> {
+    Self::new(&raw.internal, &raw.external)
+  }
+}
+
+impl From<SymbolSet> for RawSymbolSet {
+  fn from(symbols: SymbolSet) -> Self {
+    Self {
+      internal: symbols.internal.iter().collect(),
+      external: symbols.external.iter().collect(),
+    }
+  }
+}
@@ -0,0 +1,21 @@
+//! Synchronization primitives shared by workers, swapped with the ones of
+//! [loom](https://docs.rs/loom) when built with `--cfg wakuchin_loom`, so the
+//! shutdown orderings can be checked exhaustively:
+//!
+//! ```bash
+//! RUSTFLAGS="--cfg wakuchin_loom" cargo test -p wakuchin --release --lib loom
+//! ```
+
+#[cfg(not(wakuchin_loom))]
+pub use std::hint::spin_loop;
+#[cfg(not(wakuchin_loom))]
+pub use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(not(wakuchin_loom))]
+pub use std::sync::{Condvar, Mutex};
+
+#[cfg(wakuchin_loom)]
+pub use loom::hint::spin_loop;
+#[cfg(wakuchin_loom)]
+pub use loom::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(wakuchin_loom)]
+pub use loom::sync::{Condvar, Mutex};
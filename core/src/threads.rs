@@ -0,0 +1,292 @@
+//! Names, priority and core affinity of the threads of researches
+//!
+//! Threads are named after their role, `wakuchin-worker-3` for the third
+//! worker, `wakuchin-render` and `wakuchin-hits` for the threads rendering
+//! the progress and counting the hits, so they can be told apart in
+//! profilers and `htop`. A [`ThreadConfig`] additionally lowers their
+//! priority, so researches in the background leave the cores to the desktop,
+//! or pins the workers to cores.
+//!
+//! # WebAssembly
+//!
+//! Threads cannot be spawned on `wasm32`, so parallel researches are only
+//! available with the `wasm-parallel` feature, which runs them on the Web
+//! Workers of the global pool of rayon instead. The application sets it up
+//! with [wasm-bindgen-rayon](https://docs.rs/wasm-bindgen-rayon), awaiting
+//! `initThreadPool` before the first research, which is then run from a Web
+//! Worker as the main thread of browsers cannot block. The render and the hit
+//! counter take a thread of the pool each, so it needs at least 3 threads and
+//! the workers default to the remaining ones.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::thread::{self, JoinHandle, Scope, ScopedJoinHandle};
+
+/// Prefix of the names of all threads spawned by researches.
+const PREFIX: &str = "wakuchin";
+
+/// Whether the threads of researches are taken from the global pool of
+/// rayon instead of spawned, see the [module](self).
+pub(crate) const POOLED: bool =
+  cfg!(all(target_arch = "wasm32", feature = "wasm-parallel"));
+
+/// Threads of the global pool taken by the render and the hit counter.
+pub(crate) const HELPER_THREADS: usize = 2;
+
+/// Priority of the threads of a research, see
+/// [`ThreadConfig::priority`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThreadPriority {
+  /// Priority of the thread the research is run from.
+  #[default]
+  Normal,
+
+  /// Below the normal priority, nice 10 on Linux.
+  Low,
+
+  /// Only run when nothing else is, nice 19 on Linux.
+  Lowest,
+}
+
+/// Priority and core affinity of the threads of a research, see
+/// [`ResearchBuilder::thread_config`](crate::builder::ResearchBuilder::thread_config).
+///
+/// They are only applied on Linux, Android and Windows, and ignored if the
+/// system refuses them.
+///
+/// # Examples
+///
+/// ```rust
+/// use wakuchin::threads::{ThreadConfig, ThreadPriority};
+///
+/// // the first worker on core 2, the second on core 3, the third on core 2
+/// let config = ThreadConfig::new()
+///   .with_priority(ThreadPriority::Low)
+///   .with_cores([2, 3]);
+///
+/// assert_eq!(config.cores, [2, 3]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreadConfig {
+  /// Priority of the workers and of the threads rendering the progress and
+  /// counting the hits
+  pub priority: ThreadPriority,
+  /// Cores the workers are pinned to in turn, not pinned if empty
+  pub cores: Vec<usize>,
+}
+
+impl ThreadConfig {
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[must_use]
+  pub fn with_priority(mut self, priority: ThreadPriority) -> Self {
+    self.priority = priority;
+    self
+  }
+
+  #[must_use]
+  pub fn with_cores(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+    self.cores = cores.into_iter().collect();
+    self
+  }
+
+  /// Apply the config to the calling thread, the worker at `worker` (from
+  /// 0) if it is one.
+  pub(crate) fn apply(&self, worker: Option<usize>) {
+    // best effort, a research runs the same at any priority
+    if self.priority != ThreadPriority::Normal {
+      let _ = sys::set_priority(self.priority);
+    }
+
+    if let Some(worker) = worker.filter(|_| !self.cores.is_empty()) {
+      let _ = sys::set_affinity(self.cores[worker % self.cores.len()]);
+    }
+  }
+}
+
+/// Return the name of the worker at `worker`, from 0.
+pub(crate) fn worker_name(worker: usize) -> String {
+  format!("{PREFIX}-worker-{}", worker + 1)
+}
+
+/// Return the name of the `role` thread.
+pub(crate) fn name(role: &str) -> String {
+  format!("{PREFIX}-{role}")
+}
+
+/// Handle to join a thread of [`spawn`].
+pub(crate) enum Handle<T> {
+  Thread(JoinHandle<T>),
+  /// Job on the global pool, sending its result once done
+  Pooled(flume::Receiver<thread::Result<T>>),
+}
+
+impl<T> Handle<T> {
+  pub fn is_finished(&self) -> bool {
+    match self {
+      Handle::Thread(handle) => handle.is_finished(),
+      Handle::Pooled(result) => !result.is_empty(),
+    }
+  }
+
+  /// Wait for the thread to finish, returning the panic of it if any.
+  pub fn join(self) -> thread::Result<T> {
+    match self {
+      Handle::Thread(handle) => handle.join(),
+      Handle::Pooled(result) => {
+        result.recv().expect("pooled thread ended without a result")
+      }
+    }
+  }
+}
+
+/// Spawn `f` on a thread named after `role`, or on the global pool if
+/// [`POOLED`].
+pub(crate) fn spawn<F, T>(role: &str, f: F) -> Handle<T>
+where
+  F: FnOnce() -> T + Send + 'static,
+  T: Send + 'static,
+{
+  if POOLED {
+    let (tx, rx) = flume::bounded(1);
+
+    rayon_core::spawn(move || {
+      // the handle may have been dropped
+      let _ = tx.send(catch_unwind(AssertUnwindSafe(f)));
+    });
+
+    return Handle::Pooled(rx);
+  }
+
+  Handle::Thread(
+    thread::Builder::new()
+      .name(name(role))
+      .spawn(f)
+      .expect("failed to spawn thread"),
+  )
+}
+
+/// Spawn `f` on a thread of `scope` named `name`.
+pub(crate) fn spawn_scoped<'scope, F, T>(
+  scope: &'scope Scope<'scope, '_>,
+  name: String,
+  f: F,
+) -> ScopedJoinHandle<'scope, T>
+where
+  F: FnOnce() -> T + Send + 'scope,
+  T: Send + 'scope,
+{
+  thread::Builder::new()
+    .name(name)
+    .spawn_scoped(scope, f)
+    .expect("failed to spawn thread")
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+  use std::io;
+  use std::mem;
+
+  use super::ThreadPriority;
+
+  pub(super) fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+    let nice = match priority {
+      ThreadPriority::Normal => 0,
+      ThreadPriority::Low => 10,
+      ThreadPriority::Lowest => 19,
+    };
+
+    // the nice value of a thread id only applies to that thread on Linux
+    let result = unsafe {
+      let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+
+      libc::setpriority(libc::PRIO_PROCESS, tid, nice)
+    };
+
+    if result != 0 {
+      return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+  }
+
+  pub(super) fn set_affinity(core: usize) -> io::Result<()> {
+    if core >= libc::CPU_SETSIZE as usize {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "no such core"));
+    }
+
+    let result = unsafe {
+      let mut set: libc::cpu_set_t = mem::zeroed();
+
+      libc::CPU_SET(core, &mut set);
+      libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+
+    if result != 0 {
+      return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(windows)]
+mod sys {
+  use std::ffi::c_void;
+  use std::io;
+
+  use super::ThreadPriority;
+
+  const THREAD_PRIORITY_BELOW_NORMAL: i32 = -1;
+  const THREAD_PRIORITY_LOWEST: i32 = -2;
+
+  #[link(name = "kernel32")]
+  extern "system" {
+    fn GetCurrentThread() -> *mut c_void;
+    fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+    fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+  }
+
+  pub(super) fn set_priority(priority: ThreadPriority) -> io::Result<()> {
+    let priority = match priority {
+      ThreadPriority::Normal => return Ok(()),
+      ThreadPriority::Low => THREAD_PRIORITY_BELOW_NORMAL,
+      ThreadPriority::Lowest => THREAD_PRIORITY_LOWEST,
+    };
+
+    if unsafe { SetThreadPriority(GetCurrentThread(), priority) } == 0 {
+      return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+  }
+
+  pub(super) fn set_affinity(core: usize) -> io::Result<()> {
+    let mask = 1usize.checked_shl(core as u32).ok_or_else(|| {
+      io::Error::new(io::ErrorKind::InvalidInput, "no such core")
+    })?;
+
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } == 0 {
+      return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+mod sys {
+  use std::io;
+
+  use super::ThreadPriority;
+
+  pub(super) fn set_priority(_priority: ThreadPriority) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+  }
+
+  pub(super) fn set_affinity(_core: usize) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+  }
+}
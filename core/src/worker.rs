@@ -1,32 +1,45 @@
 //! Wakuchin researcher main functions
 
-use std::panic::resume_unwind;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::thread::{available_parallelism, scope};
+use std::thread::available_parallelism;
 use std::time::Duration;
 
-use divide_range::RangeDivisions;
-use flume::bounded;
-use regex::Regex;
-
-use crate::channel::{channel, watch};
+use crate::cancel::CancellationToken;
+use crate::checkpoint::Checkpoint;
+use crate::engine::{self, Executor, Research, Source};
 use crate::error::WakuchinError;
-use crate::handlers::ProgressHandler;
-use crate::hit::counter::ThreadHitCounter;
-use crate::progress::{
-  DoneDetail, IdleDetail, ProcessingDetail, Progress, ProgressKind,
-};
-use crate::render::{Render, ThreadRender};
-use crate::result::{Hit, HitCount, WakuchinResult};
-use crate::{check, gen};
+use crate::generator::GeneratorFactory;
+use crate::handlers::snapshot::SnapshotReader;
+use crate::handlers::{HandlerErrorPolicy, ProgressHandler, WarningCallback};
+use crate::pattern::{MatchMode, Matchers, Patterns, RegexEngine};
+use crate::pause::PauseHandle;
+use crate::progress::ProgressIntervalPolicy;
+use crate::prune::PruneRule;
+use crate::result::{Hit, WakuchinResult};
+use crate::rng::RngBackend;
+use crate::symbol::SymbolSet;
+use crate::threads::{self, ThreadConfig};
 
 type Result<T> = std::result::Result<T, WakuchinError>;
 
-#[cfg(not(target_arch = "wasm32"))]
-use signal_hook::consts::SIGINT;
+pub(crate) fn get_total_workers(workers: usize) -> Result<usize> {
+  if threads::POOLED {
+    let threads = rayon_core::current_num_threads();
+
+    // the workers would wait for the helpers forever
+    if threads <= threads::HELPER_THREADS {
+      return Err(
+        anyhow::anyhow!("the thread pool needs at least 3 threads").into(),
+      );
+    }
+
+    if workers == 0 {
+      return Ok(threads - threads::HELPER_THREADS);
+    }
+  }
 
-fn get_total_workers(workers: usize) -> Result<usize> {
   if workers != 0 {
     return Ok(workers);
   }
@@ -41,7 +54,7 @@ fn get_total_workers(workers: usize) -> Result<usize> {
 /// * `tries` - number of tries
 ///   If you passed zero, this function do nothing and return immediately with an empty `WakuchinResult`.
 /// * `times` - wakuchin times n, cannot be zero
-/// * `regex` - compiled regular expression to detect hit
+/// * `patterns` - compiled regular expression to detect hit, a slice of them to count the hits of each pattern separately or [`Matchers`] (see [`Patterns`]), recompiled from their pattern to match bytes, so use inline flags like `(?i)` instead of `RegexBuilder` options, or pass `regex::bytes::Regex`es which are matched as compiled
 /// * `progress_handler` - handler function to handle progress
 /// * `progress_interval` - progress refresh interval
 /// * `workers` - number of workers you want to use, default to number of logical cores
@@ -76,7 +89,7 @@ fn get_total_workers(workers: usize) -> Result<usize> {
 ///
 /// # Panics
 ///
-/// This function panics when workers raised panic (mostly about disconnected channels).
+/// Panics of workers are not propagated. The other workers go on, and each panic is reported in [`WakuchinResult::failures`] with the tries its worker left untried, which can be turned into [`WakuchinError::WorkerError`](crate::error::WakuchinError::WorkerError).
 ///
 /// # Examples
 ///
@@ -103,177 +116,106 @@ fn get_total_workers(workers: usize) -> Result<usize> {
 /// #   Ok(())
 /// # }
 /// ```
+#[inline]
 pub fn run_par(
   tries: usize,
   times: usize,
-  regex: &Regex,
+  patterns: &(impl Patterns + ?Sized),
   progress_handler: Box<dyn ProgressHandler>,
   progress_interval: Duration,
   workers: usize,
 ) -> Result<WakuchinResult> {
-  if tries == 0 {
-    return Ok(WakuchinResult {
-      tries: 0,
-      hits_total: 0,
-      hits: Vec::new(),
-      hits_detail: Vec::new(),
-    });
-  }
-
-  if times == 0 {
-    return Err(WakuchinError::TimesIsZero);
-  }
-
-  let total_workers = get_total_workers(workers)?;
-
-  let is_stopped_accidentially = Arc::new(AtomicBool::new(false));
-  let (hit_tx, hit_rx) = channel();
-
-  let (progress_tx_vec, progress_rx_vec): (Vec<_>, Vec<_>) = (0..total_workers)
-    .map(|id| watch(Progress(ProgressKind::Idle(IdleDetail { id: id + 1 }))))
-    .unzip();
-
-  let mut hits_detail = Vec::new();
-
-  let counter = ThreadHitCounter::new(hit_rx);
-
-  let mut render = ThreadRender::new(
-    is_stopped_accidentially.clone(),
-    counter.clone(),
-    progress_rx_vec,
-    progress_handler,
+  run_par_with_options(
     tries,
-    total_workers,
-  );
-
-  // used internally to prevent 'static lifetime issues
-  #[cfg(not(target_arch = "wasm32"))]
-  let (internal_stop_tx, internal_stop_rx) = bounded(1);
-
-  let hits = scope::<_, Result<Vec<HitCount>>>(|s| {
-    // signal handler
-    #[cfg(not(target_arch = "wasm32"))]
-    let signal_id = unsafe {
-      signal_hook_registry::register(SIGINT, move || {
-        internal_stop_tx.send(()).unwrap();
-      })
-    }?;
-
-    let is_stopped_accidentially = is_stopped_accidentially.as_ref();
-
-    #[cfg(not(target_arch = "wasm32"))]
-    let signal_handle = s.spawn(|| loop {
-      if counter.count_stopped.load(Ordering::Acquire) {
-        return;
-      }
-
-      if internal_stop_rx.is_full() {
-        is_stopped_accidentially.store(true, Ordering::SeqCst);
-
-        return;
-      }
-
-      std::hint::spin_loop();
-    });
-
-    // hit handler
-    let hit_handle = s.spawn(|| counter.run());
-
-    // progress reporter
-    let ui_handle = s.spawn::<_, Result<()>>(|| {
-      render.run(progress_interval)?;
-
-      Ok(())
-    });
-
-    let mut worker_handles = Vec::with_capacity(workers);
-
-    (0..tries)
-      .divide_evenly_into(total_workers)
-      .zip(progress_tx_vec.into_iter())
-      .enumerate()
-      .for_each(|(id, (wakuchins, progress_tx))| {
-        let regex = regex.clone();
-        let hit_tx = hit_tx.clone();
-
-        worker_handles.push(s.spawn(move || {
-          let total = wakuchins.len();
-
-          let mut hits = Vec::new();
-
-          for (current, (i, wakuchin)) in
-            wakuchins.map(|i| (i, gen(times))).enumerate()
-          {
-            if is_stopped_accidentially.load(Ordering::Relaxed) {
-              drop(hit_tx);
-
-              return Err(WakuchinError::Cancelled);
-            }
-
-            if check(&wakuchin, &regex) {
-              let hit = Hit::new(i, &*wakuchin);
-
-              hit_tx
-                .send(hit.clone())
-                .expect("hit channel is unavailable");
-
-              hits.push(hit);
-            }
-
-            if !progress_tx.is_closed() {
-              progress_tx
-                .send(Progress(ProgressKind::Processing(
-                  ProcessingDetail::new(id + 1, wakuchin, current, total),
-                )))
-                .expect("progress channel is unavailable");
-            }
-          }
-
-          drop(hit_tx);
-
-          if !progress_tx.is_closed() {
-            progress_tx
-              .send(Progress(ProgressKind::Done(DoneDetail {
-                id: id + 1,
-                total,
-              })))
-              .unwrap();
-          }
-
-          Ok(hits)
-        }));
-      });
-
-    for worker_handle in worker_handles {
-      for hit in worker_handle.join().unwrap_or_else(|e| resume_unwind(e))? {
-        hits_detail.push(hit);
-      }
-    }
-
-    // cleanup
-    drop(hit_tx);
+    times,
+    &patterns.to_matchers(),
+    progress_handler,
+    progress_interval.into(),
+    workers,
+    RunOptions::default(),
+  )
+}
 
-    // after all workers have finished, wait for ui and hit threads to finish
-    hit_handle.join().unwrap_or_else(|e| resume_unwind(e));
-    ui_handle.join().unwrap_or_else(|e| resume_unwind(e))?;
+/// Options of a research which are only set through
+/// [`ResearchBuilder`](crate::builder::ResearchBuilder).
+#[derive(Clone, Default)]
+pub(crate) struct RunOptions {
+  pub seed: Option<u64>,
+  pub pause: PauseHandle,
+  pub cancel: CancellationToken,
+  pub symbols: SymbolSet,
+  pub rng: RngBackend,
+  pub generator: Option<GeneratorFactory>,
+  pub shutdown_grace: Option<Duration>,
+  pub capture_matches: bool,
+  pub match_mode: MatchMode,
+  pub regex_engine: RegexEngine,
+  pub notable_rarity: Option<u128>,
+  pub notable_patterns: Option<Matchers>,
+  /// Hits of a pattern by its index which stop the research
+  pub hit_targets: BTreeMap<usize, usize>,
+  /// Hits of all patterns which stop the research
+  pub hit_limit: Option<usize>,
+  /// Confidence and margin of the hit probability which stop the research
+  pub confidence_target: Option<(f64, f64)>,
+  pub time_limit: Option<Duration>,
+  pub prune: Option<PruneRule>,
+  /// File the checkpoint is written to and how often
+  pub checkpoint: Option<(PathBuf, Duration)>,
+  pub resume: Option<Checkpoint>,
+  /// Called with every hit as it is found
+  pub on_hit: Vec<HitCallback>,
+  /// Hits kept in `hits_detail`, all of them if `None`
+  pub max_hit_details: Option<usize>,
+  pub threads: ThreadConfig,
+  /// Time without progress after which workers are reported as stalled
+  pub stall_timeout: Option<Duration>,
+  /// Tries between calls to the callback of sequential researches
+  pub yield_every: Option<(usize, YieldCallback)>,
+  /// Reader the progress handler is wrapped to record snapshots to
+  pub snapshots: Option<SnapshotReader>,
+  pub handler_error_policy: HandlerErrorPolicy,
+  /// Called with the errors of the progress handler the research goes on
+  /// despite
+  pub on_warning: Option<WarningCallback>,
+}
 
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-      signal_handle.join().unwrap_or_else(|e| resume_unwind(e));
-      signal_hook_registry::unregister(signal_id);
-    }
+/// Callback of [`ResearchBuilder::on_hit`](crate::builder::ResearchBuilder::on_hit).
+pub(crate) type HitCallback = Arc<dyn Fn(&Hit) + Send + Sync>;
 
-    Ok(counter.get_all().into_hit_counts())
-  })?;
+/// Callback of [`ResearchBuilder::yield_every`](crate::builder::ResearchBuilder::yield_every).
+pub(crate) type YieldCallback = Arc<dyn Fn() + Send + Sync>;
 
-  let hits_total = hits.iter().map(|c| c.hits).sum::<usize>();
+/// Time given to the render and hit counter threads to stop after all
+/// workers finished, see
+/// [`ResearchBuilder::shutdown_grace`](crate::builder::ResearchBuilder::shutdown_grace).
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
 
-  Ok(WakuchinResult {
-    tries,
-    hits_total,
-    hits,
-    hits_detail,
-  })
+/// Same as [`run_par`], but with the options of
+/// [`ResearchBuilder`](crate::builder::ResearchBuilder).
+pub(crate) fn run_par_with_options(
+  tries: usize,
+  times: usize,
+  patterns: &Matchers,
+  progress_handler: Box<dyn ProgressHandler>,
+  progress_interval: ProgressIntervalPolicy,
+  workers: usize,
+  options: RunOptions,
+) -> Result<WakuchinResult> {
+  let executor = Executor::Threads(get_total_workers(workers)?);
+
+  engine::run(
+    Research {
+      tries,
+      source: Source::Random { times },
+      patterns,
+      progress_handler,
+      progress_interval,
+      options,
+      compiled: None,
+    },
+    executor,
+  )
 }
 
 /// Research wakuchin with sequential.
@@ -284,7 +226,7 @@ pub fn run_par(
 /// * `tries` - number of tries
 ///   If you passed zero, this function do nothing and return immediately with an empty `WakuchinResult`.
 /// * `times` - wakuchin times n, cannot be zero
-/// * `regex` - compiled regular expression to detect hit
+/// * `patterns` - compiled regular expression to detect hit, a slice of them to count the hits of each pattern separately or [`Matchers`] (see [`Patterns`]), recompiled from their pattern to match bytes, so use inline flags like `(?i)` instead of `RegexBuilder` options, or pass `regex::bytes::Regex`es which are matched as compiled
 /// * `progress_handler` - handler function to handle progress
 /// * `progress_interval` - progress refresh interval
 ///
@@ -342,147 +284,118 @@ pub fn run_par(
 /// #
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
+#[inline]
 pub fn run_seq(
   tries: usize,
   times: usize,
-  regex: &Regex,
+  patterns: &(impl Patterns + ?Sized),
   progress_handler: Box<dyn ProgressHandler>,
   progress_interval: Duration,
 ) -> Result<WakuchinResult> {
-  if tries == 0 {
-    return Ok(WakuchinResult {
-      tries: 0,
-      hits_total: 0,
-      hits: Vec::new(),
-      hits_detail: Vec::new(),
-    });
-  }
-
-  if times == 0 {
-    return Err(WakuchinError::TimesIsZero);
-  }
-
-  let is_stopped_accidentially = AtomicBool::new(false);
-
-  // used internally to prevent 'static lifetime issues
-  #[cfg(not(target_arch = "wasm32"))]
-  let (internal_stop_tx, internal_stop_rx) = bounded(1);
-
-  let (hits_detail, hits) = scope(|s| {
-    let is_stopped_accidentially = &is_stopped_accidentially;
-
-    #[cfg(not(target_arch = "wasm32"))]
-    let signal_id = unsafe {
-      signal_hook_registry::register(SIGINT, move || {
-        internal_stop_tx.send(()).unwrap();
-      })
-    }?;
-
-    #[cfg(not(target_arch = "wasm32"))]
-    let signal_handle = s.spawn(|| loop {
-      if is_stopped_accidentially.load(Ordering::SeqCst) {
-        return;
-      }
-
-      if internal_stop_rx.is_full() {
-        is_stopped_accidentially.store(true, Ordering::SeqCst);
-
-        return;
-      }
-
-      std::hint::spin_loop();
-    });
-
-    let mut render = Render::new(progress_handler);
-
-    render.invoke_before_start()?;
+  run_seq_with_options(
+    tries,
+    times,
+    &patterns.to_matchers(),
+    progress_handler,
+    progress_interval.into(),
+    RunOptions::default(),
+  )
+}
 
-    render.render_progress(
+/// Same as [`run_seq`], but with the options of
+/// [`ResearchBuilder`](crate::builder::ResearchBuilder).
+pub(crate) fn run_seq_with_options(
+  tries: usize,
+  times: usize,
+  patterns: &Matchers,
+  progress_handler: Box<dyn ProgressHandler>,
+  progress_interval: ProgressIntervalPolicy,
+  options: RunOptions,
+) -> Result<WakuchinResult> {
+  engine::run(
+    Research {
+      tries,
+      source: Source::Random { times },
+      patterns,
+      progress_handler,
       progress_interval,
-      Progress(ProgressKind::Idle(IdleDetail { id: 0 })),
-      false,
-    )?;
-
-    let mut hits_detail_err = Ok(());
-
-    let hits_detail = (0..tries)
-      .map(|_| gen(times))
-      .enumerate()
-      .map(|(i, wakuchin)| {
-        render.render_progress(
-          progress_interval,
-          Progress(ProgressKind::Processing(ProcessingDetail::new(
-            0,
-            wakuchin.clone(),
-            i,
-            tries,
-          ))),
-          false,
-        )?;
-
-        if is_stopped_accidentially.load(Ordering::SeqCst) {
-          return Err(WakuchinError::Cancelled);
-        }
-
-        if check(&wakuchin, regex) {
-          let hit = Hit::new(i, &*wakuchin);
-
-          render.handle_hit(wakuchin);
-
-          Ok(Some(hit))
-        } else {
-          Ok(None)
-        }
-      })
-      .scan(
-        &mut hits_detail_err,
-        |hits_detail_err, result| match result {
-          Ok(result) => Some(result),
-          Err(err) => {
-            **hits_detail_err = Err(err);
-
-            None
-          }
-        },
-      )
-      .flatten()
-      .collect();
-
-    if matches!(hits_detail_err, Err(WakuchinError::Cancelled)) {
-      render.invoke_on_accidential_stop()?;
-
-      return Err(WakuchinError::Cancelled);
-    }
-
-    // cleanup
-    is_stopped_accidentially.store(true, Ordering::SeqCst);
-
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-      signal_handle.join().unwrap_or_else(|e| resume_unwind(e));
-      signal_hook_registry::unregister(signal_id);
-    }
-
-    render.render_progress(
-      Duration::ZERO,
-      Progress(ProgressKind::Done(DoneDetail {
-        id: 0,
-        total: tries,
-      })),
-      true,
-    )?;
-
-    render.invoke_after_finish()?;
-
-    Ok((hits_detail, render.hits()))
-  })?;
-
-  let hits_total = hits.iter().map(|c| c.hits).sum::<usize>();
+      options,
+      compiled: None,
+    },
+    Executor::Inline,
+  )
+}
 
-  Ok(WakuchinResult {
-    tries,
-    hits_total,
-    hits,
-    hits_detail,
-  })
+/// Research the strings of `candidates` with parallelism, instead of
+/// generated ones.
+///
+/// Each candidate is one try, and hits are reported at the index of the
+/// candidate. The candidates are collected first, so the workers can split
+/// them evenly.
+///
+/// # Arguments
+///
+/// * `candidates` - strings to check, e.g. a corpus read from a file
+///   If there are none, this function do nothing and return immediately with an empty `WakuchinResult`.
+/// * `patterns` - compiled regular expression to detect hit, a slice of them to count the hits of each pattern separately or [`Matchers`] (see [`Patterns`]), recompiled from their pattern to match bytes, so use inline flags like `(?i)` instead of `RegexBuilder` options, or pass `regex::bytes::Regex`es which are matched as compiled
+/// * `progress_handler` - handler function to handle progress
+/// * `progress_interval` - progress refresh interval
+/// * `workers` - number of workers you want to use, default to number of logical cores
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use regex::Regex;
+///
+/// use wakuchin::handlers::empty::EmptyProgressHandler;
+/// use wakuchin::worker::run_list;
+///
+/// let candidates = ["WKCN", "NCKW", "WKNC", "WKCN"].map(String::from);
+///
+/// let result = run_list(
+///   candidates,
+///   &Regex::new(r"^WK")?,
+///   Box::new(EmptyProgressHandler::new()),
+///   Duration::from_secs(1),
+///   2,
+/// )?;
+///
+/// let mut hits_on = result
+///   .hits_detail
+///   .iter()
+///   .map(|hit| hit.hit_on)
+///   .collect::<Vec<_>>();
+///
+/// hits_on.sort_unstable();
+///
+/// assert_eq!(result.tries, 4);
+/// assert_eq!(result.hits_total, 3);
+/// assert_eq!(hits_on, [0, 2, 3]);
+/// #
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn run_list(
+  candidates: impl IntoIterator<Item = String>,
+  patterns: &(impl Patterns + ?Sized),
+  progress_handler: Box<dyn ProgressHandler>,
+  progress_interval: Duration,
+  workers: usize,
+) -> Result<WakuchinResult> {
+  let candidates = candidates.into_iter().collect::<Vec<_>>();
+  let executor = Executor::Threads(get_total_workers(workers)?);
+
+  engine::run(
+    Research {
+      tries: candidates.len(),
+      source: Source::List(&candidates),
+      patterns: &patterns.to_matchers(),
+      progress_handler,
+      progress_interval: progress_interval.into(),
+      options: RunOptions::default(),
+      compiled: None,
+    },
+    executor,
+  )
 }